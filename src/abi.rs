@@ -0,0 +1,381 @@
+use crate::{
+  definition::{
+    Contract, ContractPart, ErrorDefinition, EventDefinition, FunctionDefinition,
+    StateVariableDeclaration,
+  },
+  evm::Word,
+  expression::Parameter,
+  hex::to_hex,
+  json::{self, Json},
+  literal::BigUint,
+  state_mutability::StateMutability,
+};
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeMap;
+
+pub fn keccak256(input: &[u8]) -> Vec<u8> {
+  let mut hasher = Keccak256::new();
+  hasher.input(input);
+  hasher.result().to_vec()
+}
+
+/// The first four bytes of the hash of a function signature.
+pub fn selector(signature: &str) -> Vec<u8> {
+  keccak256(signature.as_bytes())[0..4].to_vec()
+}
+
+fn state_mutability_name(state_mutability: &Option<StateMutability>) -> &'static str {
+  match state_mutability {
+    Some(StateMutability::Pure) => "pure",
+    Some(StateMutability::View) => "view",
+    Some(StateMutability::Payable) => "payable",
+    None => "nonpayable",
+  }
+}
+
+fn parameters_json(parameters: &[Parameter]) -> String {
+  let entries: Vec<String> = parameters
+    .iter()
+    .map(|p| {
+      format!(
+        r#"{{"name":"{}","type":"{}"}}"#,
+        p.identifier.clone().unwrap_or_default(),
+        p.typename.abi_name()
+      )
+    })
+    .collect();
+  format!("[{}]", entries.join(","))
+}
+
+fn function_json(function: &FunctionDefinition) -> String {
+  match &function.identifier {
+    None => format!(
+      r#"{{"type":"fallback","stateMutability":"{}"}}"#,
+      state_mutability_name(&function.state_mutability)
+    ),
+    Some(identifier) => format!(
+      r#"{{"type":"function","name":"{}","inputs":{},"outputs":{},"stateMutability":"{}"}}"#,
+      identifier,
+      parameters_json(&function.parameter_list),
+      parameters_json(&function.returns),
+      state_mutability_name(&function.state_mutability)
+    ),
+  }
+}
+
+fn getter_json(decl: &StateVariableDeclaration) -> String {
+  let (keys, value) = decl.getter_types();
+  let inputs: Vec<String> = keys
+    .iter()
+    .map(|key| format!(r#"{{"name":"","type":"{}"}}"#, key.abi_name()))
+    .collect();
+  format!(
+    r#"{{"type":"function","name":"{}","inputs":[{}],"outputs":[{{"name":"","type":"{}"}}],"stateMutability":"view"}}"#,
+    decl.identifier,
+    inputs.join(","),
+    value.abi_name()
+  )
+}
+
+fn event_json(event: &EventDefinition) -> String {
+  let inputs: Vec<String> = event
+    .parameters
+    .iter()
+    .map(|p| {
+      format!(
+        r#"{{"name":"{}","type":"{}","indexed":{}}}"#,
+        p.identifier.clone().unwrap_or_default(),
+        p.type_name.abi_name(),
+        p.indexed
+      )
+    })
+    .collect();
+  format!(
+    r#"{{"type":"event","name":"{}","inputs":[{}],"anonymous":{}}}"#,
+    event.identifier,
+    inputs.join(","),
+    event.anonymous
+  )
+}
+
+fn error_json(error: &ErrorDefinition) -> String {
+  format!(
+    r#"{{"type":"error","name":"{}","inputs":{}}}"#,
+    error.identifier,
+    parameters_json(&error.parameter_list)
+  )
+}
+
+/// Describes the externally visible interface of a contract as a JSON array.
+pub fn to_json(contract: &Contract) -> String {
+  let mut entries = vec![];
+  for part in &contract.contract_part {
+    match part {
+      ContractPart::ConstructorDefinition(ctor) => entries.push(format!(
+        r#"{{"type":"constructor","inputs":{},"stateMutability":"nonpayable"}}"#,
+        parameters_json(&ctor.parameter_list)
+      )),
+      ContractPart::FunctionDefinition(function)
+        if function.identifier.is_none() || function.is_external() =>
+      {
+        entries.push(function_json(function))
+      }
+      ContractPart::EventDefinition(event) => entries.push(event_json(event)),
+      ContractPart::ErrorDefinition(error) => entries.push(error_json(error)),
+      ContractPart::StateVariableDeclaration(decl) if decl.is_public() => {
+        entries.push(getter_json(decl))
+      }
+      _ => {}
+    }
+  }
+  format!("[{}]", entries.join(","))
+}
+
+/// The output types of the functions in an ABI JSON array, by selector.
+pub fn output_types(abi: &Json) -> BTreeMap<Vec<u8>, Vec<String>> {
+  let entries = match abi {
+    Json::Array(entries) => entries,
+    _ => return BTreeMap::new(),
+  };
+  let types = |parameters: Option<&Json>| -> Option<Vec<String>> {
+    match parameters? {
+      Json::Array(parameters) => parameters
+        .iter()
+        .map(|parameter| parameter.get("type")?.as_str().map(String::from))
+        .collect(),
+      _ => None,
+    }
+  };
+  entries
+    .iter()
+    .filter(|entry| entry.get("type").and_then(Json::as_str) == Some("function"))
+    .filter_map(|entry| {
+      let name = entry.get("name")?.as_str()?;
+      let inputs = types(entry.get("inputs"))?;
+      let outputs = types(entry.get("outputs"))?;
+      Some((
+        selector(&format!("{}({})", name, inputs.join(","))),
+        outputs,
+      ))
+    })
+    .collect()
+}
+
+/// The element type and, for static arrays, the length of an array type like `uint8[2][]`.
+fn array_type(type_name: &str) -> Option<(&str, Option<usize>)> {
+  let open = type_name.strip_suffix(']')?.rfind('[')?;
+  let length = &type_name[open + 1..type_name.len() - 1];
+  let length = if length.is_empty() {
+    None
+  } else {
+    Some(length.parse().ok()?)
+  };
+  Some((&type_name[..open], length))
+}
+
+/// Whether values of the type are encoded in the tail, with an offset to them in the head.
+fn is_dynamic(type_name: &str) -> bool {
+  match array_type(type_name) {
+    Some((element, Some(_))) => is_dynamic(element),
+    Some((_, None)) => true,
+    None => type_name == "string" || type_name == "bytes",
+  }
+}
+
+/// The number of bytes a value of the type takes up in the head.
+fn head_size(type_name: &str) -> usize {
+  match array_type(type_name) {
+    Some((element, Some(length))) if !is_dynamic(element) => length * head_size(element),
+    _ => 32,
+  }
+}
+
+/// Decodes ABI encoded values of the types into JSON: numbers as decimal strings, addresses
+/// and byte strings as hex, and arrays as arrays. Returns `None` if the data doesn't fit.
+pub fn decode(types: &[String], data: &[u8]) -> Option<Vec<String>> {
+  let mut head = 0;
+  types
+    .iter()
+    .map(|type_name| {
+      let value = decode_value(type_name, data, head);
+      head += head_size(type_name);
+      value
+    })
+    .collect()
+}
+
+/// Decodes the value whose head is at the position of the encoded tuple.
+fn decode_value(type_name: &str, tuple: &[u8], position: usize) -> Option<String> {
+  let word = |data: &[u8], position: usize| {
+    data
+      .get(position..position.checked_add(32)?)
+      .map(Word::from_bytes_be)
+  };
+  let usize_at =
+    |data: &[u8], position: usize| word(data, position)?.to_u64().map(|value| value as usize);
+  let (data, position) = if is_dynamic(type_name) {
+    (tuple.get(usize_at(tuple, position)?..)?, 0)
+  } else {
+    (tuple, position)
+  };
+  if let Some((element, length)) = array_type(type_name) {
+    let (length, elements) = match length {
+      Some(length) => (length, data.get(position..)?),
+      None => (usize_at(data, 0)?, data.get(32..)?),
+    };
+    let types = vec![element.to_string(); length];
+    return Some(format!("[{}]", decode(&types, elements)?.join(",")));
+  }
+  if type_name == "string" || type_name == "bytes" {
+    let length = usize_at(data, 0)?;
+    let bytes = data.get(32..32usize.checked_add(length)?)?;
+    return match type_name {
+      "string" => Some(json::string(std::str::from_utf8(bytes).ok()?)),
+      _ => Some(json::string(&format!("0x{}", hex(bytes)))),
+    };
+  }
+  let value = word(data, position)?;
+  let decimal = |value: Word| BigUint::from_bytes_be(&value.to_bytes_be()).to_string();
+  let bytes = value.to_bytes_be();
+  let json = match type_name {
+    "bool" => match value.to_u64()? {
+      0 => "false".to_string(),
+      1 => "true".to_string(),
+      _ => return None,
+    },
+    "address" => json::string(&format!("0x{}", hex(&bytes[12..]))),
+    _ if type_name.starts_with("uint") => json::string(&decimal(value)),
+    _ if type_name.starts_with("int") && value.is_negative() => {
+      json::string(&format!("-{}", decimal(-value)))
+    }
+    _ if type_name.starts_with("int") => json::string(&decimal(value)),
+    _ => match type_name
+      .strip_prefix("bytes")
+      .and_then(|size| size.parse::<usize>().ok())
+    {
+      Some(size) if size <= 32 => json::string(&format!("0x{}", hex(&bytes[..size]))),
+      _ => json::string(&format!("0x{}", hex(&bytes))),
+    },
+  };
+  Some(json)
+}
+
+fn hex(bytes: &[u8]) -> String {
+  let bytes: Vec<u32> = bytes.iter().map(|b| *b as u32).collect();
+  to_hex(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::root::parse;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn computes_selector() {
+    assert_eq!(
+      selector("transfer(address,uint256)"),
+      vec![0xa9, 0x05, 0x9c, 0xbb]
+    )
+  }
+
+  #[test]
+  fn describes_functions_and_events() {
+    let input = b"pragma solidity ^0.5.6;
+contract Token {
+  event Transfer(address indexed from, address indexed to, uint256 value);
+  function transfer(address to, uint256 value) public returns (bool) { }
+  function secret() private { }
+}";
+    let (_, root) = parse(input).ok().unwrap();
+    assert_eq!(
+      to_json(&root.contracts[0]),
+      concat!(
+        r#"[{"type":"event","name":"Transfer","inputs":["#,
+        r#"{"name":"from","type":"address","indexed":true},"#,
+        r#"{"name":"to","type":"address","indexed":true},"#,
+        r#"{"name":"value","type":"uint256","indexed":false}],"anonymous":false},"#,
+        r#"{"type":"function","name":"transfer","inputs":["#,
+        r#"{"name":"to","type":"address"},{"name":"value","type":"uint256"}],"#,
+        r#""outputs":[{"name":"","type":"bool"}],"stateMutability":"nonpayable"}]"#
+      )
+    )
+  }
+
+  #[test]
+  fn describes_errors() {
+    let input = b"pragma solidity ^0.8.0;
+contract Token {
+  error InsufficientBalance(uint256 available, uint256 required);
+}";
+    let (_, root) = parse(input).ok().unwrap();
+    assert_eq!(
+      to_json(&root.contracts[0]),
+      concat!(
+        r#"[{"type":"error","name":"InsufficientBalance","inputs":["#,
+        r#"{"name":"available","type":"uint256"},{"name":"required","type":"uint256"}]}]"#
+      )
+    )
+  }
+
+  #[test]
+  fn decodes_return_values() {
+    let words = |values: &[u64]| -> Vec<u8> {
+      values
+        .iter()
+        .flat_map(|value| Word::from_u64(*value).to_bytes_be().to_vec())
+        .collect()
+    };
+    let types: Vec<String> = [
+      "int8",
+      "bool",
+      "bytes2",
+      "uint8[2]",
+      "string",
+      "uint256[]",
+      "bytes",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect();
+    let mut data = (-Word::from_u64(3)).to_bytes_be().to_vec();
+    data.extend(words(&[1]));
+    data.extend((Word::from_u64(0xcafe) << 240).to_bytes_be().to_vec());
+    data.extend(words(&[4, 5, 0x100, 0x140, 0x1a0, 2]));
+    data.extend((Word::from_u64(0x6869) << 240).to_bytes_be().to_vec());
+    data.extend(words(&[2, 6, 7, 1]));
+    data.extend((Word::from_u64(0xff) << 248).to_bytes_be().to_vec());
+    assert_eq!(
+      decode(&types, &data),
+      Some(
+        vec![
+          r#""-3""#,
+          "true",
+          r#""0xcafe""#,
+          r#"["4","5"]"#,
+          r#""hi""#,
+          r#"["6","7"]"#,
+          r#""0xff""#,
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect()
+      )
+    );
+    // Data that is too short for the types can't be decoded
+    assert_eq!(decode(&types, &data[..200]), None);
+  }
+
+  #[test]
+  fn finds_output_types_by_selector() {
+    let abi = br#"[
+  {"type":"function","name":"get","inputs":[{"name":"a","type":"uint256"}],"outputs":[{"name":"","type":"string"}]},
+  {"type":"event","name":"get","inputs":[],"anonymous":false}
+]"#;
+    let (_, abi) = json::parse(abi).ok().unwrap();
+    let mut expected = BTreeMap::new();
+    expected.insert(selector("get(uint256)"), vec!["string".to_string()]);
+    assert_eq!(output_types(&abi), expected);
+  }
+}
@@ -1,7 +1,11 @@
 use crate::atom::delimiter::Delimiter;
 use nom::{
-    bytes::complete::take_while1, character::is_alphanumeric, combinator::map, named, take_until1,
-    IResult,
+    bytes::complete::{take_while, take_while1, take_while_m_n},
+    character::{is_alphabetic, is_alphanumeric},
+    combinator::{map, not, peek, recognize},
+    named,
+    sequence::preceded,
+    take_until1, IResult,
 };
 use std::fmt;
 use std::str::from_utf8;
@@ -37,10 +41,24 @@ impl fmt::Display for Atom {
     }
 }
 
-pub fn parse_identifier<'a>(i: &[u8]) -> IResult<&[u8], String> {
-    map(take_while1(is_alphanumeric), |b| {
-        from_utf8(b).unwrap().to_string()
-    })(i)
+pub fn is_identifier_char(c: u8) -> bool {
+    is_alphanumeric(c) || c == b'_' || c == b'$'
+}
+
+/// Succeeds if the input doesn't continue with an identifier, so that keywords aren't mistaken
+/// for the start of a longer identifier.
+pub fn word_end(i: &[u8]) -> IResult<&[u8], ()> {
+    not(peek(take_while1(is_identifier_char)))(i)
+}
+
+pub fn parse_identifier(i: &[u8]) -> IResult<&[u8], String> {
+    map(
+        recognize(preceded(
+            take_while_m_n(1, 1, |c| is_alphabetic(c) || c == b'_' || c == b'$'),
+            take_while(is_identifier_char),
+        )),
+        |b| from_utf8(b).unwrap().to_string(),
+    )(i)
 }
 
 pub fn parse_anything_till_semi(i: &[u8]) -> IResult<&[u8], Atom> {
@@ -81,6 +99,23 @@ mod tests {
         assert_eq!((from_utf8(remaining).unwrap(), atom), ("", "a".to_string()))
     }
 
+    #[test]
+    fn parses_identifier_with_underscores() {
+        let input = "_total$supply_ = 1";
+        let (remaining, atom) = parse_identifier(input.as_bytes()).ok().unwrap();
+        assert_eq!(
+            (from_utf8(remaining).unwrap(), atom),
+            (" = 1", "_total$supply_".to_string())
+        )
+    }
+
+    #[test]
+    fn identifier_should_not_start_with_a_digit() {
+        let input = "1abc";
+        let result = parse_identifier(input.as_bytes());
+        result.expect_err("should be None");
+    }
+
     #[test]
     fn parse_identifier_should_never_be_an_empty_str() {
         let input = "           ";
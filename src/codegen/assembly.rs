@@ -0,0 +1,437 @@
+use crate::{codegen::EvmVersion, op_codes::OpCode, span::Span};
+
+pub type Tag = usize;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AssemblyItem {
+  Operation(OpCode),
+  Push(Vec<u8>),
+  PushTag(Tag),
+  Tag(Tag),
+  PushSubSize(usize),
+  PushSubOffset(usize),
+  /// The size of the assembled code including its sub assemblies, which is where the constructor
+  /// arguments start in the creation code.
+  PushProgramSize,
+  /// Describes the code that follows in listings, without generating anything.
+  Comment(String),
+}
+
+/// Whether a jump enters or leaves an internal function, which debuggers use to keep track of
+/// the call stack.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JumpType {
+  Regular,
+  IntoFunction,
+  OutOfFunction,
+}
+
+impl Default for JumpType {
+  fn default() -> JumpType {
+    JumpType::Regular
+  }
+}
+
+/// The part of the source an assembly item was generated from, for source maps.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SourceLocation {
+  pub span: Option<Span>,
+  pub jump: JumpType,
+  /// How many modifiers the code is nested in, counting the `_` it is expanded from.
+  pub modifier_depth: usize,
+}
+
+/// A list of EVM instructions with symbolic jump targets. Sub assemblies (e.g. the runtime code
+/// inside the creation code) are appended after the main code when assembled.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Assembly {
+  pub items: Vec<AssemblyItem>,
+  pub subs: Vec<Assembly>,
+  /// The source location of each item.
+  pub locations: Vec<SourceLocation>,
+  location: SourceLocation,
+  stack_height: usize,
+  tag_count: usize,
+}
+
+fn minimal_bytes(value: usize) -> Vec<u8> {
+  let bytes: Vec<u8> = value
+    .to_be_bytes()
+    .iter()
+    .cloned()
+    .skip_while(|b| *b == 0)
+    .collect();
+  if bytes.is_empty() {
+    vec![0]
+  } else {
+    bytes
+  }
+}
+
+impl Assembly {
+  pub fn new() -> Assembly {
+    Assembly::default()
+  }
+
+  pub fn stack_height(&self) -> usize {
+    self.stack_height
+  }
+
+  /// Used after unconditional jumps and at jump destinations, where the height can't be derived
+  /// from the preceding instruction.
+  pub fn set_stack_height(&mut self, height: usize) {
+    self.stack_height = height;
+  }
+
+  /// Attributes the items that follow to `span`, returning the previous span to restore later.
+  pub fn set_span(&mut self, span: Option<Span>) -> Option<Span> {
+    std::mem::replace(&mut self.location.span, span)
+  }
+
+  /// Sets the modifier depth of the items that follow, returning the previous one.
+  pub fn set_modifier_depth(&mut self, depth: usize) -> usize {
+    std::mem::replace(&mut self.location.modifier_depth, depth)
+  }
+
+  /// Marks the last item, which must be a jump, as entering or leaving an internal function.
+  pub fn mark_jump(&mut self, jump: JumpType) {
+    assert_eq!(
+      self.items.last(),
+      Some(&AssemblyItem::Operation(OpCode::JUMP)),
+      "only jumps can enter or leave functions"
+    );
+    self.locations.last_mut().unwrap().jump = jump;
+  }
+
+  fn append(&mut self, item: AssemblyItem) {
+    self.items.push(item);
+    self.locations.push(self.location);
+  }
+
+  pub fn op(&mut self, op: OpCode) {
+    assert!(
+      self.stack_height >= op.stack_args(),
+      "stack underflow emitting {:?}",
+      op
+    );
+    self.stack_height = self.stack_height - op.stack_args() + op.stack_returns();
+    self.append(AssemblyItem::Operation(op));
+  }
+
+  /// Pushes a big endian value, stripped of its leading zeros.
+  pub fn push(&mut self, bytes: &[u8]) {
+    let mut value: Vec<u8> = bytes.iter().cloned().skip_while(|b| *b == 0).collect();
+    if value.is_empty() {
+      value.push(0);
+    }
+    assert!(value.len() <= 32, "cannot push more than 32 bytes");
+    self.stack_height += 1;
+    self.append(AssemblyItem::Push(value));
+  }
+
+  pub fn push_usize(&mut self, value: usize) {
+    self.push(&minimal_bytes(value));
+  }
+
+  pub fn dup(&mut self, n: usize) {
+    assert!((1..=16).contains(&n), "DUP{} is out of range", n);
+    self.op(OpCode::from_byte(OpCode::DUP1 as u8 + n as u8 - 1).unwrap());
+  }
+
+  pub fn swap(&mut self, n: usize) {
+    assert!((1..=16).contains(&n), "SWAP{} is out of range", n);
+    self.op(OpCode::from_byte(OpCode::SWAP1 as u8 + n as u8 - 1).unwrap());
+  }
+
+  pub fn new_tag(&mut self) -> Tag {
+    self.tag_count += 1;
+    self.tag_count - 1
+  }
+
+  pub fn push_tag(&mut self, tag: Tag) {
+    self.stack_height += 1;
+    self.append(AssemblyItem::PushTag(tag));
+  }
+
+  pub fn tag(&mut self, tag: Tag) {
+    self.append(AssemblyItem::Tag(tag));
+  }
+
+  pub fn comment(&mut self, text: String) {
+    self.append(AssemblyItem::Comment(text));
+  }
+
+  pub fn jump(&mut self, tag: Tag) {
+    self.push_tag(tag);
+    self.op(OpCode::JUMP);
+  }
+
+  /// Jumps to `tag` if the top of the stack is non zero, consuming it.
+  pub fn jumpi(&mut self, tag: Tag) {
+    self.push_tag(tag);
+    self.op(OpCode::JUMPI);
+  }
+
+  pub fn append_sub(&mut self, sub: Assembly) -> usize {
+    self.subs.push(sub);
+    self.subs.len() - 1
+  }
+
+  pub fn push_sub_size(&mut self, sub: usize) {
+    self.stack_height += 1;
+    self.append(AssemblyItem::PushSubSize(sub));
+  }
+
+  pub fn push_sub_offset(&mut self, sub: usize) {
+    self.stack_height += 1;
+    self.append(AssemblyItem::PushSubOffset(sub));
+  }
+
+  pub fn push_program_size(&mut self) {
+    self.stack_height += 1;
+    self.append(AssemblyItem::PushProgramSize);
+  }
+
+  /// Reorders the top `layout.len()` stack items, identified by the symbols in `layout`, so that
+  /// they end up as `target`. Symbols that don't appear in `target` are popped.
+  pub fn rearrange(&mut self, layout: &[usize], target: &[usize]) -> Result<(), String> {
+    let mut layout = layout.to_vec();
+    loop {
+      match layout.iter().rposition(|s| !target.contains(s)) {
+        None => break,
+        Some(index) => {
+          let depth = layout.len() - 1 - index;
+          if depth > 0 {
+            self.checked_swap(depth)?;
+            let top = layout.len() - 1;
+            layout.swap(index, top);
+          }
+          self.op(OpCode::POP);
+          layout.pop();
+        }
+      }
+    }
+    assert_eq!(layout.len(), target.len(), "cannot rearrange the stack");
+    for (position, symbol) in target.iter().enumerate() {
+      if layout[position] == *symbol {
+        continue;
+      }
+      let top = layout.len() - 1;
+      let index = layout.iter().position(|s| s == symbol).unwrap();
+      if index != top {
+        self.checked_swap(top - index)?;
+        layout.swap(index, top);
+      }
+      self.checked_swap(top - position)?;
+      layout.swap(position, top);
+    }
+    Ok(())
+  }
+
+  fn checked_swap(&mut self, n: usize) -> Result<(), String> {
+    if n > 16 {
+      return Err("Stack too deep, try removing local variables.".to_string());
+    }
+    self.swap(n);
+    Ok(())
+  }
+
+  fn item_size(
+    item: &AssemblyItem,
+    width: usize,
+    sub_sizes: &[usize],
+    evm_version: EvmVersion,
+  ) -> usize {
+    match item {
+      AssemblyItem::Operation(_) | AssemblyItem::Tag(_) => 1,
+      AssemblyItem::Push(bytes) => Assembly::push_codes(bytes, evm_version).len(),
+      AssemblyItem::PushTag(_) | AssemblyItem::PushSubOffset(_) | AssemblyItem::PushProgramSize => {
+        1 + width
+      }
+      AssemblyItem::PushSubSize(sub) => 1 + minimal_bytes(sub_sizes[*sub]).len(),
+      AssemblyItem::Comment(_) => 0,
+    }
+  }
+
+  fn push_op(size: usize) -> u32 {
+    OpCode::PUSH1 as u32 + size as u32 - 1
+  }
+
+  /// The narrowest push of a value, which is `PUSH0` for zero if the EVM version has it.
+  fn push_codes(bytes: &[u8], evm_version: EvmVersion) -> Vec<u32> {
+    if bytes == [0] && evm_version.has_push0() {
+      return vec![OpCode::PUSH0 as u32];
+    }
+    let mut codes = vec![Assembly::push_op(bytes.len())];
+    codes.extend(bytes.iter().map(|b| *b as u32));
+    codes
+  }
+
+  fn assemble_with_width(
+    &self,
+    width: usize,
+    subs: &[Vec<u32>],
+    evm_version: EvmVersion,
+  ) -> Option<Vec<u32>> {
+    let sub_sizes: Vec<usize> = subs.iter().map(|s| s.len()).collect();
+    let mut tag_positions = vec![0; self.tag_count];
+    let mut code_size = 0;
+    for item in &self.items {
+      if let AssemblyItem::Tag(tag) = item {
+        tag_positions[*tag] = code_size;
+      }
+      code_size += Assembly::item_size(item, width, &sub_sizes, evm_version);
+    }
+    let mut sub_offsets = vec![];
+    let mut total_size = code_size;
+    for size in &sub_sizes {
+      sub_offsets.push(total_size);
+      total_size += size;
+    }
+    if width < 8 && total_size >= 1 << (8 * width) {
+      return None;
+    }
+
+    let fixed_width = |value: usize| -> Vec<u32> {
+      (0..width)
+        .rev()
+        .map(|i| ((value >> (8 * i)) & 0xff) as u32)
+        .collect()
+    };
+    let mut codes = Vec::<u32>::new();
+    for item in &self.items {
+      match item {
+        AssemblyItem::Operation(op) => codes.push(*op as u32),
+        AssemblyItem::Push(bytes) => codes.extend(Assembly::push_codes(bytes, evm_version)),
+        AssemblyItem::PushTag(tag) => {
+          codes.push(Assembly::push_op(width));
+          codes.extend(fixed_width(tag_positions[*tag]));
+        }
+        AssemblyItem::Tag(_) => codes.push(OpCode::JUMPDEST as u32),
+        AssemblyItem::PushSubSize(sub) => {
+          let bytes = minimal_bytes(sub_sizes[*sub]);
+          codes.push(Assembly::push_op(bytes.len()));
+          codes.extend(bytes.iter().map(|b| *b as u32));
+        }
+        AssemblyItem::PushSubOffset(sub) => {
+          codes.push(Assembly::push_op(width));
+          codes.extend(fixed_width(sub_offsets[*sub]));
+        }
+        AssemblyItem::PushProgramSize => {
+          codes.push(Assembly::push_op(width));
+          codes.extend(fixed_width(total_size));
+        }
+        AssemblyItem::Comment(_) => {}
+      }
+    }
+    for sub in subs {
+      codes.extend(sub);
+    }
+    Some(codes)
+  }
+
+  pub fn assemble(&self, evm_version: EvmVersion) -> Vec<u32> {
+    let subs: Vec<Vec<u32>> = self.subs.iter().map(|s| s.assemble(evm_version)).collect();
+    let mut width = 1;
+    loop {
+      if let Some(codes) = self.assemble_with_width(width, &subs, evm_version) {
+        return codes;
+      }
+      width += 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn assembles_jump_to_tag() {
+    let mut asm = Assembly::new();
+    let tag = asm.new_tag();
+    asm.jump(tag);
+    asm.op(OpCode::STOP);
+    asm.tag(tag);
+    assert_eq!(
+      asm.assemble(EvmVersion::default()),
+      vec![0x60, 0x04, 0x56, 0x00, 0x5b]
+    );
+  }
+
+  #[test]
+  fn widens_tags_for_large_code() {
+    let mut asm = Assembly::new();
+    let tag = asm.new_tag();
+    asm.jump(tag);
+    for _ in 0..300 {
+      asm.op(OpCode::STOP);
+    }
+    asm.tag(tag);
+    let codes = asm.assemble(EvmVersion::default());
+    assert_eq!(codes[0..4].to_vec(), vec![0x61, 0x01, 0x30, 0x56]);
+    assert_eq!(codes[0x130], 0x5b);
+  }
+
+  #[test]
+  fn appends_sub_assemblies() {
+    let mut sub = Assembly::new();
+    sub.op(OpCode::STOP);
+    let mut asm = Assembly::new();
+    let index = asm.append_sub(sub);
+    asm.push_sub_size(index);
+    asm.push_sub_offset(index);
+    assert_eq!(
+      asm.assemble(EvmVersion::default()),
+      vec![0x60, 0x01, 0x60, 0x04, 0x00]
+    );
+  }
+
+  #[test]
+  fn pushes_program_size_including_subs() {
+    let mut sub = Assembly::new();
+    sub.op(OpCode::STOP);
+    let mut asm = Assembly::new();
+    asm.append_sub(sub);
+    asm.push_program_size();
+    assert_eq!(asm.assemble(EvmVersion::default()), vec![0x60, 0x03, 0x00]);
+  }
+
+  #[test]
+  fn pushes_constants_with_minimal_width() {
+    let mut asm = Assembly::new();
+    asm.push(&[0]);
+    asm.push(&[0, 0, 0x01, 0x00]);
+    asm.push(&[0xff; 32]);
+    let mut expected = vec![0x5f, 0x61, 0x01, 0x00, 0x7f];
+    expected.extend(vec![0xff; 32]);
+    assert_eq!(asm.assemble(EvmVersion::Shanghai), expected);
+    expected.splice(0..1, vec![0x60, 0x00]);
+    assert_eq!(asm.assemble(EvmVersion::Paris), expected);
+  }
+
+  #[test]
+  fn rearranges_stack() {
+    let mut asm = Assembly::new();
+    asm.set_stack_height(4);
+    asm.rearrange(&[0, 1, 2, 3], &[2, 3, 0]).unwrap();
+    assert_eq!(asm.stack_height(), 3);
+    let mut stack = vec![0, 1, 2, 3];
+    for item in &asm.items {
+      match item {
+        AssemblyItem::Operation(OpCode::POP) => {
+          stack.pop();
+        }
+        AssemblyItem::Operation(op) => {
+          let n = *op as usize - OpCode::SWAP1 as usize + 1;
+          let top = stack.len() - 1;
+          stack.swap(top, top - n);
+        }
+        _ => unreachable!(),
+      }
+    }
+    assert_eq!(stack, vec![2, 3, 0]);
+  }
+}
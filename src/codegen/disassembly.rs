@@ -0,0 +1,108 @@
+use crate::{
+  codegen::assembly::{Assembly, AssemblyItem},
+  op_codes::OpCode,
+};
+
+fn hex(bytes: &[u8]) -> String {
+  let digits: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+  format!("0x{}", digits)
+}
+
+/// Turns bytecode back into instructions, like `PUSH1 0x80 PUSH1 0x40 MSTORE`. Bytes that are no
+/// instruction are shown as `INVALID_0x..`.
+pub fn disassemble(code: &[u8]) -> String {
+  let mut instructions = vec![];
+  let mut i = 0;
+  while i < code.len() {
+    let instruction = match OpCode::from_byte(code[i]) {
+      Some(op) => format!("{:?}", op),
+      None => format!("INVALID_{}", hex(&code[i..=i])),
+    };
+    if (0x60..=0x7f).contains(&code[i]) {
+      let end = (i + 1 + (code[i] - 0x5f) as usize).min(code.len());
+      instructions.push(format!("{} {}", instruction, hex(&code[i + 1..end])));
+      i = end;
+    } else {
+      instructions.push(instruction);
+      i += 1;
+    }
+  }
+  instructions.join(" ")
+}
+
+/// Lists the assembly before jump targets and sub assembly offsets are resolved, like
+/// `solc --asm` does.
+pub fn listing(asm: &Assembly) -> String {
+  let mut lines = vec![];
+  for item in &asm.items {
+    lines.push(match item {
+      AssemblyItem::Operation(op) => format!("    {}", format!("{:?}", op).to_lowercase()),
+      AssemblyItem::Push(bytes) => format!("    {}", hex(bytes)),
+      AssemblyItem::PushTag(tag) => format!("    tag_{}", tag),
+      AssemblyItem::Tag(tag) => format!("  tag_{}:", tag),
+      AssemblyItem::PushSubSize(sub) => format!("    dataSize(sub_{})", sub),
+      AssemblyItem::PushSubOffset(sub) => format!("    dataOffset(sub_{})", sub),
+      AssemblyItem::PushProgramSize => "    bytecodeSize".to_string(),
+      AssemblyItem::Comment(text) => format!("    /* {} */", text),
+    });
+  }
+  for (index, sub) in asm.subs.iter().enumerate() {
+    if index == 0 {
+      lines.push("stop".to_string());
+    }
+    lines.push(String::new());
+    lines.push(format!("sub_{}: assembly {{", index));
+    for line in listing(sub).lines() {
+      if line.is_empty() {
+        lines.push(String::new());
+      } else {
+        lines.push(format!("    {}", line));
+      }
+    }
+    lines.push("}".to_string());
+  }
+  lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn disassembles_bytecode() {
+    assert_eq!(
+      disassemble(&[0x60, 0x80, 0x60, 0x40, 0x52, 0x5f, 0x0c, 0x61, 0x01]),
+      "PUSH1 0x80 PUSH1 0x40 MSTORE PUSH0 INVALID_0x0c PUSH2 0x01"
+    )
+  }
+
+  #[test]
+  fn lists_tags_comments_and_subs() {
+    let mut sub = Assembly::new();
+    sub.op(OpCode::STOP);
+    let mut asm = Assembly::new();
+    let tag = asm.new_tag();
+    asm.comment("function f()".to_string());
+    asm.tag(tag);
+    let sub = asm.append_sub(sub);
+    asm.push_sub_size(sub);
+    asm.push_sub_offset(sub);
+    asm.jump(tag);
+    assert_eq!(
+      listing(&asm),
+      "    /* function f() */
+  tag_0:
+    dataSize(sub_0)
+    dataOffset(sub_0)
+    tag_0
+    jump
+stop
+
+sub_0: assembly {
+        stop
+}"
+    )
+  }
+}
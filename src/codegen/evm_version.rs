@@ -0,0 +1,72 @@
+/// The hard fork the bytecode is generated for. Versions before Constantinople are not supported,
+/// since the generated code relies on its shift instructions.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum EvmVersion {
+  Constantinople,
+  Petersburg,
+  Istanbul,
+  Berlin,
+  London,
+  Paris,
+  Shanghai,
+  Cancun,
+}
+
+impl Default for EvmVersion {
+  fn default() -> EvmVersion {
+    EvmVersion::Shanghai
+  }
+}
+
+impl EvmVersion {
+  const ALL: [EvmVersion; 8] = [
+    EvmVersion::Constantinople,
+    EvmVersion::Petersburg,
+    EvmVersion::Istanbul,
+    EvmVersion::Berlin,
+    EvmVersion::London,
+    EvmVersion::Paris,
+    EvmVersion::Shanghai,
+    EvmVersion::Cancun,
+  ];
+
+  /// The name solc uses for the version, e.g. in the `evmVersion` setting.
+  pub fn name(self) -> &'static str {
+    match self {
+      EvmVersion::Constantinople => "constantinople",
+      EvmVersion::Petersburg => "petersburg",
+      EvmVersion::Istanbul => "istanbul",
+      EvmVersion::Berlin => "berlin",
+      EvmVersion::London => "london",
+      EvmVersion::Paris => "paris",
+      EvmVersion::Shanghai => "shanghai",
+      EvmVersion::Cancun => "cancun",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Result<EvmVersion, String> {
+    EvmVersion::ALL
+      .iter()
+      .cloned()
+      .find(|version| version.name() == name)
+      .ok_or_else(|| format!("Invalid EVM version: \"{}\"", name))
+  }
+
+  /// `PUSH0` was introduced in Shanghai (EIP-3855).
+  pub fn has_push0(self) -> bool {
+    self >= EvmVersion::Shanghai
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_evm_version_names() {
+    assert_eq!(EvmVersion::from_name("paris"), Ok(EvmVersion::Paris));
+    assert!(!EvmVersion::Paris.has_push0());
+    assert!(EvmVersion::from_name("cancun").unwrap().has_push0());
+    assert!(EvmVersion::from_name("frontier").is_err());
+  }
+}
@@ -0,0 +1,488 @@
+use crate::{
+  definition::{
+    Contract, ContractPart, ErrorDefinition, EventDefinition, FunctionDefinition,
+    ModifierDefinition, Placeholder, StateVariableDeclaration, StoragePosition,
+  },
+  elementary_type_name::ElementaryTypeName,
+  expression::{Reference, TypeName},
+  literal::Rational,
+  op_codes::OpCode,
+  storage_location::StorageLocation,
+};
+
+pub use crate::codegen::{
+  assembly::{Assembly, JumpType, Tag},
+  evm_version::EvmVersion,
+};
+
+pub mod assembly;
+pub mod disassembly;
+mod evm_version;
+pub mod source_map;
+
+/// Picks the declaration taking the given number of arguments among those of the same name, or
+/// the only one if none does.
+pub fn overload<T: Copy>(
+  candidates: Vec<T>,
+  parameter_count: impl Fn(T) -> usize,
+  arg_count: usize,
+) -> Option<T> {
+  match candidates
+    .iter()
+    .find(|&&c| parameter_count(c) == arg_count)
+  {
+    Some(candidate) => Some(*candidate),
+    None if candidates.len() == 1 => Some(candidates[0]),
+    None => None,
+  }
+}
+
+/// Position of the free memory pointer, as laid out by solc.
+pub const FREE_MEMORY_POINTER: u8 = 0x40;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Local {
+  pub name: String,
+  /// Index of the stack slot, counted from the bottom of the stack.
+  pub position: usize,
+  pub type_name: TypeName,
+  /// Where the value of a reference type lives; the local holds its address or slot.
+  pub storage_location: Option<StorageLocation>,
+}
+
+pub struct Scope {
+  locals: usize,
+  stack_height: usize,
+}
+
+/// Describes the function body or modifier being generated, so that `return` knows where to go.
+pub struct Frame {
+  pub exit: Tag,
+  pub stack_height: usize,
+  /// Stack positions and types of the return variables.
+  pub returns: Vec<(usize, TypeName)>,
+}
+
+pub struct Context<'a> {
+  pub asm: Assembly,
+  pub contracts: &'a [Contract],
+  pub contract: &'a Contract,
+  locals: Vec<Local>,
+  functions: Vec<(&'a FunctionDefinition, Tag)>,
+  pending_functions: Vec<(&'a FunctionDefinition, Tag)>,
+  frame: Option<Frame>,
+  placeholder: Option<Placeholder<'a>>,
+  unchecked: bool,
+  /// The contracts whose code contains the code being generated, ending with `contract`.
+  creators: Vec<&'a str>,
+  /// The sub assemblies holding the creation code of contracts created with `new`.
+  creation_subs: Vec<(&'a str, usize)>,
+}
+
+impl<'a> Context<'a> {
+  /// Creates the context for code of `contract` that is embedded in the code of `creators`.
+  pub fn new(
+    contracts: &'a [Contract],
+    contract: &'a Contract,
+    creators: &[&'a str],
+  ) -> Context<'a> {
+    let mut creators = creators.to_vec();
+    creators.push(&contract.identifier);
+    Context {
+      asm: Assembly::new(),
+      contracts,
+      contract,
+      locals: vec![],
+      functions: vec![],
+      pending_functions: vec![],
+      frame: None,
+      placeholder: None,
+      unchecked: false,
+      creators,
+      creation_subs: vec![],
+    }
+  }
+
+  pub fn find_contract(&self, name: &str) -> Option<&'a Contract> {
+    self.contracts.iter().find(|c| c.identifier == name)
+  }
+
+  /// Returns the storage position and declaration of a state variable.
+  pub fn state_variable(
+    &self,
+    name: &str,
+  ) -> Option<(StoragePosition, &'a StateVariableDeclaration)> {
+    self
+      .contract
+      .storage_layout()
+      .into_iter()
+      .find(|(_, decl)| decl.identifier == name)
+  }
+
+  /// Returns the function with the given name and number of parameters, or the only function of
+  /// that name, so that calling it reports how the arguments don't match.
+  pub fn function(&self, name: &str, arg_count: usize) -> Option<&'a FunctionDefinition> {
+    overload(
+      self
+        .contract
+        .functions()
+        .into_iter()
+        .filter(|f| f.identifier.as_deref() == Some(name))
+        .collect(),
+      |f| f.parameter_list.len(),
+      arg_count,
+    )
+  }
+
+  pub fn event(&self, name: &str, arg_count: usize) -> Option<&'a EventDefinition> {
+    overload(
+      self
+        .contract
+        .contract_part
+        .iter()
+        .filter_map(|part| match part {
+          ContractPart::EventDefinition(event) if event.identifier == name => Some(event),
+          _ => None,
+        })
+        .collect(),
+      |event| event.parameters.len(),
+      arg_count,
+    )
+  }
+
+  pub fn error(&self, name: &str, arg_count: usize) -> Option<&'a ErrorDefinition> {
+    overload(
+      self
+        .contract
+        .contract_part
+        .iter()
+        .filter_map(|part| match part {
+          ContractPart::ErrorDefinition(error) if error.identifier == name => Some(error),
+          _ => None,
+        })
+        .collect(),
+      |error| error.parameter_list.len(),
+      arg_count,
+    )
+  }
+
+  pub fn modifier(&self, name: &str) -> Option<&'a ModifierDefinition> {
+    self
+      .contract
+      .contract_part
+      .iter()
+      .find_map(|part| match part {
+        ContractPart::ModifierDefinition(modifier) if modifier.identifier == name => Some(modifier),
+        _ => None,
+      })
+  }
+
+  /// Returns the sub assembly holding the creation code of `contract`, generating it the first
+  /// time it is needed.
+  pub fn creation_sub(&mut self, contract: &'a Contract) -> Result<usize, String> {
+    if self.creators.contains(&contract.identifier.as_str()) {
+      return Err(
+        "Circular reference to contract bytecode via \"new\" is not allowed.".to_string(),
+      );
+    }
+    let existing = self
+      .creation_subs
+      .iter()
+      .find(|(name, _)| *name == contract.identifier)
+      .map(|(_, sub)| *sub);
+    if let Some(sub) = existing {
+      return Ok(sub);
+    }
+    let assembly = contract.creation_assembly(self.contracts, &self.creators)?;
+    let sub = self.asm.append_sub(assembly);
+    self.creation_subs.push((&contract.identifier, sub));
+    Ok(sub)
+  }
+
+  /// Returns the entry tag of an internal function, scheduling its body to be generated.
+  pub fn function_tag(&mut self, function: &'a FunctionDefinition) -> Tag {
+    let generated = self
+      .functions
+      .iter()
+      .find(|(f, _)| std::ptr::eq(*f, function))
+      .map(|(_, tag)| *tag);
+    match generated {
+      Some(tag) => tag,
+      None => {
+        let tag = self.asm.new_tag();
+        self.functions.push((function, tag));
+        self.pending_functions.push((function, tag));
+        tag
+      }
+    }
+  }
+
+  /// Generates the bodies of every internal function referenced so far.
+  pub fn generate_functions(&mut self) -> Result<(), String> {
+    while let Some((function, tag)) = self.pending_functions.pop() {
+      function.body_op_codes(self, tag)?;
+    }
+    Ok(())
+  }
+
+  pub fn open_scope(&self) -> Scope {
+    Scope {
+      locals: self.locals.len(),
+      stack_height: self.asm.stack_height(),
+    }
+  }
+
+  /// Pops everything that was pushed since the scope was opened.
+  pub fn close_scope(&mut self, scope: Scope) {
+    while self.asm.stack_height() > scope.stack_height {
+      self.asm.op(OpCode::POP);
+    }
+    self.locals.truncate(scope.locals);
+  }
+
+  pub fn bind_local(
+    &mut self,
+    name: &str,
+    type_name: &TypeName,
+    storage_location: &Option<StorageLocation>,
+    position: usize,
+  ) {
+    self.locals.push(Local {
+      name: name.to_string(),
+      position,
+      type_name: type_name.clone(),
+      storage_location: storage_location.clone(),
+    });
+  }
+
+  /// Binds the item on top of the stack to a new local variable.
+  pub fn push_local(
+    &mut self,
+    name: &str,
+    type_name: &TypeName,
+    storage_location: &Option<StorageLocation>,
+  ) {
+    let position = self.asm.stack_height() - 1;
+    self.bind_local(name, type_name, storage_location, position);
+  }
+
+  pub fn locals(&self) -> &[Local] {
+    &self.locals
+  }
+
+  /// Swaps the visible locals, e.g. to hide the locals of a function from its modifiers.
+  pub fn replace_locals(&mut self, locals: Vec<Local>) -> Vec<Local> {
+    std::mem::replace(&mut self.locals, locals)
+  }
+
+  pub fn local(&self, name: &str) -> Option<Local> {
+    self.locals.iter().rev().find(|l| l.name == name).cloned()
+  }
+
+  /// Copies the stack slot at `position` onto the top of the stack.
+  pub fn load_local(&mut self, position: usize) -> Result<(), String> {
+    let depth = self.asm.stack_height() - position;
+    if depth > 16 {
+      return Err("Stack too deep, try removing local variables.".to_string());
+    }
+    self.asm.dup(depth);
+    Ok(())
+  }
+
+  /// Moves the top of the stack into the stack slot at `position`.
+  pub fn store_local(&mut self, position: usize) -> Result<(), String> {
+    let depth = self.asm.stack_height() - 1 - position;
+    if depth > 16 {
+      return Err("Stack too deep, try removing local variables.".to_string());
+    }
+    if depth > 0 {
+      self.asm.swap(depth);
+    }
+    self.asm.op(OpCode::POP);
+    Ok(())
+  }
+
+  pub fn enter_frame(&mut self, frame: Frame) -> Option<Frame> {
+    self.frame.replace(frame)
+  }
+
+  pub fn leave_frame(&mut self, previous: Option<Frame>) {
+    self.frame = previous;
+  }
+
+  pub fn frame(&self) -> Option<&Frame> {
+    self.frame.as_ref()
+  }
+
+  /// Pops everything above the current function's frame and jumps to its exit.
+  pub fn jump_to_exit(&mut self) -> Result<(), String> {
+    let (exit, frame_height) = match &self.frame {
+      Some(frame) => (frame.exit, frame.stack_height),
+      None => {
+        return Err("\"return\" is only allowed inside of functions and modifiers".to_string())
+      }
+    };
+    let height = self.asm.stack_height();
+    while self.asm.stack_height() > frame_height {
+      self.asm.op(OpCode::POP);
+    }
+    self.asm.jump(exit);
+    self.asm.set_stack_height(height);
+    Ok(())
+  }
+
+  pub fn enter_placeholder(
+    &mut self,
+    placeholder: Option<Placeholder<'a>>,
+  ) -> Option<Placeholder<'a>> {
+    std::mem::replace(&mut self.placeholder, placeholder)
+  }
+
+  /// The code that replaces `_` in the modifier being generated.
+  pub fn placeholder(&self) -> Option<Placeholder<'a>> {
+    self.placeholder.clone()
+  }
+
+  /// Turns the overflow checks of arithmetic operations off or on, returning the previous
+  /// setting.
+  pub fn set_unchecked(&mut self, unchecked: bool) -> bool {
+    std::mem::replace(&mut self.unchecked, unchecked)
+  }
+
+  /// Whether code inside of an `unchecked` block is being generated.
+  pub fn is_unchecked(&self) -> bool {
+    self.unchecked
+  }
+
+  /// Emits `revert(0, 0)`.
+  pub fn revert(&mut self) {
+    self.asm.push(&[0]);
+    self.asm.op(OpCode::DUP1);
+    self.asm.op(OpCode::REVERT);
+  }
+
+  /// Reverts with the return data of the last call, forwarding the error of the callee.
+  pub fn forward_revert(&mut self) {
+    self.asm.op(OpCode::RETURNDATASIZE);
+    self.asm.push(&[0]);
+    self.asm.op(OpCode::DUP1);
+    self.asm.op(OpCode::RETURNDATACOPY);
+    self.asm.op(OpCode::RETURNDATASIZE);
+    self.asm.push(&[0]);
+    self.asm.op(OpCode::REVERT);
+  }
+
+  /// Reverts with `Panic(code)`, the error solc raises for failed assertions, arithmetic
+  /// errors and out-of-bounds accesses.
+  pub fn panic(&mut self, code: u8) {
+    self.asm.push(&[0x4e, 0x48, 0x7b, 0x71]);
+    self.asm.push(&[0xe0]);
+    self.asm.op(OpCode::SHL);
+    self.asm.push(&[0]);
+    self.asm.op(OpCode::MSTORE);
+    self.asm.push(&[code]);
+    self.asm.push(&[4]);
+    self.asm.op(OpCode::MSTORE);
+    self.asm.push(&[0x24]);
+    self.asm.push(&[0]);
+    self.asm.op(OpCode::REVERT);
+  }
+
+  /// Reverts if the call carries any ether.
+  pub fn non_payable_guard(&mut self) {
+    let tag = self.asm.new_tag();
+    self.asm.op(OpCode::CALLVALUE);
+    self.asm.op(OpCode::DUP1);
+    self.asm.op(OpCode::ISZERO);
+    self.asm.jumpi(tag);
+    self.revert();
+    self.asm.tag(tag);
+    self.asm.op(OpCode::POP);
+  }
+
+  /// Replaces the mapping slot and key on top of the stack with the slot of the value,
+  /// `keccak256(key . slot)`, using the scratch space at 0x00.
+  pub fn mapping_slot(&mut self) {
+    self.asm.push(&[0]);
+    self.asm.op(OpCode::MSTORE);
+    self.asm.push(&[0x20]);
+    self.asm.op(OpCode::MSTORE);
+    self.asm.push(&[0x40]);
+    self.asm.push(&[0]);
+    self.asm.op(OpCode::SHA3);
+  }
+
+  /// Pushes the slot of a state variable and, if it shares the slot with others, its byte
+  /// offset, returning the reference to it.
+  pub fn push_storage_position(
+    &mut self,
+    position: StoragePosition,
+    type_name: &TypeName,
+  ) -> Reference {
+    self.asm.push_usize(position.slot);
+    if type_name.is_packed() {
+      self.asm.push_usize(position.offset);
+    }
+    Reference {
+      location: StorageLocation::Storage,
+      type_name: type_name.clone(),
+    }
+  }
+
+  pub fn load_free_memory_pointer(&mut self) {
+    self.asm.push(&[FREE_MEMORY_POINTER]);
+    self.asm.op(OpCode::MLOAD);
+  }
+
+  /// Pushes the value of a constant, which has to be an integer that fits into 256 bits.
+  pub fn push_constant(&mut self, value: &Rational) -> Result<(), String> {
+    if !value.is_integer() {
+      return Err(format!(
+        "Type rational_const {} is not implicitly convertible to an integer type.",
+        value
+      ));
+    }
+    match value.to_word() {
+      Some(word) => {
+        self.asm.push(&word);
+        Ok(())
+      }
+      None => Err(format!("int_const {} does not fit into 256 bits", value)),
+    }
+  }
+
+  /// Cleans the dirty higher order bits of the value on top of the stack. Signed numbers are
+  /// sign extended and `bytesN` values are left aligned, so their lower order bits are cleaned.
+  pub fn cleanup(&mut self, type_name: &TypeName) {
+    match type_name {
+      TypeName::UserDefinedTypeName(_) => {
+        self.asm.push(&[0xff; 20]);
+        self.asm.op(OpCode::AND);
+      }
+      TypeName::ElementaryTypeName(ElementaryTypeName::Bool) => {
+        self.asm.op(OpCode::ISZERO);
+        self.asm.op(OpCode::ISZERO);
+      }
+      TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(bytes)) if bytes.size() < 32 => {
+        let mut mask = vec![0; 32];
+        for byte in mask.iter_mut().take(bytes.size()) {
+          *byte = 0xff;
+        }
+        self.asm.push(&mask);
+        self.asm.op(OpCode::AND);
+      }
+      TypeName::ElementaryTypeName(name) => match name.size() {
+        Some(size) if size < 32 && name.is_signed() => {
+          self.asm.push(&[size as u8 - 1]);
+          self.asm.op(OpCode::SIGNEXTEND);
+        }
+        Some(size) if size < 32 => {
+          self.asm.push(&vec![0xff; size]);
+          self.asm.op(OpCode::AND);
+        }
+        _ => {}
+      },
+      _ => {}
+    }
+  }
+}
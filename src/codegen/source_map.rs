@@ -0,0 +1,85 @@
+use crate::codegen::assembly::{Assembly, AssemblyItem, JumpType};
+
+/// The compressed source map of the code of an assembly, without its sub assemblies, like solc
+/// generates it for the source with index `source_index`. There is one `s:l:f:j:m` entry per
+/// instruction: the start and length of the source range, the source index, the jump type and
+/// the modifier depth. Fields that are the same as in the previous entry are left out, and so
+/// are trailing colons.
+pub fn source_map(asm: &Assembly, source_index: usize, source_length: usize) -> String {
+  let mut entries = vec![];
+  let mut previous: Vec<String> = vec![];
+  for (item, location) in asm.items.iter().zip(asm.locations.iter()) {
+    if let AssemblyItem::Comment(_) = item {
+      continue;
+    }
+    let (start, length, index) = match location.span {
+      Some(span) => (
+        span.start(source_length).to_string(),
+        span.length.to_string(),
+        source_index.to_string(),
+      ),
+      None => ("-1".to_string(), "-1".to_string(), "-1".to_string()),
+    };
+    let jump = match location.jump {
+      JumpType::Regular => "-",
+      JumpType::IntoFunction => "i",
+      JumpType::OutOfFunction => "o",
+    };
+    let fields = vec![
+      start,
+      length,
+      index,
+      jump.to_string(),
+      location.modifier_depth.to_string(),
+    ];
+    let entry: Vec<&str> = fields
+      .iter()
+      .enumerate()
+      .map(|(i, field)| match previous.get(i) {
+        Some(p) if p == field => "",
+        _ => field,
+      })
+      .collect();
+    entries.push(entry.join(":").trim_end_matches(':').to_string());
+    previous = fields;
+  }
+  entries.join(";")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{op_codes::OpCode, span::Span};
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn compresses_repeated_fields() {
+    let mut asm = Assembly::new();
+    asm.push(&[0x80]);
+    asm.set_span(Some(Span {
+      rest: 20,
+      length: 8,
+    }));
+    asm.comment("function f()".to_string());
+    asm.push(&[0x40]);
+    asm.op(OpCode::MSTORE);
+    let tag = asm.new_tag();
+    asm.jump(tag);
+    asm.mark_jump(JumpType::IntoFunction);
+    asm.set_span(Some(Span {
+      rest: 15,
+      length: 8,
+    }));
+    asm.set_modifier_depth(1);
+    asm.tag(tag);
+    asm.set_stack_height(1);
+    asm.set_span(None);
+    asm.op(OpCode::JUMP);
+    asm.mark_jump(JumpType::OutOfFunction);
+    assert_eq!(
+      source_map(&asm, 0, 30),
+      "-1:-1:-1:-:0;10:8:0;;;:::i;15:::-:1;-1:-1:-1:o"
+    )
+  }
+}
@@ -1,14 +1,17 @@
 use crate::{
-    expression::{parse_parameter_list, FunctionCall, Parameter, TypeName},
+    codegen::Context,
+    definition::contract_part::{parse_function_attributes, ModifierInvocation, Placeholder},
+    expression::{parse_parameter_list, Parameter},
     op_codes::OpCode,
-    statement::{parse_block, Statement, VariableDeclaration, VariableDefinition},
-    visibility::{parse as parse_visibility, Visibility},
+    span::{spanned, Span},
+    state_mutability::StateMutability,
+    statement::{parse_block, Statement},
+    visibility::Visibility,
 };
 use nom::{
     bytes::complete::tag,
     character::complete::multispace0,
-    combinator::{complete, map, opt},
-    dbg_dmp,
+    combinator::{complete, map},
     sequence::{preceded, tuple},
     IResult,
 };
@@ -17,54 +20,86 @@ use nom::{
 pub struct Constructor {
     pub parameter_list: Vec<Parameter>,
     pub visibility: Option<Visibility>,
-    pub statements: Vec<Statement>,
+    pub state_mutability: Option<StateMutability>,
+    pub modifiers: Vec<ModifierInvocation>,
+    pub statements: Vec<(Statement, Span)>,
+    pub span: Span,
 }
 
 impl Constructor {
-    pub fn op_codes(self) -> Vec<u32> {
-        // TODO: Don't return this if its payable
-        let non_payable_guard = vec![
-            OpCode::CALLVALUE as u32,
-            OpCode::DUP1 as u32,
-            OpCode::ISZERO as u32,
-            OpCode::PUSH2 as u32,
-            0x00,
-            0x10,
-            OpCode::JUMPI as u32,
-            OpCode::PUSH1 as u32,
-            0x00,
-            OpCode::DUP1 as u32,
-            OpCode::REVERT as u32,
-            OpCode::JUMPDEST as u32,
-            OpCode::POP as u32,
-        ];
+    /// The body of the constructor, run by the creation code before the runtime code is returned.
+    pub fn op_codes<'a>(&'a self, ctx: &mut Context<'a>) -> Result<(), String> {
+        let outer = ctx.asm.set_span(Some(self.span));
+        self.decode_parameters(ctx)?;
+        let placeholder = Placeholder {
+            modifiers: &self.modifiers,
+            index: 0,
+            block: &self.statements,
+            locals: ctx.locals().to_vec(),
+            returns: vec![],
+        };
+        placeholder.op_codes(ctx)?;
+        ctx.asm.set_span(outer);
+        Ok(())
+    }
 
-        let mut codes = vec![];
-        codes.extend(non_payable_guard);
-        // TODO: CTOR PARAMS
-        for statement in self.statements {
-            codes.extend(statement.op_codes())
+    /// Pushes the ABI encoded arguments, which are appended to the creation code, and binds them
+    /// to the parameters. Reverts if the arguments are too short.
+    fn decode_parameters(&self, ctx: &mut Context) -> Result<(), String> {
+        if self.parameter_list.is_empty() {
+            return Ok(());
         }
-
-        codes
+        let valid = ctx.asm.new_tag();
+        ctx.asm.push_usize(32 * self.parameter_list.len());
+        ctx.asm.push_program_size();
+        ctx.asm.op(OpCode::ADD);
+        ctx.asm.op(OpCode::CODESIZE);
+        ctx.asm.op(OpCode::LT);
+        ctx.asm.op(OpCode::ISZERO);
+        ctx.asm.jumpi(valid);
+        ctx.revert();
+        ctx.asm.tag(valid);
+        for (index, param) in self.parameter_list.iter().enumerate() {
+            if !param.typename.is_value_type() {
+                return Err(format!(
+                    "Constructor parameters of type {} are not supported yet",
+                    param.typename.abi_name()
+                ));
+            }
+            ctx.asm.push(&[0x20]);
+            ctx.asm.push_usize(32 * index);
+            ctx.asm.push_program_size();
+            ctx.asm.op(OpCode::ADD);
+            ctx.asm.push(&[0]);
+            ctx.asm.op(OpCode::CODECOPY);
+            ctx.asm.push(&[0]);
+            ctx.asm.op(OpCode::MLOAD);
+            ctx.cleanup(&param.typename);
+            if let Some(identifier) = &param.identifier {
+                ctx.push_local(identifier, &param.typename, &param.storage_location);
+            }
+        }
+        Ok(())
     }
 }
 
-use std::str::from_utf8;
 pub fn parse(i: &[u8]) -> IResult<&[u8], Constructor> {
     map(
-        tuple((
+        spanned(tuple((
             preceded(multispace0, complete(tag("constructor"))),
             preceded(multispace0, parse_parameter_list),
-            opt(preceded(multispace0, parse_visibility)),
+            parse_function_attributes,
             preceded(multispace0, parse_block),
-        )),
-        |x| {
-            let (_, parameter_list, visibility, statements) = x;
+        ))),
+        |(x, span)| {
+            let (_, parameter_list, attributes, statements) = x;
             Constructor {
                 parameter_list,
                 statements,
-                visibility,
+                span,
+                visibility: attributes.visibility,
+                state_mutability: attributes.state_mutability,
+                modifiers: attributes.modifiers,
             }
         },
     )(i)
@@ -74,8 +109,11 @@ pub fn parse(i: &[u8]) -> IResult<&[u8], Constructor> {
 mod tests {
     use super::*;
     use crate::elementary_type_name::ElementaryTypeName;
-    use crate::expression::{Expression, FunctionCallArguments, PrimaryExpression, TypeName};
+    use crate::expression::{
+        Expression, FunctionCall, FunctionCallArguments, PrimaryExpression, TypeName,
+    };
     use crate::literal::Number;
+    use crate::statement::{VariableDeclaration, VariableDefinition};
     use pretty_assertions::assert_eq;
     use std::str::from_utf8;
 
@@ -98,33 +136,45 @@ mod tests {
                             identifier: Some("lol".to_string())
                         }],
                         visibility: Some(Visibility::Public),
-                        statements: vec![Statement::VariableDefinition(VariableDefinition {
-                            declarations: vec![VariableDeclaration {
-                                type_name: TypeName::ElementaryTypeName(
-                                    ElementaryTypeName::Address
-                                ),
-                                storage_location: None,
-                                identifier: "to".to_string(),
-                            },],
-                            rhs: Expression::FunctionCall(FunctionCall {
-                                expr: Box::new(Expression::PrimaryExpression(
-                                    PrimaryExpression::ElementaryTypeNameExpression(
+                        state_mutability: None,
+                        modifiers: vec![],
+                        statements: vec![(
+                            Statement::VariableDefinition(VariableDefinition {
+                                declarations: vec![Some(VariableDeclaration {
+                                    type_name: TypeName::ElementaryTypeName(
                                         ElementaryTypeName::Address
-                                    )
-                                )),
-                                args: FunctionCallArguments::ExpressionList(vec![
-                                    Expression::PrimaryExpression(
-                                        PrimaryExpression::NumberLiteral((
-                                            Number::Hex(
-                                                "0xFB88dE099e13c3ED21F80a7a1E49f8CAEcF10df6"
-                                                    .to_string()
-                                            ),
-                                            None
-                                        ))
-                                    )
-                                ])
-                            })
-                        })],
+                                    ),
+                                    storage_location: None,
+                                    identifier: "to".to_string(),
+                                })],
+                                rhs: Expression::FunctionCall(FunctionCall {
+                                    expr: Box::new(Expression::PrimaryExpression(
+                                        PrimaryExpression::ElementaryTypeNameExpression(
+                                            ElementaryTypeName::Address
+                                        )
+                                    )),
+                                    args: FunctionCallArguments::ExpressionList(vec![
+                                        Expression::PrimaryExpression(
+                                            PrimaryExpression::NumberLiteral((
+                                                Number::Hex(
+                                                    "0xFB88dE099e13c3ED21F80a7a1E49f8CAEcF10df6"
+                                                        .to_string()
+                                                ),
+                                                None
+                                            ))
+                                        )
+                                    ])
+                                })
+                            }),
+                            Span {
+                                rest: 68,
+                                length: 65
+                            }
+                        )],
+                        span: Span {
+                            rest: 103,
+                            length: 103
+                        },
                     }
                 )
             )
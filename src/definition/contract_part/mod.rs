@@ -1,53 +1,157 @@
 use crate::{
-  atom::parse_identifier,
-  definition::constructor::{parse as parse_constructor, Constructor},
+  abi,
+  atom::{
+    keyword::{parse_error, parse_event, parse_modifier, parse_struct},
+    parse_identifier, word_end,
+  },
+  codegen::{Context, Frame, JumpType, Local, Tag},
+  definition::{
+    constructor::{parse as parse_constructor, Constructor},
+    StoragePosition,
+  },
+  elementary_type_name::{ElementaryTypeName, UInt},
   expression::{
-    parse_expression, parse_parameter_list, parse_type_name, Expression, Parameter, TypeName,
+    array_length, bytes, encoding, parse_call_arguments, parse_expression, parse_parameter_list,
+    parse_type_name, push_default_value, Expression, FunctionCallArguments, Parameter, Reference,
+    TypeName,
   },
-  state_mutability::StateMutability,
-  statement::{parse_block, Statement},
+  op_codes::OpCode,
+  span::{spanned, Span},
+  state_mutability::{parse as parse_state_mutability, StateMutability},
+  statement::{block_op_codes, parse_block, Statement},
+  storage_location::StorageLocation,
   visibility::{parse as parse_visibility, Visibility},
 };
 use nom::{
   branch::alt,
   bytes::complete::tag,
   character::complete::{char, multispace0, multispace1},
-  combinator::{complete, map, opt},
-  dbg_dmp,
-  sequence::{preceded, terminated, tuple},
+  combinator::{complete, map, map_opt, opt, verify},
+  multi::{many0, separated_list},
+  sequence::{delimited, preceded, terminated, tuple},
   IResult,
 };
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct StateVariableDeclaration {
   pub type_name: TypeName,
-  // TODO: pub visibility: 'public' | 'internal' | 'private' | 'constant',
+  // TODO: 'constant'
+  pub visibility: Option<Visibility>,
   pub identifier: String,
-  pub rhs: Expression,
+  pub rhs: Option<Expression>,
+}
+
+impl StateVariableDeclaration {
+  pub fn is_public(&self) -> bool {
+    self.visibility == Some(Visibility::Public)
+  }
+
+  /// The parameters and return type of the getter of a public state variable: one key per
+  /// level of mapping and one index per level of array.
+  pub fn getter_types(&self) -> (Vec<TypeName>, &TypeName) {
+    let mut keys = vec![];
+    let mut type_name = &self.type_name;
+    loop {
+      match type_name {
+        TypeName::Mapping(key, value) => {
+          keys.push(*key.clone());
+          type_name = value.as_ref();
+        }
+        TypeName::Array(element, _) => {
+          keys.push(TypeName::ElementaryTypeName(ElementaryTypeName::UInt(
+            UInt::Uint256,
+          )));
+          type_name = element.as_ref();
+        }
+        _ => return (keys, type_name),
+      }
+    }
+  }
+
+  pub fn signature(&self) -> String {
+    let (keys, _) = self.getter_types();
+    let keys: Vec<String> = keys.iter().map(|k| k.abi_name()).collect();
+    format!("{}({})", self.identifier, keys.join(","))
+  }
+
+  pub fn selector(&self) -> Vec<u8> {
+    abi::selector(&self.signature())
+  }
+
+  /// The getter of a public state variable, called from the dispatcher.
+  pub fn getter_op_codes(
+    &self,
+    ctx: &mut Context,
+    position: StoragePosition,
+  ) -> Result<(), String> {
+    ctx.non_payable_guard();
+    let (keys, value) = self.getter_types();
+    if !value.is_value_type() && !value.is_byte_array() {
+      return Err(format!(
+        "Getters for values of type {} are not supported yet",
+        value.abi_name()
+      ));
+    }
+    let mut reference = ctx.push_storage_position(position, &self.type_name);
+    for (index, key) in keys.iter().enumerate() {
+      if key.is_dynamic() {
+        return Err(format!(
+          "Mapping keys of type {} are not supported yet",
+          key.abi_name()
+        ));
+      }
+      ctx.asm.push_usize(4 + 32 * index);
+      ctx.asm.op(OpCode::CALLDATALOAD);
+      reference.type_name = reference.type_name.index_storage(ctx)?;
+    }
+    if value.is_byte_array() {
+      bytes::copy_to_memory(ctx)?;
+    } else {
+      reference.load(ctx)?;
+    }
+    let base = ctx.asm.stack_height() - 1;
+    abi_return(&[value.clone()], base, ctx)
+  }
+}
+
+/// ABI encodes the values at the stack positions from `base` on and returns them to the
+/// caller.
+fn abi_return(types: &[TypeName], base: usize, ctx: &mut Context) -> Result<(), String> {
+  ctx.load_free_memory_pointer();
+  let pointer = ctx.asm.stack_height() - 1;
+  ctx.load_local(pointer)?;
+  encoding::encode(types, base, ctx)?;
+  ctx.load_local(pointer)?;
+  ctx.asm.op(OpCode::SWAP1);
+  ctx.asm.op(OpCode::SUB);
+  ctx.load_local(pointer)?;
+  ctx.asm.op(OpCode::RETURN);
+  ctx.asm.set_stack_height(0);
+  Ok(())
 }
 
 pub fn parse_state_variable_declaration(i: &[u8]) -> IResult<&[u8], StateVariableDeclaration> {
   map(
     tuple((
       preceded(multispace0, parse_type_name),
-      preceded(multispace1, parse_identifier),
-      preceded(
+      many0(preceded(
         multispace1,
-        preceded(
-          char('='),
-          preceded(
-            multispace0,
-            terminated(parse_expression, preceded(multispace0, char(';'))),
-          ),
-        ),
-      ),
+        terminated(parse_visibility, word_end),
+      )),
+      preceded(multispace1, parse_identifier),
+      opt(preceded(
+        preceded(multispace0, char('=')),
+        preceded(multispace0, parse_expression),
+      )),
+      preceded(multispace0, char(';')),
     )),
     |d| {
-      let (type_name, identifier, expr) = d;
+      let (type_name, visibility, identifier, rhs, _) = d;
       StateVariableDeclaration {
         type_name,
+        visibility: visibility.last().cloned(),
         identifier,
-        rhs: expr,
+        rhs,
       }
     },
   )(i)
@@ -57,17 +161,222 @@ pub fn parse_state_variable_declaration(i: &[u8]) -> IResult<&[u8], StateVariabl
 #[derive(Debug, PartialEq, Clone)]
 pub struct UsingForDeclaration {}
 
-// TODO: StructDefinition
+/// A struct, which can be parsed but not compiled yet.
 #[derive(Debug, PartialEq, Clone)]
-pub struct StructDefinition {}
+pub struct StructDefinition {
+  pub identifier: String,
+  pub members: Vec<(TypeName, String)>,
+}
+
+pub fn parse_struct_definition(i: &[u8]) -> IResult<&[u8], StructDefinition> {
+  map(
+    tuple((
+      preceded(multispace0, terminated(parse_struct, word_end)),
+      preceded(multispace1, parse_identifier),
+      preceded(
+        multispace0,
+        delimited(
+          char('{'),
+          many0(tuple((
+            preceded(multispace0, parse_type_name),
+            preceded(multispace1, parse_identifier),
+            preceded(multispace0, char(';')),
+          ))),
+          preceded(multispace0, char('}')),
+        ),
+      ),
+    )),
+    |(_, identifier, members)| StructDefinition {
+      identifier,
+      members: members
+        .into_iter()
+        .map(|(type_name, name, _)| (type_name, name))
+        .collect(),
+    },
+  )(i)
+}
 
-// TODO: ModifierDefinition
 #[derive(Debug, PartialEq, Clone)]
-pub struct ModifierDefinition {}
+pub struct ModifierDefinition {
+  pub identifier: String,
+  pub parameter_list: Vec<Parameter>,
+  pub block: Vec<(Statement, Span)>,
+  pub span: Span,
+}
+
+pub fn parse_modifier_definition(i: &[u8]) -> IResult<&[u8], ModifierDefinition> {
+  map(
+    spanned(tuple((
+      preceded(multispace0, terminated(parse_modifier, word_end)),
+      preceded(multispace1, parse_identifier),
+      opt(preceded(multispace0, parse_parameter_list)),
+      preceded(multispace0, parse_block),
+    ))),
+    |(x, span)| {
+      let (_, identifier, parameter_list, block) = x;
+      ModifierDefinition {
+        identifier,
+        parameter_list: parameter_list.unwrap_or_default(),
+        block,
+        span,
+      }
+    },
+  )(i)
+}
 
-// TODO: ModifierInvocation
 #[derive(Debug, PartialEq, Clone)]
-pub struct ModifierInvocation {}
+pub struct ModifierInvocation {
+  pub identifier: String,
+  pub args: Vec<Expression>,
+}
+
+fn parse_modifier_invocation(i: &[u8]) -> IResult<&[u8], ModifierInvocation> {
+  map(
+    tuple((
+      verify(parse_identifier, |identifier: &str| identifier != "returns"),
+      opt(preceded(
+        multispace0,
+        map_opt(parse_call_arguments, |args| match args {
+          FunctionCallArguments::ExpressionList(args) => Some(args),
+          FunctionCallArguments::NameValueList(_) => None,
+        }),
+      )),
+    )),
+    |x| {
+      let (identifier, args) = x;
+      ModifierInvocation {
+        identifier,
+        args: args.unwrap_or_default(),
+      }
+    },
+  )(i)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum FunctionAttribute {
+  Visibility(Visibility),
+  StateMutability(StateMutability),
+  Modifier(ModifierInvocation),
+}
+
+fn parse_function_attribute(i: &[u8]) -> IResult<&[u8], FunctionAttribute> {
+  alt((
+    map(
+      terminated(parse_visibility, word_end),
+      FunctionAttribute::Visibility,
+    ),
+    map(
+      terminated(parse_state_mutability, word_end),
+      FunctionAttribute::StateMutability,
+    ),
+    map(parse_modifier_invocation, FunctionAttribute::Modifier),
+  ))(i)
+}
+
+/// The visibility, state mutability and modifiers following the parameters of a function or
+/// constructor, in any order.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FunctionAttributes {
+  pub visibility: Option<Visibility>,
+  pub state_mutability: Option<StateMutability>,
+  pub modifiers: Vec<ModifierInvocation>,
+}
+
+pub fn parse_function_attributes(i: &[u8]) -> IResult<&[u8], FunctionAttributes> {
+  map(
+    many0(preceded(multispace0, parse_function_attribute)),
+    |attributes| {
+      attributes
+        .into_iter()
+        .fold(FunctionAttributes::default(), |mut acc, attribute| {
+          match attribute {
+            FunctionAttribute::Visibility(v) => acc.visibility = Some(v),
+            FunctionAttribute::StateMutability(m) => acc.state_mutability = Some(m),
+            FunctionAttribute::Modifier(m) => acc.modifiers.push(m),
+          }
+          acc
+        })
+    },
+  )(i)
+}
+
+/// The code that replaces the `_` of a modifier: the next modifier in invocation order, or the
+/// body of the function once every modifier has been applied.
+#[derive(Debug, Clone)]
+pub struct Placeholder<'a> {
+  pub modifiers: &'a [ModifierInvocation],
+  pub index: usize,
+  pub block: &'a [(Statement, Span)],
+  /// The locals of the function, visible to its body and to the modifier arguments.
+  pub locals: Vec<Local>,
+  pub returns: Vec<(usize, TypeName)>,
+}
+
+impl<'a> Placeholder<'a> {
+  pub fn op_codes(&self, ctx: &mut Context<'a>) -> Result<(), String> {
+    let scope = ctx.open_scope();
+    let outer = ctx.replace_locals(self.locals.clone());
+    let (statements, span, returns, next) = match self.modifiers.get(self.index) {
+      None => (self.block, None, self.returns.clone(), None),
+      Some(invocation) => {
+        let modifier = ctx
+          .modifier(&invocation.identifier)
+          .ok_or_else(|| format!("Undeclared modifier: {}", invocation.identifier))?;
+        if invocation.args.len() != modifier.parameter_list.len() {
+          return Err(format!(
+            "Wrong argument count for modifier invocation: {} arguments given but expected {}.",
+            invocation.args.len(),
+            modifier.parameter_list.len()
+          ));
+        }
+        let base = ctx.asm.stack_height();
+        for (arg, param) in invocation.args.iter().zip(modifier.parameter_list.iter()) {
+          arg.converted_value(&param.typename, &param.storage_location, ctx)?;
+        }
+        ctx.replace_locals(vec![]);
+        for (index, param) in modifier.parameter_list.iter().enumerate() {
+          if let Some(identifier) = &param.identifier {
+            ctx.bind_local(
+              identifier,
+              &param.typename,
+              &param.storage_location,
+              base + index,
+            );
+          }
+        }
+        let next = Placeholder {
+          index: self.index + 1,
+          ..self.clone()
+        };
+        (&modifier.block[..], Some(modifier.span), vec![], Some(next))
+      }
+    };
+
+    let exit = ctx.asm.new_tag();
+    let previous_placeholder = ctx.enter_placeholder(next);
+    let previous_frame = ctx.enter_frame(Frame {
+      exit,
+      stack_height: ctx.asm.stack_height(),
+      returns,
+    });
+    // The code of a modifier belongs to its definition, the function body to the `_` it replaces
+    let outer_span = span.map(|span| ctx.asm.set_span(Some(span)));
+    let outer_depth = ctx.asm.set_modifier_depth(self.index);
+    let body = ctx.open_scope();
+    block_op_codes(statements, ctx)?;
+    ctx.close_scope(body);
+    ctx.asm.tag(exit);
+    ctx.asm.set_modifier_depth(outer_depth);
+    if let Some(outer_span) = outer_span {
+      ctx.asm.set_span(outer_span);
+    }
+    ctx.leave_frame(previous_frame);
+    ctx.enter_placeholder(previous_placeholder);
+    ctx.replace_locals(outer);
+    ctx.close_scope(scope);
+    Ok(())
+  }
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionDefinition {
@@ -76,23 +385,150 @@ pub struct FunctionDefinition {
   pub state_mutability: Option<StateMutability>,
   pub parameter_list: Vec<Parameter>,
   pub returns: Vec<Parameter>,
-  pub block: Vec<Statement>,
+  pub modifiers: Vec<ModifierInvocation>,
+  pub block: Vec<(Statement, Span)>,
+  pub span: Span,
+}
+
+impl FunctionDefinition {
+  pub fn parameter_names(&self) -> Vec<Option<&str>> {
+    self
+      .parameter_list
+      .iter()
+      .map(|param| param.identifier.as_deref())
+      .collect()
+  }
+
+  /// Whether the function can be called through a message call.
+  pub fn is_external(&self) -> bool {
+    match self.visibility {
+      Some(Visibility::Internal) | Some(Visibility::Private) => false,
+      _ => self.identifier.is_some(),
+    }
+  }
+
+  pub fn signature(&self) -> String {
+    let params: Vec<String> = self
+      .parameter_list
+      .iter()
+      .map(|p| p.typename.abi_name())
+      .collect();
+    format!(
+      "{}({})",
+      self.identifier.clone().unwrap_or_default(),
+      params.join(",")
+    )
+  }
+
+  pub fn selector(&self) -> Vec<u8> {
+    abi::selector(&self.signature())
+  }
+
+  /// Entry point from the dispatcher: decodes the calldata, runs the function and returns the
+  /// ABI encoded results.
+  pub fn external_op_codes<'a>(&'a self, ctx: &mut Context<'a>) -> Result<(), String> {
+    let outer = ctx.asm.set_span(Some(self.span));
+    if self.state_mutability != Some(StateMutability::Payable) {
+      ctx.non_payable_guard();
+    }
+    let return_tag = ctx.asm.new_tag();
+    ctx.asm.push_tag(return_tag);
+    for (index, param) in self.parameter_list.iter().enumerate() {
+      if !param.typename.is_value_type() && !param.typename.is_byte_array() {
+        return Err(format!(
+          "Parameters of type {} are not supported yet",
+          param.typename.abi_name()
+        ));
+      }
+      ctx.asm.push_usize(4 + 32 * index);
+      ctx.asm.op(OpCode::CALLDATALOAD);
+      if param.typename.is_byte_array() {
+        ctx.asm.push(&[4]);
+        ctx.asm.op(OpCode::ADD);
+        bytes::abi_decode(ctx);
+      } else {
+        ctx.cleanup(&param.typename);
+      }
+    }
+    let function_tag = ctx.function_tag(self);
+    ctx.asm.jump(function_tag);
+    ctx.asm.mark_jump(JumpType::IntoFunction);
+    ctx.asm.tag(return_tag);
+    ctx.asm.set_stack_height(self.returns.len());
+
+    if self.returns.is_empty() {
+      ctx.asm.op(OpCode::STOP);
+    } else {
+      let types: Vec<TypeName> = self.returns.iter().map(|p| p.typename.clone()).collect();
+      abi_return(&types, 0, ctx)?;
+    }
+    ctx.asm.set_span(outer);
+    Ok(())
+  }
+
+  /// The body of the function, entered with the return tag and the arguments on the stack. It
+  /// jumps back to the return tag with only the return values left on the stack.
+  pub fn body_op_codes<'a>(&'a self, ctx: &mut Context<'a>, tag: Tag) -> Result<(), String> {
+    let params = self.parameter_list.len();
+    let outer_span = ctx.asm.set_span(Some(self.span));
+    ctx.asm.set_stack_height(1 + params);
+    ctx.asm.comment(format!("function {}", self.signature()));
+    ctx.asm.tag(tag);
+    let outer = ctx.replace_locals(vec![]);
+    for (index, param) in self.parameter_list.iter().enumerate() {
+      if let Some(identifier) = &param.identifier {
+        ctx.bind_local(
+          identifier,
+          &param.typename,
+          &param.storage_location,
+          1 + index,
+        );
+      }
+    }
+    let mut returns = vec![];
+    for param in &self.returns {
+      push_default_value(&param.typename, &param.storage_location, ctx)?;
+      returns.push((ctx.asm.stack_height() - 1, param.typename.clone()));
+      if let Some(identifier) = &param.identifier {
+        ctx.push_local(identifier, &param.typename, &param.storage_location);
+      }
+    }
+
+    let placeholder = Placeholder {
+      modifiers: &self.modifiers,
+      index: 0,
+      block: &self.block,
+      locals: ctx.locals().to_vec(),
+      returns,
+    };
+    placeholder.op_codes(ctx)?;
+    ctx.replace_locals(outer);
+
+    let layout: Vec<usize> = (0..ctx.asm.stack_height()).collect();
+    let mut target: Vec<usize> = layout[1 + params..].to_vec();
+    target.push(0);
+    ctx.asm.rearrange(&layout, &target)?;
+    ctx.asm.op(OpCode::JUMP);
+    ctx.asm.mark_jump(JumpType::OutOfFunction);
+    ctx.asm.set_span(outer_span);
+    Ok(())
+  }
 }
 
 use std::str::from_utf8;
 fn parse_function_definition(i: &[u8]) -> IResult<&[u8], FunctionDefinition> {
   map(
-    preceded(
+    spanned(preceded(
       preceded(multispace0, complete(tag("function"))),
       tuple((
         opt(preceded(multispace1, parse_identifier)),
         preceded(multispace0, parse_parameter_list),
-        opt(preceded(multispace0, parse_visibility)),
+        parse_function_attributes,
         opt(preceded(
-          multispace1,
+          multispace0,
           preceded(
             complete(tag("returns")),
-            preceded(multispace1, parse_parameter_list),
+            preceded(multispace0, parse_parameter_list),
           ),
         )),
         alt((
@@ -100,24 +536,290 @@ fn parse_function_definition(i: &[u8]) -> IResult<&[u8], FunctionDefinition> {
           parse_block,
         )),
       )),
-    ),
-    |x| {
-      let (identifier, parameter_list, visibility, returns, block) = x;
+    )),
+    |(x, span)| {
+      let (identifier, parameter_list, attributes, returns, block) = x;
       FunctionDefinition {
         identifier,
         parameter_list,
-        visibility,
-        state_mutability: None,
+        visibility: attributes.visibility,
+        state_mutability: attributes.state_mutability,
         returns: returns.unwrap_or(Vec::new()),
+        modifiers: attributes.modifiers,
         block,
+        span,
       }
     },
   )(i)
 }
 
-// TODO: EventDefinition
 #[derive(Debug, PartialEq, Clone)]
-pub struct EventDefinition {}
+pub struct EventParameter {
+  pub type_name: TypeName,
+  pub indexed: bool,
+  pub identifier: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EventDefinition {
+  pub identifier: String,
+  pub parameters: Vec<EventParameter>,
+  pub anonymous: bool,
+}
+
+impl EventDefinition {
+  pub fn parameter_names(&self) -> Vec<Option<&str>> {
+    self
+      .parameters
+      .iter()
+      .map(|param| param.identifier.as_deref())
+      .collect()
+  }
+
+  pub fn signature(&self) -> String {
+    let params: Vec<String> = self
+      .parameters
+      .iter()
+      .map(|p| p.type_name.abi_name())
+      .collect();
+    format!("{}({})", self.identifier, params.join(","))
+  }
+
+  /// The first topic of every log emitted by a non anonymous event.
+  pub fn topic(&self) -> Vec<u8> {
+    abi::keccak256(self.signature().as_bytes())
+  }
+
+  /// Emits a log whose topics are the signature hash and the indexed arguments, with the
+  /// remaining arguments ABI encoded into its data.
+  pub fn emit_op_codes<'a>(
+    &'a self,
+    args: &[&'a Expression],
+    ctx: &mut Context<'a>,
+  ) -> Result<(), String> {
+    let indexed: Vec<usize> = (0..self.parameters.len())
+      .filter(|i| self.parameters[*i].indexed)
+      .collect();
+    let data: Vec<usize> = (0..self.parameters.len())
+      .filter(|i| !self.parameters[*i].indexed)
+      .collect();
+    if self.anonymous && indexed.len() > 4 {
+      return Err("More than 4 indexed arguments for anonymous event.".to_string());
+    }
+    if !self.anonymous && indexed.len() > 3 {
+      return Err("More than 3 indexed arguments for event.".to_string());
+    }
+
+    let start = ctx.asm.stack_height();
+    for (arg, param) in args.iter().zip(self.parameters.iter()) {
+      if let TypeName::Array(..) = &param.type_name {
+        if let Some(Reference {
+          location: StorageLocation::Storage,
+          ..
+        }) = arg.reference_type(ctx)
+        {
+          return Err(format!(
+            "Event parameters of type {} from storage are not supported yet",
+            param.type_name.abi_name()
+          ));
+        }
+      }
+      arg.converted_value(&param.type_name, &None, ctx)?;
+    }
+    // The data is the ABI encoding of the values that are not indexed
+    let types: Vec<TypeName> = data
+      .iter()
+      .map(|index| self.parameters[*index].type_name.clone())
+      .collect();
+    let base = ctx.asm.stack_height();
+    for index in &data {
+      ctx.load_local(start + index)?;
+    }
+    ctx.load_free_memory_pointer();
+    let pointer = ctx.asm.stack_height() - 1;
+    ctx.load_local(pointer)?;
+    encoding::encode(&types, base, ctx)?;
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::SUB);
+    let size = ctx.asm.stack_height() - 1;
+    for index in indexed.iter().rev() {
+      let type_name = &self.parameters[*index].type_name;
+      ctx.load_local(start + index)?;
+      match type_name {
+        // Arrays are hashed like solc does, as the concatenation of their padded elements
+        TypeName::Array(element, length) => {
+          if !element.is_value_type() {
+            return Err(format!(
+              "Indexed event parameters of type {} are not supported yet",
+              type_name.abi_name()
+            ));
+          }
+          match length {
+            Some(length) => {
+              ctx.asm.push_usize(32 * array_length(length)?);
+              ctx.asm.op(OpCode::SWAP1);
+            }
+            None => {
+              ctx.asm.op(OpCode::DUP1);
+              ctx.asm.op(OpCode::MLOAD);
+              ctx.asm.push(&[5]);
+              ctx.asm.op(OpCode::SHL);
+              ctx.asm.op(OpCode::SWAP1);
+              ctx.asm.push(&[0x20]);
+              ctx.asm.op(OpCode::ADD);
+            }
+          }
+          ctx.asm.op(OpCode::SHA3);
+        }
+        // Dynamic values live in memory, prefixed with their length
+        _ if type_name.is_dynamic() => {
+          ctx.asm.op(OpCode::DUP1);
+          ctx.asm.op(OpCode::MLOAD);
+          ctx.asm.op(OpCode::SWAP1);
+          ctx.asm.push(&[0x20]);
+          ctx.asm.op(OpCode::ADD);
+          ctx.asm.op(OpCode::SHA3);
+        }
+        _ => {}
+      }
+    }
+    let mut topics = indexed.len();
+    if !self.anonymous {
+      ctx.asm.push(&self.topic());
+      topics += 1;
+    }
+    ctx.load_local(size)?;
+    ctx.load_local(pointer)?;
+    ctx
+      .asm
+      .op(OpCode::from_byte(OpCode::LOG0 as u8 + topics as u8).unwrap());
+
+    let layout: Vec<usize> = (start..ctx.asm.stack_height()).collect();
+    ctx.asm.rearrange(&layout, &[])
+  }
+}
+
+fn parse_event_parameter(i: &[u8]) -> IResult<&[u8], EventParameter> {
+  map(
+    tuple((
+      parse_type_name,
+      opt(complete(preceded(
+        multispace1,
+        terminated(tag("indexed"), word_end),
+      ))),
+      opt(complete(preceded(multispace1, parse_identifier))),
+    )),
+    |x| {
+      let (type_name, indexed, identifier) = x;
+      EventParameter {
+        type_name,
+        indexed: indexed.is_some(),
+        identifier,
+      }
+    },
+  )(i)
+}
+
+pub fn parse_event_definition(i: &[u8]) -> IResult<&[u8], EventDefinition> {
+  map(
+    tuple((
+      preceded(multispace0, parse_event),
+      preceded(multispace1, parse_identifier),
+      preceded(
+        multispace0,
+        delimited(
+          char('('),
+          separated_list(
+            preceded(multispace0, char(',')),
+            preceded(multispace0, parse_event_parameter),
+          ),
+          preceded(multispace0, char(')')),
+        ),
+      ),
+      opt(complete(preceded(
+        multispace0,
+        terminated(tag("anonymous"), word_end),
+      ))),
+      preceded(multispace0, char(';')),
+    )),
+    |x| {
+      let (_, identifier, parameters, anonymous, _) = x;
+      EventDefinition {
+        identifier,
+        parameters,
+        anonymous: anonymous.is_some(),
+      }
+    },
+  )(i)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ErrorDefinition {
+  pub identifier: String,
+  pub parameter_list: Vec<Parameter>,
+}
+
+impl ErrorDefinition {
+  pub fn parameter_names(&self) -> Vec<Option<&str>> {
+    self
+      .parameter_list
+      .iter()
+      .map(|param| param.identifier.as_deref())
+      .collect()
+  }
+
+  pub fn signature(&self) -> String {
+    let params: Vec<String> = self
+      .parameter_list
+      .iter()
+      .map(|p| p.typename.abi_name())
+      .collect();
+    format!("{}({})", self.identifier, params.join(","))
+  }
+
+  pub fn selector(&self) -> Vec<u8> {
+    abi::selector(&self.signature())
+  }
+
+  /// Reverts with the selector of the error followed by the ABI encoded arguments.
+  pub fn revert_op_codes<'a>(
+    &'a self,
+    args: &[&'a Expression],
+    ctx: &mut Context<'a>,
+  ) -> Result<(), String> {
+    let start = ctx.asm.stack_height();
+    for (arg, param) in args.iter().zip(self.parameter_list.iter()) {
+      arg.converted_value(&param.typename, &param.storage_location, ctx)?;
+    }
+    let types: Vec<TypeName> = self
+      .parameter_list
+      .iter()
+      .map(|param| param.typename.clone())
+      .collect();
+    encoding::revert_with_selector(&self.selector(), &types, start, ctx)?;
+    ctx.asm.set_stack_height(start);
+    Ok(())
+  }
+}
+
+pub fn parse_error_definition(i: &[u8]) -> IResult<&[u8], ErrorDefinition> {
+  map(
+    tuple((
+      preceded(multispace0, terminated(parse_error, word_end)),
+      preceded(multispace1, parse_identifier),
+      preceded(multispace0, parse_parameter_list),
+      preceded(multispace0, char(';')),
+    )),
+    |x| {
+      let (_, identifier, parameter_list, _) = x;
+      ErrorDefinition {
+        identifier,
+        parameter_list,
+      }
+    },
+  )(i)
+}
 
 // TODO: EnumDefinition
 #[derive(Debug, PartialEq, Clone)]
@@ -133,28 +835,19 @@ pub enum ContractPart {
   ModifierInvocation(ModifierInvocation),
   FunctionDefinition(FunctionDefinition),
   EventDefinition(EventDefinition),
+  ErrorDefinition(ErrorDefinition),
   EnumDefinition(EnumDefinition),
 }
 
-impl ContractPart {
-  pub fn op_codes(self) -> Vec<u32> {
-    let mut codes = vec![];
-    match self {
-      ContractPart::ConstructorDefinition(ctor) => {
-        codes.extend(ctor.op_codes());
-      }
-      _ => {}
-    }
-
-    codes
-  }
-}
-
 pub fn parse(i: &[u8]) -> IResult<&[u8], ContractPart> {
   alt((
     map(parse_constructor, |x| {
       ContractPart::ConstructorDefinition(x)
     }),
+    map(parse_event_definition, ContractPart::EventDefinition),
+    map(parse_error_definition, ContractPart::ErrorDefinition),
+    map(parse_modifier_definition, ContractPart::ModifierDefinition),
+    map(parse_struct_definition, ContractPart::StructDefinition),
     map(parse_state_variable_declaration, |x| {
       ContractPart::StateVariableDeclaration(x)
     }),
@@ -171,6 +864,7 @@ mod tests {
   use crate::{
     elementary_type_name::{ElementaryTypeName, UInt},
     expression::PrimaryExpression,
+    literal::Boolean,
     visibility::Visibility,
   };
   use pretty_assertions::assert_eq;
@@ -190,15 +884,44 @@ mod tests {
           "",
           StateVariableDeclaration {
             type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
+            visibility: None,
             identifier: "a".to_string(),
-            rhs: Expression::MemberAccess(
+            rhs: Some(Expression::MemberAccess(
               Box::new(Expression::PrimaryExpression(
                 PrimaryExpression::Identifier("b".to_string())
               )),
               Box::new(Expression::PrimaryExpression(
                 PrimaryExpression::Identifier("c".to_string())
               ))
-            )
+            ))
+          }
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_public_mapping_declaration() {
+    let input = b"mapping(address => uint256) public balances;";
+    let result = parse_state_variable_declaration(input);
+    if result.is_err() {
+      result.expect("should parse public mapping");
+    } else {
+      let (remaining, declaration) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), declaration),
+        (
+          "",
+          StateVariableDeclaration {
+            type_name: TypeName::Mapping(
+              Box::new(TypeName::ElementaryTypeName(ElementaryTypeName::Address)),
+              Box::new(TypeName::ElementaryTypeName(ElementaryTypeName::UInt(
+                UInt::Uint256
+              )))
+            ),
+            visibility: Some(Visibility::Public),
+            identifier: "balances".to_string(),
+            rhs: None,
           }
         )
       )
@@ -234,7 +957,198 @@ mod tests {
               }
             ],
             returns: vec![],
+            modifiers: vec![],
             block: vec![],
+            span: Span {
+              rest: 54,
+              length: 54
+            },
+          }
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_event_definition() {
+    let input = b"event Transfer(address indexed from, address indexed, uint256 value) anonymous;";
+    let result = parse_event_definition(input);
+    if result.is_err() {
+      result.expect("should parse event definition");
+    } else {
+      let (remaining, event) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), event),
+        (
+          "",
+          EventDefinition {
+            identifier: "Transfer".to_string(),
+            parameters: vec![
+              EventParameter {
+                type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Address),
+                indexed: true,
+                identifier: Some("from".to_string()),
+              },
+              EventParameter {
+                type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Address),
+                indexed: true,
+                identifier: None,
+              },
+              EventParameter {
+                type_name: TypeName::ElementaryTypeName(ElementaryTypeName::UInt(UInt::Uint256)),
+                indexed: false,
+                identifier: Some("value".to_string()),
+              },
+            ],
+            anonymous: true,
+          }
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_error_definition() {
+    let input = b"error InsufficientBalance(uint256 available, uint256);";
+    let result = parse_error_definition(input);
+    if result.is_err() {
+      result.expect("should parse error definition");
+    } else {
+      let (remaining, error) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), error),
+        (
+          "",
+          ErrorDefinition {
+            identifier: "InsufficientBalance".to_string(),
+            parameter_list: vec![
+              Parameter {
+                typename: TypeName::ElementaryTypeName(ElementaryTypeName::UInt(UInt::Uint256)),
+                storage_location: None,
+                identifier: Some("available".to_string())
+              },
+              Parameter {
+                typename: TypeName::ElementaryTypeName(ElementaryTypeName::UInt(UInt::Uint256)),
+                storage_location: None,
+                identifier: None
+              }
+            ],
+          }
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_struct_definition() {
+    let input = b"struct Account { uint256 balance; mapping(address => bool) allowed; }";
+    let result = parse_struct_definition(input);
+    if result.is_err() {
+      result.expect("should parse struct definition");
+    } else {
+      let (remaining, definition) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), definition),
+        (
+          "",
+          StructDefinition {
+            identifier: "Account".to_string(),
+            members: vec![
+              (
+                TypeName::ElementaryTypeName(ElementaryTypeName::UInt(UInt::Uint256)),
+                "balance".to_string()
+              ),
+              (
+                TypeName::Mapping(
+                  Box::new(TypeName::ElementaryTypeName(ElementaryTypeName::Address)),
+                  Box::new(TypeName::ElementaryTypeName(ElementaryTypeName::Bool))
+                ),
+                "allowed".to_string()
+              ),
+            ],
+          }
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_modifier_definition() {
+    let input = b"modifier onlyOwner(address owner) { _; }";
+    let result = parse_modifier_definition(input);
+    if result.is_err() {
+      result.expect("should parse modifier definition");
+    } else {
+      let (remaining, modifier) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), modifier),
+        (
+          "",
+          ModifierDefinition {
+            identifier: "onlyOwner".to_string(),
+            parameter_list: vec![Parameter {
+              typename: TypeName::ElementaryTypeName(ElementaryTypeName::Address),
+              storage_location: None,
+              identifier: Some("owner".to_string())
+            }],
+            block: vec![(Statement::Placeholder, Span { rest: 4, length: 2 })],
+            span: Span {
+              rest: 40,
+              length: 40
+            },
+          }
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_function_with_modifiers() {
+    let input = b"function f() public onlyOwner(a) view locked returns (bool) { return true; }";
+    let result = parse_function_definition(input);
+    if result.is_err() {
+      result.expect("should parse function with modifiers");
+    } else {
+      let (remaining, function) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), function),
+        (
+          "",
+          FunctionDefinition {
+            identifier: Some("f".to_string()),
+            visibility: Some(Visibility::Public),
+            state_mutability: Some(StateMutability::View),
+            parameter_list: vec![],
+            returns: vec![Parameter {
+              typename: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
+              storage_location: None,
+              identifier: None
+            }],
+            modifiers: vec![
+              ModifierInvocation {
+                identifier: "onlyOwner".to_string(),
+                args: vec![Expression::PrimaryExpression(
+                  PrimaryExpression::Identifier("a".to_string())
+                )],
+              },
+              ModifierInvocation {
+                identifier: "locked".to_string(),
+                args: vec![],
+              },
+            ],
+            block: vec![(
+              Statement::Return(Some(Expression::PrimaryExpression(
+                PrimaryExpression::BooleanLiteral(Boolean::True)
+              ))),
+              Span {
+                rest: 14,
+                length: 12
+              }
+            )],
+            span: Span {
+              rest: 76,
+              length: 76
+            },
           }
         )
       )
@@ -1,29 +1,69 @@
 use crate::atom::parse_identifier;
 use crate::{
+  codegen::{Assembly, Context, EvmVersion, FREE_MEMORY_POINTER},
   definition::{
-    contract_part::{parse as parse_contract_part, ContractPart, FunctionDefinition},
-    contract_type::{parse as parse_contract_type, ContractType},
+    contract_part::parse as parse_contract_part, contract_type::parse as parse_contract_type,
   },
-  elementary_type_name::{ElementaryTypeName, UInt},
-  expression::{Parameter, TypeName},
   op_codes::OpCode,
-  visibility::Visibility,
+  span::{spanned, Span},
+  state_mutability::StateMutability,
 };
 use nom::{
   character::complete::{char, multispace0, multispace1},
   combinator::{complete, map},
-  dbg_dmp,
   multi::many0,
-  sequence::{delimited, preceded, terminated, tuple},
+  sequence::{preceded, terminated, tuple},
   IResult,
 };
 
+pub use crate::definition::{
+  constructor::Constructor,
+  contract_part::{
+    ContractPart, ErrorDefinition, EventDefinition, FunctionDefinition, ModifierDefinition,
+    Placeholder, StateVariableDeclaration, StructDefinition,
+  },
+  contract_type::ContractType,
+};
+
 mod constructor;
 mod contract_part;
 mod contract_type;
 
-trait OpCodes {
-  fn op_codes() -> Vec<OpCode>;
+/// Where a state variable is stored: its first slot and, for value types that share the slot
+/// with others, the offset of its lowest order byte from the lowest order byte of the slot.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct StoragePosition {
+  pub slot: usize,
+  pub offset: usize,
+}
+
+/// A function the dispatcher can jump to.
+enum Entry<'a> {
+  Function(&'a FunctionDefinition),
+  Getter(StoragePosition, &'a StateVariableDeclaration),
+}
+
+impl<'a> Entry<'a> {
+  fn selector(&self) -> Vec<u8> {
+    match self {
+      Entry::Function(function) => function.selector(),
+      Entry::Getter(_, decl) => decl.selector(),
+    }
+  }
+
+  fn signature(&self) -> String {
+    match self {
+      Entry::Function(function) => function.signature(),
+      Entry::Getter(_, decl) => decl.signature(),
+    }
+  }
+
+  fn op_codes(&self, ctx: &mut Context<'a>) -> Result<(), String> {
+    match self {
+      Entry::Function(function) => function.external_op_codes(ctx),
+      Entry::Getter(position, decl) => decl.getter_op_codes(ctx, *position),
+    }
+  }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -31,44 +71,295 @@ pub struct Contract {
   pub contract_type: ContractType,
   pub identifier: String,
   pub contract_part: Vec<ContractPart>,
+  pub span: Span,
 }
 
 impl Contract {
-  pub fn op_codes(self) -> Vec<u32> {
-    if self.contract_type == ContractType::Contract {
-      let mut codes = vec![
-        OpCode::PUSH1 as u32,
-        0x80,
-        OpCode::PUSH1 as u32,
-        0x40,
-        OpCode::MSTORE as u32,
-      ];
-
-      // TODO: Maybe dynamic dispatch?
-      for part in self.contract_part {
-        codes.extend(part.op_codes());
+  pub fn functions(&self) -> Vec<&FunctionDefinition> {
+    self
+      .contract_part
+      .iter()
+      .filter_map(|part| match part {
+        ContractPart::FunctionDefinition(function) => Some(function),
+        _ => None,
+      })
+      .collect()
+  }
+
+  pub fn constructor(&self) -> Option<&Constructor> {
+    self.contract_part.iter().find_map(|part| match part {
+      ContractPart::ConstructorDefinition(ctor) => Some(ctor),
+      _ => None,
+    })
+  }
+
+  pub fn state_variables(&self) -> Vec<&StateVariableDeclaration> {
+    self
+      .contract_part
+      .iter()
+      .filter_map(|part| match part {
+        ContractPart::StateVariableDeclaration(decl) => Some(decl),
+        _ => None,
+      })
+      .collect()
+  }
+
+  pub fn structs(&self) -> Vec<&StructDefinition> {
+    self
+      .contract_part
+      .iter()
+      .filter_map(|part| match part {
+        ContractPart::StructDefinition(definition) => Some(definition),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// Fails if the contract defines a struct, since they can't be compiled yet. Names the state
+  /// variable that stores it if there is one, like a mapping to the struct.
+  fn reject_structs(&self) -> Result<(), String> {
+    let structs = self.structs();
+    for decl in self.state_variables() {
+      if let Some(definition) = structs
+        .iter()
+        .find(|definition| decl.type_name.refers_to(&definition.identifier))
+      {
+        return Err(format!(
+          "Structs are not supported yet: state variable {} stores struct {}",
+          decl.identifier, definition.identifier
+        ));
       }
+    }
+    match structs.first() {
+      Some(definition) => Err(format!(
+        "Structs are not supported yet: struct {}",
+        definition.identifier
+      )),
+      None => Ok(()),
+    }
+  }
+
+  /// The storage position of every state variable, laid out like solc does. Value types that
+  /// are smaller than a slot share it with the variables after them as long as they fit,
+  /// starting at the lowest order byte. Everything else starts at a new slot, and so does the
+  /// variable after it.
+  pub fn storage_layout(&self) -> Vec<(StoragePosition, &StateVariableDeclaration)> {
+    let (mut slot, mut offset) = (0, 0);
+    self
+      .state_variables()
+      .into_iter()
+      .map(|decl| {
+        let bytes = decl.type_name.storage_bytes().unwrap_or(32).min(32);
+        if offset + bytes > 32 {
+          slot += 1;
+          offset = 0;
+        }
+        let position = StoragePosition { slot, offset };
+        if decl.type_name.is_packed() {
+          offset += bytes;
+        } else {
+          slot += decl.type_name.storage_size().unwrap_or(1);
+          offset = 0;
+        }
+        (position, decl)
+      })
+      .collect()
+  }
+
+  /// The functions the dispatcher can jump to: the external functions and the getters of public
+  /// state variables.
+  fn entries(&self) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = self
+      .functions()
+      .into_iter()
+      .filter(|f| f.is_external())
+      .map(Entry::Function)
+      .collect();
+    entries.extend(
+      self
+        .storage_layout()
+        .into_iter()
+        .filter(|(_, decl)| decl.is_public())
+        .map(|(position, decl)| Entry::Getter(position, decl)),
+    );
+    entries
+  }
+
+  /// The signature and selector of every function that can be called externally.
+  pub fn method_identifiers(&self) -> Vec<(String, Vec<u8>)> {
+    self
+      .entries()
+      .iter()
+      .map(|entry| (entry.signature(), entry.selector()))
+      .collect()
+  }
 
-      return codes;
+  /// The signature and first topic of every event that is not anonymous.
+  pub fn event_topics(&self) -> Vec<(String, Vec<u8>)> {
+    self
+      .contract_part
+      .iter()
+      .filter_map(|part| match part {
+        ContractPart::EventDefinition(event) if !event.anonymous => {
+          Some((event.signature(), event.topic()))
+        }
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// The signature and selector of every custom error.
+  pub fn error_selectors(&self) -> Vec<(String, Vec<u8>)> {
+    self
+      .contract_part
+      .iter()
+      .filter_map(|part| match part {
+        ContractPart::ErrorDefinition(error) => Some((error.signature(), error.selector())),
+        _ => None,
+      })
+      .collect()
+  }
+
+  fn fallback(&self) -> Option<&FunctionDefinition> {
+    self
+      .functions()
+      .into_iter()
+      .find(|f| f.identifier.is_none())
+  }
+
+  /// The code that is stored on chain: dispatches the call to the function matching the
+  /// selector in the first four bytes of the calldata.
+  pub fn runtime_assembly<'a>(
+    &'a self,
+    contracts: &'a [Contract],
+    creators: &[&'a str],
+  ) -> Result<Assembly, String> {
+    self.reject_structs()?;
+    let mut ctx = Context::new(contracts, self, creators);
+    ctx.asm.set_span(Some(self.span));
+    ctx.asm.push(&[0x80]);
+    ctx.asm.push(&[FREE_MEMORY_POINTER]);
+    ctx.asm.op(OpCode::MSTORE);
+
+    let fallback = ctx.asm.new_tag();
+    let functions = self.entries();
+    let entries: Vec<_> = functions.iter().map(|_| ctx.asm.new_tag()).collect();
+    ctx.asm.push(&[4]);
+    ctx.asm.op(OpCode::CALLDATASIZE);
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.jumpi(fallback);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::CALLDATALOAD);
+    ctx.asm.push(&[0xe0]);
+    ctx.asm.op(OpCode::SHR);
+    for (function, entry) in functions.iter().zip(entries.iter()) {
+      ctx.asm.op(OpCode::DUP1);
+      ctx.asm.push(&function.selector());
+      ctx.asm.op(OpCode::EQ);
+      ctx.asm.jumpi(*entry);
+    }
+    ctx.asm.op(OpCode::POP);
+
+    ctx.asm.tag(fallback);
+    ctx.asm.comment("fallback".to_string());
+    match self.fallback() {
+      Some(function) => {
+        if !function.parameter_list.is_empty() || !function.returns.is_empty() {
+          return Err("Fallback function cannot take parameters or return values.".to_string());
+        }
+        function.external_op_codes(&mut ctx)?;
+      }
+      None => ctx.revert(),
+    }
+
+    for (function, entry) in functions.iter().zip(entries.iter()) {
+      ctx.asm.set_stack_height(1);
+      ctx.asm.tag(*entry);
+      ctx
+        .asm
+        .comment(format!("external {}", function.signature()));
+      ctx.asm.op(OpCode::POP);
+      function.op_codes(&mut ctx)?;
     }
-    return vec![];
+    ctx.generate_functions()?;
+    Ok(ctx.asm)
   }
 
-  pub fn runtime_op_codes(self) -> Vec<u32> {
-    if self.contract_type == ContractType::Contract {
-      let mut codes = vec![OpCode::PUSH1 as u32, 0x40, OpCode::MLOAD as u32];
+  /// The code run on deployment: initializes the state variables, runs the constructor and
+  /// returns the runtime code. `creators` are the contracts whose code embeds this one, which
+  /// it may not create in turn.
+  pub fn creation_assembly<'a>(
+    &'a self,
+    contracts: &'a [Contract],
+    creators: &[&'a str],
+  ) -> Result<Assembly, String> {
+    let runtime = self.runtime_assembly(contracts, creators)?;
+    let mut ctx = Context::new(contracts, self, creators);
+    ctx.asm.set_span(Some(self.span));
+    ctx.asm.push(&[0x80]);
+    ctx.asm.push(&[FREE_MEMORY_POINTER]);
+    ctx.asm.op(OpCode::MSTORE);
+    let constructor = self.constructor();
+    let payable = constructor
+      .map(|ctor| ctor.state_mutability == Some(StateMutability::Payable))
+      .unwrap_or(false);
+    if !payable {
+      ctx.non_payable_guard();
+    }
 
-      return codes;
+    for (position, decl) in self.storage_layout() {
+      decl.type_name.storage_size()?;
+      if let Some(rhs) = &decl.rhs {
+        if !decl.type_name.is_value_type() && !decl.type_name.is_byte_array() {
+          return Err(format!(
+            "Initializers of type {} are not supported yet",
+            decl.type_name.abi_name()
+          ));
+        }
+        rhs.converted_value(&decl.type_name, &None, &mut ctx)?;
+        ctx
+          .push_storage_position(position, &decl.type_name)
+          .store(&mut ctx)?;
+      }
+    }
+    if let Some(ctor) = constructor {
+      ctx.asm.comment("constructor".to_string());
+      ctor.op_codes(&mut ctx)?;
     }
 
-    return vec![];
+    let sub = ctx.asm.append_sub(runtime);
+    ctx.asm.push_sub_size(sub);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push_sub_offset(sub);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::CODECOPY);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::RETURN);
+    ctx.asm.op(OpCode::INVALID);
+    ctx.generate_functions()?;
+    Ok(ctx.asm)
+  }
+
+  /// The creation bytecode of the contract, with the runtime code appended to it.
+  pub fn op_codes(
+    &self,
+    contracts: &[Contract],
+    evm_version: EvmVersion,
+  ) -> Result<Vec<u32>, String> {
+    if self.contract_type != ContractType::Contract {
+      return Ok(vec![]);
+    }
+    Ok(
+      self
+        .creation_assembly(contracts, &[])?
+        .assemble(evm_version),
+    )
   }
 }
 
-use std::str::from_utf8;
 pub fn parse_contract(i: &[u8]) -> IResult<&[u8], Contract> {
   complete(map(
-    tuple((
+    spanned(tuple((
       parse_contract_type,
       preceded(multispace1, parse_identifier),
       terminated(
@@ -78,13 +369,14 @@ pub fn parse_contract(i: &[u8]) -> IResult<&[u8], Contract> {
         ),
         preceded(multispace0, char('}')),
       ),
-    )),
-    |x| {
+    ))),
+    |(x, span)| {
       let (contract_type, identifier, contract_part) = x;
       Contract {
         contract_type,
         identifier,
-        contract_part: contract_part,
+        contract_part,
+        span,
       }
     },
   ))(i)
@@ -93,6 +385,13 @@ pub fn parse_contract(i: &[u8]) -> IResult<&[u8], Contract> {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::{
+    abi,
+    codegen::assembly::AssemblyItem,
+    elementary_type_name::{ElementaryTypeName, UInt},
+    expression::{Parameter, TypeName},
+    visibility::Visibility,
+  };
 
   use pretty_assertions::assert_eq;
   use std::str::from_utf8;
@@ -128,8 +427,17 @@ mod tests {
                 }
               ],
               returns: vec![],
+              modifiers: vec![],
               block: vec![],
-            },)]
+              span: Span {
+                rest: 56,
+                length: 54
+              },
+            },)],
+            span: Span {
+              rest: 81,
+              length: 81
+            },
           }
         )
       )
@@ -151,10 +459,137 @@ mod tests {
           Contract {
             contract_type: ContractType::Contract,
             identifier: "ERC20".to_string(),
-            contract_part: vec![]
+            contract_part: vec![],
+            span: Span {
+              rest: 18,
+              length: 18
+            },
           }
         )
       )
     }
   }
+
+  #[test]
+  fn emits_logs_for_events() {
+    let input = "contract Token {
+  event Transfer(address indexed from, address indexed to, uint256 value);
+  function transfer(address to, uint256 value) public {
+    emit Transfer(to, to, value);
+  }
+}";
+    let (_, contract) = parse_contract(input.as_bytes()).ok().unwrap();
+    let contracts = vec![contract];
+    let asm = contracts[0].runtime_assembly(&contracts, &[]).unwrap();
+    assert!(asm.items.contains(&AssemblyItem::Operation(OpCode::LOG3)));
+    assert!(asm.items.contains(&AssemblyItem::Push(abi::keccak256(
+      b"Transfer(address,address,uint256)"
+    ))));
+  }
+
+  #[test]
+  fn rejects_structs() {
+    let assembly = |input: &str| {
+      let (_, contract) = parse_contract(input.as_bytes()).ok().unwrap();
+      let contracts = vec![contract];
+      contracts[0].runtime_assembly(&contracts, &[]).map(|_| ())
+    };
+    assert_eq!(
+      assembly(
+        "contract Bank {
+  struct Account { uint256 balance; address owner; }
+  mapping(address => Account) accounts;
+}"
+      ),
+      Err(
+        "Structs are not supported yet: state variable accounts stores struct Account".to_string()
+      )
+    );
+    assert_eq!(
+      assembly("contract Bank { struct Account { uint256 balance; } }"),
+      Err("Structs are not supported yet: struct Account".to_string())
+    );
+  }
+
+  #[test]
+  fn reports_mismatched_named_arguments() {
+    let assembly = |body: &str| {
+      let input = format!(
+        "contract A {{
+  event E(uint256 a);
+  error Failed(uint256 a);
+  function g(uint256 a) public {{}}
+  function f() public {{ {} }}
+}}",
+        body
+      );
+      let (_, contract) = parse_contract(input.as_bytes()).ok().unwrap();
+      let contracts = vec![contract];
+      contracts[0].runtime_assembly(&contracts, &[]).map(|_| ())
+    };
+    assert_eq!(
+      assembly("g({a: 1, a: 2});"),
+      Err("Duplicate named argument \"a\".".to_string())
+    );
+    assert_eq!(
+      assembly("g({b: 1});"),
+      Err("Named argument \"b\" does not match function declaration.".to_string())
+    );
+    assert_eq!(
+      assembly("g({a: 1, b: 2});"),
+      Err("Named argument \"b\" does not match function declaration.".to_string())
+    );
+    assert_eq!(
+      assembly("g(1, 2);"),
+      Err("Wrong argument count for function call: 2 arguments given but expected 1.".to_string())
+    );
+    assert_eq!(
+      assembly("emit E({a: 1, a: 2});"),
+      Err("Duplicate named argument \"a\".".to_string())
+    );
+    assert_eq!(
+      assembly("revert Failed({b: 1});"),
+      Err("Named argument \"b\" does not match function declaration.".to_string())
+    );
+    assert_eq!(assembly("g({a: 1});"), Ok(()));
+  }
+
+  #[test]
+  fn rejects_operands_of_incompatible_types() {
+    let assembly = |body: &str| {
+      let input = format!(
+        "contract A {{
+  function f(bool b, uint256 u, int256 i, address a) public {{ {} }}
+}}",
+        body
+      );
+      let (_, contract) = parse_contract(input.as_bytes()).ok().unwrap();
+      let contracts = vec![contract];
+      contracts[0].runtime_assembly(&contracts, &[]).map(|_| ())
+    };
+    assert_eq!(
+      assembly("require(u < i);"),
+      Err("Operator < not compatible with types uint256 and int256".to_string())
+    );
+    assert_eq!(
+      assembly("require(b < true);"),
+      Err("Operator < not compatible with types bool and bool".to_string())
+    );
+    assert_eq!(
+      assembly("require(a == u);"),
+      Err("Operator == not compatible with types address and uint256".to_string())
+    );
+    assert_eq!(
+      assembly("require(b && u);"),
+      Err("Operator && not compatible with type uint256".to_string())
+    );
+    assert_eq!(
+      assembly("require(!1);"),
+      Err("Operator ! not compatible with type int_const".to_string())
+    );
+    assert_eq!(
+      assembly("require(b == true && a != address(0) || i >= 0 - 1);"),
+      Ok(())
+    );
+  }
 }
@@ -1,10 +1,18 @@
 use crate::atom::{
     delimiter::{parse_semicolon, Delimiter},
-    keyword::parse_pragma,
-    parse_anything_till_semi, parse_identifier, Atom,
+    keyword::{parse_import, parse_pragma},
+    parse_anything_till_semi, parse_identifier, word_end, Atom,
+};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while},
+    character::complete::{char, multispace0},
+    combinator::map,
+    multi::separated_nonempty_list,
+    sequence::{delimited, preceded, separated_pair, terminated},
 };
-use nom::combinator::map;
 use nom::{named, ws, IResult};
+use std::str::from_utf8;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct PragmaDirective {
@@ -24,6 +32,50 @@ pub fn parse_pragma_directive(i: &[u8]) -> IResult<&[u8], PragmaDirective> {
     })(i)
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportDirective {
+    pub path: String,
+    /// The names imported with `import {A, B} from "path";`. Empty if every contract of the
+    /// file is imported.
+    pub symbols: Vec<String>,
+}
+
+fn parse_path(i: &[u8]) -> IResult<&[u8], String> {
+    map(
+        alt((
+            delimited(char('"'), take_while(|c| c != b'"'), char('"')),
+            delimited(char('\''), take_while(|c| c != b'\''), char('\'')),
+        )),
+        |path| from_utf8(path).unwrap().to_string(),
+    )(i)
+}
+
+/// `import "path";` or `import {A, B} from "path";`
+pub fn parse_import_directive(i: &[u8]) -> IResult<&[u8], ImportDirective> {
+    map(
+        delimited(
+            preceded(multispace0, terminated(parse_import, word_end)),
+            alt((
+                map(preceded(multispace0, parse_path), |path| (vec![], path)),
+                separated_pair(
+                    delimited(
+                        preceded(multispace0, char('{')),
+                        separated_nonempty_list(
+                            preceded(multispace0, char(',')),
+                            preceded(multispace0, parse_identifier),
+                        ),
+                        preceded(multispace0, char('}')),
+                    ),
+                    preceded(multispace0, terminated(tag("from"), word_end)),
+                    preceded(multispace0, parse_path),
+                ),
+            )),
+            preceded(multispace0, char(';')),
+        ),
+        |(symbols, path)| ImportDirective { path, symbols },
+    )(i)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,4 +98,32 @@ mod tests {
             )
         )
     }
+
+    #[test]
+    fn parses_import_directives() {
+        let input = "import \"./Token.sol\";";
+        let (remaining, directive) = parse_import_directive(input.as_bytes()).ok().unwrap();
+        assert_eq!(
+            (from_utf8(remaining).unwrap(), directive),
+            (
+                "",
+                ImportDirective {
+                    path: "./Token.sol".to_string(),
+                    symbols: vec![]
+                }
+            )
+        );
+        let input = "import { A, B } from '@lib/C.sol';";
+        let (remaining, directive) = parse_import_directive(input.as_bytes()).ok().unwrap();
+        assert_eq!(
+            (from_utf8(remaining).unwrap(), directive),
+            (
+                "",
+                ImportDirective {
+                    path: "@lib/C.sol".to_string(),
+                    symbols: vec!["A".to_string(), "B".to_string()]
+                }
+            )
+        )
+    }
 }
@@ -0,0 +1,109 @@
+use crate::atom::word_end;
+use nom::{
+    bytes::complete::tag,
+    character::complete::{char, digit1},
+    combinator::{map_res, opt},
+    sequence::{separated_pair, terminated, tuple},
+    IResult,
+};
+use std::str::from_utf8;
+
+const FIXED: &str = r#"fixed"#;
+const UFIXED: &str = r#"ufixed"#;
+
+/// The width of a fixed point number type `fixedMxN`, which has `M` bits of which `N` are
+/// decimal places.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Fixed {
+    pub bits: usize,
+    pub decimals: usize,
+}
+
+impl Fixed {
+    /// `fixed` and `ufixed` are aliases for `fixed128x18` and `ufixed128x18`.
+    pub const DEFAULT: Fixed = Fixed {
+        bits: 128,
+        decimals: 18,
+    };
+}
+
+fn parse_width(i: &[u8]) -> IResult<&[u8], Fixed> {
+    map_res(
+        separated_pair(digit1, char('x'), digit1),
+        |(bits, decimals): (&[u8], &[u8])| {
+            let bits = from_utf8(bits).unwrap().parse::<usize>();
+            let decimals = from_utf8(decimals).unwrap().parse::<usize>();
+            match (bits, decimals) {
+                (Ok(bits), Ok(decimals))
+                    if bits >= 8 && bits <= 256 && bits % 8 == 0 && decimals <= 80 =>
+                {
+                    Ok(Fixed { bits, decimals })
+                }
+                _ => Err("invalid fixed point width"),
+            }
+        },
+    )(i)
+}
+
+fn parse_fixed_with_prefix<'a>(prefix: &'static str, i: &'a [u8]) -> IResult<&'a [u8], Fixed> {
+    let (i, (_, width)) = terminated(tuple((tag(prefix), opt(parse_width))), word_end)(i)?;
+    Ok((i, width.unwrap_or(Fixed::DEFAULT)))
+}
+
+/// Parses `fixed` or `fixedMxN`.
+pub fn parse_fixed(i: &[u8]) -> IResult<&[u8], Fixed> {
+    parse_fixed_with_prefix(FIXED, i)
+}
+
+/// Parses `ufixed` or `ufixedMxN`.
+pub fn parse_ufixed(i: &[u8]) -> IResult<&[u8], Fixed> {
+    parse_fixed_with_prefix(UFIXED, i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::from_utf8;
+
+    #[test]
+    fn parses_fixed_without_width() {
+        let input = "fixed a";
+        let result = parse_fixed(input.as_bytes());
+        if result.is_err() {
+            result.expect("error");
+        } else {
+            let (remaining, fixed) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), fixed),
+                (" a", Fixed::DEFAULT)
+            )
+        }
+    }
+
+    #[test]
+    fn parses_ufixed_with_width() {
+        let input = "ufixed64x10 a";
+        let result = parse_ufixed(input.as_bytes());
+        if result.is_err() {
+            result.expect("error");
+        } else {
+            let (remaining, fixed) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), fixed),
+                (
+                    " a",
+                    Fixed {
+                        bits: 64,
+                        decimals: 10
+                    }
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_fixed_width() {
+        assert!(parse_fixed(b"fixed7x10 a").is_err());
+        assert!(parse_fixed(b"fixed8x81 a").is_err());
+    }
+}
@@ -0,0 +1,75 @@
+def snake_to_upper_camel(s):
+    words = s.split("_")
+    return "".join([word.lower().capitalize() for word in words])
+
+
+print("// GENERATED: DO NOT EDIT")
+print("""use crate::atom::word_end;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    combinator::map,
+    sequence::terminated,
+    IResult,
+};
+""")
+with open('fixed_bytes.txt') as f:
+    lines = [line.rstrip() for line in f]
+    for line in lines:
+        print(f"const {line.upper()}: &str = r#\"{line}\"#;")
+    print("")
+    print("""#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FixedBytes {""")
+    for line in lines:
+        print(f"\t{snake_to_upper_camel(line)},")
+    print("}")
+    print("")
+    print("impl FixedBytes {")
+    print("\tpub fn size(self) -> usize {")
+    print("\t\tmatch self {")
+    for line in lines:
+        size = line[len("bytes"):] or "1"
+        print(f"\t\t\tFixedBytes::{snake_to_upper_camel(line)} => {size},")
+    print("\t\t}")
+    print("\t}")
+    print("}")
+    print("")
+    for line in lines:
+
+        print(f"""fn parse_{line.lower()}(i: &[u8]) -> IResult<&[u8], FixedBytes> {{
+    map(
+        terminated(tag({line.upper()}), word_end),
+        |_| FixedBytes::{snake_to_upper_camel(line)},
+    )(i)
+}}""")
+    print(
+        "pub fn parse(i: &[u8]) -> IResult<&[u8], FixedBytes> {")
+    print("\talt((")
+    count = 0
+    rounds = 0
+    for line in lines:
+        if count == 20:
+            print("\talt((")
+            count = 0
+            rounds += 1
+        print(f"\t\tparse_{line.lower()},")
+        count += 1
+    for i in range(rounds):
+        print("\t))")
+    print("\t))(i)")
+    print("}")
+
+    print("")
+    print("#[cfg(test)]")
+    print(
+        "mod tests {\n\tuse super::*;\n\tuse std::str::from_utf8;\n")
+    for line in lines:
+        print(f"""\t#[test]
+\tfn parses_{line.lower()}() {{
+\t    let input = r#"{line} a"#;
+\t    let (remaining, name) = parse_{line.lower()}(input.as_bytes()).ok().unwrap();
+\t    assert_eq!(
+\t        (from_utf8(remaining).unwrap(), name),
+\t        (" a", FixedBytes::{snake_to_upper_camel(line)}))
+\t}}""")
+print("}")
@@ -1,29 +1,105 @@
+use crate::atom::word_end;
+pub use crate::elementary_type_name::fixed::Fixed;
+use crate::elementary_type_name::fixed::{parse_fixed, parse_ufixed};
+use crate::elementary_type_name::fixed_bytes::parse as parse_fixed_bytes;
+pub use crate::elementary_type_name::fixed_bytes::FixedBytes;
+use crate::elementary_type_name::int::parse as parse_int;
+pub use crate::elementary_type_name::int::Int;
 use crate::elementary_type_name::uint::parse as parse_uInt;
 pub use crate::elementary_type_name::uint::UInt;
-use nom::{branch::alt, combinator::map, named, tag, IResult};
+use nom::{
+    branch::alt,
+    character::complete::multispace1,
+    combinator::map,
+    named,
+    sequence::{terminated, tuple},
+    tag, IResult,
+};
 
+mod fixed;
+mod fixed_bytes;
+mod int;
 mod uint;
 
 const ADDRESS: &str = r#"address"#;
+const PAYABLE: &str = r#"payable"#;
 const BOOL: &str = r#"bool"#;
 const STRING: &str = r#"string"#;
-const INT: &str = r#"int"#;
-const BYTE: &str = r#"byte"#;
-const FIXED: &str = r#"fixed"#;
-const UFIXED: &str = r#"ufixed"#;
+const BYTES: &str = r#"bytes"#;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ElementaryTypeName {
     Address,
+    AddressPayable,
     Bool,
     String,
-    Int,
+    Int(Int),
     UInt(UInt),
-    Byte,
-    Fixed,
-    Ufixed,
+    Bytes,
+    FixedBytes(FixedBytes),
+    Fixed(Fixed),
+    Ufixed(Fixed),
 }
 
+impl ElementaryTypeName {
+    /// The canonical name of the type, as used in function and event signatures.
+    pub fn abi_name(&self) -> String {
+        match self {
+            ElementaryTypeName::Address | ElementaryTypeName::AddressPayable => {
+                "address".to_string()
+            }
+            ElementaryTypeName::Bool => "bool".to_string(),
+            ElementaryTypeName::String => "string".to_string(),
+            ElementaryTypeName::Int(int) => format!("int{}", int.bits()),
+            ElementaryTypeName::UInt(uint) => format!("uint{}", uint.bits()),
+            ElementaryTypeName::Bytes => "bytes".to_string(),
+            ElementaryTypeName::FixedBytes(bytes) => format!("bytes{}", bytes.size()),
+            ElementaryTypeName::Fixed(fixed) => format!("fixed{}x{}", fixed.bits, fixed.decimals),
+            ElementaryTypeName::Ufixed(fixed) => {
+                format!("ufixed{}x{}", fixed.bits, fixed.decimals)
+            }
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            ElementaryTypeName::String | ElementaryTypeName::Bytes => true,
+            _ => false,
+        }
+    }
+
+    /// The number of bytes a value of the type takes up, or `None` for `bytes` and `string`.
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            ElementaryTypeName::Address | ElementaryTypeName::AddressPayable => Some(20),
+            ElementaryTypeName::Bool => Some(1),
+            ElementaryTypeName::String | ElementaryTypeName::Bytes => None,
+            ElementaryTypeName::Int(int) => Some(int.bits() / 8),
+            ElementaryTypeName::UInt(uint) => Some(uint.bits() / 8),
+            ElementaryTypeName::FixedBytes(bytes) => Some(bytes.size()),
+            ElementaryTypeName::Fixed(fixed) | ElementaryTypeName::Ufixed(fixed) => {
+                Some(fixed.bits / 8)
+            }
+        }
+    }
+
+    /// Whether values of the type are two's complement signed numbers.
+    pub fn is_signed(&self) -> bool {
+        match self {
+            ElementaryTypeName::Int(_) | ElementaryTypeName::Fixed(_) => true,
+            _ => false,
+        }
+    }
+}
+
+fn parse_address_payable(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
+    named!(address, tag!(ADDRESS));
+    named!(payable, tag!(PAYABLE));
+    map(
+        terminated(tuple((address, multispace1, payable)), word_end),
+        |_| ElementaryTypeName::AddressPayable,
+    )(i)
+}
 fn parse_address(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
     named!(semi, tag!(ADDRESS));
     map(semi, |_| ElementaryTypeName::Address)(i)
@@ -36,32 +112,22 @@ fn parse_string(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
     named!(semi, tag!(STRING));
     map(semi, |_| ElementaryTypeName::String)(i)
 }
-fn parse_int(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
-    named!(semi, tag!(INT));
-    map(semi, |_| ElementaryTypeName::Int)(i)
-}
-fn parse_byte(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
-    named!(semi, tag!(BYTE));
-    map(semi, |_| ElementaryTypeName::Byte)(i)
-}
-fn parse_fixed(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
-    named!(semi, tag!(FIXED));
-    map(semi, |_| ElementaryTypeName::Fixed)(i)
-}
-fn parse_ufixed(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
-    named!(semi, tag!(UFIXED));
-    map(semi, |_| ElementaryTypeName::Ufixed)(i)
+fn parse_bytes(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
+    named!(semi, tag!(BYTES));
+    map(terminated(semi, word_end), |_| ElementaryTypeName::Bytes)(i)
 }
 pub fn parse(i: &[u8]) -> IResult<&[u8], ElementaryTypeName> {
     alt((
+        parse_address_payable,
         parse_address,
         parse_bool,
         parse_string,
-        parse_int,
+        map(parse_int, |x| ElementaryTypeName::Int(x)),
         map(parse_uInt, |x| ElementaryTypeName::UInt(x)),
-        parse_byte,
-        parse_fixed,
-        parse_ufixed,
+        parse_bytes,
+        map(parse_fixed_bytes, |x| ElementaryTypeName::FixedBytes(x)),
+        map(parse_fixed, |x| ElementaryTypeName::Fixed(x)),
+        map(parse_ufixed, |x| ElementaryTypeName::Ufixed(x)),
     ))(i)
 }
 
@@ -99,39 +165,86 @@ mod tests {
     }
     #[test]
     fn parses_int() {
-        let input = format!("{} a", INT);
-        let (remaining, name) = parse_int(input.as_bytes()).ok().unwrap();
+        let input = "int a";
+        let (remaining, name) = parse(input.as_bytes()).ok().unwrap();
         assert_eq!(
             (from_utf8(remaining).unwrap(), name),
-            (" a", ElementaryTypeName::Int)
+            (" a", ElementaryTypeName::Int(Int::Int))
+        )
+    }
+
+    #[test]
+    fn parses_bytes() {
+        let input = format!("{} a", BYTES);
+        let (remaining, name) = parse(input.as_bytes()).ok().unwrap();
+        assert_eq!(
+            (from_utf8(remaining).unwrap(), name),
+            (" a", ElementaryTypeName::Bytes)
         )
     }
 
     #[test]
     fn parses_byte() {
-        let input = format!("{} a", BYTE);
-        let (remaining, name) = parse_byte(input.as_bytes()).ok().unwrap();
+        let input = "byte a";
+        let (remaining, name) = parse(input.as_bytes()).ok().unwrap();
         assert_eq!(
             (from_utf8(remaining).unwrap(), name),
-            (" a", ElementaryTypeName::Byte)
+            (" a", ElementaryTypeName::FixedBytes(FixedBytes::Byte))
         )
     }
     #[test]
     fn parses_fixed() {
-        let input = format!("{} a", FIXED);
-        let (remaining, name) = parse_fixed(input.as_bytes()).ok().unwrap();
+        let input = "fixed a";
+        let (remaining, name) = parse(input.as_bytes()).ok().unwrap();
         assert_eq!(
             (from_utf8(remaining).unwrap(), name),
-            (" a", ElementaryTypeName::Fixed)
+            (" a", ElementaryTypeName::Fixed(Fixed::DEFAULT))
         )
     }
     #[test]
     fn parses_ufixed() {
-        let input = format!("{} a", UFIXED);
-        let (remaining, name) = parse_ufixed(input.as_bytes()).ok().unwrap();
+        let input = "ufixed a";
+        let (remaining, name) = parse(input.as_bytes()).ok().unwrap();
         assert_eq!(
             (from_utf8(remaining).unwrap(), name),
-            (" a", ElementaryTypeName::Ufixed)
+            (" a", ElementaryTypeName::Ufixed(Fixed::DEFAULT))
         )
     }
+
+    #[test]
+    fn parses_address_payable() {
+        let input = "address  payable a";
+        let (remaining, name) = parse(input.as_bytes()).ok().unwrap();
+        assert_eq!(
+            (from_utf8(remaining).unwrap(), name),
+            (" a", ElementaryTypeName::AddressPayable)
+        )
+    }
+    #[test]
+    fn parses_sized_types() {
+        let names = ["int8", "uint64", "bytes32", "fixed64x10", "ufixed256x80"];
+        for name in names.iter() {
+            let input = format!("{} a", name);
+            let (remaining, parsed) = parse(input.as_bytes()).ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), parsed.abi_name()),
+                (" a", name.to_string())
+            )
+        }
+    }
+    #[test]
+    fn exposes_size_and_signedness() {
+        let (_, int) = parse(b"int16").ok().unwrap();
+        let (_, bytes) = parse(b"bytes7").ok().unwrap();
+        let (_, address) = parse(b"address payable").ok().unwrap();
+        assert_eq!(
+            (int.size(), int.is_signed(), int.abi_name()),
+            (Some(2), true, "int16".to_string())
+        );
+        assert_eq!((bytes.size(), bytes.is_signed()), (Some(7), false));
+        assert_eq!(
+            (address.size(), address.abi_name()),
+            (Some(20), "address".to_string())
+        );
+    }
 }
@@ -4,12 +4,12 @@ def snake_to_upper_camel(s):
 
 
 print("// GENERATED: DO NOT EDIT")
-print("""use nom::{
+print("""use crate::atom::word_end;
+use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::one_of,
-    combinator::{map, peek},
-    sequence::{terminated, tuple},
+    combinator::map,
+    sequence::terminated,
     IResult,
 };
 """)
@@ -24,14 +24,31 @@ pub enum UInt {""")
         print(f"\t{snake_to_upper_camel(line)},")
     print("}")
     print("")
+    print("impl UInt {")
+    print("\tpub fn bits(self) -> usize {")
+    print("\t\tmatch self {")
+    for line in lines:
+        bits = line[len("uint"):] or "256"
+        print(f"\t\t\tUInt::{snake_to_upper_camel(line)} => {bits},")
+    print("\t\t}")
+    print("\t}")
+    print("")
+    print("\t/// The sized type with the given number of bits, e.g. `uint8` for 8.")
+    print("\tpub fn from_bits(bits: usize) -> Option<UInt> {")
+    print("\t\tmatch bits {")
+    for line in lines:
+        if line != "uint":
+            print(f"\t\t\t{line[len('uint'):]} => Some(UInt::{snake_to_upper_camel(line)}),")
+    print("\t\t\t_ => None,")
+    print("\t\t}")
+    print("\t}")
+    print("}")
+    print("")
     for line in lines:
 
         print(f"""fn parse_{line.lower()}(i: &[u8]) -> IResult<&[u8], UInt> {{
     map(
-        tuple((
-            peek(terminated(tag({line.upper()}), one_of(" \\n\\r)"))),
-            tag({line.upper()}),
-        )),
+        terminated(tag({line.upper()}), word_end),
         |_| UInt::{snake_to_upper_camel(line)},
     )(i)
 }}""")
@@ -0,0 +1,1577 @@
+mod precompile;
+mod word;
+
+pub use crate::evm::word::Word;
+
+use crate::{
+  abi::{self, keccak256},
+  json,
+  op_codes::OpCode,
+};
+use std::collections::BTreeMap;
+
+/// The gas limit of transactions that don't specify one.
+pub const DEFAULT_GAS: u64 = 30_000_000;
+
+const MAX_DEPTH: usize = 1024;
+const MAX_CODE_SIZE: usize = 24_576;
+const CALL_STIPEND: u64 = 2300;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Account {
+  pub nonce: u64,
+  pub balance: Word,
+  pub code: Vec<u8>,
+  pub storage: BTreeMap<Word, Word>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Log {
+  pub address: Word,
+  pub topics: Vec<Word>,
+  pub data: Vec<u8>,
+}
+
+/// The values of the block the transactions are executed in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+  pub number: u64,
+  pub timestamp: u64,
+  pub coinbase: Word,
+  pub gas_limit: u64,
+  pub chain_id: u64,
+  pub base_fee: u64,
+  pub prevrandao: Word,
+}
+
+impl Default for Block {
+  fn default() -> Block {
+    Block {
+      number: 1,
+      timestamp: 1,
+      coinbase: Word::ZERO,
+      gas_limit: DEFAULT_GAS,
+      chain_id: 1,
+      base_fee: 0,
+      prevrandao: Word::ZERO,
+    }
+  }
+}
+
+/// The result of a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+  pub success: bool,
+  /// The return data, or the revert data if the transaction failed.
+  pub output: Vec<u8>,
+  pub gas_used: u64,
+  pub logs: Vec<Log>,
+  /// The address of the contract deployed by the transaction.
+  pub created: Option<Word>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+  let digits: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+  format!("0x{}", digits)
+}
+
+fn address_hex(address: Word) -> String {
+  hex(&address.to_bytes_be()[12..])
+}
+
+impl Outcome {
+  /// The message of an `Error(string)` revert, or the code of a `Panic(uint256)`.
+  pub fn revert_reason(&self) -> Option<String> {
+    if self.success || self.output.len() < 4 {
+      return None;
+    }
+    let (selector, data) = self.output.split_at(4);
+    let word = |index: usize| {
+      data
+        .get(index * 32..index * 32 + 32)
+        .and_then(|word| Word::from_bytes_be(word).to_u64())
+        .map(|value| value as usize)
+    };
+    if selector == &keccak256(b"Error(string)")[..4] {
+      let length = word(1)?;
+      let bytes = data.get(64..64usize.checked_add(length)?)?;
+      String::from_utf8(bytes.to_vec()).ok()
+    } else if selector == &keccak256(b"Panic(uint256)")[..4] {
+      Some(format!("Panic(0x{:02x})", word(0)?))
+    } else {
+      None
+    }
+  }
+
+  /// Describes the outcome as JSON. The return value is decoded with the output types of the
+  /// called function if they are known, otherwise the return data is split into words.
+  pub fn to_json(&self, output_types: Option<&[String]>) -> String {
+    let words: Vec<String> = output_types
+      .and_then(|types| abi::decode(types, &self.output))
+      .unwrap_or_else(|| {
+        self
+          .output
+          .chunks(32)
+          .map(|word| json::string(&hex(word)))
+          .collect()
+      });
+    let logs: Vec<String> = self
+      .logs
+      .iter()
+      .map(|log| {
+        let topics: Vec<String> = log
+          .topics
+          .iter()
+          .map(|topic| json::string(&hex(&topic.to_bytes_be())))
+          .collect();
+        format!(
+          r#"{{"address":"{}","topics":[{}],"data":"{}"}}"#,
+          address_hex(log.address),
+          topics.join(","),
+          hex(&log.data)
+        )
+      })
+      .collect();
+    format!(
+      r#"{{"success":{},"address":{},"returnData":"{}","returnValue":[{}],"gasUsed":{},"logs":[{}],"revertReason":{}}}"#,
+      self.success,
+      self
+        .created
+        .map_or("null".to_string(), |address| json::string(&address_hex(
+          address
+        ))),
+      hex(&self.output),
+      if self.success {
+        words.join(",")
+      } else {
+        String::new()
+      },
+      self.gas_used,
+      logs.join(","),
+      self
+        .revert_reason()
+        .map_or("null".to_string(), |reason| json::string(&reason))
+    )
+  }
+}
+
+/// How a call frame ended. Failures other than reverts consume all the gas of the frame.
+enum Exit {
+  Return(Vec<u8>),
+  Revert(Vec<u8>),
+  Failure,
+}
+
+struct FrameResult {
+  success: bool,
+  output: Vec<u8>,
+  gas_left: u64,
+}
+
+impl FrameResult {
+  fn failure(gas_left: u64) -> FrameResult {
+    FrameResult {
+      success: false,
+      output: vec![],
+      gas_left,
+    }
+  }
+}
+
+/// Whose code runs in which context.
+struct Message {
+  origin: Word,
+  caller: Word,
+  /// The account whose storage and balance are used.
+  address: Word,
+  value: Word,
+  data: Vec<u8>,
+  gas: u64,
+  is_static: bool,
+  depth: usize,
+}
+
+struct Machine<'c> {
+  code: &'c [u8],
+  jumpdests: Vec<bool>,
+  pc: usize,
+  stack: Vec<Word>,
+  memory: Vec<u8>,
+  gas: u64,
+  returndata: Vec<u8>,
+}
+
+fn memory_cost(words: u64) -> u64 {
+  3 * words + words * words / 512
+}
+
+fn words(size: u64) -> u64 {
+  size.div_ceil(32)
+}
+
+/// The fixed part of the gas cost, roughly following the Istanbul schedule without refunds.
+/// Costs that depend on the operands are charged where they are executed.
+fn base_cost(op: OpCode) -> u64 {
+  match op {
+    OpCode::STOP | OpCode::RETURN | OpCode::REVERT => 0,
+    OpCode::JUMPDEST => 1,
+    OpCode::ADDRESS
+    | OpCode::ORIGIN
+    | OpCode::CALLER
+    | OpCode::CALLVALUE
+    | OpCode::CALLDATASIZE
+    | OpCode::CODESIZE
+    | OpCode::GASPRICE
+    | OpCode::COINBASE
+    | OpCode::TIMESTAMP
+    | OpCode::NUMBER
+    | OpCode::DIFFICULTY
+    | OpCode::GASLIMIT
+    | OpCode::CHAINID
+    | OpCode::BASEFEE
+    | OpCode::RETURNDATASIZE
+    | OpCode::POP
+    | OpCode::GETPC
+    | OpCode::MSIZE
+    | OpCode::GAS
+    | OpCode::PUSH0 => 2,
+    OpCode::MUL
+    | OpCode::DIV
+    | OpCode::SDIV
+    | OpCode::MOD
+    | OpCode::SMOD
+    | OpCode::SIGNEXTEND
+    | OpCode::SELFBALANCE => 5,
+    OpCode::ADDMOD | OpCode::MULMOD | OpCode::JUMP => 8,
+    OpCode::JUMPI | OpCode::EXP => 10,
+    OpCode::BLOCKHASH => 20,
+    OpCode::SHA3 => 30,
+    OpCode::LOG0 | OpCode::LOG1 | OpCode::LOG2 | OpCode::LOG3 | OpCode::LOG4 => 375,
+    OpCode::BALANCE
+    | OpCode::EXTCODESIZE
+    | OpCode::EXTCODECOPY
+    | OpCode::EXTCODEHASH
+    | OpCode::CALL
+    | OpCode::CALLCODE
+    | OpCode::DELEGATECALL
+    | OpCode::STATICCALL => 700,
+    OpCode::SLOAD => 800,
+    OpCode::SSTORE => 0,
+    OpCode::SELFDESTRUCT => 5000,
+    OpCode::CREATE | OpCode::CREATE2 => 32000,
+    _ => 3,
+  }
+}
+
+impl<'c> Machine<'c> {
+  fn new(code: &'c [u8], gas: u64) -> Machine<'c> {
+    let mut jumpdests = vec![false; code.len()];
+    let mut pc = 0;
+    while pc < code.len() {
+      match code[pc] {
+        0x5b => jumpdests[pc] = true,
+        0x60..=0x7f => pc += (code[pc] - 0x5f) as usize,
+        _ => {}
+      }
+      pc += 1;
+    }
+    Machine {
+      code,
+      jumpdests,
+      pc: 0,
+      stack: vec![],
+      memory: vec![],
+      gas,
+      returndata: vec![],
+    }
+  }
+
+  fn use_gas(&mut self, amount: u64) -> Result<(), Exit> {
+    if amount > self.gas {
+      self.gas = 0;
+      return Err(Exit::Failure);
+    }
+    self.gas -= amount;
+    Ok(())
+  }
+
+  fn pop(&mut self) -> Result<Word, Exit> {
+    self.stack.pop().ok_or(Exit::Failure)
+  }
+
+  fn pop_usize(&mut self) -> Result<usize, Exit> {
+    match self.pop()?.to_u64() {
+      Some(value) if value <= u32::MAX as u64 => Ok(value as usize),
+      _ => Err(Exit::Failure),
+    }
+  }
+
+  fn push(&mut self, value: Word) -> Result<(), Exit> {
+    if self.stack.len() >= 1024 {
+      return Err(Exit::Failure);
+    }
+    self.stack.push(value);
+    Ok(())
+  }
+
+  fn push_bool(&mut self, value: bool) -> Result<(), Exit> {
+    self.push(if value { Word::ONE } else { Word::ZERO })
+  }
+
+  /// Pops an offset and a size, and grows the memory to contain the range.
+  fn pop_range(&mut self) -> Result<(usize, usize), Exit> {
+    let offset = self.pop()?;
+    let size = self.pop_usize()?;
+    Ok((self.range(offset, size)?, size))
+  }
+
+  /// Grows the memory to contain `size` bytes at the offset, which is returned.
+  fn range(&mut self, offset: Word, size: usize) -> Result<usize, Exit> {
+    if size == 0 {
+      return Ok(0);
+    }
+    let offset = match offset.to_u64() {
+      Some(offset) if offset <= u32::MAX as u64 => offset as usize,
+      _ => return Err(Exit::Failure),
+    };
+    let end = (offset + size) as u64;
+    if end > self.memory.len() as u64 {
+      let (old, new) = (words(self.memory.len() as u64), words(end));
+      self.use_gas(memory_cost(new) - memory_cost(old))?;
+      self.memory.resize(new as usize * 32, 0);
+    }
+    Ok(offset)
+  }
+
+  /// Copies `data[source..]` into memory, padded with zeros, charging for every word copied.
+  fn copy_to_memory(&mut self, data: &[u8]) -> Result<(), Exit> {
+    let destination = self.pop()?;
+    let source = self.pop()?.to_u64().unwrap_or(u64::MAX);
+    let size = self.pop_usize()?;
+    let destination = self.range(destination, size)?;
+    self.use_gas(3 * words(size as u64))?;
+    for i in 0..size {
+      self.memory[destination + i] = source
+        .checked_add(i as u64)
+        .and_then(|index| data.get(index as usize))
+        .cloned()
+        .unwrap_or(0);
+    }
+    Ok(())
+  }
+}
+
+/// In-memory accounts that transactions are executed against.
+#[derive(Debug, Clone, Default)]
+pub struct World {
+  pub accounts: BTreeMap<Word, Account>,
+  pub block: Block,
+  logs: Vec<Log>,
+  destructed: Vec<Word>,
+}
+
+impl World {
+  pub fn new() -> World {
+    World::default()
+  }
+
+  pub fn account(&self, address: Word) -> Option<&Account> {
+    self.accounts.get(&address)
+  }
+
+  pub fn account_mut(&mut self, address: Word) -> &mut Account {
+    self.accounts.entry(address).or_default()
+  }
+
+  pub fn storage(&self, address: Word, key: Word) -> Word {
+    self
+      .accounts
+      .get(&address)
+      .and_then(|account| account.storage.get(&key))
+      .cloned()
+      .unwrap_or_default()
+  }
+
+  pub fn balance(&self, address: Word) -> Word {
+    self
+      .accounts
+      .get(&address)
+      .map(|a| a.balance)
+      .unwrap_or_default()
+  }
+
+  fn code(&self, address: Word) -> Vec<u8> {
+    self
+      .accounts
+      .get(&address)
+      .map(|account| account.code.clone())
+      .unwrap_or_default()
+  }
+
+  /// Deploys the creation code with the default gas limit.
+  pub fn deploy(&mut self, caller: Word, code: Vec<u8>) -> Outcome {
+    self.transact(caller, None, Word::ZERO, code, DEFAULT_GAS)
+  }
+
+  /// Calls a contract with the default gas limit.
+  pub fn call(&mut self, caller: Word, to: Word, data: Vec<u8>) -> Outcome {
+    self.transact(caller, Some(to), Word::ZERO, data, DEFAULT_GAS)
+  }
+
+  /// Executes a transaction, which deploys the data as creation code if there is no recipient.
+  /// Gas is free, but the value has to be covered by the balance of the caller.
+  pub fn transact(
+    &mut self,
+    caller: Word,
+    to: Option<Word>,
+    value: Word,
+    data: Vec<u8>,
+    gas: u64,
+  ) -> Outcome {
+    self.logs.clear();
+    self.destructed.clear();
+    let data_cost: u64 = data.iter().map(|b| if *b == 0 { 4 } else { 16 }).sum();
+    let intrinsic = 21000 + data_cost + if to.is_none() { 32000 } else { 0 };
+    if gas < intrinsic {
+      return Outcome {
+        success: false,
+        output: vec![],
+        gas_used: gas,
+        logs: vec![],
+        created: None,
+      };
+    }
+    let nonce = self.account_mut(caller).nonce;
+    self.account_mut(caller).nonce += 1;
+    let message = Message {
+      origin: caller,
+      caller,
+      address: to.unwrap_or_else(|| create_address(caller, nonce)),
+      value,
+      data,
+      gas: gas - intrinsic,
+      is_static: false,
+      depth: 0,
+    };
+    let (result, created) = match to {
+      Some(to) => (self.call_message(message, to, true), None),
+      None => {
+        let address = message.address;
+        let result = self.create_message(message);
+        let created = if result.success { Some(address) } else { None };
+        (result, created)
+      }
+    };
+    for address in self.destructed.drain(..) {
+      self.accounts.remove(&address);
+    }
+    Outcome {
+      success: result.success,
+      output: result.output,
+      gas_used: gas - result.gas_left,
+      logs: if result.success {
+        std::mem::take(&mut self.logs)
+      } else {
+        vec![]
+      },
+      created,
+    }
+  }
+
+  fn snapshot(&self) -> (BTreeMap<Word, Account>, usize, usize) {
+    (
+      self.accounts.clone(),
+      self.logs.len(),
+      self.destructed.len(),
+    )
+  }
+
+  fn restore(&mut self, snapshot: (BTreeMap<Word, Account>, usize, usize)) {
+    let (accounts, logs, destructed) = snapshot;
+    self.accounts = accounts;
+    self.logs.truncate(logs);
+    self.destructed.truncate(destructed);
+  }
+
+  fn transfer(&mut self, from: Word, to: Word, value: Word) -> bool {
+    if self.balance(from) < value {
+      return false;
+    }
+    self.account_mut(from).balance = self.balance(from) - value;
+    self.account_mut(to).balance = self.balance(to) + value;
+    true
+  }
+
+  /// Runs the code of `code_address` for the message, transferring the value to the message's
+  /// address if `transfer` is set.
+  fn call_message(&mut self, message: Message, code_address: Word, transfer: bool) -> FrameResult {
+    if message.depth > MAX_DEPTH {
+      return FrameResult::failure(message.gas);
+    }
+    let snapshot = self.snapshot();
+    if transfer && !self.transfer(message.caller, message.address, message.value) {
+      return FrameResult::failure(message.gas);
+    }
+    let result = if precompile::is_precompile(code_address) {
+      let execution = precompile::execute(code_address, &message.data, message.gas);
+      match execution.output {
+        Some(output) => FrameResult {
+          success: true,
+          output,
+          gas_left: message.gas - execution.gas_used,
+        },
+        None => FrameResult::failure(0),
+      }
+    } else {
+      let code = self.code(code_address);
+      self.execute(&code, &message)
+    };
+    if !result.success {
+      self.restore(snapshot);
+    }
+    result
+  }
+
+  /// Runs the creation code in the message's data and stores the returned code at its address.
+  fn create_message(&mut self, mut message: Message) -> FrameResult {
+    if message.depth > MAX_DEPTH || self.balance(message.caller) < message.value {
+      return FrameResult::failure(message.gas);
+    }
+    let existing = self.account(message.address);
+    if existing.is_some_and(|a| a.nonce > 0 || !a.code.is_empty()) {
+      return FrameResult::failure(0);
+    }
+    let snapshot = self.snapshot();
+    self.account_mut(message.address).nonce = 1;
+    self.transfer(message.caller, message.address, message.value);
+    let init_code = std::mem::take(&mut message.data);
+    let mut result = self.execute(&init_code, &message);
+    if result.success {
+      let deposit = 200 * result.output.len() as u64;
+      if result.output.len() > MAX_CODE_SIZE
+        || result.output.first() == Some(&0xef)
+        || deposit > result.gas_left
+      {
+        result = FrameResult::failure(0);
+      } else {
+        result.gas_left -= deposit;
+        let code = std::mem::take(&mut result.output);
+        self.account_mut(message.address).code = code;
+      }
+    }
+    if !result.success {
+      self.restore(snapshot);
+    }
+    result
+  }
+
+  fn execute(&mut self, code: &[u8], message: &Message) -> FrameResult {
+    let mut machine = Machine::new(code, message.gas);
+    let exit = loop {
+      if let Err(exit) = self.step(&mut machine, message) {
+        break exit;
+      }
+    };
+    match exit {
+      Exit::Return(output) => FrameResult {
+        success: true,
+        output,
+        gas_left: machine.gas,
+      },
+      Exit::Revert(output) => FrameResult {
+        success: false,
+        output,
+        gas_left: machine.gas,
+      },
+      Exit::Failure => FrameResult::failure(0),
+    }
+  }
+
+  /// Executes a single instruction. Halting is reported as an error, so that `?` can be used for
+  /// exceptional halts.
+  fn step(&mut self, m: &mut Machine, message: &Message) -> Result<(), Exit> {
+    let byte = match m.code.get(m.pc) {
+      Some(byte) => *byte,
+      None => return Err(Exit::Return(vec![])),
+    };
+    let op = OpCode::from_byte(byte).ok_or(Exit::Failure)?;
+    m.use_gas(base_cost(op))?;
+    m.pc += 1;
+    match byte {
+      0x5f..=0x7f => {
+        let size = (byte - 0x5f) as usize;
+        let end = (m.pc + size).min(m.code.len());
+        let mut immediate = m.code[m.pc..end].to_vec();
+        immediate.resize(size, 0);
+        m.pc += size;
+        return m.push(Word::from_bytes_be(&immediate));
+      }
+      0x80..=0x8f => {
+        let depth = (byte - 0x7f) as usize;
+        if m.stack.len() < depth {
+          return Err(Exit::Failure);
+        }
+        let value = m.stack[m.stack.len() - depth];
+        return m.push(value);
+      }
+      0x90..=0x9f => {
+        let depth = (byte - 0x8f) as usize;
+        let top = m.stack.len().checked_sub(1).ok_or(Exit::Failure)?;
+        let other = top.checked_sub(depth).ok_or(Exit::Failure)?;
+        m.stack.swap(top, other);
+        return Ok(());
+      }
+      0xa0..=0xa4 => {
+        if message.is_static {
+          return Err(Exit::Failure);
+        }
+        let (offset, size) = m.pop_range()?;
+        let mut topics = vec![];
+        for _ in 0..byte - 0xa0 {
+          topics.push(m.pop()?);
+        }
+        m.use_gas(375 * topics.len() as u64 + 8 * size as u64)?;
+        self.logs.push(Log {
+          address: message.address,
+          topics,
+          data: m.memory[offset..offset + size].to_vec(),
+        });
+        return Ok(());
+      }
+      _ => {}
+    }
+    match op {
+      OpCode::STOP => return Err(Exit::Return(vec![])),
+      OpCode::ADD => binary(m, |a, b| a + b)?,
+      OpCode::MUL => binary(m, |a, b| a * b)?,
+      OpCode::SUB => binary(m, |a, b| a - b)?,
+      OpCode::DIV => binary(m, |a, b| a.div_rem(b).0)?,
+      OpCode::SDIV => binary(m, |a, b| a.sdiv(b))?,
+      OpCode::MOD => binary(m, |a, b| a.div_rem(b).1)?,
+      OpCode::SMOD => binary(m, |a, b| a.smod(b))?,
+      OpCode::ADDMOD => {
+        let (a, b, n) = (m.pop()?, m.pop()?, m.pop()?);
+        m.push(a.addmod(b, n))?;
+      }
+      OpCode::MULMOD => {
+        let (a, b, n) = (m.pop()?, m.pop()?, m.pop()?);
+        m.push(a.mulmod(b, n))?;
+      }
+      OpCode::EXP => {
+        let (base, exponent) = (m.pop()?, m.pop()?);
+        m.use_gas(50 * exponent.byte_len() as u64)?;
+        m.push(base.pow(exponent))?;
+      }
+      OpCode::SIGNEXTEND => binary(m, |bytes, value| value.signextend(bytes))?,
+      OpCode::LT => binary(m, |a, b| bool_word(a < b))?,
+      OpCode::GT => binary(m, |a, b| bool_word(a > b))?,
+      OpCode::SLT => binary(m, |a, b| bool_word(a.slt(b)))?,
+      OpCode::SGT => binary(m, |a, b| bool_word(b.slt(a)))?,
+      OpCode::EQ => binary(m, |a, b| bool_word(a == b))?,
+      OpCode::ISZERO => {
+        let a = m.pop()?;
+        m.push_bool(a.is_zero())?;
+      }
+      OpCode::AND => binary(m, |a, b| a.and(b))?,
+      OpCode::OR => binary(m, |a, b| a.or(b))?,
+      OpCode::XOR => binary(m, |a, b| a.xor(b))?,
+      OpCode::NOT => {
+        let a = m.pop()?;
+        m.push(!a)?;
+      }
+      OpCode::BYTE => binary(m, |index, value| value.byte(index))?,
+      OpCode::SHL => binary(m, |shift, value| value << shift_amount(shift))?,
+      OpCode::SHR => binary(m, |shift, value| value >> shift_amount(shift))?,
+      OpCode::SAR => binary(m, |shift, value| value.sar(shift_amount(shift)))?,
+      OpCode::SHA3 => {
+        let (offset, size) = m.pop_range()?;
+        m.use_gas(6 * words(size as u64))?;
+        let hash = keccak256(&m.memory[offset..offset + size]);
+        m.push(Word::from_bytes_be(&hash))?;
+      }
+      OpCode::ADDRESS => m.push(message.address)?,
+      OpCode::BALANCE => {
+        let address = m.pop()?.to_address();
+        m.push(self.balance(address))?;
+      }
+      OpCode::ORIGIN => m.push(message.origin)?,
+      OpCode::CALLER => m.push(message.caller)?,
+      OpCode::CALLVALUE => m.push(message.value)?,
+      OpCode::CALLDATALOAD => {
+        let offset = m.pop()?.to_u64().unwrap_or(u64::MAX);
+        let mut bytes = [0; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+          *byte = offset
+            .checked_add(i as u64)
+            .and_then(|index| message.data.get(index as usize))
+            .cloned()
+            .unwrap_or(0);
+        }
+        m.push(Word::from_bytes_be(&bytes))?;
+      }
+      OpCode::CALLDATASIZE => m.push(Word::from_u64(message.data.len() as u64))?,
+      OpCode::CALLDATACOPY => m.copy_to_memory(&message.data)?,
+      OpCode::CODESIZE => m.push(Word::from_u64(m.code.len() as u64))?,
+      OpCode::CODECOPY => {
+        let code = m.code;
+        m.copy_to_memory(code)?
+      }
+      OpCode::GASPRICE => m.push(Word::ZERO)?,
+      OpCode::EXTCODESIZE => {
+        let address = m.pop()?.to_address();
+        m.push(Word::from_u64(self.code(address).len() as u64))?;
+      }
+      OpCode::EXTCODECOPY => {
+        let address = m.pop()?.to_address();
+        m.copy_to_memory(&self.code(address))?;
+      }
+      OpCode::EXTCODEHASH => {
+        let address = m.pop()?.to_address();
+        let hash = match self.account(address) {
+          Some(account) => Word::from_bytes_be(&keccak256(&account.code)),
+          None => Word::ZERO,
+        };
+        m.push(hash)?;
+      }
+      OpCode::RETURNDATASIZE => m.push(Word::from_u64(m.returndata.len() as u64))?,
+      OpCode::RETURNDATACOPY => {
+        let destination = m.pop()?;
+        let source = m.pop()?.to_u64().ok_or(Exit::Failure)? as usize;
+        let size = m.pop_usize()?;
+        let destination = m.range(destination, size)?;
+        if source
+          .checked_add(size)
+          .is_none_or(|end| end > m.returndata.len())
+        {
+          return Err(Exit::Failure);
+        }
+        m.use_gas(3 * words(size as u64))?;
+        m.memory[destination..destination + size]
+          .copy_from_slice(&m.returndata[source..source + size]);
+      }
+      OpCode::BLOCKHASH => {
+        m.pop()?;
+        m.push(Word::ZERO)?;
+      }
+      OpCode::COINBASE => m.push(self.block.coinbase)?,
+      OpCode::TIMESTAMP => m.push(Word::from_u64(self.block.timestamp))?,
+      OpCode::NUMBER => m.push(Word::from_u64(self.block.number))?,
+      OpCode::DIFFICULTY => m.push(self.block.prevrandao)?,
+      OpCode::GASLIMIT => m.push(Word::from_u64(self.block.gas_limit))?,
+      OpCode::CHAINID => m.push(Word::from_u64(self.block.chain_id))?,
+      OpCode::SELFBALANCE => m.push(self.balance(message.address))?,
+      OpCode::BASEFEE => m.push(Word::from_u64(self.block.base_fee))?,
+      OpCode::POP => {
+        m.pop()?;
+      }
+      OpCode::MLOAD => {
+        let offset = m.pop()?;
+        let offset = m.range(offset, 32)?;
+        let value = Word::from_bytes_be(&m.memory[offset..offset + 32]);
+        m.push(value)?;
+      }
+      OpCode::MSTORE => {
+        let (offset, value) = (m.pop()?, m.pop()?);
+        let offset = m.range(offset, 32)?;
+        m.memory[offset..offset + 32].copy_from_slice(&value.to_bytes_be());
+      }
+      OpCode::MSTORE8 => {
+        let (offset, value) = (m.pop()?, m.pop()?);
+        let offset = m.range(offset, 1)?;
+        m.memory[offset] = value.to_bytes_be()[31];
+      }
+      OpCode::SLOAD => {
+        let key = m.pop()?;
+        m.push(self.storage(message.address, key))?;
+      }
+      OpCode::SSTORE => {
+        if message.is_static || m.gas <= CALL_STIPEND {
+          return Err(Exit::Failure);
+        }
+        let (key, value) = (m.pop()?, m.pop()?);
+        let current = self.storage(message.address, key);
+        m.use_gas(if current.is_zero() && !value.is_zero() {
+          20000
+        } else {
+          5000
+        })?;
+        let storage = &mut self.account_mut(message.address).storage;
+        if value.is_zero() {
+          storage.remove(&key);
+        } else {
+          storage.insert(key, value);
+        }
+      }
+      OpCode::JUMP => {
+        let destination = m.pop()?;
+        jump(m, destination)?;
+      }
+      OpCode::JUMPI => {
+        let (destination, condition) = (m.pop()?, m.pop()?);
+        if !condition.is_zero() {
+          jump(m, destination)?;
+        }
+      }
+      OpCode::GETPC => m.push(Word::from_u64(m.pc as u64 - 1))?,
+      OpCode::MSIZE => m.push(Word::from_u64(m.memory.len() as u64))?,
+      OpCode::GAS => m.push(Word::from_u64(m.gas))?,
+      OpCode::JUMPDEST => {}
+      OpCode::CREATE | OpCode::CREATE2 => {
+        if message.is_static {
+          return Err(Exit::Failure);
+        }
+        let value = m.pop()?;
+        let (offset, size) = m.pop_range()?;
+        let init_code = m.memory[offset..offset + size].to_vec();
+        let address = if op == OpCode::CREATE2 {
+          let salt = m.pop()?;
+          m.use_gas(6 * words(size as u64))?;
+          create2_address(message.address, salt, &init_code)
+        } else {
+          create_address(message.address, self.account_mut(message.address).nonce)
+        };
+        m.returndata = vec![];
+        if message.depth >= MAX_DEPTH || self.balance(message.address) < value {
+          return m.push(Word::ZERO);
+        }
+        self.account_mut(message.address).nonce += 1;
+        let gas = m.gas - m.gas / 64;
+        m.gas -= gas;
+        let result = self.create_message(Message {
+          origin: message.origin,
+          caller: message.address,
+          address,
+          value,
+          data: init_code,
+          gas,
+          is_static: false,
+          depth: message.depth + 1,
+        });
+        m.gas += result.gas_left;
+        if result.success {
+          m.push(address)?;
+        } else {
+          m.returndata = result.output;
+          m.push(Word::ZERO)?;
+        }
+      }
+      OpCode::CALL | OpCode::CALLCODE | OpCode::DELEGATECALL | OpCode::STATICCALL => {
+        let gas = m.pop()?;
+        let target = m.pop()?.to_address();
+        let value = match op {
+          OpCode::CALL | OpCode::CALLCODE => m.pop()?,
+          _ => Word::ZERO,
+        };
+        if op == OpCode::CALL && message.is_static && !value.is_zero() {
+          return Err(Exit::Failure);
+        }
+        let (input_offset, input_size) = m.pop_range()?;
+        let (output_offset, output_size) = m.pop_range()?;
+        let input = m.memory[input_offset..input_offset + input_size].to_vec();
+        if !value.is_zero() {
+          m.use_gas(9000)?;
+          if op == OpCode::CALL && self.account(target).is_none() {
+            m.use_gas(25000)?;
+          }
+        }
+        let available = m.gas - m.gas / 64;
+        let mut gas = gas.to_u64().map_or(available, |gas| gas.min(available));
+        m.use_gas(gas)?;
+        if !value.is_zero() {
+          gas += CALL_STIPEND;
+        }
+        let (caller, address, value) = match op {
+          OpCode::CALL | OpCode::STATICCALL => (message.address, target, value),
+          OpCode::CALLCODE => (message.address, message.address, value),
+          _ => (message.caller, message.address, message.value),
+        };
+        let child = Message {
+          origin: message.origin,
+          caller,
+          address,
+          value,
+          data: input,
+          gas,
+          is_static: message.is_static || op == OpCode::STATICCALL,
+          depth: message.depth + 1,
+        };
+        let result = if message.depth >= MAX_DEPTH || self.balance(message.address) < value {
+          FrameResult::failure(gas)
+        } else {
+          self.call_message(child, target, op == OpCode::CALL)
+        };
+        m.gas += result.gas_left;
+        let copied = output_size.min(result.output.len());
+        m.memory[output_offset..output_offset + copied].copy_from_slice(&result.output[..copied]);
+        m.returndata = result.output;
+        m.push_bool(result.success)?;
+      }
+      OpCode::RETURN | OpCode::REVERT => {
+        let (offset, size) = m.pop_range()?;
+        let output = m.memory[offset..offset + size].to_vec();
+        return Err(if op == OpCode::RETURN {
+          Exit::Return(output)
+        } else {
+          Exit::Revert(output)
+        });
+      }
+      OpCode::SELFDESTRUCT => {
+        if message.is_static {
+          return Err(Exit::Failure);
+        }
+        let beneficiary = m.pop()?.to_address();
+        let balance = self.balance(message.address);
+        self.account_mut(message.address).balance = Word::ZERO;
+        self.account_mut(beneficiary).balance = self.balance(beneficiary) + balance;
+        self.destructed.push(message.address);
+        return Err(Exit::Return(vec![]));
+      }
+      _ => return Err(Exit::Failure),
+    }
+    Ok(())
+  }
+}
+
+fn binary(m: &mut Machine, f: impl Fn(Word, Word) -> Word) -> Result<(), Exit> {
+  let (a, b) = (m.pop()?, m.pop()?);
+  m.push(f(a, b))
+}
+
+fn bool_word(value: bool) -> Word {
+  if value {
+    Word::ONE
+  } else {
+    Word::ZERO
+  }
+}
+
+fn shift_amount(shift: Word) -> usize {
+  shift.to_u64().map_or(256, |shift| shift.min(256) as usize)
+}
+
+fn jump(m: &mut Machine, destination: Word) -> Result<(), Exit> {
+  match destination.to_u64() {
+    Some(destination) if m.jumpdests.get(destination as usize) == Some(&true) => {
+      m.pc = destination as usize;
+      Ok(())
+    }
+    _ => Err(Exit::Failure),
+  }
+}
+
+/// The address of a contract created by `CREATE`, from the RLP encoding of the creator and its
+/// nonce.
+pub fn create_address(creator: Word, nonce: u64) -> Word {
+  let mut nonce_rlp: Vec<u8> = nonce
+    .to_be_bytes()
+    .iter()
+    .cloned()
+    .skip_while(|b| *b == 0)
+    .collect();
+  match nonce_rlp.as_slice() {
+    [] => nonce_rlp = vec![0x80],
+    [byte] if *byte < 0x80 => {}
+    bytes => nonce_rlp.insert(0, 0x80 + bytes.len() as u8),
+  }
+  let mut rlp = vec![0xc0 + 21 + nonce_rlp.len() as u8, 0x94];
+  rlp.extend_from_slice(&creator.to_bytes_be()[12..]);
+  rlp.extend(nonce_rlp);
+  Word::from_bytes_be(&keccak256(&rlp)).to_address()
+}
+
+/// The address of a contract created by `CREATE2`.
+pub fn create2_address(creator: Word, salt: Word, init_code: &[u8]) -> Word {
+  let mut input = vec![0xff];
+  input.extend_from_slice(&creator.to_bytes_be()[12..]);
+  input.extend_from_slice(&salt.to_bytes_be());
+  input.extend(keccak256(init_code));
+  Word::from_bytes_be(&keccak256(&input)).to_address()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{abi::selector, codegen::EvmVersion, root::parse};
+
+  use pretty_assertions::assert_eq;
+
+  const SENDER: Word = Word::ONE;
+
+  /// The creation code of the first contract in the source.
+  fn compile(source: &str) -> Vec<u8> {
+    let (_, root) = parse(source.as_bytes()).ok().unwrap();
+    root.contracts[0]
+      .op_codes(&root.contracts, EvmVersion::default())
+      .unwrap()
+      .into_iter()
+      .map(|code| code as u8)
+      .collect()
+  }
+
+  fn calldata(signature: &str, args: &[u64]) -> Vec<u8> {
+    let mut data = selector(signature);
+    for arg in args {
+      data.extend_from_slice(&Word::from_u64(*arg).to_bytes_be());
+    }
+    data
+  }
+
+  #[test]
+  fn deploys_and_calls_compiled_contract() {
+    let mut world = World::new();
+    let deployment = world.deploy(
+      SENDER,
+      compile(
+        "pragma solidity ^0.8.0;
+contract Counter {
+  uint256 count;
+  event Increased(uint256 indexed by);
+  constructor() { count = 40; }
+  function increase(uint256 by) public { count = count + by; emit Increased(by); }
+  function get() public view returns (uint256) { return count; }
+}",
+      ),
+    );
+    assert!(deployment.success);
+    let counter = deployment.created.unwrap();
+    assert_eq!(counter, create_address(SENDER, 0));
+
+    let increase = world.call(SENDER, counter, calldata("increase(uint256)", &[2]));
+    assert!(increase.success);
+    assert_eq!(
+      increase.logs,
+      vec![Log {
+        address: counter,
+        topics: vec![
+          Word::from_bytes_be(&keccak256(b"Increased(uint256)")),
+          Word::from_u64(2)
+        ],
+        data: vec![],
+      }]
+    );
+    assert_eq!(world.storage(counter, Word::ZERO), Word::from_u64(42));
+    let get = world.call(SENDER, counter, calldata("get()", &[]));
+    assert_eq!(get.output, Word::from_u64(42).to_bytes_be().to_vec());
+    assert!(get.gas_used > 21000 && get.gas_used < increase.gas_used);
+  }
+
+  #[test]
+  fn hashes_and_recovers_signers_with_precompiles() {
+    let mut world = World::new();
+    let deployment = world.deploy(
+      SENDER,
+      compile(
+        "pragma solidity ^0.8.0;
+contract Hashes {
+  function digests(bytes memory data) public pure returns (bytes32, bytes20) {
+    return (sha256(data), ripemd160(data));
+  }
+  function recover(bytes32 hash, uint8 v, bytes32 r, bytes32 s) public pure returns (address) {
+    return ecrecover(hash, v, r, s);
+  }
+}",
+      ),
+    );
+    let hashes = deployment.created.unwrap();
+    let word = |hex: &str| Word::from_bytes_be(&crate::hex::from_hex(hex).unwrap());
+
+    let mut data = calldata("digests(bytes)", &[32, 3]);
+    data.extend_from_slice(&(word("616263") << 232).to_bytes_be());
+    let digests = world.call(SENDER, hashes, data);
+    assert!(digests.success);
+    assert_eq!(
+      Word::from_bytes_be(&digests.output[..32]),
+      word("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+    );
+    assert_eq!(
+      Word::from_bytes_be(&digests.output[32..]),
+      word("8eb208f7e05d987a9b044a8e98c6b087f15a0bfc") << 96
+    );
+
+    let hash = word("38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e");
+    let s = word("789d1dd423d25f0772d2748d60f7e4b81bb14d086eba8e8e8efb6dcff8a4ae02");
+    let mut recover = |v: u64| {
+      let mut data = selector("recover(bytes32,uint8,bytes32,bytes32)");
+      for arg in &[hash, Word::from_u64(v), hash, s] {
+        data.extend_from_slice(&arg.to_bytes_be());
+      }
+      world.call(SENDER, hashes, data).output
+    };
+    assert_eq!(
+      Word::from_bytes_be(&recover(27)),
+      word("ceaccac640adf55b2028469bd36ba501f28b699d")
+    );
+    // Invalid signatures recover address 0
+    assert_eq!(Word::from_bytes_be(&recover(29)), Word::ZERO);
+  }
+
+  #[test]
+  fn reverts_state_of_failed_calls() {
+    let mut world = World::new();
+    let contract = world
+      .deploy(
+        SENDER,
+        compile(
+          "pragma solidity ^0.8.0;
+contract Guarded {
+  uint256 value;
+  function set(uint256 v, bool ok) public { value = v; require(ok, \"not ok\"); }
+}",
+        ),
+      )
+      .created
+      .unwrap();
+    let failed = world.call(SENDER, contract, calldata("set(uint256,bool)", &[2, 0]));
+    assert!(!failed.success);
+    assert_eq!(failed.output[..4].to_vec(), selector("Error(string)"));
+    assert_eq!(failed.output[68..74].to_vec(), b"not ok".to_vec());
+    assert_eq!(world.storage(contract, Word::ZERO), Word::ZERO);
+    assert!(
+      world
+        .call(SENDER, contract, calldata("set(uint256,bool)", &[1, 1]))
+        .success
+    );
+    assert_eq!(world.storage(contract, Word::ZERO), Word::ONE);
+  }
+
+  #[test]
+  fn evaluates_comparisons_and_logical_operators() {
+    let mut world = World::new();
+    let contract = world
+      .deploy(
+        SENDER,
+        compile(
+          "pragma solidity ^0.8.0;
+contract Guarded {
+  address owner;
+  uint256 count;
+  constructor() { owner = msg.sender; }
+  modifier onlyOwner() { require(msg.sender == owner); _; }
+  function bump(uint256 x) public onlyOwner { require(x == 1, \"no\"); count = count + x; }
+  function compare(int8 a, int8 b) public pure returns (bool, bool, bool, bool, bool, bool) {
+    return (a < b, a <= b, a > b, a >= b, a == b, a != b);
+  }
+  function check(uint256 x) public pure returns (bool) {
+    return x == 0 || 10 / x > 1 && !(x >= 5) && 2 > 1;
+  }
+}",
+        ),
+      )
+      .created
+      .unwrap();
+    let other = Word::from_u64(2);
+    let bools = |values: &[bool]| -> Vec<u8> {
+      values
+        .iter()
+        .flat_map(|value| Word::from_u64(*value as u64).to_bytes_be().to_vec())
+        .collect()
+    };
+
+    let refused = world.call(other, contract, calldata("bump(uint256)", &[1]));
+    assert!(!refused.success);
+    assert_eq!(refused.output, vec![]);
+    let rejected = world.call(SENDER, contract, calldata("bump(uint256)", &[2]));
+    assert_eq!(rejected.revert_reason(), Some("no".to_string()));
+    assert!(
+      world
+        .call(SENDER, contract, calldata("bump(uint256)", &[1]))
+        .success
+    );
+    assert_eq!(world.storage(contract, Word::ONE), Word::ONE);
+
+    // int8 operands are compared with their sign
+    let mut args = selector("compare(int8,int8)");
+    args.extend_from_slice(&Word::MAX.to_bytes_be());
+    args.extend_from_slice(&Word::ONE.to_bytes_be());
+    let compared = world.call(SENDER, contract, args);
+    assert_eq!(
+      compared.output,
+      bools(&[true, true, false, false, false, true])
+    );
+    let equal = world.call(SENDER, contract, calldata("compare(int8,int8)", &[3, 3]));
+    assert_eq!(
+      equal.output,
+      bools(&[false, true, false, true, true, false])
+    );
+
+    // The division is skipped for 0, which would otherwise panic
+    for (x, expected) in &[(0, true), (2, true), (5, false), (20, false)] {
+      let checked = world.call(SENDER, contract, calldata("check(uint256)", &[*x]));
+      assert_eq!(checked.output, bools(&[*expected]));
+    }
+  }
+
+  #[test]
+  fn creates_and_calls_other_contracts() {
+    let mut world = World::new();
+    let factory = world
+      .deploy(
+        SENDER,
+        compile(
+          "pragma solidity ^0.8.0;
+contract Factory {
+  function make(uint256 a) public returns (uint256) {
+    Child c = new Child(a);
+    return c.twice();
+  }
+}
+contract Child {
+  uint256 x;
+  constructor(uint256 a) { x = a; }
+  function twice() public view returns (uint256) { return x * 2; }
+}",
+        ),
+      )
+      .created
+      .unwrap();
+    let make = world.call(SENDER, factory, calldata("make(uint256)", &[21]));
+    assert!(make.success);
+    assert_eq!(make.output, Word::from_u64(42).to_bytes_be().to_vec());
+    let child = create_address(factory, 1);
+    assert_eq!(world.storage(child, Word::ZERO), Word::from_u64(21));
+  }
+
+  #[test]
+  fn fails_on_invalid_jumps_and_running_out_of_gas() {
+    let mut world = World::new();
+    let code = |world: &mut World, code: &[u8]| {
+      let address = Word::from_u64(0x1234);
+      world.account_mut(address).code = code.to_vec();
+      address
+    };
+    // PUSH1 3 JUMP JUMPDEST STOP, where 3 is the immediate of a push
+    let jump = code(&mut world, &[0x60, 0x03, 0x56, 0x60, 0x5b, 0x00]);
+    let outcome = world.transact(SENDER, Some(jump), Word::ZERO, vec![], 50000);
+    assert!(!outcome.success);
+    assert_eq!(outcome.gas_used, 50000);
+    // JUMPDEST PUSH0 JUMP loops forever
+    let forever = code(&mut world, &[0x5b, 0x5f, 0x56]);
+    assert!(!world.call(SENDER, forever, vec![]).success);
+  }
+
+  #[test]
+  fn packs_storage_like_solc() {
+    let mut world = World::new();
+    let contract = world
+      .deploy(
+        SENDER,
+        compile(
+          "pragma solidity ^0.8.0;
+contract Packed {
+  uint128 a;
+  uint128 public b;
+  uint8[4] small;
+  uint8[] list;
+  bytes4 tag;
+  int8 public negative;
+  address owner;
+  bool flag;
+  uint256 big;
+  function set(int8 n) public {
+    a = 1;
+    b = 2;
+    small[0] = 0x11;
+    small[3] = 0x43;
+    small[3] += 1;
+    list.push(5);
+    list.push(6);
+    list.push(7);
+    tag = \"abcd\";
+    negative = n;
+    owner = msg.sender;
+    flag = true;
+    big = 9;
+  }
+  function get() public view returns (uint128, uint8, uint8, bytes4, int8, bool) {
+    return (a, small[3], list[2], tag, negative, flag);
+  }
+}",
+        ),
+      )
+      .created
+      .unwrap();
+    let mut data = selector("set(int8)");
+    data.extend_from_slice(&(-Word::from_u64(2)).to_bytes_be());
+    assert!(world.call(SENDER, contract, data).success);
+    let word = |hex: &str| Word::from_bytes_be(&crate::hex::from_hex(hex).unwrap());
+    let slot = |index: Word| world.storage(contract, index);
+    // Both uint128 share slot 0, filled from the right
+    assert_eq!(slot(Word::ZERO), (Word::from_u64(2) << 128).or(Word::ONE));
+    // All four uint8 fit in slot 1
+    assert_eq!(slot(Word::from_u64(1)), word("44000011"));
+    // The dynamic array keeps its length in slot 2 and packs its elements at keccak(2)
+    assert_eq!(slot(Word::from_u64(2)), Word::from_u64(3));
+    let data = Word::from_bytes_be(&keccak256(&Word::from_u64(2).to_bytes_be()));
+    assert_eq!(slot(data), word("070605"));
+    // bytes4, int8, address and bool share slot 3
+    assert_eq!(
+      slot(Word::from_u64(3)),
+      word(&format!("01{:040x}fe61626364", 1))
+    );
+    assert_eq!(slot(Word::from_u64(4)), Word::from_u64(9));
+
+    let get = world.call(SENDER, contract, calldata("get()", &[]));
+    assert!(get.success);
+    let output: Vec<Word> = get.output.chunks(32).map(Word::from_bytes_be).collect();
+    assert_eq!(
+      output,
+      vec![
+        Word::ONE,
+        Word::from_u64(0x44),
+        Word::from_u64(7),
+        word("61626364") << 224,
+        -Word::from_u64(2),
+        Word::ONE,
+      ]
+    );
+    let getter = world.call(SENDER, contract, calldata("b()", &[]));
+    assert_eq!(getter.output, Word::from_u64(2).to_bytes_be().to_vec());
+    let getter = world.call(SENDER, contract, calldata("negative()", &[]));
+    assert_eq!(getter.output, (-Word::from_u64(2)).to_bytes_be().to_vec());
+  }
+
+  #[test]
+  fn exposes_packed_storage_positions_to_inline_assembly() {
+    let mut world = World::new();
+    let contract = world
+      .deploy(
+        SENDER,
+        compile(
+          "pragma solidity ^0.8.0;
+contract Positions {
+  uint128 a;
+  uint64 b;
+  uint256 c;
+  function set() public { a = 1; b = 7; c = 9; }
+  function positions() public pure returns (uint256 bs, uint256 bo, uint256 cs, uint256 co) {
+    assembly { bs := b.slot bo := b.offset cs := c.slot co := c.offset }
+  }
+  function readB() public view returns (uint256 v) {
+    assembly { v := and(shr(mul(b.offset, 8), sload(b.slot)), 0xffffffffffffffff) }
+  }
+}",
+        ),
+      )
+      .created
+      .unwrap();
+    let positions = world.call(SENDER, contract, calldata("positions()", &[]));
+    let words: Vec<u64> = positions
+      .output
+      .chunks(32)
+      .map(|word| Word::from_bytes_be(word).to_u64().unwrap())
+      .collect();
+    // b shares slot 0 with a, 16 bytes from the right, and c starts slot 1
+    assert_eq!(words, vec![0, 16, 1, 0]);
+    assert!(world.call(SENDER, contract, calldata("set()", &[])).success);
+    let read = world.call(SENDER, contract, calldata("readB()", &[]));
+    assert_eq!(read.output, Word::from_u64(7).to_bytes_be().to_vec());
+  }
+
+  #[test]
+  fn stores_mappings_at_hashed_slots() {
+    let mut world = World::new();
+    let contract = world
+      .deploy(
+        SENDER,
+        compile(
+          "pragma solidity ^0.8.0;
+contract Maps {
+  uint256 total;
+  mapping(address => uint256) public balances;
+  mapping(address => mapping(address => uint256)) public allowance;
+  mapping(uint256 => uint8) public small;
+  function set(address owner, address spender, uint256 value) public {
+    total = value;
+    balances[owner] = value;
+    allowance[owner][spender] = value + 1;
+    small[value] = 200;
+  }
+}",
+        ),
+      )
+      .created
+      .unwrap();
+    let (owner, spender) = (0xa11ce, 0xb0b);
+    let set = calldata("set(address,address,uint256)", &[owner, spender, 42]);
+    assert!(world.call(SENDER, contract, set).success);
+    // The value of a key is at keccak256(key . slot), and nested mappings hash again
+    let slot = |key: u64, slot: Word| {
+      let mut input = Word::from_u64(key).to_bytes_be().to_vec();
+      input.extend_from_slice(&slot.to_bytes_be());
+      Word::from_bytes_be(&keccak256(&input))
+    };
+    let storage = |key: Word| world.storage(contract, key);
+    assert_eq!(storage(Word::ZERO), Word::from_u64(42));
+    assert_eq!(storage(slot(owner, Word::from_u64(1))), Word::from_u64(42));
+    assert_eq!(
+      storage(slot(spender, slot(owner, Word::from_u64(2)))),
+      Word::from_u64(43)
+    );
+    assert_eq!(storage(slot(42, Word::from_u64(3))), Word::from_u64(200));
+
+    let get = |world: &mut World, signature: &str, args: &[u64]| {
+      Word::from_bytes_be(
+        &world
+          .call(SENDER, contract, calldata(signature, args))
+          .output,
+      )
+    };
+    assert_eq!(
+      get(&mut world, "balances(address)", &[owner]),
+      Word::from_u64(42)
+    );
+    assert_eq!(get(&mut world, "balances(address)", &[spender]), Word::ZERO);
+    assert_eq!(
+      get(&mut world, "allowance(address,address)", &[owner, spender]),
+      Word::from_u64(43)
+    );
+    assert_eq!(
+      get(&mut world, "small(uint256)", &[42]),
+      Word::from_u64(200)
+    );
+  }
+
+  #[test]
+  fn encodes_dynamic_event_data_and_hashes_indexed_arrays() {
+    let mut world = World::new();
+    let contract = world
+      .deploy(
+        SENDER,
+        compile(
+          "pragma solidity ^0.8.0;
+contract Events {
+  event Note(address indexed from, string text, uint256 value, bytes data);
+  event Tagged(uint256[] indexed ids, uint256[2] indexed pair, string indexed name);
+  function note() public {
+    emit Note(msg.sender, \"hello\", 7, hex\"cafe\");
+  }
+  function tag() public {
+    uint256[] memory ids = new uint256[](2);
+    ids[0] = 1;
+    ids[1] = 2;
+    uint256[2] memory pair = [uint256(3), uint256(4)];
+    emit Tagged(ids, pair, \"x\");
+  }
+}",
+        ),
+      )
+      .created
+      .unwrap();
+    let words = |values: &[u64]| -> Vec<u8> {
+      values
+        .iter()
+        .flat_map(|value| Word::from_u64(*value).to_bytes_be().to_vec())
+        .collect()
+    };
+    let padded = |bytes: &[u8]| {
+      let mut word = bytes.to_vec();
+      word.resize(32, 0);
+      word
+    };
+    let hash = |bytes: &[u8]| Word::from_bytes_be(&keccak256(bytes));
+
+    let note = world.call(SENDER, contract, calldata("note()", &[]));
+    assert!(note.success);
+    // Strings and bytes are encoded in the tail, after the offsets to them
+    let mut data = words(&[0x60, 7, 0xa0, 5]);
+    data.extend(padded(b"hello"));
+    data.extend(words(&[2]));
+    data.extend(padded(&[0xca, 0xfe]));
+    assert_eq!(
+      note.logs,
+      vec![Log {
+        address: contract,
+        topics: vec![hash(b"Note(address,string,uint256,bytes)"), SENDER],
+        data,
+      }]
+    );
+
+    let tag = world.call(SENDER, contract, calldata("tag()", &[]));
+    assert!(tag.success);
+    // Indexed arrays are hashed as the concatenation of their padded elements
+    assert_eq!(
+      tag.logs,
+      vec![Log {
+        address: contract,
+        topics: vec![
+          hash(b"Tagged(uint256[],uint256[2],string)"),
+          hash(&words(&[1, 2])),
+          hash(&words(&[3, 4])),
+          hash(b"x"),
+        ],
+        data: vec![],
+      }]
+    );
+  }
+
+  #[test]
+  fn encodes_static_arrays_in_event_data() {
+    let mut world = World::new();
+    let contract = world
+      .deploy(
+        SENDER,
+        compile(
+          "pragma solidity ^0.8.0;
+contract Events {
+  event E(uint256[2] a);
+  event Grid(uint8 size, uint256[3] cells, string name);
+  function e() public {
+    emit E([uint256(1), 2]);
+  }
+  function grid() public {
+    uint256[3] memory cells;
+    cells[0] = 1;
+    cells[2] = 3;
+    emit Grid(6, cells, \"g\");
+  }
+}",
+        ),
+      )
+      .created
+      .unwrap();
+    let words = |values: &[u64]| -> Vec<u8> {
+      values
+        .iter()
+        .flat_map(|value| Word::from_u64(*value).to_bytes_be().to_vec())
+        .collect()
+    };
+    let hash = |bytes: &[u8]| Word::from_bytes_be(&keccak256(bytes));
+
+    let e = world.call(SENDER, contract, calldata("e()", &[]));
+    assert!(e.success);
+    assert_eq!(
+      e.logs,
+      vec![Log {
+        address: contract,
+        topics: vec![hash(b"E(uint256[2])")],
+        data: words(&[1, 2]),
+      }]
+    );
+
+    let grid = world.call(SENDER, contract, calldata("grid()", &[]));
+    assert!(grid.success);
+    // The elements of static arrays are encoded in place, so the string comes after 5 words
+    let mut data = words(&[6, 1, 0, 3, 0xa0, 1]);
+    data.extend(b"g");
+    data.resize(data.len() + 31, 0);
+    assert_eq!(
+      grid.logs,
+      vec![Log {
+        address: contract,
+        topics: vec![hash(b"Grid(uint8,uint256[3],string)")],
+        data,
+      }]
+    );
+  }
+
+  #[test]
+  fn fails_calls_to_unsupported_precompiles() {
+    let mut world = World::new();
+    let modexp = world.transact(SENDER, Some(Word::from_u64(5)), Word::ZERO, vec![], 50000);
+    assert!(!modexp.success);
+    assert_eq!(modexp.gas_used, 50000);
+    // The world is still usable afterwards
+    let identity = world.call(SENDER, Word::from_u64(4), b"abc".to_vec());
+    assert_eq!((identity.success, identity.output), (true, b"abc".to_vec()));
+  }
+}
@@ -0,0 +1,181 @@
+use crate::{abi::keccak256, evm::word::Word};
+use ripemd160::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// The order of the secp256k1 group, which signature values have to be below.
+const SECP256K1_ORDER: [u8; 32] = [
+  0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+  0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+/// The last address of a precompiled contract. Only `0x01` to `0x04` are implemented, calls to
+/// modexp, ecadd, ecmul, ecpairing, blake2f and point evaluation fail and consume all gas.
+const LAST_PRECOMPILE: u64 = 0x0a;
+
+/// The output of a precompiled contract, or `None` if it failed, together with the gas it
+/// costs.
+pub struct Execution {
+  pub output: Option<Vec<u8>>,
+  pub gas_used: u64,
+}
+
+fn words(size: usize) -> u64 {
+  (size as u64).div_ceil(32)
+}
+
+/// Whether the address is one of the precompiled contracts.
+pub fn is_precompile(address: Word) -> bool {
+  match address.to_u64() {
+    Some(index) => (1..=LAST_PRECOMPILE).contains(&index),
+    None => false,
+  }
+}
+
+/// Runs the precompiled contract at the address with the given gas.
+pub fn execute(address: Word, input: &[u8], gas: u64) -> Execution {
+  let index = address.to_u64().unwrap_or_default();
+  let cost = match index {
+    1 => 3000,
+    2 => 60 + 12 * words(input.len()),
+    3 => 600 + 120 * words(input.len()),
+    4 => 15 + 3 * words(input.len()),
+    _ => {
+      return Execution {
+        output: None,
+        gas_used: gas,
+      }
+    }
+  };
+  if cost > gas {
+    return Execution {
+      output: None,
+      gas_used: gas,
+    };
+  }
+  let output = match index {
+    1 => ecrecover(input),
+    2 => Sha256::digest(input).to_vec(),
+    3 => {
+      let mut output = vec![0; 12];
+      output.extend_from_slice(&Ripemd160::digest(input));
+      output
+    }
+    _ => input.to_vec(),
+  };
+  Execution {
+    output: Some(output),
+    gas_used: cost,
+  }
+}
+
+/// The address that signed the hash as a word, or nothing if the signature is invalid.
+fn ecrecover(input: &[u8]) -> Vec<u8> {
+  let mut padded = [0; 128];
+  let length = input.len().min(128);
+  padded[..length].copy_from_slice(&input[..length]);
+  let word = |i: usize| Word::from_bytes_be(&padded[i * 32..(i + 1) * 32]);
+  let (v, r, s) = (word(1), word(2), word(3));
+  let valid = |value: Word| !value.is_zero() && value < Word::from_bytes_be(&SECP256K1_ORDER);
+  if (v != Word::from_u64(27) && v != Word::from_u64(28)) || !valid(r) || !valid(s) {
+    return vec![];
+  }
+  let mut hash = [0; 32];
+  hash.copy_from_slice(&padded[..32]);
+  let mut signature = [0; 64];
+  signature.copy_from_slice(&padded[64..]);
+  let recovery_id = match secp256k1::RecoveryId::parse(v.to_u64().unwrap() as u8 - 27) {
+    Ok(recovery_id) => recovery_id,
+    Err(_) => return vec![],
+  };
+  match secp256k1::recover(
+    &secp256k1::Message::parse(&hash),
+    &secp256k1::Signature::parse(&signature),
+    &recovery_id,
+  ) {
+    Ok(public_key) => {
+      let mut output = vec![0; 12];
+      output.extend_from_slice(&keccak256(&public_key.serialize()[1..])[12..]);
+      output
+    }
+    Err(_) => vec![],
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+  }
+
+  fn bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+      .step_by(2)
+      .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+      .collect()
+  }
+
+  fn run(address: u64, input: &[u8]) -> (String, u64) {
+    let execution = execute(Word::from_u64(address), input, 10_000);
+    (hex(&execution.output.unwrap()), execution.gas_used)
+  }
+
+  #[test]
+  fn hashes_with_sha256_and_ripemd160() {
+    assert_eq!(
+      run(2, b"abc"),
+      (
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+        72
+      )
+    );
+    assert_eq!(
+      run(3, b"abc"),
+      (
+        "0000000000000000000000008eb208f7e05d987a9b044a8e98c6b087f15a0bfc".to_string(),
+        720
+      )
+    );
+    assert_eq!(run(4, b"abc"), ("616263".to_string(), 18));
+  }
+
+  #[test]
+  fn recovers_signer_addresses() {
+    let hash = "38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e";
+    let r = "38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e";
+    let s = "789d1dd423d25f0772d2748d60f7e4b81bb14d086eba8e8e8efb6dcff8a4ae02";
+    let input = |v: &str, s: &str| bytes(&format!("{}{:0>64}{}{}", hash, v, r, s));
+    assert_eq!(
+      run(1, &input("1b", s)),
+      (
+        "000000000000000000000000ceaccac640adf55b2028469bd36ba501f28b699d".to_string(),
+        3000
+      )
+    );
+    // Invalid recovery ids and signature values give no output
+    assert_eq!(run(1, &input("1d", s)), ("".to_string(), 3000));
+    assert_eq!(
+      run(1, &input("1b", &hex(&SECP256K1_ORDER))),
+      ("".to_string(), 3000)
+    );
+    assert!(execute(Word::from_u64(1), &input("1b", s), 2999)
+      .output
+      .is_none());
+  }
+
+  #[test]
+  fn fails_for_unsupported_precompiles() {
+    for address in 5..=LAST_PRECOMPILE {
+      let execution = execute(Word::from_u64(address), &[], 10_000);
+      assert_eq!(
+        (execution.output, execution.gas_used),
+        (None, 10_000),
+        "precompile {:#04x}",
+        address
+      );
+    }
+  }
+}
@@ -0,0 +1,394 @@
+use crate::literal::BigUint;
+use std::{
+  cmp::Ordering,
+  fmt,
+  ops::{Add, Mul, Neg, Not, Shl, Shr, Sub},
+};
+
+/// A 256 bit word of the EVM, stored as 64 bit limbs with the least significant one first.
+/// Arithmetic wraps around like it does in the EVM.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Word([u64; 4]);
+
+impl Word {
+  pub const ZERO: Word = Word([0; 4]);
+  pub const ONE: Word = Word([1, 0, 0, 0]);
+  pub const MAX: Word = Word([u64::MAX; 4]);
+
+  pub fn from_u64(value: u64) -> Word {
+    Word([value, 0, 0, 0])
+  }
+
+  /// Interprets up to 32 big endian bytes, like the immediate of a push.
+  pub fn from_bytes_be(bytes: &[u8]) -> Word {
+    let mut limbs = [0; 4];
+    for (i, byte) in bytes.iter().rev().take(32).enumerate() {
+      limbs[i / 8] |= (*byte as u64) << (8 * (i % 8));
+    }
+    Word(limbs)
+  }
+
+  pub fn to_bytes_be(&self) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    for (i, limb) in self.0.iter().rev().enumerate() {
+      bytes[i * 8..i * 8 + 8].copy_from_slice(&limb.to_be_bytes());
+    }
+    bytes
+  }
+
+  /// The lower 20 bytes, which is how words are used as addresses.
+  pub fn to_address(&self) -> Word {
+    Word([self.0[0], self.0[1], self.0[2] & 0xffff_ffff, 0])
+  }
+
+  pub fn is_zero(&self) -> bool {
+    *self == Word::ZERO
+  }
+
+  /// The value, if it fits into 64 bits.
+  pub fn to_u64(&self) -> Option<u64> {
+    if self.0[1..].iter().all(|limb| *limb == 0) {
+      Some(self.0[0])
+    } else {
+      None
+    }
+  }
+
+  pub fn bit(&self, index: usize) -> bool {
+    index < 256 && self.0[index / 64] >> (index % 64) & 1 == 1
+  }
+
+  /// Whether the most significant bit is set, i.e. the value is negative in two's complement.
+  pub fn is_negative(&self) -> bool {
+    self.bit(255)
+  }
+
+  /// The number of bytes without leading zeros.
+  pub fn byte_len(&self) -> usize {
+    self.to_bytes_be().iter().skip_while(|b| **b == 0).count()
+  }
+
+  pub fn overflowing_add(self, other: Word) -> (Word, bool) {
+    let mut limbs = [0; 4];
+    let mut carry = false;
+    for (i, limb) in limbs.iter_mut().enumerate() {
+      let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+      let (sum, c2) = sum.overflowing_add(carry as u64);
+      *limb = sum;
+      carry = c1 || c2;
+    }
+    (Word(limbs), carry)
+  }
+
+  pub fn and(self, other: Word) -> Word {
+    let mut limbs = self.0;
+    for (limb, o) in limbs.iter_mut().zip(other.0.iter()) {
+      *limb &= o;
+    }
+    Word(limbs)
+  }
+
+  pub fn or(self, other: Word) -> Word {
+    let mut limbs = self.0;
+    for (limb, o) in limbs.iter_mut().zip(other.0.iter()) {
+      *limb |= o;
+    }
+    Word(limbs)
+  }
+
+  pub fn xor(self, other: Word) -> Word {
+    let mut limbs = self.0;
+    for (limb, o) in limbs.iter_mut().zip(other.0.iter()) {
+      *limb ^= o;
+    }
+    Word(limbs)
+  }
+
+  /// Arithmetic shift right, which keeps the sign.
+  pub fn sar(self, shift: usize) -> Word {
+    if self.is_negative() {
+      !(!self >> shift)
+    } else {
+      self >> shift
+    }
+  }
+
+  /// Quotient and remainder, which are both zero for a zero divisor.
+  pub fn div_rem(self, divisor: Word) -> (Word, Word) {
+    if divisor.is_zero() {
+      return (Word::ZERO, Word::ZERO);
+    }
+    let (mut quotient, mut remainder) = (Word::ZERO, Word::ZERO);
+    for i in (0..256).rev() {
+      let carry = remainder.is_negative();
+      remainder = remainder << 1;
+      if self.bit(i) {
+        remainder.0[0] |= 1;
+      }
+      if carry || remainder >= divisor {
+        remainder = remainder - divisor;
+        quotient.0[i / 64] |= 1 << (i % 64);
+      }
+    }
+    (quotient, remainder)
+  }
+
+  fn abs(self) -> Word {
+    if self.is_negative() {
+      -self
+    } else {
+      self
+    }
+  }
+
+  pub fn sdiv(self, divisor: Word) -> Word {
+    let quotient = self.abs().div_rem(divisor.abs()).0;
+    if self.is_negative() != divisor.is_negative() {
+      -quotient
+    } else {
+      quotient
+    }
+  }
+
+  /// The remainder of signed division, which has the sign of the dividend.
+  pub fn smod(self, divisor: Word) -> Word {
+    let remainder = self.abs().div_rem(divisor.abs()).1;
+    if self.is_negative() {
+      -remainder
+    } else {
+      remainder
+    }
+  }
+
+  fn to_big(self) -> BigUint {
+    BigUint::from_bytes_be(&self.to_bytes_be())
+  }
+
+  fn from_big(value: &BigUint) -> Word {
+    Word::from_bytes_be(&value.to_bytes_be())
+  }
+
+  /// `(self + other) % modulus` without wrapping the sum.
+  pub fn addmod(self, other: Word, modulus: Word) -> Word {
+    if modulus.is_zero() {
+      return Word::ZERO;
+    }
+    Word::from_big(
+      &self
+        .to_big()
+        .add(&other.to_big())
+        .div_rem(&modulus.to_big())
+        .1,
+    )
+  }
+
+  /// `(self * other) % modulus` without wrapping the product.
+  pub fn mulmod(self, other: Word, modulus: Word) -> Word {
+    if modulus.is_zero() {
+      return Word::ZERO;
+    }
+    Word::from_big(
+      &self
+        .to_big()
+        .mul(&other.to_big())
+        .div_rem(&modulus.to_big())
+        .1,
+    )
+  }
+
+  pub fn pow(self, exponent: Word) -> Word {
+    let mut result = Word::ONE;
+    for i in (0..256).rev() {
+      result = result * result;
+      if exponent.bit(i) {
+        result = result * self;
+      }
+    }
+    result
+  }
+
+  /// Extends the sign of the lowest `bytes + 1` bytes.
+  pub fn signextend(self, bytes: Word) -> Word {
+    match bytes.to_u64() {
+      Some(bytes) if bytes < 31 => {
+        let bit = 8 * bytes as usize + 7;
+        let mask = Word::MAX << (bit + 1);
+        if self.bit(bit) {
+          self.or(mask)
+        } else {
+          self.and(!mask)
+        }
+      }
+      _ => self,
+    }
+  }
+
+  /// The byte at the index, counted from the most significant one.
+  pub fn byte(self, index: Word) -> Word {
+    match index.to_u64() {
+      Some(index) if index < 32 => Word::from_u64(self.to_bytes_be()[index as usize] as u64),
+      _ => Word::ZERO,
+    }
+  }
+
+  pub fn slt(self, other: Word) -> bool {
+    let sign = Word::ONE << 255;
+    self.xor(sign) < other.xor(sign)
+  }
+}
+
+impl Add for Word {
+  type Output = Word;
+
+  fn add(self, other: Word) -> Word {
+    self.overflowing_add(other).0
+  }
+}
+
+impl Sub for Word {
+  type Output = Word;
+
+  fn sub(self, other: Word) -> Word {
+    self + -other
+  }
+}
+
+impl Mul for Word {
+  type Output = Word;
+
+  fn mul(self, other: Word) -> Word {
+    let mut limbs = [0; 4];
+    for i in 0..4 {
+      let mut carry = 0u128;
+      for j in 0..4 - i {
+        let product = self.0[i] as u128 * other.0[j] as u128 + limbs[i + j] as u128 + carry;
+        limbs[i + j] = product as u64;
+        carry = product >> 64;
+      }
+    }
+    Word(limbs)
+  }
+}
+
+impl Neg for Word {
+  type Output = Word;
+
+  fn neg(self) -> Word {
+    !self + Word::ONE
+  }
+}
+
+impl Not for Word {
+  type Output = Word;
+
+  fn not(self) -> Word {
+    Word([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+  }
+}
+
+impl Shl<usize> for Word {
+  type Output = Word;
+
+  fn shl(self, shift: usize) -> Word {
+    if shift >= 256 {
+      return Word::ZERO;
+    }
+    let mut limbs = [0; 4];
+    let (words, bits) = (shift / 64, shift % 64);
+    for (i, limb) in limbs.iter_mut().enumerate().skip(words) {
+      *limb = self.0[i - words] << bits;
+      if bits > 0 && i > words {
+        *limb |= self.0[i - words - 1] >> (64 - bits);
+      }
+    }
+    Word(limbs)
+  }
+}
+
+impl Shr<usize> for Word {
+  type Output = Word;
+
+  fn shr(self, shift: usize) -> Word {
+    if shift >= 256 {
+      return Word::ZERO;
+    }
+    let mut limbs = [0; 4];
+    let (words, bits) = (shift / 64, shift % 64);
+    for (i, limb) in limbs.iter_mut().enumerate().take(4 - words) {
+      *limb = self.0[i + words] >> bits;
+      if bits > 0 && i + words < 3 {
+        *limb |= self.0[i + words + 1] << (64 - bits);
+      }
+    }
+    Word(limbs)
+  }
+}
+
+impl Ord for Word {
+  fn cmp(&self, other: &Word) -> Ordering {
+    self.0.iter().rev().cmp(other.0.iter().rev())
+  }
+}
+
+impl PartialOrd for Word {
+  fn partial_cmp(&self, other: &Word) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl fmt::Debug for Word {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "0x")?;
+    for byte in self.to_bytes_be().iter() {
+      write!(f, "{:02x}", byte)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn wraps_arithmetic() {
+    let minus_one = Word::MAX;
+    assert_eq!(minus_one + Word::from_u64(2), Word::ONE);
+    assert_eq!(Word::ZERO - Word::ONE, minus_one);
+    assert_eq!(
+      (Word::ONE << 128) * (Word::ONE << 128),
+      Word::ZERO,
+      "2^256 wraps to zero"
+    );
+    assert_eq!(
+      Word::from_u64(100).div_rem(Word::from_u64(7)),
+      (Word::from_u64(14), Word::from_u64(2))
+    );
+    assert_eq!(minus_one.div_rem(Word::from_u64(3)).1, Word::ZERO);
+    assert_eq!(Word::from_u64(2).pow(Word::from_u64(255)), Word::ONE << 255);
+    assert_eq!(
+      minus_one.mulmod(minus_one, Word::from_u64(12)),
+      Word::from_u64(9)
+    );
+  }
+
+  #[test]
+  fn handles_signed_values() {
+    let minus_seven = -Word::from_u64(7);
+    assert_eq!(minus_seven.sdiv(Word::from_u64(2)), -Word::from_u64(3));
+    assert_eq!(minus_seven.smod(Word::from_u64(2)), -Word::ONE);
+    assert_eq!(minus_seven.sar(1), -Word::from_u64(4));
+    assert!(minus_seven.slt(Word::ZERO));
+    assert_eq!(Word::from_u64(0xff).signextend(Word::ZERO), Word::MAX);
+    assert_eq!(
+      Word::from_u64(0x17f).signextend(Word::ZERO),
+      Word::from_u64(0x7f)
+    );
+    assert_eq!(
+      Word::from_u64(0xab).byte(Word::from_u64(31)),
+      Word::from_u64(0xab)
+    );
+  }
+}
@@ -0,0 +1,541 @@
+use crate::{
+    codegen::Context,
+    elementary_type_name::{ElementaryTypeName, UInt},
+    expression::{binary_operator::BinaryOperator, Expression, PrimaryExpression, TypeName},
+    literal::{number_value, Rational},
+    op_codes::OpCode,
+};
+
+/// Panic code for arithmetic overflow and underflow.
+const OVERFLOW: u8 = 0x11;
+/// Panic code for division or modulo by zero.
+const DIVISION_BY_ZERO: u8 = 0x12;
+
+const UINT256: TypeName = TypeName::ElementaryTypeName(ElementaryTypeName::UInt(UInt::Uint256));
+
+/// The width in bits and the signedness of an integer type.
+pub fn integer(type_name: &TypeName) -> Option<(usize, bool)> {
+    match type_name {
+        TypeName::ElementaryTypeName(name) => match name {
+            ElementaryTypeName::Int(_) | ElementaryTypeName::UInt(_) => {
+                Some((name.size()? * 8, name.is_signed()))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The largest value of the type, `2^(bits - 1) - 1` or `2^bits - 1`.
+fn max_value(bits: usize, signed: bool) -> Vec<u8> {
+    let mut value = vec![0xff; bits / 8];
+    if signed {
+        value[0] = 0x7f;
+    }
+    value
+}
+
+/// The smallest value of a signed type, `-2^(bits - 1)` in two's complement.
+fn min_value(bits: usize) -> Vec<u8> {
+    let mut value = vec![0xff; 32];
+    value[32 - bits / 8] = 0x80;
+    for byte in value.iter_mut().skip(32 - bits / 8 + 1) {
+        *byte = 0;
+    }
+    value
+}
+
+/// Whether the expression is made of number literals only, so that it is evaluated at compile
+/// time.
+pub fn is_constant(expr: &Expression) -> bool {
+    match expr {
+        Expression::PrimaryExpression(PrimaryExpression::NumberLiteral(_)) => true,
+        Expression::BinaryOperation(lhs, op, rhs) => {
+            op.is_arithmetic() && is_constant(lhs) && is_constant(rhs)
+        }
+        _ => false,
+    }
+}
+
+/// Evaluates a constant expression with arbitrary precision, returning `None` if the
+/// expression is not constant.
+pub fn constant_value(expr: &Expression) -> Result<Option<Rational>, String> {
+    if !is_constant(expr) {
+        return Ok(None);
+    }
+    match expr {
+        Expression::PrimaryExpression(PrimaryExpression::NumberLiteral(literal)) => {
+            number_value(literal).map(Some)
+        }
+        Expression::BinaryOperation(lhs, op, rhs) => {
+            let lhs = constant_value(lhs)?.unwrap();
+            let rhs = constant_value(rhs)?.unwrap();
+            let value = match op {
+                BinaryOperator::Add => lhs.add(&rhs),
+                BinaryOperator::Sub => lhs.sub(&rhs),
+                BinaryOperator::Mul => lhs.mul(&rhs),
+                BinaryOperator::Div => lhs.div(&rhs),
+                BinaryOperator::Mod => lhs.rem(&rhs),
+                BinaryOperator::Exp => lhs.pow(&rhs),
+                _ => return Ok(None),
+            };
+            value.map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Pushes a constant for a value of the given type, which it has to fit into.
+pub fn constant_op_codes(
+    value: &Rational,
+    type_name: &TypeName,
+    ctx: &mut Context,
+) -> Result<(), String> {
+    match integer(type_name) {
+        Some((bits, signed)) if !value.fits(bits, signed) => Err(format!(
+            "Type {} {} is not implicitly convertible to expected type {}.",
+            if value.is_integer() {
+                "int_const"
+            } else {
+                "rational_const"
+            },
+            value,
+            type_name.abi_name()
+        )),
+        Some(_) => ctx.push_constant(value),
+        None => match type_name {
+            TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(bytes)) => {
+                if !value.fits(bytes.size() * 8, false) {
+                    return Err(format!(
+                        "Type int_const {} is not implicitly convertible to expected type {}.",
+                        value,
+                        type_name.abi_name()
+                    ));
+                }
+                // Fixed size byte arrays are left aligned
+                ctx.push_constant(value)?;
+                if bytes.size() < 32 {
+                    ctx.asm.push_usize(256 - bytes.size() * 8);
+                    ctx.asm.op(OpCode::SHL);
+                }
+                Ok(())
+            }
+            _ => {
+                ctx.push_constant(value)?;
+                ctx.cleanup(type_name);
+                Ok(())
+            }
+        },
+    }
+}
+
+/// The error for operands whose types the operator cannot be applied to, where `None` is the
+/// type of number literals.
+pub fn incompatible_types(
+    op: BinaryOperator,
+    lhs_type: &Option<TypeName>,
+    rhs_type: &Option<TypeName>,
+) -> String {
+    let name = |type_name: &Option<TypeName>| match type_name {
+        Some(type_name) => type_name.abi_name(),
+        None => "int_const".to_string(),
+    };
+    format!(
+        "Operator {} not compatible with types {} and {}",
+        op.token(),
+        name(lhs_type),
+        name(rhs_type)
+    )
+}
+
+/// The type both operands are converted to, which is also the type of the result. Number
+/// literals take the type of the other operand. The result of `**` has the type of the base.
+pub fn result_type(
+    lhs: &Expression,
+    op: BinaryOperator,
+    rhs: &Expression,
+    ctx: &Context,
+) -> Result<TypeName, String> {
+    let lhs_type = lhs.value_type(ctx);
+    let rhs_type = rhs.value_type(ctx);
+    let incompatible = || incompatible_types(op, &lhs_type, &rhs_type);
+    if op == BinaryOperator::Exp {
+        match rhs_type.as_ref().map(integer) {
+            Some(Some((_, true))) => {
+                return Err(
+                    "Exponentiation power is not allowed to be a signed integer type.".to_string(),
+                )
+            }
+            Some(None) => return Err(incompatible()),
+            _ => {}
+        }
+        let base = lhs_type.clone().unwrap_or(UINT256);
+        return match integer(&base) {
+            Some(_) => Ok(base),
+            None => Err(incompatible()),
+        };
+    }
+    match (&lhs_type, &rhs_type) {
+        (Some(a), Some(b)) => match (integer(a), integer(b)) {
+            (Some((a_bits, a_signed)), Some((b_bits, b_signed))) if a_signed == b_signed => {
+                Ok(if a_bits >= b_bits { a } else { b }.clone())
+            }
+            _ => Err(incompatible()),
+        },
+        (Some(type_name), None) | (None, Some(type_name)) => match integer(type_name) {
+            Some(_) => Ok(type_name.clone()),
+            None => Err(incompatible()),
+        },
+        (None, None) => Ok(UINT256),
+    }
+}
+
+/// Pushes the result of `lhs op rhs`.
+pub fn binary_op_codes<'a>(
+    lhs: &'a Expression,
+    op: BinaryOperator,
+    rhs: &'a Expression,
+    ctx: &mut Context<'a>,
+) -> Result<(), String> {
+    let type_name = result_type(lhs, op, rhs, ctx)?;
+    lhs.converted_value(&type_name, &None, ctx)?;
+    let rhs_type = match op {
+        BinaryOperator::Exp => rhs.value_type(ctx).unwrap_or(UINT256),
+        _ => type_name.clone(),
+    };
+    rhs.converted_value(&rhs_type, &None, ctx)?;
+    operation(op, &type_name, ctx)
+}
+
+/// Replaces the two operands on top of the stack with the result of the operation. Overflows
+/// revert with `Panic(0x11)`, unless inside of an `unchecked` block where the result wraps
+/// around. Division by zero always reverts with `Panic(0x12)`.
+pub fn operation(
+    op: BinaryOperator,
+    type_name: &TypeName,
+    ctx: &mut Context,
+) -> Result<(), String> {
+    let (bits, signed) = integer(type_name).ok_or_else(|| {
+        format!(
+            "Operator {} not compatible with type {}",
+            op.token(),
+            type_name.abi_name()
+        )
+    })?;
+    let x = ctx.asm.stack_height() - 2;
+    let y = x + 1;
+    let checked = !ctx.is_unchecked();
+
+    if op == BinaryOperator::Div || op == BinaryOperator::Mod {
+        ctx.load_local(y)?;
+        ctx.asm.op(OpCode::ISZERO);
+        panic_if(DIVISION_BY_ZERO, ctx);
+    }
+    if checked {
+        match (op, signed) {
+            (BinaryOperator::Exp, _) => return checked_exp(bits, signed, ctx),
+            (BinaryOperator::Add, false) => {
+                // x > max - y
+                ctx.load_local(y)?;
+                ctx.asm.push(&max_value(bits, false));
+                ctx.asm.op(OpCode::SUB);
+                ctx.load_local(x)?;
+                ctx.asm.op(OpCode::GT);
+                panic_if(OVERFLOW, ctx);
+            }
+            (BinaryOperator::Sub, false) => {
+                // x < y
+                ctx.load_local(y)?;
+                ctx.load_local(x)?;
+                ctx.asm.op(OpCode::LT);
+                panic_if(OVERFLOW, ctx);
+            }
+            (BinaryOperator::Mul, false) => {
+                // x != 0 && y > max / x
+                ctx.load_local(x)?;
+                ctx.asm.push(&max_value(bits, false));
+                ctx.asm.op(OpCode::DIV);
+                ctx.load_local(y)?;
+                ctx.asm.op(OpCode::GT);
+                ctx.load_local(x)?;
+                ctx.asm.op(OpCode::ISZERO);
+                ctx.asm.op(OpCode::ISZERO);
+                ctx.asm.op(OpCode::AND);
+                panic_if(OVERFLOW, ctx);
+            }
+            (BinaryOperator::Add, true) => {
+                // x >= 0 && y > max - x
+                ctx.load_local(x)?;
+                ctx.asm.push(&max_value(bits, true));
+                ctx.asm.op(OpCode::SUB);
+                ctx.load_local(y)?;
+                ctx.asm.op(OpCode::SGT);
+                push_sign(x, false, ctx)?;
+                ctx.asm.op(OpCode::ISZERO);
+                ctx.asm.op(OpCode::AND);
+                panic_if(OVERFLOW, ctx);
+                // x < 0 && y < min - x
+                ctx.load_local(x)?;
+                ctx.asm.push(&min_value(bits));
+                ctx.asm.op(OpCode::SUB);
+                ctx.load_local(y)?;
+                ctx.asm.op(OpCode::SLT);
+                push_sign(x, false, ctx)?;
+                ctx.asm.op(OpCode::AND);
+                panic_if(OVERFLOW, ctx);
+            }
+            (BinaryOperator::Sub, true) => {
+                // y >= 0 && x < min + y
+                ctx.load_local(y)?;
+                ctx.asm.push(&min_value(bits));
+                ctx.asm.op(OpCode::ADD);
+                ctx.load_local(x)?;
+                ctx.asm.op(OpCode::SLT);
+                push_sign(y, false, ctx)?;
+                ctx.asm.op(OpCode::ISZERO);
+                ctx.asm.op(OpCode::AND);
+                panic_if(OVERFLOW, ctx);
+                // y < 0 && x > max + y
+                ctx.load_local(y)?;
+                ctx.asm.push(&max_value(bits, true));
+                ctx.asm.op(OpCode::ADD);
+                ctx.load_local(x)?;
+                ctx.asm.op(OpCode::SGT);
+                push_sign(y, false, ctx)?;
+                ctx.asm.op(OpCode::AND);
+                panic_if(OVERFLOW, ctx);
+            }
+            (BinaryOperator::Mul, true) => {
+                // x > 0 && y > 0 && x > max / y
+                ctx.load_local(y)?;
+                ctx.asm.push(&max_value(bits, true));
+                ctx.asm.op(OpCode::DIV);
+                ctx.load_local(x)?;
+                ctx.asm.op(OpCode::GT);
+                signed_mul_guard(x, y, true, true, ctx)?;
+                // x > 0 && y < 0 && y < min / x
+                ctx.load_local(x)?;
+                ctx.asm.push(&min_value(bits));
+                ctx.asm.op(OpCode::SDIV);
+                ctx.load_local(y)?;
+                ctx.asm.op(OpCode::SLT);
+                signed_mul_guard(x, y, true, false, ctx)?;
+                // x < 0 && y > 0 && x < min / y
+                ctx.load_local(y)?;
+                ctx.asm.push(&min_value(bits));
+                ctx.asm.op(OpCode::SDIV);
+                ctx.load_local(x)?;
+                ctx.asm.op(OpCode::SLT);
+                signed_mul_guard(x, y, false, true, ctx)?;
+                // x < 0 && y < 0 && x < max / y
+                ctx.load_local(y)?;
+                ctx.asm.push(&max_value(bits, true));
+                ctx.asm.op(OpCode::SDIV);
+                ctx.load_local(x)?;
+                ctx.asm.op(OpCode::SLT);
+                signed_mul_guard(x, y, false, false, ctx)?;
+            }
+            (BinaryOperator::Div, true) => {
+                // x == min && y == -1
+                ctx.load_local(x)?;
+                ctx.asm.push(&min_value(bits));
+                ctx.asm.op(OpCode::EQ);
+                ctx.load_local(y)?;
+                ctx.asm.push(&[0]);
+                ctx.asm.op(OpCode::NOT);
+                ctx.asm.op(OpCode::EQ);
+                ctx.asm.op(OpCode::AND);
+                panic_if(OVERFLOW, ctx);
+            }
+            _ => {}
+        }
+    }
+
+    match (op, signed) {
+        (BinaryOperator::Add, _) => ctx.asm.op(OpCode::ADD),
+        (BinaryOperator::Mul, _) => ctx.asm.op(OpCode::MUL),
+        (BinaryOperator::Sub, _) => {
+            ctx.asm.op(OpCode::SWAP1);
+            ctx.asm.op(OpCode::SUB);
+        }
+        (BinaryOperator::Div, false) => {
+            ctx.asm.op(OpCode::SWAP1);
+            ctx.asm.op(OpCode::DIV);
+        }
+        (BinaryOperator::Div, true) => {
+            ctx.asm.op(OpCode::SWAP1);
+            ctx.asm.op(OpCode::SDIV);
+        }
+        (BinaryOperator::Mod, false) => {
+            ctx.asm.op(OpCode::SWAP1);
+            ctx.asm.op(OpCode::MOD);
+        }
+        (BinaryOperator::Mod, true) => {
+            ctx.asm.op(OpCode::SWAP1);
+            ctx.asm.op(OpCode::SMOD);
+        }
+        (BinaryOperator::Exp, _) => {
+            ctx.asm.op(OpCode::SWAP1);
+            ctx.asm.op(OpCode::EXP);
+        }
+        _ => {
+            return Err(format!(
+                "Operator {} not compatible with type {}",
+                op.token(),
+                type_name.abi_name()
+            ))
+        }
+    }
+    if !checked {
+        ctx.cleanup(type_name);
+    }
+    Ok(())
+}
+
+/// Consumes the condition on top of the stack and panics if it holds.
+fn panic_if(code: u8, ctx: &mut Context) {
+    let ok = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(ok);
+    ctx.panic(code);
+    ctx.asm.tag(ok);
+}
+
+/// Pushes whether the signed value at `position` is positive, or negative.
+fn push_sign(position: usize, positive: bool, ctx: &mut Context) -> Result<(), String> {
+    ctx.asm.push(&[0]);
+    ctx.load_local(position)?;
+    ctx.asm.op(if positive { OpCode::SGT } else { OpCode::SLT });
+    Ok(())
+}
+
+/// Panics if the condition on top of the stack holds for operands of the given signs.
+fn signed_mul_guard(
+    x: usize,
+    y: usize,
+    x_positive: bool,
+    y_positive: bool,
+    ctx: &mut Context,
+) -> Result<(), String> {
+    push_sign(x, x_positive, ctx)?;
+    ctx.asm.op(OpCode::AND);
+    push_sign(y, y_positive, ctx)?;
+    ctx.asm.op(OpCode::AND);
+    panic_if(OVERFLOW, ctx);
+    Ok(())
+}
+
+/// Replaces the base and exponent on top of the stack with the power, reverting on overflow.
+/// Signed bases are raised by their absolute value and negated afterwards if the exponent is
+/// odd.
+fn checked_exp(bits: usize, signed: bool, ctx: &mut Context) -> Result<(), String> {
+    let base = ctx.asm.stack_height() - 2;
+    let exponent = base + 1;
+    if !signed {
+        ctx.asm.push(&[1]);
+        power(base, exponent, &max_value(bits, false), ctx)?;
+        ctx.asm.op(OpCode::SWAP2);
+        ctx.asm.op(OpCode::POP);
+        ctx.asm.op(OpCode::POP);
+        return Ok(());
+    }
+
+    // negative = base < 0 && exponent is odd
+    push_sign(base, false, ctx)?;
+    ctx.asm.push(&[1]);
+    ctx.load_local(exponent)?;
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.op(OpCode::AND);
+    let negative = exponent + 1;
+    // base = abs(base), using the all ones mask of negative numbers
+    ctx.load_local(base)?;
+    ctx.asm.push(&[0xff]);
+    ctx.asm.op(OpCode::SAR);
+    ctx.load_local(base)?;
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::XOR);
+    ctx.asm.op(OpCode::SUB);
+    ctx.store_local(base)?;
+
+    // -2^(bits - 1) is the result with the largest absolute value
+    let mut limit = vec![0; bits / 8];
+    limit[0] = 0x80;
+    ctx.asm.push(&[1]);
+    power(base, exponent, &limit, ctx)?;
+    let positive = ctx.asm.new_tag();
+    let end = ctx.asm.new_tag();
+    ctx.load_local(negative)?;
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(positive);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::SUB);
+    ctx.asm.jump(end);
+    ctx.asm.tag(positive);
+    ctx.asm.push(&max_value(bits, true));
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::GT);
+    panic_if(OVERFLOW, ctx);
+    ctx.asm.tag(end);
+    ctx.asm.op(OpCode::SWAP3);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    Ok(())
+}
+
+/// Multiplies the result on top of the stack by `base^exponent` using exponentiation by
+/// squaring, consuming the base and exponent in place. Panics if the result exceeds `limit`.
+/// Squaring the base can only overflow if the result would, as the remaining exponent is
+/// non zero.
+fn power(base: usize, exponent: usize, limit: &[u8], ctx: &mut Context) -> Result<(), String> {
+    let result = ctx.asm.stack_height() - 1;
+    let start = ctx.asm.new_tag();
+    let done = ctx.asm.new_tag();
+    let even = ctx.asm.new_tag();
+    ctx.asm.tag(start);
+    ctx.load_local(exponent)?;
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(done);
+
+    ctx.load_local(exponent)?;
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(even);
+    // result != 0 && base > limit / result
+    ctx.load_local(result)?;
+    ctx.asm.push(limit);
+    ctx.asm.op(OpCode::DIV);
+    ctx.load_local(base)?;
+    ctx.asm.op(OpCode::GT);
+    ctx.load_local(result)?;
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.op(OpCode::AND);
+    panic_if(OVERFLOW, ctx);
+    ctx.load_local(base)?;
+    ctx.asm.op(OpCode::MUL);
+    ctx.asm.tag(even);
+
+    ctx.load_local(exponent)?;
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::SHR);
+    ctx.store_local(exponent)?;
+    ctx.load_local(exponent)?;
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(start);
+    // base > limit / base
+    ctx.load_local(base)?;
+    ctx.asm.push(limit);
+    ctx.asm.op(OpCode::DIV);
+    ctx.load_local(base)?;
+    ctx.asm.op(OpCode::GT);
+    panic_if(OVERFLOW, ctx);
+    ctx.load_local(base)?;
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.op(OpCode::MUL);
+    ctx.store_local(base)?;
+    ctx.asm.jump(start);
+    ctx.asm.tag(done);
+    Ok(())
+}
@@ -0,0 +1,495 @@
+use crate::{
+    codegen::Context,
+    elementary_type_name::{ElementaryTypeName, FixedBytes, Int, UInt},
+    expression::{
+        arithmetic::constant_value, bytes, Expression, PrimaryExpression, Reference, TypeName,
+    },
+    literal::Number,
+    op_codes::OpCode,
+    storage_location::StorageLocation,
+};
+
+/// Empty dynamic memory arrays all point to this zero word, like in solc.
+const ZERO_SLOT: u8 = 0x60;
+
+/// The number of elements of a static array, which has to be a constant.
+pub fn array_length(length: &Expression) -> Result<usize, String> {
+    let parsed = match constant_value(length) {
+        Ok(Some(value)) => value.to_usize(),
+        _ => None,
+    };
+    match parsed {
+        Some(0) => Err("Array with zero length specified.".to_string()),
+        Some(length) => Ok(length),
+        None => Err("Invalid array length, expected integer literal.".to_string()),
+    }
+}
+
+impl TypeName {
+    /// Replaces the slot of a mapping or array and the key or index on top of it with the slot
+    /// of the element, returning the type of the element.
+    pub fn index_storage(&self, ctx: &mut Context) -> Result<TypeName, String> {
+        match self {
+            TypeName::Mapping(key, value) => {
+                ctx.cleanup(key);
+                ctx.mapping_slot();
+                if value.is_packed() {
+                    ctx.asm.push(&[0]);
+                }
+                Ok(*value.clone())
+            }
+            TypeName::Array(element, Some(length)) => {
+                bounds_check_static(array_length(length)?, ctx);
+                element_slot(element, ctx)?;
+                Ok(*element.clone())
+            }
+            TypeName::Array(element, None) => {
+                let in_bounds = ctx.asm.new_tag();
+                ctx.asm.op(OpCode::DUP2);
+                ctx.asm.op(OpCode::SLOAD);
+                ctx.asm.op(OpCode::DUP2);
+                ctx.asm.op(OpCode::LT);
+                ctx.asm.jumpi(in_bounds);
+                ctx.panic(0x32);
+                ctx.asm.tag(in_bounds);
+                ctx.asm.op(OpCode::SWAP1);
+                data_slot(ctx);
+                ctx.asm.op(OpCode::SWAP1);
+                element_slot(element, ctx)?;
+                Ok(*element.clone())
+            }
+            TypeName::ElementaryTypeName(ElementaryTypeName::Bytes) => {
+                Err("Assigning to elements of bytes in storage is not supported yet".to_string())
+            }
+            TypeName::ElementaryTypeName(ElementaryTypeName::String) => {
+                Err("Index access for string is not possible.".to_string())
+            }
+            _ => Err(format!(
+                "Indexed expression has to be a type, mapping or array (is {})",
+                self.abi_name()
+            )),
+        }
+    }
+
+    /// Replaces the pointer to a memory array and the index on top of it with the address of
+    /// the element. Every element takes up a word, nested arrays are stored as pointers.
+    pub fn index_memory(&self, ctx: &mut Context) -> Result<TypeName, String> {
+        match self {
+            TypeName::Array(element, Some(length)) => {
+                bounds_check_static(array_length(length)?, ctx);
+                ctx.asm.push(&[5]);
+                ctx.asm.op(OpCode::SHL);
+                ctx.asm.op(OpCode::ADD);
+                Ok(*element.clone())
+            }
+            TypeName::Array(element, None) => {
+                let in_bounds = ctx.asm.new_tag();
+                ctx.asm.op(OpCode::DUP2);
+                ctx.asm.op(OpCode::MLOAD);
+                ctx.asm.op(OpCode::DUP2);
+                ctx.asm.op(OpCode::LT);
+                ctx.asm.jumpi(in_bounds);
+                ctx.panic(0x32);
+                ctx.asm.tag(in_bounds);
+                ctx.asm.push(&[5]);
+                ctx.asm.op(OpCode::SHL);
+                ctx.asm.op(OpCode::ADD);
+                ctx.asm.push(&[0x20]);
+                ctx.asm.op(OpCode::ADD);
+                Ok(*element.clone())
+            }
+            TypeName::ElementaryTypeName(ElementaryTypeName::Bytes) => {
+                bytes::index_memory(ctx);
+                Ok(TypeName::ElementaryTypeName(
+                    ElementaryTypeName::FixedBytes(FixedBytes::Bytes1),
+                ))
+            }
+            TypeName::ElementaryTypeName(ElementaryTypeName::String) => {
+                Err("Index access for string is not possible.".to_string())
+            }
+            _ => Err(format!(
+                "Indexed expression has to be a type, mapping or array (is {})",
+                self.abi_name()
+            )),
+        }
+    }
+}
+
+/// Panics unless the index on top of the stack is below `length`.
+fn bounds_check_static(length: usize, ctx: &mut Context) {
+    let in_bounds = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push_usize(length);
+    ctx.asm.op(OpCode::GT);
+    ctx.asm.jumpi(in_bounds);
+    ctx.panic(0x32);
+    ctx.asm.tag(in_bounds);
+}
+
+/// Replaces the slot of a dynamic storage array with the slot of its first element,
+/// `keccak256(slot)`.
+fn data_slot(ctx: &mut Context) {
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::SHA3);
+}
+
+/// Replaces the first slot and the index on top of it with the slot of the element, followed
+/// by its byte offset if several elements share a slot.
+fn element_slot(element: &TypeName, ctx: &mut Context) -> Result<(), String> {
+    if let Some(per_slot) = element.elements_per_slot() {
+        ctx.asm.push_usize(per_slot);
+        ctx.asm.op(OpCode::DUP2);
+        ctx.asm.op(OpCode::DIV);
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.push_usize(per_slot);
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.op(OpCode::MOD);
+        ctx.asm.push_usize(element.storage_bytes()?);
+        ctx.asm.op(OpCode::MUL);
+        ctx.asm.op(OpCode::SWAP2);
+        ctx.asm.op(OpCode::ADD);
+        ctx.asm.op(OpCode::SWAP1);
+        return Ok(());
+    }
+    let size = element.storage_size()?;
+    if size > 1 {
+        ctx.asm.push_usize(size);
+        ctx.asm.op(OpCode::MUL);
+    }
+    ctx.asm.op(OpCode::ADD);
+    Ok(())
+}
+
+fn element_reference(element: &TypeName) -> Reference {
+    Reference {
+        location: StorageLocation::Storage,
+        type_name: element.clone(),
+    }
+}
+
+/// Stores the value on top of the stack at the location below it.
+fn store_below(reference: &Reference, ctx: &mut Context) -> Result<(), String> {
+    for depth in (1..=reference.size()).rev() {
+        ctx.asm.swap(depth);
+    }
+    reference.store(ctx)
+}
+
+/// Pushes the initial value of a local variable: zero, or a pointer to a zeroed memory array.
+pub fn push_default_value(
+    type_name: &TypeName,
+    storage_location: &Option<StorageLocation>,
+    ctx: &mut Context,
+) -> Result<(), String> {
+    if type_name.is_value_type() {
+        ctx.asm.push(&[0]);
+        return Ok(());
+    }
+    match (type_name, storage_location) {
+        (TypeName::Array(_, None), Some(StorageLocation::Memory))
+        | (TypeName::ElementaryTypeName(_), Some(StorageLocation::Memory)) => {
+            ctx.asm.push(&[ZERO_SLOT]);
+            Ok(())
+        }
+        (TypeName::Array(element, Some(length)), Some(StorageLocation::Memory)) => {
+            if !element.is_value_type() {
+                return Err(format!(
+                    "Memory arrays of type {} are not supported yet",
+                    type_name.abi_name()
+                ));
+            }
+            let size = 32 * array_length(length)?;
+            ctx.load_free_memory_pointer();
+            ctx.asm.push_usize(size);
+            ctx.asm.op(OpCode::CALLDATASIZE);
+            ctx.asm.op(OpCode::DUP3);
+            ctx.asm.op(OpCode::CALLDATACOPY);
+            ctx.asm.op(OpCode::DUP1);
+            ctx.asm.push_usize(size);
+            ctx.asm.op(OpCode::ADD);
+            ctx.asm.push(&[0x40]);
+            ctx.asm.op(OpCode::MSTORE);
+            Ok(())
+        }
+        (_, Some(StorageLocation::Storage)) => {
+            Err("Uninitialized storage pointers are not allowed.".to_string())
+        }
+        _ => Err(format!(
+            "Data location must be \"memory\" for variable of type {}",
+            type_name.abi_name()
+        )),
+    }
+}
+
+/// `new T[](length)`: allocates a zeroed dynamic array in memory.
+pub fn new_op_codes<'a>(
+    type_name: &TypeName,
+    args: &'a [Expression],
+    ctx: &mut Context<'a>,
+) -> Result<usize, String> {
+    let element = match type_name {
+        TypeName::Array(element, None) => element,
+        _ => {
+            return Err(format!(
+                "Contract creation with new {} is not supported yet",
+                type_name.abi_name()
+            ))
+        }
+    };
+    if !element.is_value_type() {
+        return Err(format!(
+            "Memory arrays of type {} are not supported yet",
+            type_name.abi_name()
+        ));
+    }
+    if args.len() != 1 {
+        return Err(format!(
+            "Wrong argument count for function call: {} arguments given but expected 1.",
+            args.len()
+        ));
+    }
+    args[0].single_value(ctx)?;
+    let in_range = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push(&[0xff; 8]);
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(in_range);
+    ctx.panic(0x41);
+    ctx.asm.tag(in_range);
+
+    ctx.load_free_memory_pointer();
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.push(&[5]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.op(OpCode::CALLDATASIZE);
+    ctx.asm.op(OpCode::DUP4);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::CALLDATACOPY);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x40]);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::POP);
+    Ok(1)
+}
+
+/// The type of an inline array like `[1, 2, 3]`. The elements take the type of the first one
+/// whose type is known, or else the smallest integer type all of the number literals fit into.
+pub fn inline_array_type(elements: &[Expression], ctx: &Context) -> Result<TypeName, String> {
+    let element = match elements.iter().find_map(|element| element.value_type(ctx)) {
+        Some(type_name) => type_name,
+        None => {
+            let mut values = vec![];
+            for element in elements {
+                values.push(constant_value(element)?.ok_or_else(|| {
+                    "Unable to deduce common type for array elements.".to_string()
+                })?);
+            }
+            let signed = values.iter().any(|value| value.negative);
+            let bits = (8..=256)
+                .step_by(8)
+                .find(|bits| values.iter().all(|value| value.fits(*bits, signed)))
+                .ok_or_else(|| "Unable to deduce common type for array elements.".to_string())?;
+            TypeName::ElementaryTypeName(if signed {
+                ElementaryTypeName::Int(Int::from_bits(bits).unwrap())
+            } else {
+                ElementaryTypeName::UInt(UInt::from_bits(bits).unwrap())
+            })
+        }
+    };
+    let length = Expression::PrimaryExpression(PrimaryExpression::NumberLiteral((
+        Number::Decimal(elements.len().to_string()),
+        None,
+    )));
+    Ok(TypeName::Array(Box::new(element), Some(Box::new(length))))
+}
+
+/// Allocates a static memory array of the given type holding the values of `elements`. All of
+/// them are evaluated before the memory is allocated, as they might allocate memory themselves.
+pub fn inline_array_op_codes<'a>(
+    elements: &'a [Expression],
+    type_name: &TypeName,
+    ctx: &mut Context<'a>,
+) -> Result<(), String> {
+    let element = match type_name {
+        TypeName::Array(element, Some(_)) if element.is_value_type() => element,
+        _ => {
+            return Err(format!(
+                "Inline arrays of type {} are not supported yet",
+                type_name.abi_name()
+            ))
+        }
+    };
+    for value in elements {
+        value.converted_value(element, &None, ctx)?;
+    }
+    ctx.load_free_memory_pointer();
+    for index in (0..elements.len()).rev() {
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.op(OpCode::DUP2);
+        ctx.asm.push_usize(32 * index);
+        ctx.asm.op(OpCode::ADD);
+        ctx.asm.op(OpCode::MSTORE);
+    }
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push_usize(32 * elements.len());
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x40]);
+    ctx.asm.op(OpCode::MSTORE);
+    Ok(())
+}
+
+/// The `length` member and the `push` and `pop` functions of arrays.
+pub fn member_op_codes<'a>(
+    array: &'a Expression,
+    member: &'a Expression,
+    ctx: &mut Context<'a>,
+) -> Result<usize, String> {
+    match member {
+        Expression::PrimaryExpression(PrimaryExpression::Identifier(name)) if name == "length" => {
+            let array = array.container(ctx)?;
+            match (&array.type_name, &array.location) {
+                (TypeName::Array(_, Some(length)), _) => {
+                    ctx.asm.op(OpCode::POP);
+                    ctx.asm.push_usize(array_length(length)?);
+                }
+                (TypeName::ElementaryTypeName(_), StorageLocation::Storage) => {
+                    ctx.asm.op(OpCode::SLOAD);
+                    bytes::storage_length(ctx);
+                }
+                (_, StorageLocation::Storage) => ctx.asm.op(OpCode::SLOAD),
+                _ => ctx.asm.op(OpCode::MLOAD),
+            }
+            Ok(1)
+        }
+        Expression::FunctionCall(call) => {
+            let name = match call.expr.as_ref() {
+                Expression::PrimaryExpression(PrimaryExpression::Identifier(name))
+                    if name == "push" || name == "pop" =>
+                {
+                    name
+                }
+                _ => return Err(format!("Member {:?} not found in array", member)),
+            };
+            let args = call.arguments()?;
+            let array = array.container(ctx)?;
+            let element = match (&array.type_name, &array.location) {
+                (TypeName::Array(element, None), StorageLocation::Storage) => element,
+                _ => {
+                    return Err(format!(
+                        "Member \"{}\" is not available in {} outside of storage.",
+                        name,
+                        array.type_name.abi_name()
+                    ))
+                }
+            };
+            if name == "push" {
+                push_op_codes(element, args, ctx)
+            } else {
+                pop_op_codes(element, args, ctx)
+            }
+        }
+        _ => Err(format!("Member {:?} not found in array", member)),
+    }
+}
+
+fn push_op_codes<'a>(
+    element: &TypeName,
+    args: &'a [Expression],
+    ctx: &mut Context<'a>,
+) -> Result<usize, String> {
+    if args.len() > 1 {
+        return Err(format!(
+            "Wrong argument count for function call: {} arguments given but expected 0 or 1.",
+            args.len()
+        ));
+    }
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.op(OpCode::SLOAD);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::SSTORE);
+    ctx.asm.op(OpCode::SWAP1);
+    data_slot(ctx);
+    ctx.asm.op(OpCode::SWAP1);
+    element_slot(element, ctx)?;
+    let reference = element_reference(element);
+    match args.first() {
+        Some(arg) => {
+            if !element.is_value_type() {
+                return Err(format!(
+                    "Pushing values of type {} is not supported yet",
+                    element.abi_name()
+                ));
+            }
+            arg.single_value(ctx)?;
+            ctx.cleanup(element);
+            store_below(&reference, ctx)?;
+        }
+        None => {
+            for _ in 0..reference.size() {
+                ctx.asm.op(OpCode::POP);
+            }
+        }
+    }
+    Ok(0)
+}
+
+fn pop_op_codes(
+    element: &TypeName,
+    args: &[Expression],
+    ctx: &mut Context,
+) -> Result<usize, String> {
+    if !args.is_empty() {
+        return Err(format!(
+            "Wrong argument count for function call: {} arguments given but expected 0.",
+            args.len()
+        ));
+    }
+    let not_empty = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.op(OpCode::SLOAD);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.jumpi(not_empty);
+    ctx.panic(0x31);
+    ctx.asm.tag(not_empty);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::SUB);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::SSTORE);
+    ctx.asm.op(OpCode::SWAP1);
+    data_slot(ctx);
+    ctx.asm.op(OpCode::SWAP1);
+    element_slot(element, ctx)?;
+    if element.is_packed() {
+        ctx.asm.push(&[0]);
+        return store_below(&element_reference(element), ctx).map(|_| 0);
+    }
+    for offset in 0..element.storage_size()? {
+        ctx.asm.push(&[0]);
+        ctx.asm.op(OpCode::DUP2);
+        if offset > 0 {
+            ctx.asm.push_usize(offset);
+            ctx.asm.op(OpCode::ADD);
+        }
+        ctx.asm.op(OpCode::SSTORE);
+    }
+    ctx.asm.op(OpCode::POP);
+    Ok(0)
+}
@@ -0,0 +1,67 @@
+def snake_to_upper_camel(s):
+    words = s.split("_")
+    return "".join([word.lower().capitalize() for word in words])
+
+
+print("// GENERATED: DO NOT EDIT")
+print("""use nom::{
+    bytes::complete::tag,
+    character::complete::one_of,
+    combinator::{map, not},
+    sequence::terminated,
+    IResult,
+};
+""")
+with open('operators.txt') as f:
+    lines = [line for line in f]
+    for line in lines:
+        name, token = line.rstrip().split(" ")
+        print(f"const {name.upper()}: &str = r#\"{token}\"#;")
+    print("")
+    print("""#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOperator {""")
+    for line in lines:
+        name, _ = line.rstrip().split(" ")
+        print(f"\t{snake_to_upper_camel(name)},")
+    print("}")
+    print("")
+    print("impl BinaryOperator {")
+    print("\tpub fn token(self) -> &'static str {")
+    print("\t\tmatch self {")
+    for line in lines:
+        name, _ = line.rstrip().split(" ")
+        print(f"\t\t\tBinaryOperator::{snake_to_upper_camel(name)} => {name.upper()},")
+    print("\t\t}")
+    print("\t}")
+    print("}")
+    print("")
+    for line in lines:
+        name, token = line.rstrip().split(" ")
+        # Single character operators must not be the start of a longer operator like `+=` or `**`.
+        forbidden = "=" + (token if len(token) == 1 else "")
+        print(f"""pub fn parse_{name}(i: &[u8]) -> IResult<&[u8], BinaryOperator> {{
+    map(
+        terminated(tag({name.upper()}), not(one_of(r#"{forbidden}"#))),
+        |_| BinaryOperator::{snake_to_upper_camel(name)},
+    )(i)
+}}""")
+        print("")
+    print("#[cfg(test)]")
+    print(
+        "mod tests {\n\tuse super::*;\n\tuse std::str::from_utf8;\n")
+    for line in lines:
+        name, token = line.rstrip().split(" ")
+        print(f"""\t#[test]
+\tfn parses_{name}() {{
+\t    let input = r#"{token}a"#;
+\t    let (remaining, op) = parse_{name}(input.as_bytes()).ok().unwrap();
+\t    assert_eq!(
+\t        (from_utf8(remaining).unwrap(), op),
+\t        ("a", BinaryOperator::{snake_to_upper_camel(name)}))
+\t}}""")
+        print(f"""\t#[test]
+\tfn rejects_{name}_assign() {{
+\t    let input = r#"{token}=a"#;
+\t    assert!(parse_{name}(input.as_bytes()).is_err())
+\t}}""")
+print("}")
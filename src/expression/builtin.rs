@@ -0,0 +1,735 @@
+use crate::{
+    abi,
+    codegen::Context,
+    elementary_type_name::{ElementaryTypeName, FixedBytes, UInt},
+    expression::{
+        arithmetic::constant_value, bytes, encoding, function::CallOptions, Expression,
+        FunctionCall, PrimaryExpression, Reference, TypeName,
+    },
+    op_codes::OpCode,
+    storage_location::StorageLocation,
+};
+
+const UINT256: ElementaryTypeName = ElementaryTypeName::UInt(UInt::Uint256);
+const BYTES32: ElementaryTypeName = ElementaryTypeName::FixedBytes(FixedBytes::Bytes32);
+
+/// The members of `msg`, `block` and `tx` that a single instruction pushes.
+const MAGIC_VARIABLES: [(&str, &str, OpCode, ElementaryTypeName); 12] = [
+    ("msg", "sender", OpCode::CALLER, ElementaryTypeName::Address),
+    ("msg", "value", OpCode::CALLVALUE, UINT256),
+    ("block", "basefee", OpCode::BASEFEE, UINT256),
+    ("block", "chainid", OpCode::CHAINID, UINT256),
+    (
+        "block",
+        "coinbase",
+        OpCode::COINBASE,
+        ElementaryTypeName::AddressPayable,
+    ),
+    ("block", "difficulty", OpCode::DIFFICULTY, UINT256),
+    ("block", "gaslimit", OpCode::GASLIMIT, UINT256),
+    ("block", "number", OpCode::NUMBER, UINT256),
+    ("block", "prevrandao", OpCode::DIFFICULTY, UINT256),
+    ("block", "timestamp", OpCode::TIMESTAMP, UINT256),
+    ("tx", "gasprice", OpCode::GASPRICE, UINT256),
+    ("tx", "origin", OpCode::ORIGIN, ElementaryTypeName::Address),
+];
+
+/// The global functions, with the types of their parameters and result.
+const FUNCTIONS: [(&str, &[ElementaryTypeName], Option<ElementaryTypeName>); 8] = [
+    ("gasleft", &[], Some(UINT256)),
+    ("blockhash", &[UINT256], Some(BYTES32)),
+    ("keccak256", &[ElementaryTypeName::Bytes], Some(BYTES32)),
+    ("sha256", &[ElementaryTypeName::Bytes], Some(BYTES32)),
+    (
+        "ripemd160",
+        &[ElementaryTypeName::Bytes],
+        Some(ElementaryTypeName::FixedBytes(FixedBytes::Bytes20)),
+    ),
+    (
+        "ecrecover",
+        &[
+            BYTES32,
+            ElementaryTypeName::UInt(UInt::Uint8),
+            BYTES32,
+            BYTES32,
+        ],
+        Some(ElementaryTypeName::Address),
+    ),
+    (
+        "payable",
+        &[ElementaryTypeName::Address],
+        Some(ElementaryTypeName::AddressPayable),
+    ),
+    ("selfdestruct", &[ElementaryTypeName::AddressPayable], None),
+];
+
+/// The functions that stop the execution with an error.
+const ERROR_FUNCTIONS: [&str; 3] = ["assert", "require", "revert"];
+
+const ADDRESS_MEMBERS: [&str; 3] = ["balance", "code", "codehash"];
+const ADDRESS_CALLS: [&str; 5] = ["transfer", "send", "call", "staticcall", "delegatecall"];
+
+/// A global variable or function, or a member of an address, that is built into the language.
+/// Declarations of the same name shadow the globals.
+pub enum Builtin<'a> {
+    /// `msg.sender`, `block.timestamp` and the other members in `MAGIC_VARIABLES`.
+    Magic(OpCode, ElementaryTypeName),
+    /// `msg.sig`, the first four bytes of the calldata.
+    MsgSig,
+    /// `this`, the current contract.
+    This,
+    /// A call to one of `FUNCTIONS`.
+    Function(&'a str, &'a FunctionCall),
+    /// A call to one of `ERROR_FUNCTIONS`.
+    ErrorFunction(&'a str, &'a FunctionCall),
+    /// `abi.encode(...)` and the other members of `abi`.
+    Abi(&'a str, &'a FunctionCall),
+    /// `a.balance`, `a.code` or `a.codehash`.
+    AddressMember(&'a Expression, &'a str),
+    /// `a.transfer(v)`, `a.send(v)`, `a.call(data)`, `a.staticcall(data)` or
+    /// `a.delegatecall(data)`.
+    AddressCall(&'a Expression, &'a str, &'a FunctionCall),
+}
+
+fn is_declared(name: &str, ctx: &Context) -> bool {
+    ctx.local(name).is_some() || ctx.state_variable(name).is_some()
+}
+
+fn is_address(expr: &Expression, ctx: &Context) -> bool {
+    match expr.value_type(ctx) {
+        Some(TypeName::ElementaryTypeName(ElementaryTypeName::Address))
+        | Some(TypeName::ElementaryTypeName(ElementaryTypeName::AddressPayable)) => true,
+        _ => false,
+    }
+}
+
+fn bytes_memory() -> Reference {
+    Reference {
+        location: StorageLocation::Memory,
+        type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bytes),
+    }
+}
+
+impl<'a> Builtin<'a> {
+    pub fn resolve(expr: &'a Expression, ctx: &Context) -> Option<Builtin<'a>> {
+        match expr {
+            Expression::PrimaryExpression(PrimaryExpression::Identifier(name))
+                if name == "this" =>
+            {
+                Some(Builtin::This)
+            }
+            Expression::FunctionCall(call) => match call.expr.as_ref() {
+                Expression::PrimaryExpression(PrimaryExpression::Identifier(name))
+                    if FUNCTIONS.iter().any(|(function, ..)| function == name)
+                        && !is_declared(name, ctx)
+                        && ctx.function(name, call.argument_count()).is_none() =>
+                {
+                    Some(Builtin::Function(name, call))
+                }
+                Expression::PrimaryExpression(PrimaryExpression::Identifier(name))
+                    if ERROR_FUNCTIONS.contains(&name.as_str())
+                        && !is_declared(name, ctx)
+                        && ctx.function(name, call.argument_count()).is_none() =>
+                {
+                    Some(Builtin::ErrorFunction(name, call))
+                }
+                _ => None,
+            },
+            Expression::MemberAccess(object, member) => match (object.as_ref(), member.as_ref()) {
+                (
+                    Expression::PrimaryExpression(PrimaryExpression::Identifier(object)),
+                    Expression::PrimaryExpression(PrimaryExpression::Identifier(member)),
+                ) if !is_declared(object, ctx) => {
+                    if object == "msg" && member == "sig" {
+                        return Some(Builtin::MsgSig);
+                    }
+                    MAGIC_VARIABLES
+                        .iter()
+                        .find(|(o, m, ..)| o == object && m == member)
+                        .map(|(_, _, op, type_name)| Builtin::Magic(*op, *type_name))
+                }
+                (
+                    Expression::PrimaryExpression(PrimaryExpression::Identifier(object)),
+                    Expression::FunctionCall(call),
+                ) if object == "abi" && !is_declared(object, ctx) => match call.expr.as_ref() {
+                    Expression::PrimaryExpression(PrimaryExpression::Identifier(name)) => {
+                        Some(Builtin::Abi(name, call))
+                    }
+                    _ => None,
+                },
+                (object, Expression::PrimaryExpression(PrimaryExpression::Identifier(member)))
+                    if ADDRESS_MEMBERS.contains(&member.as_str()) && is_address(object, ctx) =>
+                {
+                    Some(Builtin::AddressMember(object, member))
+                }
+                (object, Expression::FunctionCall(call)) => {
+                    let (name, _) = call.member_function()?;
+                    if ADDRESS_CALLS.contains(&name.as_str()) && is_address(object, ctx) {
+                        Some(Builtin::AddressCall(object, name, call))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The type of the single value the builtin evaluates to, if it is known without
+    /// generating any code.
+    pub fn value_type(&self, ctx: &Context) -> Option<TypeName> {
+        let elementary = match self {
+            Builtin::Magic(_, type_name) => *type_name,
+            Builtin::MsgSig => ElementaryTypeName::FixedBytes(FixedBytes::Bytes4),
+            Builtin::This => {
+                return Some(TypeName::UserDefinedTypeName(vec![ctx
+                    .contract
+                    .identifier
+                    .clone()]))
+            }
+            Builtin::Function(name, _) => FUNCTIONS.iter().find(|(f, ..)| f == name)?.2?,
+            Builtin::Abi("decode", call) => {
+                let types = decode_types(call.arguments().ok()?.get(1)?, ctx).ok()?;
+                return match &types[..] {
+                    [type_name] => Some(type_name.clone()),
+                    _ => None,
+                };
+            }
+            Builtin::AddressMember(_, "balance") => UINT256,
+            Builtin::AddressMember(_, "codehash") => BYTES32,
+            Builtin::AddressCall(_, "send", _) => ElementaryTypeName::Bool,
+            _ => return self.reference_type().map(|reference| reference.type_name),
+        };
+        Some(TypeName::ElementaryTypeName(elementary))
+    }
+
+    /// The location and type of the byte arrays the builtin allocates in memory.
+    pub fn reference_type(&self) -> Option<Reference> {
+        match self {
+            Builtin::Abi(name, _) if name.starts_with("encode") => Some(bytes_memory()),
+            Builtin::AddressMember(_, "code") => Some(bytes_memory()),
+            _ => None,
+        }
+    }
+
+    /// Pushes the values of the builtin, returning how many there are.
+    pub fn op_codes(&self, ctx: &mut Context<'a>) -> Result<usize, String> {
+        match self {
+            Builtin::Magic(op, _) => ctx.asm.op(*op),
+            Builtin::MsgSig => {
+                ctx.asm.push(&[0]);
+                ctx.asm.op(OpCode::CALLDATALOAD);
+                ctx.cleanup(&TypeName::ElementaryTypeName(
+                    ElementaryTypeName::FixedBytes(FixedBytes::Bytes4),
+                ));
+            }
+            Builtin::This => ctx.asm.op(OpCode::ADDRESS),
+            Builtin::Function(name, call) => return function_op_codes(name, call, ctx),
+            Builtin::ErrorFunction(name, call) => {
+                error_function_op_codes(name, call.arguments()?, ctx)?;
+                return Ok(0);
+            }
+            Builtin::Abi(name, call) => return abi_op_codes(name, call.arguments()?, ctx),
+            Builtin::AddressMember(object, member) => {
+                object.converted_value(
+                    &TypeName::ElementaryTypeName(ElementaryTypeName::Address),
+                    &None,
+                    ctx,
+                )?;
+                match *member {
+                    "balance" => ctx.asm.op(OpCode::BALANCE),
+                    "codehash" => ctx.asm.op(OpCode::EXTCODEHASH),
+                    _ => code_op_codes(ctx)?,
+                }
+            }
+            Builtin::AddressCall(object, name, call) => {
+                return address_call_op_codes(object, name, call, ctx)
+            }
+        }
+        Ok(1)
+    }
+}
+
+fn function_op_codes<'a>(
+    name: &str,
+    call: &'a FunctionCall,
+    ctx: &mut Context<'a>,
+) -> Result<usize, String> {
+    let (_, params, returns) = FUNCTIONS.iter().find(|(f, ..)| *f == name).unwrap();
+    let args = call.arguments()?;
+    if args.len() != params.len() {
+        return Err(format!(
+            "Wrong argument count for function call: {} arguments given but expected {}.",
+            args.len(),
+            params.len()
+        ));
+    }
+    let start = ctx.asm.stack_height();
+    for (arg, param) in args.iter().zip(params.iter()) {
+        arg.converted_value(
+            &TypeName::ElementaryTypeName(*param),
+            &Some(StorageLocation::Memory),
+            ctx,
+        )?;
+    }
+    match name {
+        "gasleft" => ctx.asm.op(OpCode::GAS),
+        "blockhash" => ctx.asm.op(OpCode::BLOCKHASH),
+        "keccak256" => {
+            ctx.asm.op(OpCode::DUP1);
+            ctx.asm.op(OpCode::MLOAD);
+            ctx.asm.op(OpCode::SWAP1);
+            ctx.asm.push(&[0x20]);
+            ctx.asm.op(OpCode::ADD);
+            ctx.asm.op(OpCode::SHA3);
+        }
+        "sha256" => hash_precompile_op_codes(2, ctx),
+        "ripemd160" => {
+            hash_precompile_op_codes(3, ctx);
+            ctx.asm.push(&[0x60]);
+            ctx.asm.op(OpCode::SHL);
+        }
+        "ecrecover" => {
+            ctx.load_free_memory_pointer();
+            let pointer = start + 4;
+            for index in 0..4 {
+                ctx.load_local(start + index)?;
+                ctx.load_local(pointer)?;
+                ctx.asm.push_usize(32 * index);
+                ctx.asm.op(OpCode::ADD);
+                ctx.asm.op(OpCode::MSTORE);
+            }
+            // The precompile returns nothing for invalid signatures, which recovers address 0
+            ctx.asm.push(&[0]);
+            ctx.asm.op(OpCode::DUP1);
+            ctx.asm.op(OpCode::MSTORE);
+            ctx.asm.push(&[0x20]);
+            ctx.asm.push(&[0]);
+            ctx.asm.push(&[0x80]);
+            ctx.load_local(pointer)?;
+            ctx.asm.push(&[1]);
+            precompile_call_op_codes(ctx);
+            ctx.asm.push(&[0]);
+            ctx.asm.op(OpCode::MLOAD);
+        }
+        "selfdestruct" => ctx.asm.op(OpCode::SELFDESTRUCT),
+        _ => {}
+    }
+    let values = returns.iter().count();
+    let layout: Vec<usize> = (start..ctx.asm.stack_height()).collect();
+    ctx.asm
+        .rearrange(&layout, &layout[layout.len() - values..])?;
+    Ok(values)
+}
+
+/// `require(condition, reason)`, `assert(condition)` and `revert(reason)`. Reasons are returned
+/// as `Error(string)`, and failed assertions revert with `Panic(0x01)`.
+fn error_function_op_codes<'a>(
+    name: &str,
+    args: &'a [Expression],
+    ctx: &mut Context<'a>,
+) -> Result<(), String> {
+    let (condition, reason) = match (name, args) {
+        ("assert", [condition]) | ("require", [condition]) => (Some(condition), None),
+        ("require", [condition, reason]) => (Some(condition), Some(reason)),
+        ("revert", []) => (None, None),
+        ("revert", [reason]) => (None, Some(reason)),
+        _ => {
+            return Err(format!(
+                "No matching declaration found after argument-dependent lookup for {} with {} arguments.",
+                name,
+                args.len()
+            ))
+        }
+    };
+    let height = ctx.asm.stack_height();
+    let passed = ctx.asm.new_tag();
+    if let Some(condition) = condition {
+        condition.converted_value(
+            &TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
+            &None,
+            ctx,
+        )?;
+        ctx.asm.jumpi(passed);
+    }
+    match reason {
+        _ if name == "assert" => ctx.panic(0x01),
+        Some(reason) => {
+            let string = TypeName::ElementaryTypeName(ElementaryTypeName::String);
+            reason.converted_value(&string, &Some(StorageLocation::Memory), ctx)?;
+            encoding::revert_with_selector(
+                &abi::selector("Error(string)"),
+                &[string],
+                height,
+                ctx,
+            )?;
+        }
+        None => ctx.revert(),
+    }
+    ctx.asm.set_stack_height(height);
+    if condition.is_some() {
+        ctx.asm.tag(passed);
+    }
+    Ok(())
+}
+
+/// Calls the precompile at the address on top of the stack with the remaining arguments of
+/// `STATICCALL` below it, forwarding its error if it fails.
+fn precompile_call_op_codes(ctx: &mut Context) {
+    ctx.asm.op(OpCode::GAS);
+    ctx.asm.op(OpCode::STATICCALL);
+    let success = ctx.asm.new_tag();
+    ctx.asm.jumpi(success);
+    ctx.forward_revert();
+    ctx.asm.tag(success);
+}
+
+/// Replaces the byte array in memory on top of the stack with the hash the precompile at
+/// `address` computes of it.
+fn hash_precompile_op_codes(address: u8, ctx: &mut Context) {
+    ctx.asm.push(&[0x20]);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.asm.op(OpCode::DUP4);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[address]);
+    precompile_call_op_codes(ctx);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::MLOAD);
+}
+
+/// Replaces the address on top of the stack with a copy of its code in memory.
+fn code_op_codes(ctx: &mut Context) -> Result<(), String> {
+    let address = ctx.asm.stack_height() - 1;
+    ctx.load_free_memory_pointer();
+    ctx.load_local(address)?;
+    ctx.asm.op(OpCode::EXTCODESIZE);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::DUP4);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.load_local(address)?;
+    ctx.asm.op(OpCode::EXTCODECOPY);
+    bytes::round_up(ctx);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x40]);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::POP);
+    Ok(())
+}
+
+/// The type an argument of `abi.encode` is encoded as. Number literals take the smallest of
+/// `uint256` and `int256` they fit into, unless the encoding is packed.
+fn encoding_type(arg: &Expression, packed: bool, ctx: &Context) -> Result<TypeName, String> {
+    if let Some(type_name) = arg.value_type(ctx) {
+        return Ok(type_name);
+    }
+    match constant_value(arg)? {
+        Some(_) if packed => Err(
+            "Cannot perform packed encoding for a literal. Please convert it to an explicit type first."
+                .to_string(),
+        ),
+        Some(value) if value.fits(256, false) => Ok(TypeName::ElementaryTypeName(UINT256)),
+        Some(_) => Ok(TypeName::ElementaryTypeName(ElementaryTypeName::Int(
+            crate::elementary_type_name::Int::Int256,
+        ))),
+        None => Err(format!("Cannot encode {:?}", arg)),
+    }
+}
+
+/// The types in the second argument of `abi.decode`, like `(uint256, address)`.
+fn decode_types(types: &Expression, ctx: &Context) -> Result<Vec<TypeName>, String> {
+    let components = match types {
+        Expression::Tuple(components) => components.iter().map(Option::as_ref).collect(),
+        _ => vec![Some(types)],
+    };
+    components
+        .into_iter()
+        .map(|component| match component {
+            Some(Expression::PrimaryExpression(
+                PrimaryExpression::ElementaryTypeNameExpression(name),
+            )) => Ok(TypeName::ElementaryTypeName(*name)),
+            Some(Expression::PrimaryExpression(PrimaryExpression::Identifier(name)))
+                if ctx.find_contract(name).is_some() =>
+            {
+                Ok(TypeName::UserDefinedTypeName(vec![name.clone()]))
+            }
+            _ => {
+                Err("The second argument to \"abi.decode\" has to be a tuple of types.".to_string())
+            }
+        })
+        .collect()
+}
+
+fn abi_op_codes<'a>(
+    name: &str,
+    args: &'a [Expression],
+    ctx: &mut Context<'a>,
+) -> Result<usize, String> {
+    let memory = Some(StorageLocation::Memory);
+    let start = ctx.asm.stack_height();
+    let (prefix, values) = match name {
+        "encode" | "encodePacked" => (None, args),
+        "encodeWithSelector" | "encodeWithSignature" if !args.is_empty() => {
+            (Some(&args[0]), &args[1..])
+        }
+        "decode" => return decode_op_codes(args, ctx),
+        "encodeWithSelector" | "encodeWithSignature" => {
+            return Err(format!(
+                "Expected at least one argument for \"abi.{}\".",
+                name
+            ))
+        }
+        _ => {
+            return Err(format!(
+                "Member \"{}\" not found or not visible after argument-dependent lookup in abi.",
+                name
+            ))
+        }
+    };
+    if let Some(prefix) = prefix {
+        let bytes4 =
+            TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(FixedBytes::Bytes4));
+        if name == "encodeWithSignature" {
+            prefix.converted_value(
+                &TypeName::ElementaryTypeName(ElementaryTypeName::String),
+                &memory,
+                ctx,
+            )?;
+            ctx.asm.op(OpCode::DUP1);
+            ctx.asm.op(OpCode::MLOAD);
+            ctx.asm.op(OpCode::SWAP1);
+            ctx.asm.push(&[0x20]);
+            ctx.asm.op(OpCode::ADD);
+            ctx.asm.op(OpCode::SHA3);
+            ctx.cleanup(&bytes4);
+        } else {
+            prefix.converted_value(&bytes4, &None, ctx)?;
+        }
+    }
+    let packed = name == "encodePacked";
+    let base = ctx.asm.stack_height();
+    let mut types = vec![];
+    for value in values {
+        let type_name = encoding_type(value, packed, ctx)?;
+        if let (
+            TypeName::Array(..),
+            Some(Reference {
+                location: StorageLocation::Storage,
+                ..
+            }),
+        ) = (&type_name, value.reference_type(ctx))
+        {
+            return Err(format!(
+                "Encoding values of type {} from storage is not supported yet",
+                type_name.abi_name()
+            ));
+        }
+        let location = if type_name.is_byte_array() {
+            &memory
+        } else {
+            &None
+        };
+        value.converted_value(&type_name, location, ctx)?;
+        types.push(type_name);
+    }
+    ctx.load_free_memory_pointer();
+    let pointer = ctx.asm.stack_height() - 1;
+    let mut offset = 0x20;
+    if prefix.is_some() {
+        ctx.load_local(base - 1)?;
+        ctx.load_local(pointer)?;
+        ctx.asm.push(&[0x20]);
+        ctx.asm.op(OpCode::ADD);
+        ctx.asm.op(OpCode::MSTORE);
+        offset += 4;
+    }
+    ctx.load_local(pointer)?;
+    ctx.asm.push(&[offset]);
+    ctx.asm.op(OpCode::ADD);
+    if packed {
+        encoding::encode_packed(&types, base, ctx)?;
+    } else {
+        encoding::encode(&types, base, ctx)?;
+    }
+    encoding::finish_bytes(ctx)?;
+    let layout: Vec<usize> = (start..ctx.asm.stack_height()).collect();
+    ctx.asm.rearrange(&layout, &layout[layout.len() - 1..])?;
+    Ok(1)
+}
+
+/// `abi.decode(data, (T1, T2))`, which reverts unless the data holds valid values of the types.
+fn decode_op_codes<'a>(args: &'a [Expression], ctx: &mut Context<'a>) -> Result<usize, String> {
+    if args.len() != 2 {
+        return Err("This function takes two arguments, but 1 were provided.".to_string());
+    }
+    let types = decode_types(&args[1], ctx)?;
+    let start = ctx.asm.stack_height();
+    args[0].converted_value(
+        &TypeName::ElementaryTypeName(ElementaryTypeName::Bytes),
+        &Some(StorageLocation::Memory),
+        ctx,
+    )?;
+    let long_enough = ctx.asm.new_tag();
+    ctx.asm.push_usize(32 * types.len());
+    ctx.load_local(start)?;
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(long_enough);
+    ctx.revert();
+    ctx.asm.tag(long_enough);
+    for (index, type_name) in types.iter().enumerate() {
+        if !type_name.is_value_type() {
+            return Err(format!(
+                "Decoding values of type {} is not supported yet",
+                type_name.abi_name()
+            ));
+        }
+        ctx.load_local(start)?;
+        ctx.asm.push_usize(32 * (index + 1));
+        ctx.asm.op(OpCode::ADD);
+        ctx.asm.op(OpCode::MLOAD);
+        let valid = ctx.asm.new_tag();
+        ctx.asm.op(OpCode::DUP1);
+        ctx.asm.op(OpCode::DUP1);
+        ctx.cleanup(type_name);
+        ctx.asm.op(OpCode::EQ);
+        ctx.asm.jumpi(valid);
+        ctx.revert();
+        ctx.asm.tag(valid);
+    }
+    let layout: Vec<usize> = (start..ctx.asm.stack_height()).collect();
+    ctx.asm.rearrange(&layout, &layout[1..])?;
+    Ok(types.len())
+}
+
+/// `a.transfer(v)` and `a.send(v)`, which forward a stipend of 2300 gas with the value, and the
+/// low level calls, which return whether they succeeded and the data they returned.
+fn address_call_op_codes<'a>(
+    object: &'a Expression,
+    name: &str,
+    call: &'a FunctionCall,
+    ctx: &mut Context<'a>,
+) -> Result<usize, String> {
+    let (_, options) = call.member_function().unwrap();
+    let CallOptions { value, gas, salt } = CallOptions::new(options)?;
+    let args = call.arguments()?;
+    if args.len() != 1 {
+        return Err(format!(
+            "Wrong argument count for function call: {} arguments given but expected 1.",
+            args.len()
+        ));
+    }
+    let uint256 = TypeName::ElementaryTypeName(UINT256);
+    let start = ctx.asm.stack_height();
+    object.converted_value(
+        &TypeName::ElementaryTypeName(ElementaryTypeName::Address),
+        &None,
+        ctx,
+    )?;
+
+    if name == "transfer" || name == "send" {
+        if !options.is_empty() {
+            return Err(format!("Call options are not supported for \"{}\".", name));
+        }
+        args[0].converted_value(&uint256, &None, ctx)?;
+        for _ in 0..4 {
+            ctx.asm.push(&[0]);
+        }
+        ctx.load_local(start + 1)?;
+        ctx.load_local(start)?;
+        ctx.load_local(start + 1)?;
+        ctx.asm.op(OpCode::ISZERO);
+        ctx.asm.push(&[0x08, 0xfc]);
+        ctx.asm.op(OpCode::MUL);
+        ctx.asm.op(OpCode::CALL);
+        let mut values = 1;
+        if name == "transfer" {
+            let success = ctx.asm.new_tag();
+            ctx.asm.jumpi(success);
+            ctx.forward_revert();
+            ctx.asm.tag(success);
+            values = 0;
+        }
+        let layout: Vec<usize> = (start..ctx.asm.stack_height()).collect();
+        ctx.asm
+            .rearrange(&layout, &layout[layout.len() - values..])?;
+        return Ok(values);
+    }
+
+    if salt.is_some() {
+        return Err("Function call option \"salt\" can only be used with \"new\".".to_string());
+    }
+    if value.is_some() && name != "call" {
+        return Err(format!("Cannot set option \"value\" for {}.", name));
+    }
+    args[0].converted_value(
+        &TypeName::ElementaryTypeName(ElementaryTypeName::Bytes),
+        &Some(StorageLocation::Memory),
+        ctx,
+    )?;
+    let data = start + 1;
+    for option in &[value, gas] {
+        match option {
+            Some(expr) => expr.converted_value(&uint256, &None, ctx)?,
+            None => ctx.asm.push(&[0]),
+        }
+    }
+    let options = start + 2;
+    ctx.asm.push(&[0]);
+    ctx.asm.push(&[0]);
+    ctx.load_local(data)?;
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.load_local(data)?;
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    if name == "call" {
+        ctx.load_local(options)?;
+    }
+    ctx.load_local(start)?;
+    match gas {
+        Some(_) => ctx.load_local(options + 1)?,
+        None => ctx.asm.op(OpCode::GAS),
+    }
+    ctx.asm.op(match name {
+        "call" => OpCode::CALL,
+        "staticcall" => OpCode::STATICCALL,
+        _ => OpCode::DELEGATECALL,
+    });
+
+    // Copy the return data into a new byte array
+    ctx.load_free_memory_pointer();
+    ctx.asm.op(OpCode::RETURNDATASIZE);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::RETURNDATASIZE);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::RETURNDATACOPY);
+    ctx.asm.op(OpCode::RETURNDATASIZE);
+    bytes::round_up(ctx);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x40]);
+    ctx.asm.op(OpCode::MSTORE);
+    let layout: Vec<usize> = (start..ctx.asm.stack_height()).collect();
+    ctx.asm.rearrange(&layout, &layout[layout.len() - 2..])?;
+    Ok(2)
+}
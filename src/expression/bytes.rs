@@ -0,0 +1,419 @@
+use crate::{codegen::Context, op_codes::OpCode};
+
+/// Allocates a string literal in memory, prefixed with its length.
+pub fn literal_op_codes(bytes: &[u8], ctx: &mut Context) {
+    ctx.load_free_memory_pointer();
+    ctx.asm.push_usize(bytes.len());
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::MSTORE);
+    for (index, chunk) in bytes.chunks(32).enumerate() {
+        let mut word = chunk.to_vec();
+        word.resize(32, 0);
+        ctx.asm.push(&word);
+        ctx.asm.op(OpCode::DUP2);
+        ctx.asm.push_usize(32 * (index + 1));
+        ctx.asm.op(OpCode::ADD);
+        ctx.asm.op(OpCode::MSTORE);
+    }
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push_usize(32 * (1 + (bytes.len() + 31) / 32));
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x40]);
+    ctx.asm.op(OpCode::MSTORE);
+}
+
+/// Rounds the value on top of the stack up to a multiple of 32.
+pub fn round_up(ctx: &mut Context) {
+    ctx.asm.push(&[0x1f]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x1f]);
+    ctx.asm.op(OpCode::NOT);
+    ctx.asm.op(OpCode::AND);
+}
+
+/// Replaces the slot of a byte array with the slot of its data, `keccak256(slot)`.
+fn data_slot(ctx: &mut Context) {
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::SHA3);
+}
+
+/// Decodes the length from the value in the slot of a byte array on top of the stack. Short
+/// arrays keep their data in the same slot, with `length * 2` in the lowest byte, long arrays
+/// store `length * 2 + 1` and keep their data at `keccak256(slot)`.
+pub fn storage_length(ctx: &mut Context) {
+    let long = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::SHR);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.jumpi(long);
+    ctx.asm.push(&[0x7f]);
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.tag(long);
+}
+
+/// Copies the byte array in memory below the slot on top of the stack into storage, consuming
+/// both. The data of the previous value is cleared.
+pub fn copy_to_storage(ctx: &mut Context) -> Result<(), String> {
+    let slot = ctx.asm.stack_height() - 1;
+    let pointer = slot - 1;
+
+    let short_before = ctx.asm.new_tag();
+    ctx.load_local(slot)?;
+    ctx.asm.op(OpCode::SLOAD);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(short_before);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::SHR);
+    ctx.asm.push(&[0x1f]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[5]);
+    ctx.asm.op(OpCode::SHR);
+    ctx.load_local(slot)?;
+    data_slot(ctx);
+    ctx.asm.push(&[0]);
+    let clear = ctx.asm.new_tag();
+    let cleared = ctx.asm.new_tag();
+    ctx.asm.tag(clear);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(cleared);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::SSTORE);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.jump(clear);
+    ctx.asm.set_stack_height(slot + 4);
+    ctx.asm.tag(cleared);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.tag(short_before);
+    ctx.asm.op(OpCode::POP);
+
+    let short = ctx.asm.new_tag();
+    let done = ctx.asm.new_tag();
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::GT);
+    ctx.asm.jumpi(short);
+
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.load_local(slot)?;
+    ctx.asm.op(OpCode::SSTORE);
+    ctx.asm.push(&[0x1f]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[5]);
+    ctx.asm.op(OpCode::SHR);
+    ctx.load_local(slot)?;
+    data_slot(ctx);
+    ctx.asm.push(&[0]);
+    let copy = ctx.asm.new_tag();
+    let copied = ctx.asm.new_tag();
+    ctx.asm.tag(copy);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(copied);
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.push(&[5]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::SSTORE);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.jump(copy);
+    ctx.asm.set_stack_height(slot + 4);
+    ctx.asm.tag(copied);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.jump(done);
+
+    // The data is left aligned, only the bytes within the length are kept
+    ctx.asm.set_stack_height(slot + 2);
+    ctx.asm.tag(short);
+    ctx.load_local(pointer)?;
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.push(&[3]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.asm.push(&[1, 0]);
+    ctx.asm.op(OpCode::SUB);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::SHL);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::SUB);
+    ctx.asm.op(OpCode::NOT);
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.asm.op(OpCode::OR);
+    ctx.load_local(slot)?;
+    ctx.asm.op(OpCode::SSTORE);
+    ctx.asm.tag(done);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    Ok(())
+}
+
+/// Replaces the slot of a byte array on top of the stack with a copy of it in memory.
+pub fn copy_to_memory(ctx: &mut Context) -> Result<(), String> {
+    let slot = ctx.asm.stack_height() - 1;
+    let pointer = slot + 3;
+    ctx.load_local(slot)?;
+    ctx.asm.op(OpCode::SLOAD);
+    ctx.asm.op(OpCode::DUP1);
+    storage_length(ctx);
+    ctx.load_free_memory_pointer();
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::DUP2);
+    round_up(ctx);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x40]);
+    ctx.asm.op(OpCode::MSTORE);
+
+    let short = ctx.asm.new_tag();
+    let done = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::GT);
+    ctx.asm.jumpi(short);
+
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.push(&[0x1f]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[5]);
+    ctx.asm.op(OpCode::SHR);
+    ctx.load_local(slot)?;
+    data_slot(ctx);
+    ctx.asm.push(&[0]);
+    let copy = ctx.asm.new_tag();
+    let copied = ctx.asm.new_tag();
+    ctx.asm.tag(copy);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(copied);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::SLOAD);
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.push(&[5]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.jump(copy);
+    ctx.asm.set_stack_height(pointer + 4);
+    ctx.asm.tag(copied);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.jump(done);
+
+    ctx.asm.set_stack_height(pointer + 1);
+    ctx.asm.tag(short);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.push(&[0xff]);
+    ctx.asm.op(OpCode::NOT);
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.tag(done);
+    ctx.asm.op(OpCode::SWAP3);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    Ok(())
+}
+
+/// Panics unless the index on top of the stack is below the length below it.
+fn bounds_check(ctx: &mut Context) {
+    let in_bounds = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.jumpi(in_bounds);
+    ctx.panic(0x32);
+    ctx.asm.tag(in_bounds);
+}
+
+/// Replaces the pointer to a byte array in memory and the index on top of it with the address
+/// of the byte.
+pub fn index_memory(ctx: &mut Context) {
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::MLOAD);
+    bounds_check(ctx);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+}
+
+/// Replaces the slot of a byte array in storage and the index on top of it with the byte at
+/// that index, left aligned like a `bytes1`.
+pub fn index_storage_value(ctx: &mut Context) {
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::SLOAD);
+    storage_length(ctx);
+    bounds_check(ctx);
+
+    let short = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::SLOAD);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(short);
+    ctx.asm.op(OpCode::SWAP1);
+    data_slot(ctx);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.tag(short);
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.push(&[5]);
+    ctx.asm.op(OpCode::SHR);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::SLOAD);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.push(&[0x1f]);
+    ctx.asm.op(OpCode::AND);
+    ctx.asm.op(OpCode::BYTE);
+    ctx.asm.push(&[0xf8]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::POP);
+}
+
+/// ABI encodes the byte array in memory below the destination on top of the stack: the length
+/// followed by the data, padded to a multiple of 32 bytes. Leaves the end of the encoding.
+pub fn abi_encode(ctx: &mut Context) -> Result<(), String> {
+    let destination = ctx.asm.stack_height() - 1;
+    let source = destination - 1;
+    let length = destination + 1;
+    let index = destination + 2;
+    ctx.load_local(source)?;
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.asm.push(&[0]);
+    let copy = ctx.asm.new_tag();
+    let copied = ctx.asm.new_tag();
+    ctx.asm.tag(copy);
+    ctx.load_local(length)?;
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.load_local(index)?;
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(copied);
+    ctx.load_local(index)?;
+    ctx.load_local(source)?;
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.load_local(index)?;
+    ctx.load_local(destination)?;
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.jump(copy);
+    ctx.asm.set_stack_height(index + 1);
+    ctx.asm.tag(copied);
+    ctx.asm.op(OpCode::POP);
+
+    // Clear the padding after the data
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.load_local(destination)?;
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MSTORE);
+    round_up(ctx);
+    ctx.load_local(destination)?;
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::SWAP2);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    Ok(())
+}
+
+/// Replaces the calldata offset of an ABI encoded byte array with a copy of it in memory.
+pub fn abi_decode(ctx: &mut Context) {
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.op(OpCode::CALLDATALOAD);
+    ctx.load_free_memory_pointer();
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::DUP4);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::CALLDATACOPY);
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::DUP3);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::DUP2);
+    round_up(ctx);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x40]);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::SWAP2);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+}
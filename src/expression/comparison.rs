@@ -0,0 +1,171 @@
+use crate::{
+    codegen::Context,
+    elementary_type_name::ElementaryTypeName,
+    expression::{
+        arithmetic::{constant_value, incompatible_types, integer, result_type},
+        binary_operator::BinaryOperator,
+        Expression, TypeName,
+    },
+    op_codes::OpCode,
+};
+
+const BOOL: TypeName = TypeName::ElementaryTypeName(ElementaryTypeName::Bool);
+
+impl BinaryOperator {
+    /// Whether the operator is `+`, `-`, `*`, `/`, `%` or `**`.
+    pub fn is_arithmetic(self) -> bool {
+        !self.is_comparison() && !self.is_logical()
+    }
+
+    /// Whether the operator compares its operands, resulting in a bool.
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::LessThan
+                | BinaryOperator::LessThanOrEqual
+                | BinaryOperator::GreaterThan
+                | BinaryOperator::GreaterThanOrEqual
+                | BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+        )
+    }
+
+    /// Whether the operator is `&&` or `||`, which only evaluate the right operand if it
+    /// decides the result.
+    pub fn is_logical(self) -> bool {
+        matches!(self, BinaryOperator::And | BinaryOperator::Or)
+    }
+}
+
+/// The type both operands of a comparison are converted to. Integers are compared like they
+/// are added, other value types only to values of the same type, and bools only for equality.
+fn comparison_type(
+    lhs: &Expression,
+    op: BinaryOperator,
+    rhs: &Expression,
+    ctx: &Context,
+) -> Result<TypeName, String> {
+    let lhs_type = lhs.value_type(ctx);
+    let rhs_type = rhs.value_type(ctx);
+    let is_integer = |type_name: &Option<TypeName>| {
+        type_name
+            .as_ref()
+            .is_none_or(|type_name| integer(type_name).is_some())
+    };
+    if is_integer(&lhs_type) && is_integer(&rhs_type) {
+        return result_type(lhs, op, rhs, ctx);
+    }
+    let ordered = !matches!(op, BinaryOperator::Equal | BinaryOperator::NotEqual);
+    match (&lhs_type, &rhs_type) {
+        (Some(a), Some(b)) if a == b && a.is_value_type() && (!ordered || *a != BOOL) => {
+            Ok(a.clone())
+        }
+        // Number literals convert to fixed size byte arrays
+        (
+            Some(type_name @ TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(_))),
+            None,
+        )
+        | (
+            None,
+            Some(type_name @ TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(_))),
+        ) => Ok(type_name.clone()),
+        _ => Err(incompatible_types(op, &lhs_type, &rhs_type)),
+    }
+}
+
+/// Pushes whether `lhs op rhs` holds. Comparisons of number literals are evaluated at compile
+/// time.
+pub fn comparison_op_codes<'a>(
+    lhs: &'a Expression,
+    op: BinaryOperator,
+    rhs: &'a Expression,
+    ctx: &mut Context<'a>,
+) -> Result<(), String> {
+    if let (Some(x), Some(y)) = (constant_value(lhs)?, constant_value(rhs)?) {
+        let difference = x.sub(&y)?;
+        let equal = difference.numerator.is_zero();
+        let less = difference.negative && !equal;
+        let holds = match op {
+            BinaryOperator::LessThan => less,
+            BinaryOperator::LessThanOrEqual => less || equal,
+            BinaryOperator::GreaterThan => !less && !equal,
+            BinaryOperator::GreaterThanOrEqual => !less,
+            BinaryOperator::Equal => equal,
+            BinaryOperator::NotEqual => !equal,
+            _ => return Err(format!("Operator {} is not a comparison", op.token())),
+        };
+        ctx.asm.push(&[holds as u8]);
+        return Ok(());
+    }
+    let type_name = comparison_type(lhs, op, rhs, ctx)?;
+    lhs.converted_value(&type_name, &None, ctx)?;
+    rhs.converted_value(&type_name, &None, ctx)?;
+    let (less, greater) = match integer(&type_name) {
+        Some((_, true)) => (OpCode::SLT, OpCode::SGT),
+        _ => (OpCode::LT, OpCode::GT),
+    };
+    // The right operand is on top of the stack, so `x < y` is computed as `y > x`
+    match op {
+        BinaryOperator::LessThan => ctx.asm.op(greater),
+        BinaryOperator::GreaterThan => ctx.asm.op(less),
+        BinaryOperator::LessThanOrEqual => {
+            ctx.asm.op(less);
+            ctx.asm.op(OpCode::ISZERO);
+        }
+        BinaryOperator::GreaterThanOrEqual => {
+            ctx.asm.op(greater);
+            ctx.asm.op(OpCode::ISZERO);
+        }
+        BinaryOperator::Equal => ctx.asm.op(OpCode::EQ),
+        BinaryOperator::NotEqual => {
+            ctx.asm.op(OpCode::EQ);
+            ctx.asm.op(OpCode::ISZERO);
+        }
+        _ => return Err(format!("Operator {} is not a comparison", op.token())),
+    }
+    Ok(())
+}
+
+/// Pushes the value of a bool operand, which other types cannot be converted to.
+fn bool_value<'a>(
+    operand: &'a Expression,
+    token: &str,
+    ctx: &mut Context<'a>,
+) -> Result<(), String> {
+    match operand.value_type(ctx) {
+        Some(BOOL) => operand.converted_value(&BOOL, &None, ctx),
+        type_name => Err(format!(
+            "Operator {} not compatible with type {}",
+            token,
+            type_name.map_or("int_const".to_string(), |type_name| type_name.abi_name())
+        )),
+    }
+}
+
+/// Pushes the result of `lhs && rhs` or `lhs || rhs`, skipping the right operand if the left
+/// one decides the result.
+pub fn logical_op_codes<'a>(
+    lhs: &'a Expression,
+    op: BinaryOperator,
+    rhs: &'a Expression,
+    ctx: &mut Context<'a>,
+) -> Result<(), String> {
+    let end = ctx.asm.new_tag();
+    bool_value(lhs, op.token(), ctx)?;
+    ctx.asm.op(OpCode::DUP1);
+    if op == BinaryOperator::And {
+        ctx.asm.op(OpCode::ISZERO);
+    }
+    ctx.asm.jumpi(end);
+    ctx.asm.op(OpCode::POP);
+    bool_value(rhs, op.token(), ctx)?;
+    ctx.asm.tag(end);
+    Ok(())
+}
+
+/// Pushes the result of `!operand`.
+pub fn not_op_codes<'a>(operand: &'a Expression, ctx: &mut Context<'a>) -> Result<(), String> {
+    bool_value(operand, "!", ctx)?;
+    ctx.asm.op(OpCode::ISZERO);
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use crate::{
+    codegen::Context,
+    definition::ContractType,
+    elementary_type_name::{ElementaryTypeName, FixedBytes, UInt},
+    expression::{
+        function::{CallOptions, NameValue},
+        FunctionCall, TypeName,
+    },
+    op_codes::OpCode,
+    state_mutability::StateMutability,
+};
+
+/// `new C{value: v, salt: s}(args)`: deploys a contract with CREATE, or with CREATE2 if a salt
+/// is given. The creation code of `C` is copied into memory from a sub assembly, followed by the
+/// ABI encoded constructor arguments. Pushes the address of the new contract, or reverts with the
+/// error of its constructor.
+pub fn create_op_codes<'a>(
+    path: &[String],
+    options: &'a [NameValue],
+    call: &'a FunctionCall,
+    ctx: &mut Context<'a>,
+) -> Result<usize, String> {
+    let name = path.last().unwrap();
+    let contract = ctx
+        .find_contract(name)
+        .ok_or_else(|| format!("Identifier not found or not unique: {}", name))?;
+    match contract.contract_type {
+        ContractType::Contract => {}
+        ContractType::Interface => return Err("Cannot instantiate an interface.".to_string()),
+        ContractType::Library => return Err("Cannot instantiate a library.".to_string()),
+    }
+
+    let CallOptions { value, gas, salt } = CallOptions::new(options)?;
+    if gas.is_some() {
+        return Err("Function call option \"gas\" cannot be used with \"new\".".to_string());
+    }
+    let constructor = contract.constructor();
+    let payable = constructor
+        .map(|ctor| ctor.state_mutability == Some(StateMutability::Payable))
+        .unwrap_or(false);
+    if value.is_some() && !payable {
+        return Err(format!(
+            "Cannot set option \"value\", since the constructor of contract {} is not payable.",
+            name
+        ));
+    }
+    let params = constructor.map_or(&[][..], |ctor| &ctor.parameter_list[..]);
+    let names: Vec<Option<&str>> = params.iter().map(|p| p.identifier.as_deref()).collect();
+    let args = call.ordered_arguments(&names)?;
+    let sub = ctx.creation_sub(contract)?;
+
+    let start = ctx.asm.stack_height();
+    match value {
+        Some(value) => value.converted_value(
+            &TypeName::ElementaryTypeName(ElementaryTypeName::UInt(UInt::Uint256)),
+            &None,
+            ctx,
+        )?,
+        None => ctx.asm.push(&[0]),
+    }
+    if let Some(salt) = salt {
+        salt.converted_value(
+            &TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(FixedBytes::Bytes32)),
+            &None,
+            ctx,
+        )?;
+    }
+    let first_arg = ctx.asm.stack_height();
+    for (arg, param) in args.iter().zip(params) {
+        if !param.typename.is_value_type() {
+            return Err(format!(
+                "Constructor parameters of type {} are not supported yet",
+                param.typename.abi_name()
+            ));
+        }
+        arg.converted_value(&param.typename, &param.storage_location, ctx)?;
+    }
+
+    ctx.load_free_memory_pointer();
+    let pointer = ctx.asm.stack_height() - 1;
+    ctx.asm.push_sub_size(sub);
+    ctx.asm.push_sub_offset(sub);
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::CODECOPY);
+    for index in 0..args.len() {
+        ctx.load_local(first_arg + index)?;
+        ctx.load_local(pointer)?;
+        ctx.asm.push_sub_size(sub);
+        ctx.asm.op(OpCode::ADD);
+        if index > 0 {
+            ctx.asm.push_usize(32 * index);
+            ctx.asm.op(OpCode::ADD);
+        }
+        ctx.asm.op(OpCode::MSTORE);
+    }
+
+    if salt.is_some() {
+        ctx.load_local(start + 1)?;
+    }
+    ctx.asm.push_usize(32 * args.len());
+    ctx.asm.push_sub_size(sub);
+    ctx.asm.op(OpCode::ADD);
+    ctx.load_local(pointer)?;
+    ctx.load_local(start)?;
+    ctx.asm.op(match salt {
+        Some(_) => OpCode::CREATE2,
+        None => OpCode::CREATE,
+    });
+    let created = ctx.asm.new_tag();
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.jumpi(created);
+    ctx.forward_revert();
+    ctx.asm.tag(created);
+
+    let layout: Vec<usize> = (start..ctx.asm.stack_height()).collect();
+    ctx.asm.rearrange(&layout, &layout[layout.len() - 1..])?;
+    Ok(1)
+}
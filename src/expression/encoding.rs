@@ -0,0 +1,235 @@
+use crate::{
+    codegen::Context,
+    elementary_type_name::ElementaryTypeName,
+    expression::{array_length, bytes, TypeName},
+    op_codes::OpCode,
+};
+
+/// ABI encodes the values at the stack positions from `base` on to the memory address on top of
+/// the stack, replacing it with the end of the encoding. Byte arrays are encoded in the tail,
+/// after the heads of all the values, while the elements of static arrays are encoded in place.
+pub fn encode(types: &[TypeName], base: usize, ctx: &mut Context) -> Result<(), String> {
+    let mut offsets = vec![];
+    let mut head_size = 0;
+    for type_name in types {
+        offsets.push(head_size);
+        head_size += encoded_size(type_name)?;
+    }
+    let head = ctx.asm.stack_height() - 1;
+    ctx.asm.push_usize(head_size);
+    ctx.load_local(head)?;
+    ctx.asm.op(OpCode::ADD);
+    let tail = head + 1;
+    for ((index, type_name), offset) in types.iter().enumerate().zip(offsets) {
+        if type_name.is_byte_array() {
+            ctx.load_local(head)?;
+            ctx.load_local(tail)?;
+            ctx.asm.op(OpCode::SUB);
+            ctx.load_local(head)?;
+            ctx.asm.push_usize(offset);
+            ctx.asm.op(OpCode::ADD);
+            ctx.asm.op(OpCode::MSTORE);
+            ctx.load_local(base + index)?;
+            ctx.load_local(tail)?;
+            bytes::abi_encode(ctx)?;
+            ctx.store_local(tail)?;
+            continue;
+        }
+        ctx.load_local(base + index)?;
+        ctx.load_local(head)?;
+        ctx.asm.push_usize(offset);
+        ctx.asm.op(OpCode::ADD);
+        match type_name {
+            TypeName::Array(_, Some(length)) => encode_static_array(array_length(length)?, ctx)?,
+            _ => ctx.asm.op(OpCode::MSTORE),
+        }
+    }
+    ctx.store_local(head)
+}
+
+/// The size of the encoding of a value in the head, which is a word unless it is a static array.
+fn encoded_size(type_name: &TypeName) -> Result<usize, String> {
+    match type_name {
+        TypeName::Array(element, Some(length)) if element.is_value_type() => {
+            Ok(32 * array_length(length)?)
+        }
+        _ if type_name.is_value_type() || type_name.is_byte_array() => Ok(32),
+        _ => Err(format!(
+            "Encoding values of type {} is not supported yet",
+            type_name.abi_name()
+        )),
+    }
+}
+
+/// Encodes the elements of the static array in memory below the destination on top of the
+/// stack, consuming both.
+fn encode_static_array(length: usize, ctx: &mut Context) -> Result<(), String> {
+    let destination = ctx.asm.stack_height() - 1;
+    let source = destination - 1;
+    let index = destination + 1;
+    let offset = index + 1;
+    ctx.asm.push(&[0]);
+    let encode = ctx.asm.new_tag();
+    let encoded = ctx.asm.new_tag();
+    ctx.asm.tag(encode);
+    ctx.asm.push_usize(length);
+    ctx.load_local(index)?;
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(encoded);
+    ctx.load_local(index)?;
+    ctx.asm.push(&[5]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.load_local(source)?;
+    ctx.load_local(offset)?;
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.load_local(destination)?;
+    ctx.load_local(offset)?;
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.push(&[1]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.jump(encode);
+    ctx.asm.set_stack_height(index + 1);
+    ctx.asm.tag(encoded);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::POP);
+    Ok(())
+}
+
+/// Reverts with the four byte selector followed by the ABI encoding of the values at the stack
+/// positions from `base` on, which is how `Error(string)` and custom errors are returned.
+pub fn revert_with_selector(
+    selector: &[u8],
+    types: &[TypeName],
+    base: usize,
+    ctx: &mut Context,
+) -> Result<(), String> {
+    ctx.load_free_memory_pointer();
+    let pointer = ctx.asm.stack_height() - 1;
+    ctx.asm.push(selector);
+    ctx.asm.push(&[0xe0]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.load_local(pointer)?;
+    ctx.asm.push(&[4]);
+    ctx.asm.op(OpCode::ADD);
+    encode(types, base, ctx)?;
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::SUB);
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::REVERT);
+    Ok(())
+}
+
+/// Encodes the values at the stack positions from `base` on without padding, like
+/// `abi.encodePacked`, to the memory address on top of the stack, replacing it with the end of
+/// the encoding.
+pub fn encode_packed(types: &[TypeName], base: usize, ctx: &mut Context) -> Result<(), String> {
+    let cursor = ctx.asm.stack_height() - 1;
+    for (index, type_name) in types.iter().enumerate() {
+        ctx.load_local(base + index)?;
+        if type_name.is_byte_array() {
+            ctx.load_local(cursor)?;
+            copy_data(ctx)?;
+            ctx.store_local(cursor)?;
+            continue;
+        }
+        let (size, left_aligned) = match type_name {
+            TypeName::UserDefinedTypeName(_) => (20, false),
+            TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(bytes)) => {
+                (bytes.size(), true)
+            }
+            TypeName::ElementaryTypeName(name) if type_name.is_value_type() => {
+                (name.size().unwrap(), false)
+            }
+            _ => {
+                return Err(format!(
+                    "Packed encoding of type {} is not supported yet",
+                    type_name.abi_name()
+                ))
+            }
+        };
+        if size < 32 && !left_aligned {
+            ctx.asm.push_usize(8 * (32 - size));
+            ctx.asm.op(OpCode::SHL);
+        }
+        ctx.load_local(cursor)?;
+        ctx.asm.op(OpCode::MSTORE);
+        ctx.load_local(cursor)?;
+        ctx.asm.push_usize(size);
+        ctx.asm.op(OpCode::ADD);
+        ctx.store_local(cursor)?;
+    }
+    Ok(())
+}
+
+/// Copies the data of the byte array in memory below the destination on top of the stack,
+/// without its length, replacing both with the end of the copy. Up to 31 bytes after the end
+/// may be overwritten.
+fn copy_data(ctx: &mut Context) -> Result<(), String> {
+    let destination = ctx.asm.stack_height() - 1;
+    let source = destination - 1;
+    let length = destination + 1;
+    let index = destination + 2;
+    ctx.load_local(source)?;
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.asm.push(&[0]);
+    let copy = ctx.asm.new_tag();
+    let copied = ctx.asm.new_tag();
+    ctx.asm.tag(copy);
+    ctx.load_local(length)?;
+    ctx.load_local(index)?;
+    ctx.asm.op(OpCode::LT);
+    ctx.asm.op(OpCode::ISZERO);
+    ctx.asm.jumpi(copied);
+    ctx.load_local(index)?;
+    ctx.load_local(source)?;
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MLOAD);
+    ctx.load_local(index)?;
+    ctx.load_local(destination)?;
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.jump(copy);
+    ctx.asm.set_stack_height(index + 1);
+    ctx.asm.tag(copied);
+    ctx.asm.op(OpCode::POP);
+    ctx.asm.op(OpCode::ADD);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::POP);
+    Ok(())
+}
+
+/// Turns the encoding that was written right after the pointer below the end on top of the
+/// stack into a `bytes memory` value: stores its length at the pointer and allocates it,
+/// leaving only the pointer.
+pub fn finish_bytes(ctx: &mut Context) -> Result<(), String> {
+    let pointer = ctx.asm.stack_height() - 2;
+    // Clear the padding after the data
+    ctx.asm.push(&[0]);
+    ctx.asm.op(OpCode::DUP2);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.asm.op(OpCode::DUP1);
+    bytes::round_up(ctx);
+    ctx.asm.push(&[0x40]);
+    ctx.asm.op(OpCode::MSTORE);
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::SUB);
+    ctx.asm.push(&[0x20]);
+    ctx.asm.op(OpCode::SWAP1);
+    ctx.asm.op(OpCode::SUB);
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::MSTORE);
+    Ok(())
+}
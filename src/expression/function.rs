@@ -3,23 +3,21 @@ use crate::expression::{parse_expression, parse_expression_list, Expression};
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::{
-        complete::{char, multispace0},
-        is_alphanumeric,
-    },
-    combinator::{complete, map, map_res, opt},
+    character::complete::{char, multispace0},
+    combinator::{map, opt},
     multi::separated_list,
-    named,
-    sequence::{delimited, preceded, separated_pair, terminated, tuple},
-    take_until1, take_while, IResult,
+    sequence::{delimited, preceded, separated_pair},
+    IResult,
 };
-use std::str::from_utf8;
 
 pub type NameValue = (String, Expression);
 
-// TODO: Name Value List??
-/* fn parse_name_value_list(i: &[u8]) -> IResult<&[u8], Vec<NameValue>> {
-    separated_list(char(','), preceded(multispace0, parse_name_value))(i)
+/// Parses `name: value` pairs, like the call options in `f{value: 1}()`.
+pub fn parse_name_value_list(i: &[u8]) -> IResult<&[u8], Vec<NameValue>> {
+    separated_list(
+        preceded(multispace0, char(',')),
+        preceded(multispace0, parse_name_value),
+    )(i)
 }
 
 fn parse_name_value(i: &[u8]) -> IResult<&[u8], NameValue> {
@@ -28,47 +26,60 @@ fn parse_name_value(i: &[u8]) -> IResult<&[u8], NameValue> {
         preceded(multispace0, tag(":")),
         preceded(multispace0, parse_expression),
     )(i)
-} */
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum FunctionCallArguments {
-    // NameValueList(Vec<NameValue>),
+    /// `f({to: a, amount: 1})`, matched to the parameters of the callee by name.
+    NameValueList(Vec<NameValue>),
     ExpressionList(Vec<Expression>),
 }
 
-pub fn parses_function_call(i: &[u8]) -> IResult<&[u8], (Expression, FunctionCallArguments)> {
-    named!(t1, take_while!(is_alphanumeric));
-    complete(map_res(
-        tuple((
-            terminated(t1, multispace0),
-            delimited(tag("("), parse_function_call_arguments, tag(")")),
-        )),
-        |x| {
-            let (expr, args) = x;
-            let p_expr = parse_expression(expr);
-            if p_expr.is_err() {
-                return Err(p_expr.unwrap_err());
-            } else {
-                let (_, e) = p_expr.unwrap();
-                return Ok((e, args));
-            }
-        },
+pub fn parse_function_call_arguments(i: &[u8]) -> IResult<&[u8], FunctionCallArguments> {
+    alt((
+        map(
+            delimited(
+                char('{'),
+                parse_name_value_list,
+                preceded(multispace0, char('}')),
+            ),
+            FunctionCallArguments::NameValueList,
+        ),
+        map(opt(parse_expression_list), |l| {
+            FunctionCallArguments::ExpressionList(l.unwrap_or(vec![]))
+        }),
     ))(i)
 }
 
-fn parse_function_call_arguments(i: &[u8]) -> IResult<&[u8], FunctionCallArguments> {
-    /*     alt((
-           map(delimited(tag("{"), parse_name_value_list, tag("}")), |l| {
-               FunctionCallArguments::NameValueList(l)
-           }),
-           map(opt(parse_expression_list), |l| {
-               FunctionCallArguments::ExpressionList(l)
-           }),
-       ))(i)
-    */
-    map(opt(parse_expression_list), |l| {
-        FunctionCallArguments::ExpressionList(l.unwrap_or(vec![]))
-    })(i)
+/// The options of a call like `c.f{value: 1, gas: 2}()` or `new C{salt: s}()`.
+#[derive(Debug, Default)]
+pub struct CallOptions<'a> {
+    pub value: Option<&'a Expression>,
+    pub gas: Option<&'a Expression>,
+    pub salt: Option<&'a Expression>,
+}
+
+impl<'a> CallOptions<'a> {
+    pub fn new(options: &'a [NameValue]) -> Result<CallOptions<'a>, String> {
+        let mut call_options = CallOptions::default();
+        for (name, expr) in options {
+            let option = match name.as_str() {
+                "value" => &mut call_options.value,
+                "gas" => &mut call_options.gas,
+                "salt" => &mut call_options.salt,
+                _ => {
+                    return Err(format!(
+                        "Unknown call option \"{}\". Valid options are \"salt\", \"value\" and \"gas\".",
+                        name
+                    ))
+                }
+            };
+            if option.replace(expr).is_some() {
+                return Err(format!("Duplicate option \"{}\".", name));
+            }
+        }
+        Ok(call_options)
+    }
 }
 
 #[cfg(test)]
@@ -76,12 +87,13 @@ mod tests {
     use super::*;
     use crate::elementary_type_name::ElementaryTypeName;
     use crate::expression::primary_expr::PrimaryExpression;
+    use crate::literal::Number;
     use pretty_assertions::assert_eq;
     use std::str::from_utf8;
 
     #[test]
     fn parses_name_value() {
-        /*  let input = b"a     : bool\n";
+        let input = b"a     : bool\n";
         let result = parse_name_value(input);
         if result.is_err() {
             result.expect("should parse name value");
@@ -101,24 +113,24 @@ mod tests {
                     )
                 )
             )
-        } */
+        }
     }
 
     #[test]
     fn parses_empty_name_value_list() {
-        /* let input = b"";
+        let input = b"";
         let result = parse_name_value_list(input);
         if result.is_err() {
             result.expect("should parse empty name value list");
         } else {
             let (remaining, b) = result.ok().unwrap();
             assert_eq!((from_utf8(remaining).unwrap(), b), ("", vec![]))
-        } */
+        }
     }
 
     #[test]
     fn parses_name_value_list() {
-        /* let input = b"a: bool, b: bool";
+        let input = b"a: bool, b: bool";
         let result = parse_name_value_list(input);
         if result.is_err() {
             result.expect("should parse name value list");
@@ -148,6 +160,38 @@ mod tests {
                     ]
                 )
             )
-        } */
+        }
+    }
+
+    #[test]
+    fn parses_named_function_call_arguments() {
+        let input = b"{to: a, amount: 1})";
+        let result = parse_function_call_arguments(input);
+        if result.is_err() {
+            result.expect("should parse named arguments");
+        } else {
+            let (remaining, args) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), args),
+                (
+                    ")",
+                    FunctionCallArguments::NameValueList(vec![
+                        (
+                            "to".to_string(),
+                            Expression::PrimaryExpression(PrimaryExpression::Identifier(
+                                "a".to_string()
+                            ))
+                        ),
+                        (
+                            "amount".to_string(),
+                            Expression::PrimaryExpression(PrimaryExpression::NumberLiteral((
+                                Number::Decimal("1".to_string()),
+                                None
+                            )))
+                        )
+                    ])
+                )
+            )
+        }
     }
 }
@@ -1,31 +1,62 @@
-use crate::atom::parse_identifier;
-use crate::elementary_type_name::{parse as parse_elementary_type_name, ElementaryTypeName, UInt};
+use crate::atom::{
+  keyword::{parse_mapping, parse_new},
+  parse_identifier, word_end,
+};
+use crate::codegen::{overload, Context, JumpType};
+use crate::definition::Contract;
+use crate::elementary_type_name::{
+  parse as parse_elementary_type_name, ElementaryTypeName, FixedBytes, UInt,
+};
 use crate::expression::{
+  arithmetic::{
+    binary_op_codes, constant_op_codes, constant_value, is_constant, operation, result_type,
+  },
+  array::{
+    inline_array_op_codes, inline_array_type, member_op_codes as array_member_op_codes,
+    new_op_codes,
+  },
   assignment::{parse as parse_assignment_operator, Assignment},
-  function::parses_function_call,
+  binary_operator::{
+    parse_add, parse_and, parse_div, parse_equal, parse_exp, parse_greater_than,
+    parse_greater_than_or_equal, parse_less_than, parse_less_than_or_equal, parse_mod, parse_mul,
+    parse_not_equal, parse_or, parse_sub, BinaryOperator,
+  },
+  builtin::Builtin,
+  comparison::{comparison_op_codes, logical_op_codes, not_op_codes},
+  creation::create_op_codes,
+  function::{parse_function_call_arguments, parse_name_value_list, CallOptions, NameValue},
   primary_expr::parse as parse_primary_expression,
 };
-use crate::literal::{Boolean, Literal, Number};
 use crate::op_codes::OpCode;
+use crate::state_mutability::StateMutability;
 use crate::storage_location::{parse as parse_storage_location, StorageLocation};
 use nom::{
   branch::alt,
-  bytes::complete::{tag, take_till1, take_until},
+  bytes::complete::tag,
   character::complete::{char, multispace0, multispace1},
-  combinator::{complete, flat_map, map, map_res, peek},
-  multi::many1,
+  combinator::{complete, map, map_res, opt, verify},
+  multi::many0,
   multi::{separated_list, separated_nonempty_list},
-  sequence::{delimited, preceded, separated_pair, terminated, tuple},
+  sequence::{delimited, preceded, terminated, tuple},
   IResult,
 };
-use sha3::{Digest, Keccak256};
-use std::collections::HashSet;
-use std::u32;
+mod arithmetic;
+mod array;
 mod assignment;
+mod binary_operator;
+mod builtin;
+pub mod bytes;
+mod comparison;
+mod creation;
+pub mod encoding;
 mod function;
 mod primary_expr;
 
-pub use crate::expression::{function::FunctionCallArguments, primary_expr::PrimaryExpression};
+pub use crate::expression::{
+  array::{array_length, push_default_value},
+  function::FunctionCallArguments,
+  primary_expr::PrimaryExpression,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionCall {
@@ -34,324 +65,1039 @@ pub struct FunctionCall {
 }
 
 impl FunctionCall {
-  fn hex_to_bytes(addr: &str) -> Vec<u32> {
-    let mut bytes = vec![];
-
-    let mut index = 0;
-    while index < addr.len() {
-      let byte_str = format!(
-        "{}{}",
-        addr.chars().nth(index).unwrap(),
-        addr.chars().nth(index + 1).unwrap()
-      );
-      let byte = u32::from_str_radix(&byte_str.as_str(), 16).unwrap();
-      bytes.push(byte);
-      index += 2;
+  pub fn argument_count(&self) -> usize {
+    match &self.args {
+      FunctionCallArguments::NameValueList(list) => list.len(),
+      FunctionCallArguments::ExpressionList(list) => list.len(),
     }
-
-    bytes
   }
 
-  pub fn op_codes(self) -> Vec<u32> {
-    let mut codes = vec![];
-
-    // TODO: Bring the interfaces as a parameters and check if the
-    // identifier is one of them
-    let interfaces: HashSet<&'static str> = ["GeneralERC20"].iter().cloned().collect();
-    let interface_contract_addr = self.interface_contract_address(interfaces);
-    if interface_contract_addr != None {
-      codes.push(OpCode::PUSH20 as u32);
-      let addr = interface_contract_addr.unwrap();
-      let addr_bytes = FunctionCall::hex_to_bytes(&addr);
-      codes.extend(addr_bytes);
-      // MASK
-      codes.push(OpCode::PUSH20 as u32);
-      for _ in 0..20 {
-        codes.push(0xff);
+  /// The arguments of a call to something without named parameters, like a type conversion.
+  pub fn arguments(&self) -> Result<&Vec<Expression>, String> {
+    match &self.args {
+      FunctionCallArguments::NameValueList(_) => {
+        Err("Named arguments can only be used for functions, events and constructors.".to_string())
       }
-      codes.push(OpCode::AND as u32);
-
-      return codes;
+      FunctionCallArguments::ExpressionList(list) => Ok(list),
     }
-
-    let sig = self.signature();
-    let mut hasher = Keccak256::new();
-    hasher.input(sig);
-    let hash = &hasher.result()[..];
-    codes.push(OpCode::PUSH4 as u32);
-    for byte in hash[0..4].iter() {
-      codes.push(*byte as u32);
-    }
-    codes.extend(self.param_bytecode());
-
-    codes
   }
 
-  fn param_bytecode(&self) -> Vec<u32> {
-    let mut bytecode = vec![];
-
-    match self.expr.as_ref() {
-      Expression::PrimaryExpression(state) => match state {
-        PrimaryExpression::Identifier(identifier) => match &self.args {
-          FunctionCallArguments::ExpressionList(list) => {
-            for expr in list {
-              match expr {
-                Expression::PrimaryExpression(prim_expr) => {
-                  match prim_expr {
-                    PrimaryExpression::NumberLiteral(arg_identifier) => {
-                      let (number, _) = arg_identifier;
-                      match number {
-                        Number::Hex(val) => {
-                          bytecode.push(OpCode::PUSH20 as u32);
-                          let bytes = FunctionCall::hex_to_bytes(val.trim_start_matches("0x"));
-                          bytecode.extend(bytes);
-                        }
-                        Number::Decimal(val) => {
-                          bytecode.push(OpCode::PUSH1 as u32);
-                          let hex_str = format!("{:x}", val.parse::<i32>().unwrap());
-                          let bytes = FunctionCall::hex_to_bytes(hex_str.trim_start_matches("0x"));
-                          bytecode.extend(bytes);
-                          /*    let bytes = FunctionCall::hex_to_bytes(val.trim_start_matches("0x"));
-                          bytecode.push(OpCode::PUSH1 as u32);
-                          bytecode.extend(bytes); */
-                          // TODO: FIX THE HARDCODED TYPES
-                          // args.push("uint256".to_string());
-                        }
-                      }
-                    }
-                    PrimaryExpression::BooleanLiteral(bool_lit) => match bool_lit {
-                      Boolean::False => {
-                        bytecode.push(0x00);
-                      }
-                      Boolean::True => {
-                        bytecode.push(0x01);
-                      }
-                    },
-                    _ => {
-                      panic!("{:#?} is not a valid parameter", prim_expr);
-                    }
-                  }
-                }
-                _ => {}
-              }
-            }
-          }
-          _ => panic!(
-            "{:#?} function call arguments should be an expression list",
-            self.args
-          ),
-        },
-        _ => panic!("{:#?} should be the identifier of the function call", state),
-      },
-      _ => panic!(
-        "{:#?} should be a primary expression of the function call",
-        self.expr
+  /// The arguments in the order of the parameters with the given names, to which named arguments
+  /// are matched.
+  pub fn ordered_arguments(&self, names: &[Option<&str>]) -> Result<Vec<&Expression>, String> {
+    if let FunctionCallArguments::NameValueList(list) = &self.args {
+      for (index, (name, _)) in list.iter().enumerate() {
+        if list[..index].iter().any(|(other, _)| other == name) {
+          return Err(format!("Duplicate named argument \"{}\".", name));
+        }
+        if !names.contains(&Some(name.as_str())) {
+          return Err(format!(
+            "Named argument \"{}\" does not match function declaration.",
+            name
+          ));
+        }
+      }
+    }
+    if self.argument_count() != names.len() {
+      return Err(format!(
+        "Wrong argument count for function call: {} arguments given but expected {}.",
+        self.argument_count(),
+        names.len()
+      ));
+    }
+    match &self.args {
+      FunctionCallArguments::ExpressionList(list) => Ok(list.iter().collect()),
+      FunctionCallArguments::NameValueList(list) => Ok(
+        names
+          .iter()
+          .map(|param| list.iter().find(|(name, _)| Some(name.as_str()) == *param))
+          .map(|arg| arg.map(|(_, expr)| expr).unwrap())
+          .collect(),
       ),
     }
-
-    bytecode
   }
 
-  fn interface_contract_address(&self, interfaces: HashSet<&'static str>) -> Option<String> {
+  pub fn op_codes<'a>(&'a self, ctx: &mut Context<'a>) -> Result<usize, String> {
     match self.expr.as_ref() {
-      Expression::PrimaryExpression(state) => match state {
-        PrimaryExpression::Identifier(identifier) => {
-          if (interfaces.get(&identifier.as_str())) == None {
-            return None;
+      Expression::PrimaryExpression(PrimaryExpression::ElementaryTypeNameExpression(type_name)) => {
+        let args = self.arguments()?;
+        if args.len() != 1 {
+          return Err(format!(
+            "Exactly one argument expected for explicit type conversion to {}",
+            type_name.abi_name()
+          ));
+        }
+        let target = TypeName::ElementaryTypeName(*type_name);
+        match (type_name, constant_value(&args[0])?) {
+          (ElementaryTypeName::FixedBytes(_), Some(value)) => {
+            constant_op_codes(&value, &target, ctx)?
           }
-          match &self.args {
-            FunctionCallArguments::ExpressionList(list) => {
-              let mut args = Vec::<String>::new();
-              for expr in list {
-                match expr {
-                  Expression::PrimaryExpression(prim_expr) => {
-                    match prim_expr {
-                      PrimaryExpression::NumberLiteral(arg_identifier) => {
-                        let (number, _) = arg_identifier;
-                        match number {
-                          Number::Hex(val) => {
-                            // TODO: FIX THE HARDCODED TYPES
-                            args.push(val.to_string());
-                          }
-                          _ => {
-                            panic!("{:#?} is not a valid address", number);
-                          }
-                        }
-                      }
-                      _ => {
-                        panic!("{:#?} is not a valid address", prim_expr);
-                      }
-                    }
-                  }
-                  _ => {}
-                }
-              }
-
-              if args.len() != 1 {
-                return None;
-              }
-              let addr = &args[0].trim_start_matches("0x");
-              return Some(addr.to_string());
-            }
-            _ => panic!(
-              "{:#?} function call arguments should be an expression list",
-              self.args
-            ),
+          _ => {
+            args[0].single_value(ctx)?;
+            ctx.cleanup(&target);
           }
         }
-        _ => panic!("{:#?} should be the identifier of the function call", state),
+        Ok(1)
+      }
+      Expression::New(TypeName::UserDefinedTypeName(path)) => create_op_codes(path, &[], self, ctx),
+      Expression::CallOptions(expr, options) => match expr.as_ref() {
+        Expression::New(TypeName::UserDefinedTypeName(path)) => {
+          create_op_codes(path, options, self, ctx)
+        }
+        _ => Err(format!("Call options are not supported for {:?}", expr)),
       },
-      _ => panic!(
-        "{:#?} should be a primary expression of the function call",
-        self.expr
-      ),
+      Expression::New(type_name) => new_op_codes(type_name, self.arguments()?, ctx),
+      Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) => {
+        if let Some(function) = ctx.function(identifier, self.argument_count()) {
+          let args = self.ordered_arguments(&function.parameter_names())?;
+          let height = ctx.asm.stack_height();
+          let return_tag = ctx.asm.new_tag();
+          ctx.asm.push_tag(return_tag);
+          for (arg, param) in args.iter().zip(function.parameter_list.iter()) {
+            arg.converted_value(&param.typename, &param.storage_location, ctx)?;
+          }
+          let function_tag = ctx.function_tag(function);
+          ctx.asm.jump(function_tag);
+          ctx.asm.mark_jump(JumpType::IntoFunction);
+          ctx.asm.tag(return_tag);
+          ctx.asm.set_stack_height(height + function.returns.len());
+          return Ok(function.returns.len());
+        }
+        if ctx.find_contract(identifier).is_some() && self.argument_count() == 1 {
+          self.arguments()?[0].single_value(ctx)?;
+          ctx.cleanup(&TypeName::ElementaryTypeName(ElementaryTypeName::Address));
+          return Ok(1);
+        }
+        Err(format!("Undeclared identifier: {}", identifier))
+      }
+      _ => Err(format!("{:?} is not callable", self.expr)),
     }
   }
-  fn signature(&self) -> String {
-    match self.expr.as_ref() {
-      Expression::PrimaryExpression(state) => match state {
-        PrimaryExpression::Identifier(identifier) => match &self.args {
-          FunctionCallArguments::ExpressionList(list) => {
-            let mut args = Vec::<String>::new();
-            for expr in list {
-              match expr {
-                Expression::PrimaryExpression(prim_expr) => {
-                  match prim_expr {
-                    PrimaryExpression::NumberLiteral(arg_identifier) => {
-                      let (number, _) = arg_identifier;
-                      match number {
-                        Number::Hex(val) => {
-                          // TODO: FIX THE HARDCODED TYPES
-                          args.push("address".to_string());
-                        }
-                        Number::Decimal(val) => {
-                          // TODO: FIX THE HARDCODED TYPES
-                          args.push("uint256".to_string());
-                        }
-                      }
-                    }
-                    PrimaryExpression::BooleanLiteral(bool_lit) => {
-                      let mut lit = "true";
-                      match bool_lit {
-                        Boolean::False => {
-                          lit = "false";
-                        }
-                        _ => {}
-                      }
-                      args.push(format!("bool {}", lit));
-                    }
-                    _ => {
-                      panic!("{:#?} is not a valid parameter", prim_expr);
-                    }
-                  }
-                }
-                _ => {}
-              }
-            }
 
-            format!("{}({})", identifier, args.join(","))
-          }
-          _ => panic!(
-            "{:#?} function call arguments should be an expression list",
-            self.args
-          ),
-        },
-        _ => panic!("{:#?} should be the identifier of the function call", state),
+  /// The function called through a member access like `c.f(a)` or `c.f{value: 1}(a)`, with the
+  /// options of the call.
+  fn member_function(&self) -> Option<(&String, &[NameValue])> {
+    match self.expr.as_ref() {
+      Expression::PrimaryExpression(PrimaryExpression::Identifier(name)) => Some((name, &[])),
+      Expression::CallOptions(expr, options) => match expr.as_ref() {
+        Expression::PrimaryExpression(PrimaryExpression::Identifier(name)) => Some((name, options)),
+        _ => None,
       },
-      _ => panic!(
-        "{:#?} should be a primary expression of the function call",
-        self.expr
-      ),
+      _ => None,
     }
   }
+
+  /// Calls a function of another contract through its interface.
+  fn external_op_codes<'a>(
+    &'a self,
+    address: &'a Expression,
+    ctx: &mut Context<'a>,
+  ) -> Result<usize, String> {
+    let (name, options) = self
+      .member_function()
+      .ok_or_else(|| format!("{:?} is not callable", self.expr))?;
+    let contract = address
+      .contract_type(ctx)
+      .ok_or_else(|| format!("Member \"{}\" not found or not visible", name))?;
+    let functions = contract
+      .functions()
+      .into_iter()
+      .filter(|f| f.identifier.as_ref() == Some(name))
+      .collect();
+    let function = overload(functions, |f| f.parameter_list.len(), self.argument_count())
+      .ok_or_else(|| format!("Member \"{}\" not found or not visible", name))?;
+    let args = self.ordered_arguments(&function.parameter_names())?;
+    let CallOptions { value, gas, salt } = CallOptions::new(options)?;
+    if salt.is_some() {
+      return Err("Function call option \"salt\" can only be used with \"new\".".to_string());
+    }
+    if value.is_some() && function.state_mutability != Some(StateMutability::Payable) {
+      return Err("Cannot set option \"value\" on a non-payable function type.".to_string());
+    }
+
+    let start = ctx.asm.stack_height();
+    address.single_value(ctx)?;
+    ctx.cleanup(&TypeName::ElementaryTypeName(ElementaryTypeName::Address));
+    for (arg, param) in args.iter().zip(function.parameter_list.iter()) {
+      if !param.typename.is_value_type() {
+        return Err(format!(
+          "Parameters of type {} are not supported yet",
+          param.typename.abi_name()
+        ));
+      }
+      arg.single_value(ctx)?;
+      ctx.cleanup(&param.typename);
+    }
+    let uint256 = TypeName::ElementaryTypeName(ElementaryTypeName::UInt(UInt::Uint256));
+    for option in &[value, gas] {
+      match option {
+        Some(expr) => expr.converted_value(&uint256, &None, ctx)?,
+        None => ctx.asm.push(&[0]),
+      }
+    }
+    let options = ctx.asm.stack_height() - 2;
+    ctx.load_free_memory_pointer();
+    let pointer = ctx.asm.stack_height() - 1;
+    ctx.asm.push(&function.selector());
+    ctx.asm.push(&[0xe0]);
+    ctx.asm.op(OpCode::SHL);
+    ctx.load_local(pointer)?;
+    ctx.asm.op(OpCode::MSTORE);
+    for index in 0..args.len() {
+      ctx.load_local(start + 1 + index)?;
+      ctx.load_local(pointer)?;
+      ctx.asm.push_usize(4 + 32 * index);
+      ctx.asm.op(OpCode::ADD);
+      ctx.asm.op(OpCode::MSTORE);
+    }
+
+    let has_code = ctx.asm.new_tag();
+    ctx.load_local(start)?;
+    ctx.asm.op(OpCode::EXTCODESIZE);
+    ctx.asm.jumpi(has_code);
+    ctx.revert();
+    ctx.asm.tag(has_code);
+
+    let returns = function.returns.len();
+    ctx.asm.push_usize(32 * returns);
+    ctx.load_local(pointer)?;
+    ctx.asm.push_usize(4 + 32 * args.len());
+    ctx.load_local(pointer)?;
+    ctx.load_local(options)?;
+    ctx.load_local(start)?;
+    match gas {
+      Some(_) => ctx.load_local(options + 1)?,
+      None => ctx.asm.op(OpCode::GAS),
+    }
+    ctx.asm.op(OpCode::CALL);
+    let success = ctx.asm.new_tag();
+    ctx.asm.jumpi(success);
+    ctx.forward_revert();
+    ctx.asm.tag(success);
+
+    for (index, param) in function.returns.iter().enumerate() {
+      if !param.typename.is_value_type() {
+        return Err(format!(
+          "Return values of type {} are not supported yet",
+          param.typename.abi_name()
+        ));
+      }
+      ctx.load_local(pointer)?;
+      ctx.asm.push_usize(32 * index);
+      ctx.asm.op(OpCode::ADD);
+      ctx.asm.op(OpCode::MLOAD);
+      ctx.cleanup(&param.typename);
+    }
+    let layout: Vec<usize> = (start..ctx.asm.stack_height()).collect();
+    ctx
+      .asm
+      .rearrange(&layout, &layout[layout.len() - returns..])?;
+    Ok(returns)
+  }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
   // TODO: PostFix(),
-  // TODO: New(),
-  // TODO: IndexAccess,
+  New(TypeName),
+  /// `f{value: 1}`, options for the call that follows.
+  CallOptions(Box<Expression>, Vec<NameValue>),
+  IndexAccess(Box<Expression>, Option<Box<Expression>>),
   MemberAccess(Box<Expression>, Box<Expression>),
   FunctionCall(FunctionCall),
-  // TODO:   ('!' | '~' | 'delete' | '++' | '--' | '+' | '-') Expression
-  // TODO: | Expression '**' Expression
-  // TODO: | Expression ('*' | '/' | '%') Expression
-  // TODO: | Expression ('+' | '-') Expression
+  /// `!a`, the negation of a bool.
+  Not(Box<Expression>),
+  // TODO:   ('~' | 'delete' | '++' | '--' | '+' | '-') Expression
+  /// `**`, `*`, `/`, `%`, `+` and `-`, the comparisons `<`, `<=`, `>`, `>=`, `==` and `!=`,
+  /// and `&&` and `||`.
+  BinaryOperation(Box<Expression>, BinaryOperator, Box<Expression>),
   // TODO: | Expression ('<<' | '>>') Expression
   // TODO: | Expression '&' Expression
   // TODO: | Expression '^' Expression
   // TODO: | Expression '|' Expression
-  // TODO: | Expression ('<' | '>' | '<=' | '>=') Expression
-  // TODO: | Expression ('==' | '!=') Expression
-  // TODO: | Expression '&&' Expression
-  // TODO: | Expression '||' Expression
   // TODO: | Expression '?' Expression ':' Expression
   Assignment(Box<Expression>, Assignment, Box<Expression>),
-  // TODO: | Expression ('=' | '|=' | '^=' | '&=' | '<<=' | '>>=' | '+=' | '-=' | '*=' | '/=' | '%=') Expression
+  /// `(a, b)` or `(a, , c)`, whose components may be left out on the left hand side of an
+  /// assignment.
+  Tuple(Vec<Option<Expression>>),
+  /// `[1, 2, 3]`, a static memory array.
+  InlineArray(Vec<Expression>),
   PrimaryExpression(PrimaryExpression),
 }
 
 impl Expression {
-  pub fn op_codes(self) -> Vec<u32> {
-    let mut codes = vec![];
+  /// Pushes the values of the expression onto the stack, returning how many there are.
+  pub fn op_codes<'a>(&'a self, ctx: &mut Context<'a>) -> Result<usize, String> {
+    if let Some(builtin) = Builtin::resolve(self, ctx) {
+      return builtin.op_codes(ctx);
+    }
+    match self {
+      Expression::New(type_name) => Err(format!(
+        "new {} has to be called with arguments",
+        type_name.abi_name()
+      )),
+      Expression::CallOptions(..) => Err("Call options have to be followed by a call.".to_string()),
+      Expression::MemberAccess(expr, member) => {
+        match expr.reference_type(ctx) {
+          Some(Reference {
+            type_name: TypeName::Array(..),
+            ..
+          })
+          | Some(Reference {
+            type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bytes),
+            ..
+          }) => return array_member_op_codes(expr, member, ctx),
+          _ => {}
+        }
+        match member.as_ref() {
+          Expression::FunctionCall(call) => call.external_op_codes(expr, ctx),
+          _ => Err(format!("Member access {:?} is not supported", member)),
+        }
+      }
+      Expression::FunctionCall(fn_call) => fn_call.op_codes(ctx),
+      Expression::IndexAccess(base, Some(index))
+        if base.reference_type(ctx)
+          == Some(Reference {
+            location: StorageLocation::Storage,
+            type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bytes),
+          }) =>
+      {
+        base.container(ctx)?;
+        index.single_value(ctx)?;
+        bytes::index_storage_value(ctx);
+        Ok(1)
+      }
+      Expression::IndexAccess(..) => {
+        self.reference(ctx)?.load(ctx)?;
+        Ok(1)
+      }
+      Expression::BinaryOperation(lhs, op, rhs) if op.is_comparison() => {
+        comparison_op_codes(lhs, *op, rhs, ctx)?;
+        Ok(1)
+      }
+      Expression::BinaryOperation(lhs, op, rhs) if op.is_logical() => {
+        logical_op_codes(lhs, *op, rhs, ctx)?;
+        Ok(1)
+      }
+      Expression::Not(operand) => {
+        not_op_codes(operand, ctx)?;
+        Ok(1)
+      }
+      Expression::BinaryOperation(lhs, op, rhs) => {
+        match constant_value(self)? {
+          Some(value) => ctx.push_constant(&value)?,
+          None => binary_op_codes(lhs, *op, rhs, ctx)?,
+        }
+        Ok(1)
+      }
+      Expression::Assignment(lhs, op, rhs) if *op != Assignment::Assign => {
+        let op = match op {
+          Assignment::AddAssign => BinaryOperator::Add,
+          Assignment::SubAssign => BinaryOperator::Sub,
+          Assignment::MultiAssign => BinaryOperator::Mul,
+          Assignment::DivAssign => BinaryOperator::Div,
+          Assignment::ModAssign => BinaryOperator::Mod,
+          _ => return Err(format!("{:?} is not supported", op)),
+        };
+        let type_name = lhs
+          .value_type(ctx)
+          .ok_or_else(|| "Expression has to be an lvalue.".to_string())?;
+        if let Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) =
+          lhs.as_ref()
+        {
+          if let Some(local) = ctx.local(identifier) {
+            ctx.load_local(local.position)?;
+            rhs.converted_value(&type_name, &None, ctx)?;
+            operation(op, &type_name, ctx)?;
+            ctx.asm.op(OpCode::DUP1);
+            ctx.store_local(local.position)?;
+            return Ok(1);
+          }
+        }
+        let reference = lhs.reference(ctx)?;
+        reference.dup(ctx);
+        reference.load(ctx)?;
+        rhs.converted_value(&type_name, &None, ctx)?;
+        operation(op, &type_name, ctx)?;
+        reference.store_copy(ctx)?;
+        Ok(1)
+      }
+      Expression::Assignment(lhs, _, rhs) => {
+        if let Expression::Tuple(components) = lhs.as_ref() {
+          return assign_tuple(components, rhs, ctx);
+        }
+        if let Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) =
+          lhs.as_ref()
+        {
+          if let Some(local) = ctx.local(identifier) {
+            rhs.converted_value(&local.type_name, &local.storage_location, ctx)?;
+            ctx.asm.op(OpCode::DUP1);
+            ctx.store_local(local.position)?;
+            return Ok(1);
+          }
+        }
+        match lhs.reference_type(ctx) {
+          Some(target) => rhs.converted_value(&target.type_name, &None, ctx)?,
+          None => rhs.single_value(ctx)?,
+        }
+        let reference = lhs.reference(ctx)?;
+        // Moves the value above the location
+        for depth in 1..=reference.size() {
+          ctx.asm.swap(depth);
+        }
+        reference.store_copy(ctx)?;
+        Ok(1)
+      }
+      Expression::Tuple(components) => {
+        for component in components {
+          component
+            .as_ref()
+            .ok_or_else(|| "Tuple component cannot be empty.".to_string())?
+            .single_value(ctx)?;
+        }
+        Ok(components.len())
+      }
+      Expression::InlineArray(elements) => {
+        inline_array_op_codes(elements, &inline_array_type(elements, ctx)?, ctx)?;
+        Ok(1)
+      }
+      Expression::PrimaryExpression(expr) => {
+        expr.op_codes(ctx)?;
+        Ok(1)
+      }
+    }
+  }
+
+  /// Pushes the storage slot or memory address the expression refers to.
+  fn reference<'a>(&'a self, ctx: &mut Context<'a>) -> Result<Reference, String> {
     match self {
-      Expression::MemberAccess(expr1, expr2) => {
-        // TODO: SOME CUSTOM LOGIC
+      Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) => {
+        if ctx.local(identifier).is_some() {
+          return Err("Expression has to be an lvalue.".to_string());
+        }
+        match ctx.state_variable(identifier) {
+          Some((position, decl)) => Ok(ctx.push_storage_position(position, &decl.type_name)),
+          None => Err(format!("Undeclared identifier: {}", identifier)),
+        }
+      }
+      Expression::IndexAccess(base, index) => {
+        let container = base.container(ctx)?;
+        let index = index
+          .as_ref()
+          .ok_or_else(|| "Index expression cannot be omitted.".to_string())?;
+        index.single_value(ctx)?;
+        let type_name = match container.location {
+          StorageLocation::Storage => container.type_name.index_storage(ctx)?,
+          _ => container.type_name.index_memory(ctx)?,
+        };
+        Ok(Reference {
+          location: container.location,
+          type_name,
+        })
+      }
+      _ => Err("Expression has to be an lvalue.".to_string()),
+    }
+  }
 
-        codes.extend(expr1.op_codes());
-        codes.extend(expr2.op_codes());
+  /// Pushes the slot of a storage mapping or array, or the pointer to a memory array.
+  fn container<'a>(&'a self, ctx: &mut Context<'a>) -> Result<Reference, String> {
+    if let Some(reference) = Builtin::resolve(self, ctx).and_then(|b| b.reference_type()) {
+      self.single_value(ctx)?;
+      return Ok(reference);
+    }
+    let reference = match self {
+      Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier))
+        if ctx.local(identifier).is_some() =>
+      {
+        let local = ctx.local(identifier).unwrap();
+        ctx.load_local(local.position)?;
+        Reference {
+          location: local.storage_location.unwrap_or(StorageLocation::Memory),
+          type_name: local.type_name,
+        }
+      }
+      Expression::FunctionCall(call) => match (
+        call.expr.as_ref(),
+        call.arguments().map_or(&[][..], |args| &args[..]),
+      ) {
+        (Expression::New(type_name), _) => {
+          self.single_value(ctx)?;
+          Reference {
+            location: StorageLocation::Memory,
+            type_name: type_name.clone(),
+          }
+        }
+        (
+          Expression::PrimaryExpression(PrimaryExpression::ElementaryTypeNameExpression(name)),
+          [arg],
+        ) if name.is_dynamic() => Reference {
+          type_name: TypeName::ElementaryTypeName(*name),
+          ..arg.container(ctx)?
+        },
+        _ => return Err(format!("Indexed expression {:?} is not supported", self)),
+      },
+      Expression::InlineArray(_) => {
+        self.single_value(ctx)?;
+        self.reference_type(ctx).unwrap()
       }
-      Expression::FunctionCall(fn_call) => codes.extend(fn_call.op_codes()),
-      _ => {}
+      Expression::PrimaryExpression(PrimaryExpression::StringLiteral(_))
+      | Expression::PrimaryExpression(PrimaryExpression::HexLiteral(_)) => {
+        self.single_value(ctx)?;
+        Reference {
+          location: StorageLocation::Memory,
+          type_name: TypeName::ElementaryTypeName(ElementaryTypeName::String),
+        }
+      }
+      _ => {
+        let reference = self.reference(ctx)?;
+        match reference.type_name {
+          TypeName::Mapping(..) => {}
+          _ => reference.load(ctx)?,
+        }
+        reference
+      }
+    };
+    match reference.type_name {
+      TypeName::Array(..) | TypeName::Mapping(..) => Ok(reference),
+      ref type_name if type_name.is_byte_array() => Ok(reference),
+      _ => Err(format!(
+        "Indexed expression has to be a type, mapping or array (is {})",
+        reference.type_name.abi_name()
+      )),
     }
-    codes
   }
+
+  /// The location and type of the value of the expression, as far as it is known without
+  /// generating any code.
+  pub fn reference_type(&self, ctx: &Context) -> Option<Reference> {
+    if let Some(builtin) = Builtin::resolve(self, ctx) {
+      return builtin.reference_type();
+    }
+    match self {
+      Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) => {
+        match ctx.local(identifier) {
+          Some(local) => Some(Reference {
+            location: local.storage_location.unwrap_or(StorageLocation::Memory),
+            type_name: local.type_name,
+          }),
+          None => Some(Reference {
+            location: StorageLocation::Storage,
+            type_name: ctx.state_variable(identifier)?.1.type_name.clone(),
+          }),
+        }
+      }
+      Expression::IndexAccess(base, _) => {
+        let base = base.reference_type(ctx)?;
+        match base.type_name {
+          TypeName::Mapping(_, element) | TypeName::Array(element, _) => Some(Reference {
+            location: base.location,
+            type_name: *element,
+          }),
+          TypeName::ElementaryTypeName(ElementaryTypeName::Bytes) => Some(Reference {
+            location: base.location,
+            type_name: TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(
+              FixedBytes::Bytes1,
+            )),
+          }),
+          _ => None,
+        }
+      }
+      Expression::FunctionCall(call) => match (
+        call.expr.as_ref(),
+        call.arguments().map_or(&[][..], |args| &args[..]),
+      ) {
+        (Expression::New(type_name), _) => Some(Reference {
+          location: StorageLocation::Memory,
+          type_name: type_name.clone(),
+        }),
+        (
+          Expression::PrimaryExpression(PrimaryExpression::ElementaryTypeNameExpression(name)),
+          [arg],
+        ) if name.is_dynamic() => Some(Reference {
+          type_name: TypeName::ElementaryTypeName(*name),
+          ..arg.reference_type(ctx)?
+        }),
+        _ => None,
+      },
+      Expression::InlineArray(elements) => Some(Reference {
+        location: StorageLocation::Memory,
+        type_name: inline_array_type(elements, ctx).ok()?,
+      }),
+      Expression::PrimaryExpression(PrimaryExpression::StringLiteral(_))
+      | Expression::PrimaryExpression(PrimaryExpression::HexLiteral(_)) => Some(Reference {
+        location: StorageLocation::Memory,
+        type_name: TypeName::ElementaryTypeName(ElementaryTypeName::String),
+      }),
+      _ => None,
+    }
+  }
+
+  /// The type of the value of the expression, as far as it is known without generating any
+  /// code. Number literals have no type of their own and take the type they are used with.
+  pub fn value_type(&self, ctx: &Context) -> Option<TypeName> {
+    if let Some(builtin) = Builtin::resolve(self, ctx) {
+      return builtin.value_type(ctx);
+    }
+    match self {
+      Expression::BinaryOperation(_, op, _) if !op.is_arithmetic() => {
+        Some(TypeName::ElementaryTypeName(ElementaryTypeName::Bool))
+      }
+      Expression::Not(_) => Some(TypeName::ElementaryTypeName(ElementaryTypeName::Bool)),
+      Expression::BinaryOperation(..) if is_constant(self) => None,
+      Expression::BinaryOperation(lhs, op, rhs) => result_type(lhs, *op, rhs, ctx).ok(),
+      Expression::Assignment(lhs, ..) => lhs.value_type(ctx),
+      Expression::PrimaryExpression(PrimaryExpression::NumberLiteral(_)) => None,
+      Expression::PrimaryExpression(PrimaryExpression::BooleanLiteral(_)) => {
+        Some(TypeName::ElementaryTypeName(ElementaryTypeName::Bool))
+      }
+      Expression::FunctionCall(call) => match call.expr.as_ref() {
+        Expression::PrimaryExpression(PrimaryExpression::ElementaryTypeNameExpression(name)) => {
+          Some(TypeName::ElementaryTypeName(*name))
+        }
+        Expression::New(type_name @ TypeName::UserDefinedTypeName(_)) => Some(type_name.clone()),
+        Expression::CallOptions(expr, _) => match expr.as_ref() {
+          Expression::New(type_name @ TypeName::UserDefinedTypeName(_)) => Some(type_name.clone()),
+          _ => None,
+        },
+        Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) => {
+          match ctx.function(identifier, call.argument_count()) {
+            Some(function) => match &function.returns[..] {
+              [param] => Some(param.typename.clone()),
+              _ => None,
+            },
+            None => ctx
+              .find_contract(identifier)
+              .map(|_| TypeName::UserDefinedTypeName(vec![identifier.clone()])),
+          }
+        }
+        _ => self.reference_type(ctx).map(|r| r.type_name),
+      },
+      Expression::MemberAccess(expr, member) => match member.as_ref() {
+        Expression::PrimaryExpression(PrimaryExpression::Identifier(name)) if name == "length" => {
+          Some(TypeName::ElementaryTypeName(ElementaryTypeName::UInt(
+            UInt::Uint256,
+          )))
+        }
+        Expression::FunctionCall(call) => {
+          let (name, _) = call.member_function()?;
+          let function = expr.contract_type(ctx)?.functions().into_iter().find(|f| {
+            f.identifier.as_ref() == Some(name) && f.parameter_list.len() == call.argument_count()
+          })?;
+          match &function.returns[..] {
+            [param] => Some(param.typename.clone()),
+            _ => None,
+          }
+        }
+        _ => None,
+      },
+      _ => self.reference_type(ctx).map(|r| r.type_name),
+    }
+  }
+
+  /// Generates the value of the expression for a variable of the given type and location:
+  /// value types are cleaned up and byte arrays are copied from storage into memory.
+  pub fn converted_value<'a>(
+    &'a self,
+    type_name: &TypeName,
+    storage_location: &Option<StorageLocation>,
+    ctx: &mut Context<'a>,
+  ) -> Result<(), String> {
+    if let (
+      Expression::PrimaryExpression(PrimaryExpression::StringLiteral(value)),
+      TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(bytes)),
+    )
+    | (
+      Expression::PrimaryExpression(PrimaryExpression::HexLiteral(value)),
+      TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(bytes)),
+    ) = (self, type_name)
+    {
+      if value.len() > bytes.size() {
+        return Err(format!(
+          "Type literal_string \"{}\" is not implicitly convertible to expected type {}.",
+          String::from_utf8_lossy(value),
+          type_name.abi_name()
+        ));
+      }
+      let mut word = value.clone();
+      word.resize(32, 0);
+      ctx.asm.push(&word);
+      return Ok(());
+    }
+    if let (Expression::InlineArray(elements), TypeName::Array(element, Some(length))) =
+      (self, type_name)
+    {
+      if element.is_value_type() && array_length(length)? == elements.len() {
+        return inline_array_op_codes(elements, type_name, ctx);
+      }
+    }
+    if let Some(value) = constant_value(self)? {
+      return constant_op_codes(&value, type_name, ctx);
+    }
+    self.single_value(ctx)?;
+    if type_name.is_value_type() {
+      ctx.cleanup(type_name);
+    } else if type_name.is_byte_array() && *storage_location != Some(StorageLocation::Storage) {
+      if let Some(Reference {
+        location: StorageLocation::Storage,
+        ..
+      }) = self.reference_type(ctx)
+      {
+        bytes::copy_to_memory(ctx)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Generates an expression that has to evaluate to exactly one value.
+  /// Pushes the values of the expression for variables of the given types and locations,
+  /// converting the components of a tuple one by one. Returns how many values there are.
+  pub fn converted_values<'a>(
+    &'a self,
+    targets: &[Option<(TypeName, Option<StorageLocation>)>],
+    ctx: &mut Context<'a>,
+  ) -> Result<usize, String> {
+    match (self, targets) {
+      (_, [Some((type_name, storage_location))]) => {
+        self.converted_value(type_name, storage_location, ctx)?;
+        Ok(1)
+      }
+      (Expression::Tuple(components), _) if components.len() == targets.len() => {
+        for (component, target) in components.iter().zip(targets) {
+          let component = component
+            .as_ref()
+            .ok_or_else(|| "Tuple component cannot be empty.".to_string())?;
+          match target {
+            Some((type_name, storage_location)) => {
+              component.converted_value(type_name, storage_location, ctx)?
+            }
+            None => component.single_value(ctx)?,
+          }
+        }
+        Ok(components.len())
+      }
+      _ => self.op_codes(ctx),
+    }
+  }
+
+  pub fn single_value<'a>(&'a self, ctx: &mut Context<'a>) -> Result<(), String> {
+    match self.op_codes(ctx)? {
+      1 => Ok(()),
+      n => Err(format!(
+        "{:?} evaluates to {} values instead of one",
+        self, n
+      )),
+    }
+  }
+
+  /// The contract whose functions can be called on the value of this expression.
+  fn contract_type<'a>(&self, ctx: &Context<'a>) -> Option<&'a Contract> {
+    if let Some(Builtin::This) = Builtin::resolve(self, ctx) {
+      return Some(ctx.contract);
+    }
+    match self {
+      Expression::FunctionCall(FunctionCall { expr, args }) => match (expr.as_ref(), args) {
+        (
+          Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)),
+          FunctionCallArguments::ExpressionList(list),
+        ) if list.len() == 1 => ctx.find_contract(identifier),
+        (Expression::New(TypeName::UserDefinedTypeName(path)), _) => {
+          ctx.find_contract(path.last()?)
+        }
+        (Expression::CallOptions(expr, _), _) => match expr.as_ref() {
+          Expression::New(TypeName::UserDefinedTypeName(path)) => ctx.find_contract(path.last()?),
+          _ => None,
+        },
+        _ => None,
+      },
+      Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) => {
+        let type_name = match ctx.local(identifier) {
+          Some(local) => local.type_name,
+          None => ctx.state_variable(identifier)?.1.type_name.clone(),
+        };
+        match type_name {
+          TypeName::UserDefinedTypeName(path) => ctx.find_contract(path.last()?),
+          _ => None,
+        }
+      }
+      _ => None,
+    }
+  }
+}
+
+/// `(a, , b) = rhs`: the right hand side is evaluated completely before the values are assigned
+/// from the last component to the first, so that `(a, b) = (b, a)` swaps the values.
+fn assign_tuple<'a>(
+  components: &'a [Option<Expression>],
+  rhs: &'a Expression,
+  ctx: &mut Context<'a>,
+) -> Result<usize, String> {
+  let targets: Vec<_> = components
+    .iter()
+    .map(|component| {
+      let reference = component.as_ref()?.reference_type(ctx)?;
+      Some((reference.type_name, Some(reference.location)))
+    })
+    .collect();
+  let count = rhs.converted_values(&targets, ctx)?;
+  if count != components.len() {
+    return Err(format!(
+      "Different number of components on the left hand side ({}) than on the right hand side ({}).",
+      components.len(),
+      count
+    ));
+  }
+  for component in components.iter().rev() {
+    match component {
+      None => ctx.asm.op(OpCode::POP),
+      Some(Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)))
+        if ctx.local(identifier).is_some() =>
+      {
+        let position = ctx.local(identifier).unwrap().position;
+        ctx.store_local(position)?;
+      }
+      Some(component) => component.reference(ctx)?.store(ctx)?,
+    }
+  }
+  Ok(0)
 }
 
 pub fn parse_expression(i: &[u8]) -> IResult<&[u8], Expression> {
   alt((
-    map(parse_member_access, |m| {
-      let (exp, mem) = m;
-      Expression::MemberAccess(Box::new(exp), Box::new(mem))
-    }),
-    map(parses_function_call, |f| {
-      let (expr, args) = f;
-      Expression::FunctionCall(FunctionCall {
-        expr: Box::new(expr),
-        args,
-      })
-    }),
-    delimited(char('('), parse_expression, char(')')),
     map(parse_assignment_expression, |x| {
       let (expr1, op, expr2) = x;
       Expression::Assignment(expr1, op, expr2)
     }),
-    map(parse_primary_expression, |e| {
-      Expression::PrimaryExpression(e)
-    }),
+    parse_expr_without_assignment,
   ))(i)
 }
 
 pub fn parse_expr_without_assignment(i: &[u8]) -> IResult<&[u8], Expression> {
+  parse_or_expression(i)
+}
+
+/// Parses a left associative chain of binary operations of the same precedence.
+fn parse_binary_operations(
+  i: &[u8],
+  operand: fn(&[u8]) -> IResult<&[u8], Expression>,
+  operator: fn(&[u8]) -> IResult<&[u8], BinaryOperator>,
+) -> IResult<&[u8], Expression> {
+  let (i, first) = operand(i)?;
+  let (i, rest) = many0(tuple((
+    preceded(multispace0, operator),
+    preceded(multispace0, operand),
+  )))(i)?;
+  let expr = rest.into_iter().fold(first, |lhs, (op, rhs)| {
+    Expression::BinaryOperation(Box::new(lhs), op, Box::new(rhs))
+  });
+  Ok((i, expr))
+}
+
+fn parse_or_expression(i: &[u8]) -> IResult<&[u8], Expression> {
+  parse_binary_operations(i, parse_and_expression, parse_or)
+}
+
+fn parse_and_expression(i: &[u8]) -> IResult<&[u8], Expression> {
+  parse_binary_operations(i, parse_equality_expression, parse_and)
+}
+
+fn parse_equality_expression(i: &[u8]) -> IResult<&[u8], Expression> {
+  parse_binary_operations(i, parse_relational_expression, |i| {
+    alt((parse_equal, parse_not_equal))(i)
+  })
+}
+
+fn parse_relational_expression(i: &[u8]) -> IResult<&[u8], Expression> {
+  parse_binary_operations(i, parse_additive_expression, |i| {
+    alt((
+      parse_less_than_or_equal,
+      parse_less_than,
+      parse_greater_than_or_equal,
+      parse_greater_than,
+    ))(i)
+  })
+}
+
+fn parse_additive_expression(i: &[u8]) -> IResult<&[u8], Expression> {
+  parse_binary_operations(i, parse_multiplicative_expression, |i| {
+    alt((parse_add, parse_sub))(i)
+  })
+}
+
+fn parse_multiplicative_expression(i: &[u8]) -> IResult<&[u8], Expression> {
+  parse_binary_operations(i, parse_exponentiation, |i| {
+    alt((parse_mul, parse_div, parse_mod))(i)
+  })
+}
+
+/// Parses `**`, which is right associative.
+fn parse_exponentiation(i: &[u8]) -> IResult<&[u8], Expression> {
+  map(
+    tuple((
+      parse_unary_expression,
+      opt(preceded(
+        preceded(multispace0, parse_exp),
+        preceded(multispace0, parse_exponentiation),
+      )),
+    )),
+    |(base, exponent)| match exponent {
+      Some(exponent) => {
+        Expression::BinaryOperation(Box::new(base), BinaryOperator::Exp, Box::new(exponent))
+      }
+      None => base,
+    },
+  )(i)
+}
+
+/// Parses `!a`, which binds tighter than any binary operator.
+fn parse_unary_expression(i: &[u8]) -> IResult<&[u8], Expression> {
   alt((
-    map(parse_member_access, |m| {
-      let (exp, mem) = m;
-      Expression::MemberAccess(Box::new(exp), Box::new(mem))
-    }),
-    map(parses_function_call, |f| {
-      let (expr, args) = f;
-      Expression::FunctionCall(FunctionCall {
-        expr: Box::new(expr),
-        args,
-      })
-    }),
-    delimited(char('('), parse_expression, char(')')),
-    map(parse_primary_expression, |e| {
-      Expression::PrimaryExpression(e)
-    }),
+    map(
+      preceded(char('!'), preceded(multispace0, parse_unary_expression)),
+      |operand| Expression::Not(Box::new(operand)),
+    ),
+    parse_postfix_expression,
   ))(i)
 }
 
+#[derive(Debug, PartialEq, Clone)]
+enum Postfix {
+  MemberAccess(Expression),
+  CallOptions(Vec<NameValue>),
+  FunctionCall(FunctionCallArguments),
+  IndexAccess(Option<Expression>),
+}
+
+pub fn parse_call_arguments(i: &[u8]) -> IResult<&[u8], FunctionCallArguments> {
+  delimited(
+    char('('),
+    preceded(multispace0, parse_function_call_arguments),
+    preceded(multispace0, char(')')),
+  )(i)
+}
+
+/// Parses `{value: 1, gas: 2}`, which has to contain at least one option.
+fn parse_call_options(i: &[u8]) -> IResult<&[u8], Vec<NameValue>> {
+  delimited(
+    char('{'),
+    verify(parse_name_value_list, |options: &Vec<NameValue>| {
+      !options.is_empty()
+    }),
+    preceded(multispace0, char('}')),
+  )(i)
+}
+
+fn parse_postfix(i: &[u8]) -> IResult<&[u8], Postfix> {
+  preceded(
+    multispace0,
+    alt((
+      map(
+        preceded(
+          char('.'),
+          preceded(
+            multispace0,
+            tuple((
+              parse_identifier,
+              opt(preceded(multispace0, parse_call_options)),
+              opt(preceded(multispace0, parse_call_arguments)),
+            )),
+          ),
+        ),
+        |x| {
+          let (identifier, options, args) = x;
+          let mut member = Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier));
+          if let Some(options) = options {
+            member = Expression::CallOptions(Box::new(member), options);
+          }
+          Postfix::MemberAccess(match args {
+            Some(args) => Expression::FunctionCall(FunctionCall {
+              expr: Box::new(member),
+              args,
+            }),
+            None => member,
+          })
+        },
+      ),
+      map(parse_call_arguments, Postfix::FunctionCall),
+      map(parse_call_options, Postfix::CallOptions),
+      map(
+        delimited(
+          char('['),
+          opt(preceded(multispace0, parse_expression)),
+          preceded(multispace0, char(']')),
+        ),
+        Postfix::IndexAccess,
+      ),
+    )),
+  )(i)
+}
+
+/// Parses `(a)`, which is just `a`, and tuples like `(a, b)` or `(a, , c)`.
+fn parse_tuple_expression(i: &[u8]) -> IResult<&[u8], Expression> {
+  map(
+    delimited(
+      char('('),
+      tuple((
+        opt(preceded(multispace0, parse_expression)),
+        many0(preceded(
+          preceded(multispace0, char(',')),
+          opt(preceded(multispace0, parse_expression)),
+        )),
+      )),
+      preceded(multispace0, char(')')),
+    ),
+    |(first, rest)| match (first, rest.is_empty()) {
+      (Some(expr), true) => expr,
+      (None, true) => Expression::Tuple(vec![]),
+      (first, false) => Expression::Tuple(std::iter::once(first).chain(rest).collect()),
+    },
+  )(i)
+}
+
+fn parse_postfix_expression(i: &[u8]) -> IResult<&[u8], Expression> {
+  map(
+    tuple((
+      alt((
+        parse_tuple_expression,
+        map(
+          delimited(
+            char('['),
+            parse_expression_list,
+            preceded(multispace0, char(']')),
+          ),
+          Expression::InlineArray,
+        ),
+        map(
+          preceded(
+            terminated(parse_new, word_end),
+            preceded(multispace1, parse_type_name),
+          ),
+          Expression::New,
+        ),
+        map(parse_primary_expression, Expression::PrimaryExpression),
+      )),
+      many0(parse_postfix),
+    )),
+    |x| {
+      let (expr, postfixes) = x;
+      postfixes
+        .into_iter()
+        .fold(expr, |expr, postfix| match postfix {
+          Postfix::MemberAccess(member) => {
+            Expression::MemberAccess(Box::new(expr), Box::new(member))
+          }
+          Postfix::CallOptions(options) => Expression::CallOptions(Box::new(expr), options),
+          Postfix::FunctionCall(args) => Expression::FunctionCall(FunctionCall {
+            expr: Box::new(expr),
+            args,
+          }),
+          Postfix::IndexAccess(index) => {
+            Expression::IndexAccess(Box::new(expr), index.map(Box::new))
+          }
+        })
+    },
+  )(i)
+}
+
 fn parse_assignment_expression(
   i: &[u8],
 ) -> IResult<&[u8], (Box<Expression>, Assignment, Box<Expression>)> {
@@ -360,45 +1106,174 @@ fn parse_assignment_expression(
       Box::new(x)
     }),
     preceded(multispace0, parse_assignment_operator),
-    map(preceded(multispace0, parse_expr_without_assignment), |x| {
-      Box::new(x)
-    }),
+    map(preceded(multispace0, parse_expression), |x| Box::new(x)),
   )))(i)
 }
 
-pub fn parse_expression_list(i: &[u8]) -> IResult<&[u8], Vec<Expression>> {
-  separated_nonempty_list(char(','), preceded(multispace0, parse_expression))(i)
+pub fn parse_expression_list(i: &[u8]) -> IResult<&[u8], Vec<Expression>> {
+  separated_nonempty_list(
+    preceded(multispace0, char(',')),
+    preceded(multispace0, parse_expression),
+  )(i)
+}
+
+#[allow(dead_code)]
+fn parse_member_access(i: &[u8]) -> IResult<&[u8], (Expression, Expression)> {
+  map_res(parse_postfix_expression, |x| match x {
+    Expression::MemberAccess(expr, member) => Ok((*expr, *member)),
+    _ => Err("not a member access"),
+  })(i)
+}
+
+/// Where a value lives: the storage slot or memory address of it is on top of the stack. Value
+/// types that share their storage slot with others have their byte offset within the slot on
+/// top of the slot.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Reference {
+  pub location: StorageLocation,
+  pub type_name: TypeName,
+}
+
+impl Reference {
+  /// The number of stack slots the location takes up.
+  pub fn size(&self) -> usize {
+    match self.location {
+      StorageLocation::Storage if self.type_name.is_packed() => 2,
+      _ => 1,
+    }
+  }
+
+  /// Copies the location on top of the stack.
+  pub fn dup(&self, ctx: &mut Context) {
+    for _ in 0..self.size() {
+      ctx.asm.dup(self.size());
+    }
+  }
+
+  /// Stores the value on top of the location below it, leaving a copy of the value.
+  pub fn store_copy(&self, ctx: &mut Context) -> Result<(), String> {
+    ctx.asm.op(OpCode::DUP1);
+    ctx.asm.swap(self.size() + 1);
+    if self.size() == 2 {
+      ctx.asm.op(OpCode::SWAP1);
+      ctx.asm.op(OpCode::SWAP2);
+    }
+    self.store(ctx)
+  }
+
+  /// Replaces the location on top of the stack with the value stored there. Storage arrays are
+  /// identified by their slot, so loading one leaves the slot in place.
+  pub fn load(&self, ctx: &mut Context) -> Result<(), String> {
+    match (&self.location, &self.type_name) {
+      (_, TypeName::Mapping(..)) => {
+        return Err("Mappings can only be accessed by index.".to_string())
+      }
+      (StorageLocation::Storage, type_name) if !type_name.is_value_type() => {}
+      (StorageLocation::Storage, type_name) if type_name.is_packed() => {
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.op(OpCode::SLOAD);
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.push(&[8]);
+        ctx.asm.op(OpCode::MUL);
+        ctx.asm.op(OpCode::SHR);
+        unpack(type_name, ctx)?;
+      }
+      (StorageLocation::Storage, _) => ctx.asm.op(OpCode::SLOAD),
+      (_, type_name) => {
+        ctx.asm.op(OpCode::MLOAD);
+        ctx.cleanup(type_name);
+      }
+    }
+    Ok(())
+  }
+
+  /// Stores the value below the location on top of the stack, consuming both. Byte arrays are
+  /// copied from memory into storage.
+  pub fn store(&self, ctx: &mut Context) -> Result<(), String> {
+    match (&self.location, &self.type_name) {
+      (_, TypeName::Mapping(..)) => return Err("Mappings cannot be assigned to.".to_string()),
+      (StorageLocation::Storage, type_name) if type_name.is_byte_array() => {
+        bytes::copy_to_storage(ctx)?
+      }
+      (_, type_name) if !type_name.is_value_type() => {
+        return Err(format!(
+          "Assigning to values of type {} is not supported yet",
+          type_name.abi_name()
+        ))
+      }
+      (StorageLocation::Storage, type_name) if type_name.is_packed() => {
+        // Clears the bytes of the value in the slot and puts the new ones in their place
+        ctx.asm.push(&[8]);
+        ctx.asm.op(OpCode::MUL);
+        ctx.asm.op(OpCode::DUP2);
+        ctx.asm.op(OpCode::SLOAD);
+        ctx.asm.push(&storage_mask(type_name)?);
+        ctx.asm.op(OpCode::DUP3);
+        ctx.asm.op(OpCode::SHL);
+        ctx.asm.op(OpCode::NOT);
+        ctx.asm.op(OpCode::AND);
+        ctx.asm.op(OpCode::DUP4);
+        pack(type_name, ctx)?;
+        ctx.asm.op(OpCode::DUP3);
+        ctx.asm.op(OpCode::SHL);
+        ctx.asm.op(OpCode::OR);
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.op(OpCode::POP);
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.op(OpCode::SSTORE);
+        ctx.asm.op(OpCode::POP);
+      }
+      (StorageLocation::Storage, _) => ctx.asm.op(OpCode::SSTORE),
+      (_, TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(bytes)))
+        if bytes.size() == 1 =>
+      {
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.push(&[0xf8]);
+        ctx.asm.op(OpCode::SHR);
+        ctx.asm.op(OpCode::SWAP1);
+        ctx.asm.op(OpCode::MSTORE8);
+      }
+      _ => ctx.asm.op(OpCode::MSTORE),
+    }
+    Ok(())
+  }
+}
+
+/// The mask of the lowest order bytes a packed value takes up in its slot.
+fn storage_mask(type_name: &TypeName) -> Result<Vec<u8>, String> {
+  Ok(vec![0xff; type_name.storage_bytes()?])
+}
+
+/// Turns the packed value in the lowest order bytes on top of the stack into a value of the
+/// type. `bytesN` are moved back to the highest order bytes, signed numbers are sign extended.
+fn unpack(type_name: &TypeName, ctx: &mut Context) -> Result<(), String> {
+  let bytes = type_name.storage_bytes()?;
+  ctx.asm.push(&storage_mask(type_name)?);
+  ctx.asm.op(OpCode::AND);
+  match type_name {
+    TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(_)) => {
+      ctx.asm.push_usize(256 - 8 * bytes);
+      ctx.asm.op(OpCode::SHL);
+    }
+    TypeName::ElementaryTypeName(name) if name.is_signed() => {
+      ctx.asm.push_usize(bytes - 1);
+      ctx.asm.op(OpCode::SIGNEXTEND);
+    }
+    _ => {}
+  }
+  Ok(())
 }
 
-fn parse_member_access(i: &[u8]) -> IResult<&[u8], (Expression, Expression)> {
-  map_res(
-    separated_pair(
-      take_until("."),
-      char('.'),
-      alt((
-        map(parses_function_call, |x| {
-          let (expr, args) = x;
-          Expression::FunctionCall(FunctionCall {
-            expr: Box::new(expr),
-            args,
-          })
-        }),
-        map(parse_identifier, |x| {
-          Expression::PrimaryExpression(PrimaryExpression::Identifier(x))
-        }),
-      )),
-    ),
-    |x| {
-      let (expr, id) = x;
-      let p_expr = parse_expression(expr);
-      if p_expr.is_err() {
-        return Err(p_expr.unwrap_err());
-      } else {
-        let (_, e) = p_expr.unwrap();
-        return Ok((e, id));
-      }
-    },
-  )(i)
+/// Turns the value of the type on top of the stack into the bytes it is packed into.
+fn pack(type_name: &TypeName, ctx: &mut Context) -> Result<(), String> {
+  if let TypeName::ElementaryTypeName(ElementaryTypeName::FixedBytes(_)) = type_name {
+    ctx.asm.push_usize(256 - 8 * type_name.storage_bytes()?);
+    ctx.asm.op(OpCode::SHR);
+  } else {
+    ctx.asm.push(&storage_mask(type_name)?);
+    ctx.asm.op(OpCode::AND);
+  }
+  Ok(())
 }
 
 pub type ElementaryTypeNameExpression = ElementaryTypeName;
@@ -407,10 +1282,104 @@ pub type ElementaryTypeNameExpression = ElementaryTypeName;
 pub enum TypeName {
   ElementaryTypeName(ElementaryTypeName),
   UserDefinedTypeName(Vec<String>),
-  // TODO: Mapping
-  // TODO: ArrayTypeName
+  Mapping(Box<TypeName>, Box<TypeName>),
+  /// The element type and, for static arrays, the length.
+  Array(Box<TypeName>, Option<Box<Expression>>),
   // TODO: FunctionTypeName
-  // TODO: ( 'address' 'payable' )
+}
+
+impl TypeName {
+  /// The canonical name of the type, as used in function and event signatures.
+  pub fn abi_name(&self) -> String {
+    match self {
+      TypeName::ElementaryTypeName(name) => name.abi_name(),
+      // Contracts are the only user defined types so far
+      TypeName::UserDefinedTypeName(_) => "address".to_string(),
+      TypeName::Mapping(key, value) => {
+        format!("mapping({} => {})", key.abi_name(), value.abi_name())
+      }
+      TypeName::Array(element, Some(length)) => match array_length(length) {
+        Ok(length) => format!("{}[{}]", element.abi_name(), length),
+        Err(_) => format!("{}[{:?}]", element.abi_name(), length),
+      },
+      TypeName::Array(element, None) => format!("{}[]", element.abi_name()),
+    }
+  }
+
+  /// Whether the type is or contains the user defined type with the name.
+  pub fn refers_to(&self, identifier: &str) -> bool {
+    match self {
+      TypeName::ElementaryTypeName(_) => false,
+      TypeName::UserDefinedTypeName(path) => path.last().map(String::as_str) == Some(identifier),
+      TypeName::Mapping(key, value) => key.refers_to(identifier) || value.refers_to(identifier),
+      TypeName::Array(element, _) => element.refers_to(identifier),
+    }
+  }
+
+  pub fn is_dynamic(&self) -> bool {
+    match self {
+      TypeName::ElementaryTypeName(name) => name.is_dynamic(),
+      TypeName::UserDefinedTypeName(_) | TypeName::Mapping(..) => false,
+      TypeName::Array(element, length) => length.is_none() || element.is_dynamic(),
+    }
+  }
+
+  /// Whether the type is `bytes` or `string`, which are stored packed.
+  pub fn is_byte_array(&self) -> bool {
+    match self {
+      TypeName::ElementaryTypeName(name) => name.is_dynamic(),
+      _ => false,
+    }
+  }
+
+  /// Whether the value fits into a single stack slot, as opposed to being referenced by its
+  /// location.
+  pub fn is_value_type(&self) -> bool {
+    match self {
+      TypeName::ElementaryTypeName(name) => !name.is_dynamic(),
+      TypeName::UserDefinedTypeName(_) => true,
+      TypeName::Mapping(..) | TypeName::Array(..) => false,
+    }
+  }
+
+  /// The number of storage slots the value takes up. Static arrays whose elements fit into
+  /// half a slot store several of them per slot.
+  pub fn storage_size(&self) -> Result<usize, String> {
+    match self {
+      TypeName::Array(element, Some(length)) => {
+        let length = array_length(length)?;
+        match element.elements_per_slot() {
+          Some(per_slot) => Ok(length.div_ceil(per_slot)),
+          None => Ok(length * element.storage_size()?),
+        }
+      }
+      _ => Ok(1),
+    }
+  }
+
+  /// The number of bytes the value takes up in storage, which is a multiple of 32 unless it is
+  /// a value type that shares its slot with others.
+  pub fn storage_bytes(&self) -> Result<usize, String> {
+    match self {
+      TypeName::ElementaryTypeName(name) if !name.is_dynamic() => Ok(name.size().unwrap_or(32)),
+      TypeName::UserDefinedTypeName(_) => Ok(20),
+      _ => Ok(32 * self.storage_size()?),
+    }
+  }
+
+  /// Whether the value is stored at a byte offset within a slot, because it is smaller than a
+  /// slot.
+  pub fn is_packed(&self) -> bool {
+    self.is_value_type() && self.storage_bytes().is_ok_and(|bytes| bytes < 32)
+  }
+
+  /// How many elements of this type an array stores per slot, if they are packed at all.
+  pub fn elements_per_slot(&self) -> Option<usize> {
+    match self.storage_bytes() {
+      Ok(bytes) if self.is_value_type() && bytes <= 16 => Some(32 / bytes),
+      _ => None,
+    }
+  }
 }
 
 pub fn parse_user_defined_type_name(i: &[u8]) -> IResult<&[u8], TypeName> {
@@ -419,13 +1388,61 @@ pub fn parse_user_defined_type_name(i: &[u8]) -> IResult<&[u8], TypeName> {
   })(i)
 }
 
+pub fn parse_mapping_type_name(i: &[u8]) -> IResult<&[u8], TypeName> {
+  map(
+    preceded(
+      terminated(parse_mapping, word_end),
+      delimited(
+        preceded(multispace0, char('(')),
+        tuple((
+          preceded(
+            multispace0,
+            alt((
+              map(parse_elementary_type_name, TypeName::ElementaryTypeName),
+              parse_user_defined_type_name,
+            )),
+          ),
+          preceded(
+            preceded(multispace0, tag("=>")),
+            preceded(multispace0, parse_type_name),
+          ),
+        )),
+        preceded(multispace0, char(')')),
+      ),
+    ),
+    |x| {
+      let (key, value) = x;
+      TypeName::Mapping(Box::new(key), Box::new(value))
+    },
+  )(i)
+}
+
 pub fn parse_type_name(i: &[u8]) -> IResult<&[u8], TypeName> {
-  alt((
-    map(parse_elementary_type_name, |e| {
-      TypeName::ElementaryTypeName(e)
-    }),
-    parse_user_defined_type_name,
-  ))(i)
+  map(
+    tuple((
+      alt((
+        parse_mapping_type_name,
+        map(parse_elementary_type_name, |e| {
+          TypeName::ElementaryTypeName(e)
+        }),
+        parse_user_defined_type_name,
+      )),
+      many0(preceded(
+        multispace0,
+        delimited(
+          char('['),
+          opt(preceded(multispace0, parse_expression)),
+          preceded(multispace0, char(']')),
+        ),
+      )),
+    )),
+    |x| {
+      let (type_name, lengths) = x;
+      lengths.into_iter().fold(type_name, |element, length| {
+        TypeName::Array(Box::new(element), length.map(Box::new))
+      })
+    },
+  )(i)
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -443,12 +1460,12 @@ pub fn parse_parameter(i: &[u8]) -> IResult<&[u8], Parameter> {
       alt((
         complete(map(
           tuple((
-            preceded(multispace1, parse_storage_location),
-            preceded(multispace1, parse_identifier),
+            preceded(multispace1, terminated(parse_storage_location, word_end)),
+            opt(preceded(multispace1, parse_identifier)),
           )),
           |tup| {
             let (storage, id) = tup;
-            (Some(storage), Some(id.to_string()))
+            (Some(storage), id)
           },
         )),
         complete(map(preceded(multispace1, parse_identifier), |id| {
@@ -491,7 +1508,7 @@ mod tests {
 
   use crate::elementary_type_name::ElementaryTypeName;
   use crate::expression::function::FunctionCallArguments;
-  use crate::literal::Number;
+  use crate::literal::{Boolean, Number};
   use pretty_assertions::assert_eq;
   use std::str::from_utf8;
 
@@ -598,6 +1615,28 @@ mod tests {
     }
   }
 
+  #[test]
+  fn parses_parameter_with_storage_location_only() {
+    let input = "string memory)";
+    let result = parse_parameter(input.as_bytes());
+    if result.is_err() {
+      result.expect("error");
+    } else {
+      let (remaining, param) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), param),
+        (
+          ")",
+          Parameter {
+            typename: TypeName::ElementaryTypeName(ElementaryTypeName::String),
+            storage_location: Some(StorageLocation::Memory),
+            identifier: None,
+          }
+        )
+      )
+    }
+  }
+
   #[test]
   fn parses_parameter_list_no_params() {
     let input = "(    )";
@@ -768,4 +1807,441 @@ mod tests {
       )
     }
   }
+
+  #[test]
+  fn parses_nested_mapping() {
+    let input = "mapping(address => mapping (uint256=>Token)) rest";
+    let (remaining, typename) = parse_type_name(input.as_bytes()).ok().unwrap();
+    assert_eq!(
+      (from_utf8(remaining).unwrap(), typename),
+      (
+        " rest",
+        TypeName::Mapping(
+          Box::new(TypeName::ElementaryTypeName(ElementaryTypeName::Address)),
+          Box::new(TypeName::Mapping(
+            Box::new(TypeName::ElementaryTypeName(ElementaryTypeName::UInt(
+              UInt::Uint256
+            ))),
+            Box::new(TypeName::UserDefinedTypeName(vec!["Token".to_string()]))
+          ))
+        )
+      )
+    )
+  }
+
+  #[test]
+  fn parses_index_access() {
+    let input = b"flags[owner][ 1 ] = true";
+    let result = parse_expression(input);
+    if result.is_err() {
+      result.expect("should parse index access");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          "",
+          Expression::Assignment(
+            Box::new(Expression::IndexAccess(
+              Box::new(Expression::IndexAccess(
+                Box::new(Expression::PrimaryExpression(
+                  PrimaryExpression::Identifier("flags".to_string())
+                )),
+                Some(Box::new(Expression::PrimaryExpression(
+                  PrimaryExpression::Identifier("owner".to_string())
+                )))
+              )),
+              Some(Box::new(Expression::PrimaryExpression(
+                PrimaryExpression::NumberLiteral((Number::Decimal("1".to_string()), None))
+              )))
+            )),
+            Assignment::Assign,
+            Box::new(Expression::PrimaryExpression(
+              PrimaryExpression::BooleanLiteral(Boolean::True)
+            ))
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_array_type_names() {
+    let input = "uint256[2][] memory";
+    let (remaining, typename) = parse_type_name(input.as_bytes()).ok().unwrap();
+    assert_eq!(
+      (from_utf8(remaining).unwrap(), typename),
+      (
+        " memory",
+        TypeName::Array(
+          Box::new(TypeName::Array(
+            Box::new(TypeName::ElementaryTypeName(ElementaryTypeName::UInt(
+              UInt::Uint256
+            ))),
+            Some(Box::new(Expression::PrimaryExpression(
+              PrimaryExpression::NumberLiteral((Number::Decimal("2".to_string()), None))
+            )))
+          )),
+          None
+        )
+      )
+    )
+  }
+
+  #[test]
+  fn parses_new_array_expression() {
+    let input = b"new address[](size)[0]";
+    let result = parse_expression(input);
+    if result.is_err() {
+      result.expect("should parse new expression");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          "",
+          Expression::IndexAccess(
+            Box::new(Expression::FunctionCall(FunctionCall {
+              expr: Box::new(Expression::New(TypeName::Array(
+                Box::new(TypeName::ElementaryTypeName(ElementaryTypeName::Address)),
+                None
+              ))),
+              args: FunctionCallArguments::ExpressionList(vec![Expression::PrimaryExpression(
+                PrimaryExpression::Identifier("size".to_string())
+              )])
+            })),
+            Some(Box::new(Expression::PrimaryExpression(
+              PrimaryExpression::NumberLiteral((Number::Decimal("0".to_string()), None))
+            )))
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_binary_operations_by_precedence() {
+    let input = b"a + b * c ** d ** e - f;";
+    let result = parse_expression(input);
+    let identifier = |name: &str| {
+      Box::new(Expression::PrimaryExpression(
+        PrimaryExpression::Identifier(name.to_string()),
+      ))
+    };
+    if result.is_err() {
+      result.expect("should parse binary operations");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          ";",
+          Expression::BinaryOperation(
+            Box::new(Expression::BinaryOperation(
+              identifier("a"),
+              BinaryOperator::Add,
+              Box::new(Expression::BinaryOperation(
+                identifier("b"),
+                BinaryOperator::Mul,
+                Box::new(Expression::BinaryOperation(
+                  identifier("c"),
+                  BinaryOperator::Exp,
+                  Box::new(Expression::BinaryOperation(
+                    identifier("d"),
+                    BinaryOperator::Exp,
+                    identifier("e")
+                  ))
+                ))
+              ))
+            )),
+            BinaryOperator::Sub,
+            identifier("f")
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_comparisons_and_logical_operators_by_precedence() {
+    let input = b"!a || b + c <= d && e != f;";
+    let result = parse_expression(input);
+    let identifier = |name: &str| {
+      Box::new(Expression::PrimaryExpression(
+        PrimaryExpression::Identifier(name.to_string()),
+      ))
+    };
+    if result.is_err() {
+      result.expect("should parse comparisons and logical operators");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          ";",
+          Expression::BinaryOperation(
+            Box::new(Expression::Not(identifier("a"))),
+            BinaryOperator::Or,
+            Box::new(Expression::BinaryOperation(
+              Box::new(Expression::BinaryOperation(
+                Box::new(Expression::BinaryOperation(
+                  identifier("b"),
+                  BinaryOperator::Add,
+                  identifier("c")
+                )),
+                BinaryOperator::LessThanOrEqual,
+                identifier("d")
+              )),
+              BinaryOperator::And,
+              Box::new(Expression::BinaryOperation(
+                identifier("e"),
+                BinaryOperator::NotEqual,
+                identifier("f")
+              ))
+            ))
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_compound_assignment_after_binary_operation() {
+    let input = b"a -= b / c;";
+    let result = parse_expression(input);
+    if result.is_err() {
+      result.expect("should parse compound assignment");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          ";",
+          Expression::Assignment(
+            Box::new(Expression::PrimaryExpression(
+              PrimaryExpression::Identifier("a".to_string())
+            )),
+            Assignment::SubAssign,
+            Box::new(Expression::BinaryOperation(
+              Box::new(Expression::PrimaryExpression(
+                PrimaryExpression::Identifier("b".to_string())
+              )),
+              BinaryOperator::Div,
+              Box::new(Expression::PrimaryExpression(
+                PrimaryExpression::Identifier("c".to_string())
+              ))
+            ))
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_tuple_assignment_with_empty_component() {
+    let input = b"(a, , b) = (b, 1, a);";
+    let result = parse_expression(input);
+    if result.is_err() {
+      result.expect("should parse tuple assignment");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      let identifier =
+        |name: &str| Expression::PrimaryExpression(PrimaryExpression::Identifier(name.to_string()));
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          ";",
+          Expression::Assignment(
+            Box::new(Expression::Tuple(vec![
+              Some(identifier("a")),
+              None,
+              Some(identifier("b"))
+            ])),
+            Assignment::Assign,
+            Box::new(Expression::Tuple(vec![
+              Some(identifier("b")),
+              Some(Expression::PrimaryExpression(
+                PrimaryExpression::NumberLiteral((Number::Decimal("1".to_string()), None))
+              )),
+              Some(identifier("a"))
+            ]))
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_parenthesized_expression_and_inline_array() {
+    let input = b"(a)[ [1, 2][b] ];";
+    let result = parse_expression(input);
+    if result.is_err() {
+      result.expect("should parse inline array");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      let identifier =
+        |name: &str| Expression::PrimaryExpression(PrimaryExpression::Identifier(name.to_string()));
+      let number = |value: &str| {
+        Expression::PrimaryExpression(PrimaryExpression::NumberLiteral((
+          Number::Decimal(value.to_string()),
+          None,
+        )))
+      };
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          ";",
+          Expression::IndexAccess(
+            Box::new(identifier("a")),
+            Some(Box::new(Expression::IndexAccess(
+              Box::new(Expression::InlineArray(vec![number("1"), number("2")])),
+              Some(Box::new(identifier("b")))
+            )))
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_new_contract_with_call_options() {
+    let input = b"new C{salt: s, value: 1}(a);";
+    let result = parse_expression(input);
+    if result.is_err() {
+      result.expect("should parse new expression with call options");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      let identifier =
+        |name: &str| Expression::PrimaryExpression(PrimaryExpression::Identifier(name.to_string()));
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          ";",
+          Expression::FunctionCall(FunctionCall {
+            expr: Box::new(Expression::CallOptions(
+              Box::new(Expression::New(TypeName::UserDefinedTypeName(vec![
+                "C".to_string()
+              ]))),
+              vec![
+                ("salt".to_string(), identifier("s")),
+                (
+                  "value".to_string(),
+                  Expression::PrimaryExpression(PrimaryExpression::NumberLiteral((
+                    Number::Decimal("1".to_string()),
+                    None
+                  )))
+                ),
+              ]
+            )),
+            args: FunctionCallArguments::ExpressionList(vec![identifier("a")])
+          })
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_member_call_with_options_and_named_arguments() {
+    let input = b"c.f{value: v}({to: a});";
+    let result = parse_expression(input);
+    if result.is_err() {
+      result.expect("should parse member call with options");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      let identifier =
+        |name: &str| Expression::PrimaryExpression(PrimaryExpression::Identifier(name.to_string()));
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          ";",
+          Expression::MemberAccess(
+            Box::new(identifier("c")),
+            Box::new(Expression::FunctionCall(FunctionCall {
+              expr: Box::new(Expression::CallOptions(
+                Box::new(identifier("f")),
+                vec![("value".to_string(), identifier("v"))]
+              )),
+              args: FunctionCallArguments::NameValueList(vec![("to".to_string(), identifier("a"))])
+            }))
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_abi_decode_with_tuple_of_types() {
+    let input = b"abi.decode(data, (uint256, address));";
+    let result = parse_expression(input);
+    if result.is_err() {
+      result.expect("should parse abi.decode");
+    } else {
+      let (remaining, expr) = result.ok().unwrap();
+      let type_name = |name: ElementaryTypeName| {
+        Some(Expression::PrimaryExpression(
+          PrimaryExpression::ElementaryTypeNameExpression(name),
+        ))
+      };
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), expr),
+        (
+          ";",
+          Expression::MemberAccess(
+            Box::new(Expression::PrimaryExpression(
+              PrimaryExpression::Identifier("abi".to_string())
+            )),
+            Box::new(Expression::FunctionCall(FunctionCall {
+              expr: Box::new(Expression::PrimaryExpression(
+                PrimaryExpression::Identifier("decode".to_string())
+              )),
+              args: FunctionCallArguments::ExpressionList(vec![
+                Expression::PrimaryExpression(PrimaryExpression::Identifier("data".to_string())),
+                Expression::Tuple(vec![
+                  type_name(ElementaryTypeName::UInt(UInt::Uint256)),
+                  type_name(ElementaryTypeName::Address)
+                ])
+              ])
+            }))
+          )
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn orders_named_arguments_by_parameter() {
+    let identifier =
+      |name: &str| Expression::PrimaryExpression(PrimaryExpression::Identifier(name.to_string()));
+    let call = |args: Vec<(&str, &str)>| FunctionCall {
+      expr: Box::new(identifier("f")),
+      args: FunctionCallArguments::NameValueList(
+        args
+          .into_iter()
+          .map(|(name, value)| (name.to_string(), identifier(value)))
+          .collect(),
+      ),
+    };
+    let names = [Some("to"), Some("amount")];
+    assert_eq!(
+      call(vec![("amount", "x"), ("to", "y")]).ordered_arguments(&names),
+      Ok(vec![&identifier("y"), &identifier("x")])
+    );
+    assert_eq!(
+      call(vec![("to", "x"), ("to", "y")]).ordered_arguments(&names),
+      Err("Duplicate named argument \"to\".".to_string())
+    );
+    assert_eq!(
+      call(vec![("to", "x"), ("value", "y")]).ordered_arguments(&names),
+      Err("Named argument \"value\" does not match function declaration.".to_string())
+    );
+    assert_eq!(
+      call(vec![("to", "x"), ("to", "y"), ("amount", "z")]).ordered_arguments(&names),
+      Err("Duplicate named argument \"to\".".to_string())
+    );
+    assert_eq!(
+      call(vec![("value", "x")]).ordered_arguments(&names),
+      Err("Named argument \"value\" does not match function declaration.".to_string())
+    );
+    assert!(call(vec![("to", "x")]).ordered_arguments(&names).is_err());
+  }
 }
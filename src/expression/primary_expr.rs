@@ -1,18 +1,21 @@
-use crate::atom::parse_identifier;
+use crate::atom::{parse_identifier, word_end};
+use crate::codegen::Context;
 use crate::elementary_type_name::{parse as parse_elementary_type_name, ElementaryTypeName};
-use crate::literal::{parse as parse_literal, Boolean, Literal, NumberLiteral};
+use crate::expression::bytes;
+use crate::literal::{number_value, parse as parse_literal, Boolean, Literal, NumberLiteral};
 use nom::{
     branch::alt,
-    combinator::{complete, map, map_res},
+    combinator::{complete, map},
+    sequence::terminated,
     IResult,
 };
-use std::str::from_utf8;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum PrimaryExpression {
     BooleanLiteral(Boolean),
     NumberLiteral(NumberLiteral),
-    // TODO: HexLiteral
+    StringLiteral(Vec<u8>),
+    HexLiteral(Vec<u8>),
     // TODO Tuple Expression
     Identifier(String),
     ElementaryTypeNameExpression(ElementaryTypeNameExpression),
@@ -20,14 +23,44 @@ pub enum PrimaryExpression {
 
 pub type ElementaryTypeNameExpression = ElementaryTypeName;
 
+impl PrimaryExpression {
+    pub fn op_codes(&self, ctx: &mut Context) -> Result<(), String> {
+        match self {
+            PrimaryExpression::BooleanLiteral(Boolean::True) => ctx.asm.push(&[1]),
+            PrimaryExpression::BooleanLiteral(Boolean::False) => ctx.asm.push(&[0]),
+            PrimaryExpression::NumberLiteral(literal) => {
+                ctx.push_constant(&number_value(literal)?)?
+            }
+            PrimaryExpression::StringLiteral(value) | PrimaryExpression::HexLiteral(value) => {
+                bytes::literal_op_codes(value, ctx)
+            }
+            PrimaryExpression::Identifier(identifier) => {
+                if let Some(local) = ctx.local(identifier) {
+                    ctx.load_local(local.position)?;
+                } else if let Some((position, decl)) = ctx.state_variable(identifier) {
+                    ctx.push_storage_position(position, &decl.type_name)
+                        .load(ctx)?;
+                } else {
+                    return Err(format!("Undeclared identifier: {}", identifier));
+                }
+            }
+            PrimaryExpression::ElementaryTypeNameExpression(type_name) => {
+                return Err(format!("{} is a type, not a value", type_name.abi_name()))
+            }
+        }
+        Ok(())
+    }
+}
+
 pub fn parse(i: &[u8]) -> IResult<&[u8], PrimaryExpression> {
     alt((
-        map_res(parse_literal, |l| match l {
-            Literal::Boolean(b) => Ok(PrimaryExpression::BooleanLiteral(b)),
-            Literal::Number(n) => Ok(PrimaryExpression::NumberLiteral(n)),
-            _ => Err("not a primary expression"),
+        map(terminated(parse_literal, word_end), |l| match l {
+            Literal::Boolean(b) => PrimaryExpression::BooleanLiteral(b),
+            Literal::Number(n) => PrimaryExpression::NumberLiteral(n),
+            Literal::String(s) => PrimaryExpression::StringLiteral(s),
+            Literal::Hex(h) => PrimaryExpression::HexLiteral(h),
         }),
-        complete(map(parse_elementary_type_name, |n| {
+        complete(map(terminated(parse_elementary_type_name, word_end), |n| {
             PrimaryExpression::ElementaryTypeNameExpression(n)
         })),
         complete(map(parse_identifier, |id| {
@@ -35,3 +68,44 @@ pub fn parse(i: &[u8]) -> IResult<&[u8], PrimaryExpression> {
         })),
     ))(i)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use std::str::from_utf8;
+
+    #[test]
+    fn identifiers_may_start_with_a_keyword() {
+        let input = b"trueValue addressBook";
+        let result = parse(input);
+        if result.is_err() {
+            result.expect("should parse identifier");
+        } else {
+            let (remaining, expr) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), expr),
+                (
+                    " addressBook",
+                    PrimaryExpression::Identifier("trueValue".to_string())
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn parses_string_literal() {
+        let input = b"\"Token\")";
+        let result = parse(input);
+        if result.is_err() {
+            result.expect("should parse string literal");
+        } else {
+            let (remaining, expr) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), expr),
+                (")", PrimaryExpression::StringLiteral(b"Token".to_vec()))
+            )
+        }
+    }
+}
@@ -0,0 +1,40 @@
+/// Encodes bytes, or op codes that each fit into a byte, as lowercase hex without a prefix.
+pub fn to_hex(codes: &[u32]) -> String {
+  codes.iter().map(|code| format!("{:02x}", code)).collect()
+}
+
+/// Parses hex encoded bytes, with or without a `0x` prefix.
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+  let hex = hex.trim();
+  let hex = hex.strip_prefix("0x").unwrap_or(hex);
+  if !hex.len().is_multiple_of(2) {
+    return Err(format!("Odd number of hex digits: {}", hex));
+  }
+  (0..hex.len())
+    .step_by(2)
+    .map(|i| {
+      hex
+        .get(i..i + 2)
+        .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        .ok_or_else(|| format!("Invalid hex: {}", hex))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn round_trips_hex() {
+    assert_eq!(to_hex(&[0x00, 0x0f, 0xab]), "000fab");
+    assert_eq!(from_hex(" 0x000fAB "), Ok(vec![0x00, 0x0f, 0xab]));
+    assert_eq!(
+      from_hex("abc"),
+      Err("Odd number of hex digits: abc".to_string())
+    );
+    assert_eq!(from_hex("0xzz"), Err("Invalid hex: zz".to_string()));
+  }
+}
@@ -0,0 +1,236 @@
+use crate::hex::to_hex;
+use nom::{
+  branch::alt,
+  bytes::complete::{is_not, tag, take_while_m_n},
+  character::{
+    complete::{char, digit1, multispace0, one_of},
+    is_hex_digit,
+  },
+  combinator::{map, map_opt, opt, recognize, value},
+  multi::{fold_many0, separated_list},
+  sequence::{delimited, preceded, separated_pair, terminated, tuple},
+  IResult,
+};
+use std::{collections::BTreeMap, str::from_utf8};
+
+/// A JSON value. Objects keep their members in the order they were written in.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  /// The number as written, which may not fit into any primitive type.
+  Number(String),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+impl Json {
+  /// The member of an object with the given key.
+  pub fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(value) => Some(value),
+      _ => None,
+    }
+  }
+}
+
+/// Encodes a string as a JSON string literal.
+pub fn string(value: &str) -> String {
+  let mut json = String::from("\"");
+  for c in value.chars() {
+    match c {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      '\n' => json.push_str("\\n"),
+      c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+      c => json.push(c),
+    }
+  }
+  json.push('"');
+  json
+}
+
+/// A JSON object that maps each signature to its hex encoded hash, sorted by signature.
+pub fn hashes(hashes: Vec<(String, Vec<u8>)>) -> String {
+  let hashes: BTreeMap<String, Vec<u8>> = hashes.into_iter().collect();
+  let entries: Vec<String> = hashes
+    .iter()
+    .map(|(signature, hash)| {
+      let hash: Vec<u32> = hash.iter().map(|b| *b as u32).collect();
+      format!("{}:\"{}\"", string(signature), to_hex(&hash))
+    })
+    .collect();
+  format!("{{{}}}", entries.join(","))
+}
+
+fn parse_hex_escape(i: &[u8]) -> IResult<&[u8], u32> {
+  map(
+    preceded(tag("\\u"), take_while_m_n(4, 4, is_hex_digit)),
+    |hex: &[u8]| u32::from_str_radix(from_utf8(hex).unwrap(), 16).unwrap(),
+  )(i)
+}
+
+/// `\uXXXX`, where characters outside of the basic multilingual plane are written as a
+/// surrogate pair.
+fn parse_unicode_escape(i: &[u8]) -> IResult<&[u8], char> {
+  alt((
+    map_opt(
+      tuple((parse_hex_escape, parse_hex_escape)),
+      |(high, low)| match (high, low) {
+        (0xd800..=0xdbff, 0xdc00..=0xdfff) => {
+          std::char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+        }
+        _ => None,
+      },
+    ),
+    map_opt(parse_hex_escape, std::char::from_u32),
+  ))(i)
+}
+
+fn parse_escape_sequence(i: &[u8]) -> IResult<&[u8], char> {
+  alt((
+    preceded(
+      char('\\'),
+      map(one_of("\"\\/bfnrt"), |c| match c {
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        c => c,
+      }),
+    ),
+    parse_unicode_escape,
+  ))(i)
+}
+
+fn parse_string(i: &[u8]) -> IResult<&[u8], String> {
+  delimited(
+    char('"'),
+    fold_many0(
+      alt((
+        map(is_not("\"\\"), |s: &[u8]| {
+          String::from_utf8_lossy(s).into_owned()
+        }),
+        map(parse_escape_sequence, |c| c.to_string()),
+      )),
+      String::new(),
+      |mut string, part| {
+        string.push_str(&part);
+        string
+      },
+    ),
+    char('"'),
+  )(i)
+}
+
+fn parse_number(i: &[u8]) -> IResult<&[u8], String> {
+  map(
+    recognize(tuple((
+      opt(char('-')),
+      digit1,
+      opt(preceded(char('.'), digit1)),
+      opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+    ))),
+    |number| from_utf8(number).unwrap().to_string(),
+  )(i)
+}
+
+fn parse_array(i: &[u8]) -> IResult<&[u8], Vec<Json>> {
+  delimited(
+    char('['),
+    separated_list(preceded(multispace0, char(',')), parse_value),
+    preceded(multispace0, char(']')),
+  )(i)
+}
+
+fn parse_object(i: &[u8]) -> IResult<&[u8], Vec<(String, Json)>> {
+  delimited(
+    char('{'),
+    separated_list(
+      preceded(multispace0, char(',')),
+      separated_pair(
+        preceded(multispace0, parse_string),
+        preceded(multispace0, char(':')),
+        parse_value,
+      ),
+    ),
+    preceded(multispace0, char('}')),
+  )(i)
+}
+
+fn parse_value(i: &[u8]) -> IResult<&[u8], Json> {
+  preceded(
+    multispace0,
+    alt((
+      value(Json::Null, tag("null")),
+      value(Json::Bool(true), tag("true")),
+      value(Json::Bool(false), tag("false")),
+      map(parse_number, Json::Number),
+      map(parse_string, Json::String),
+      map(parse_array, Json::Array),
+      map(parse_object, Json::Object),
+    )),
+  )(i)
+}
+
+/// Parses a JSON document, which may be surrounded by whitespace.
+pub fn parse(i: &[u8]) -> IResult<&[u8], Json> {
+  terminated(parse_value, multispace0)(i)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn parses_nested_values() {
+    let input = br#" { "a": [1, -2.5e3, true, null], "b" : { }, "c": [] } "#;
+    let result = parse(input);
+    if result.is_err() {
+      result.expect("should parse nested values");
+    } else {
+      let (remaining, json) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), json),
+        (
+          "",
+          Json::Object(vec![
+            (
+              "a".to_string(),
+              Json::Array(vec![
+                Json::Number("1".to_string()),
+                Json::Number("-2.5e3".to_string()),
+                Json::Bool(true),
+                Json::Null,
+              ])
+            ),
+            ("b".to_string(), Json::Object(vec![])),
+            ("c".to_string(), Json::Array(vec![])),
+          ])
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn parses_escape_sequences() {
+    let input = br#""a\"b\\c\/\n\u00e9\ud83d\ude00""#;
+    let (remaining, json) = parse(input).ok().unwrap();
+    assert_eq!(
+      (from_utf8(remaining).unwrap(), json),
+      ("", Json::String("a\"b\\c/\n\u{e9}\u{1f600}".to_string()))
+    );
+    assert_eq!(string("a\"b\\c\n\u{1}"), r#""a\"b\\c\n\u0001""#);
+  }
+}
@@ -1,16 +1,30 @@
-use crate::root::parse;
+use crate::{
+  codegen::{disassembly, source_map, Assembly, EvmVersion},
+  definition::ContractType,
+  evm::{Word, World},
+  hex::{from_hex, to_hex},
+  root::parse,
+};
 use console_error_panic_hook;
+use std::{cell::RefCell, collections::BTreeMap};
 use wasm_bindgen::prelude::*;
 
+mod abi;
 mod atom;
+mod codegen;
 mod definition;
 mod directive;
 mod elementary_type_name;
+pub mod evm;
 mod expression;
+mod hex;
+mod json;
 mod literal;
 #[allow(dead_code)]
 mod op_codes;
 mod root;
+mod span;
+mod standard_json;
 #[allow(dead_code)]
 mod state_mutability;
 mod statement;
@@ -19,45 +33,492 @@ mod storage_location;
 mod token;
 #[allow(dead_code)]
 mod visibility;
+mod yul;
 
 #[wasm_bindgen]
 pub fn compile(assem_code: &str) -> String {
+  compile_for_evm_version(assem_code, EvmVersion::default().name())
+}
+
+/// Like `compile`, but targets the given hard fork, e.g. `"paris"` for chains without `PUSH0`.
+#[wasm_bindgen]
+pub fn compile_for_evm_version(assem_code: &str, evm_version: &str) -> String {
   console_error_panic_hook::set_once();
+  let evm_version = EvmVersion::from_name(evm_version)
+    .unwrap_or_else(|message| panic!("compile failed: {}", message));
   let compile_result = parse(assem_code.as_bytes());
   if compile_result.is_err() {
     // let err = compile_result.unwrap_err();
     // TODO: Figure out how to get anything from this error
     panic!("compile failed");
   } else {
-    let (_, root) = compile_result.ok().unwrap();
-    println!("{:#?}", root);
+    let (remaining, root) = compile_result.ok().unwrap();
+    // Everything after the last contract that parsed would otherwise be dropped silently
+    if let Some(position) = remaining.iter().position(|c| !c.is_ascii_whitespace()) {
+      panic!(
+        "compile failed: unexpected input at offset {}",
+        assem_code.len() - remaining.len() + position
+      );
+    }
+    match root.op_codes(evm_version) {
+      Ok(codes) => to_hex(&codes),
+      Err(message) => panic!("compile failed: {}", message),
+    }
+  }
+}
+
+/// The accounts that `deploy` and `call` run against, and the output types of the functions of
+/// the contracts that were deployed with their ABI, by address and selector.
+struct Chain {
+  world: World,
+  output_types: BTreeMap<Word, BTreeMap<Vec<u8>, Vec<String>>>,
+}
+
+impl Chain {
+  fn new() -> Chain {
+    Chain {
+      world: World::new(),
+      output_types: BTreeMap::new(),
+    }
+  }
+}
+
+thread_local! {
+  /// The chain of `deploy` and `call`, which lives as long as the module.
+  static CHAIN: RefCell<Chain> = RefCell::new(Chain::new());
+}
+
+/// The account that sends the transactions of `deploy` and `call`.
+const SENDER: Word = Word::ONE;
+
+/// Deploys creation code, followed by the ABI encoded constructor arguments, to the in-memory
+/// chain. Returns the outcome as JSON, including the address of the new contract. Given the
+/// ABI of the contract as JSON, `call` decodes the return values of its functions.
+#[wasm_bindgen]
+pub fn deploy(bytecode: &str, args: &str, abi: Option<String>) -> String {
+  console_error_panic_hook::set_once();
+  let mut code = from_hex(bytecode).unwrap_or_else(|message| panic!("deploy failed: {}", message));
+  code.extend(from_hex(args).unwrap_or_else(|message| panic!("deploy failed: {}", message)));
+  let output_types = abi.map(|abi| match json::parse(abi.as_bytes()) {
+    Ok((b"", abi)) => abi::output_types(&abi),
+    _ => panic!("deploy failed: Invalid ABI: {}", abi),
+  });
+  CHAIN.with(|chain| {
+    let mut chain = chain.borrow_mut();
+    let outcome = chain.world.deploy(SENDER, code);
+    if let (Some(address), Some(output_types)) = (outcome.created, output_types) {
+      chain.output_types.insert(address, output_types);
+    }
+    outcome.to_json(None)
+  })
+}
+
+/// Calls a contract on the in-memory chain. Returns the outcome as JSON, with the return value,
+/// logs, gas used and revert reason.
+#[wasm_bindgen]
+pub fn call(address: &str, calldata: &str) -> String {
+  console_error_panic_hook::set_once();
+  let address = from_hex(address)
+    .ok()
+    .filter(|address| address.len() == 20)
+    .map(|address| Word::from_bytes_be(&address))
+    .unwrap_or_else(|| panic!("call failed: Invalid address: {}", address));
+  let calldata = from_hex(calldata).unwrap_or_else(|message| panic!("call failed: {}", message));
+  CHAIN.with(|chain| {
+    let mut chain = chain.borrow_mut();
+    let outcome = chain.world.call(SENDER, address, calldata.clone());
+    let output_types = chain
+      .output_types
+      .get(&address)
+      .and_then(|functions| functions.get(calldata.get(..4)?));
+    outcome.to_json(output_types.map(Vec::as_slice))
+  })
+}
+
+/// Forgets all accounts deployed with `deploy`.
+#[wasm_bindgen]
+pub fn reset_chain() {
+  CHAIN.with(|chain| *chain.borrow_mut() = Chain::new());
+}
+
+/// Compiles a standalone Yul object. Returns the creation code of the object and the code of its
+/// first child object, which is the runtime code by convention.
+#[wasm_bindgen]
+pub fn compile_yul(source: &str) -> String {
+  console_error_panic_hook::set_once();
+  let object = match yul::parse_object(source.as_bytes()) {
+    Ok((remaining, object)) if String::from_utf8_lossy(remaining).trim().is_empty() => object,
+    _ => panic!("compile failed"),
+  };
+  let evm_version = EvmVersion::default();
+  let assemble = |object: &yul::YulObject| {
+    object
+      .assembly()
+      .map(|asm| to_hex(&asm.assemble(evm_version)))
+      .unwrap_or_else(|message| panic!("compile failed: {}", message))
+  };
+  let deployed_bytecode = object.objects.first().map(assemble).unwrap_or_default();
+  format!(
+    r#"{{"bytecode":"{}","deployedBytecode":"{}"}}"#,
+    assemble(&object),
+    deployed_bytecode
+  )
+}
+
+/// Turns hex encoded bytecode back into instructions, like `PUSH1 0x80 PUSH1 0x40 MSTORE`.
+#[wasm_bindgen]
+pub fn disassemble(bytecode: &str) -> String {
+  console_error_panic_hook::set_once();
+  let code = from_hex(bytecode).unwrap_or_else(|message| panic!("disassemble failed: {}", message));
+  disassembly::disassemble(&code)
+}
 
-    let mut bytecode = String::new();
-    for code in root.clone().op_codes() {
-      let hex = format!("{:02x}", code);
-      bytecode.push_str(&hex);
+/// Lists the creation assembly of every contract in the source, with its labels, comments and
+/// sub assemblies, like `solc --asm`.
+#[wasm_bindgen]
+pub fn assembly(source: &str) -> String {
+  console_error_panic_hook::set_once();
+  let root = match parse(source.as_bytes()) {
+    Ok((_, root)) => root,
+    Err(_) => panic!("compile failed"),
+  };
+  let mut listings = vec![];
+  for contract in &root.contracts {
+    if contract.contract_type != ContractType::Contract {
+      continue;
     }
-    for code in root.clone().runtime_op_codes() {
-      let hex = format!("{:02x}", code);
-      bytecode.push_str(&hex);
+    let asm = contract
+      .creation_assembly(&root.contracts, &[])
+      .unwrap_or_else(|message| panic!("compile failed: {}", message));
+    listings.push(format!(
+      "======= {} =======\nEVM assembly:\n{}\n",
+      contract.identifier,
+      disassembly::listing(&asm)
+    ));
+  }
+  listings.join("\n")
+}
+
+/// Returns the compressed source maps of the creation and runtime code of every contract in the
+/// source, keyed by contract name, in the `s:l:f:j:m` format of solc.
+#[wasm_bindgen]
+pub fn source_maps(source: &str) -> String {
+  console_error_panic_hook::set_once();
+  let root = match parse(source.as_bytes()) {
+    Ok((_, root)) => root,
+    Err(_) => panic!("compile failed"),
+  };
+  let map = |asm: Result<Assembly, String>| {
+    asm
+      .map(|asm| source_map::source_map(&asm, 0, source.len()))
+      .unwrap_or_else(|message| panic!("compile failed: {}", message))
+  };
+  let mut entries = vec![];
+  for contract in &root.contracts {
+    if contract.contract_type != ContractType::Contract {
+      continue;
     }
-    return bytecode;
+    entries.push(format!(
+      r#""{}":{{"sourceMap":"{}","deployedSourceMap":"{}"}}"#,
+      contract.identifier,
+      map(contract.creation_assembly(&root.contracts, &[])),
+      map(contract.runtime_assembly(&root.contracts, &[]))
+    ));
   }
+  format!("{{{}}}", entries.join(","))
+}
+
+/// Compiles a Standard JSON input, like `solc --standard-json` does, and returns the Standard
+/// JSON output. Problems with the input or the sources are reported in its `errors`.
+#[wasm_bindgen]
+pub fn compile_standard(input: &str) -> String {
+  console_error_panic_hook::set_once();
+  standard_json::compile(input)
+}
+
+/// Returns the ABI of every contract in the source, keyed by contract name.
+#[wasm_bindgen]
+pub fn abi(source: &str) -> String {
+  console_error_panic_hook::set_once();
+  let compile_result = parse(source.as_bytes());
+  if compile_result.is_err() {
+    panic!("compile failed");
+  }
+  let (_, root) = compile_result.ok().unwrap();
+  let entries: Vec<String> = root
+    .contracts
+    .iter()
+    .map(|contract| format!(r#""{}":{}"#, contract.identifier, abi::to_json(contract)))
+    .collect();
+  format!("{{{}}}", entries.join(","))
+}
+
+/// Returns the selector of every external function, the topic of every event and the selector
+/// of every custom error, keyed by contract name and then by signature.
+#[wasm_bindgen]
+pub fn method_identifiers(source: &str) -> String {
+  console_error_panic_hook::set_once();
+  let compile_result = parse(source.as_bytes());
+  if compile_result.is_err() {
+    panic!("compile failed");
+  }
+  let (_, root) = compile_result.ok().unwrap();
+  let entries: Vec<String> = root
+    .contracts
+    .iter()
+    .map(|contract| {
+      format!(
+        r#""{}":{{"methods":{},"events":{},"errors":{}}}"#,
+        contract.identifier,
+        json::hashes(contract.method_identifiers()),
+        json::hashes(contract.event_topics()),
+        json::hashes(contract.error_selectors())
+      )
+    })
+    .collect();
+  format!("{{{}}}", entries.join(","))
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::op_codes::OpCode;
   use std::fs;
 
+  fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+  }
+
   use pretty_assertions::assert_eq;
 
   #[test]
+  #[ignore = "solc 0.5.6 lays out the code differently and appends metadata, so the bytes diverge"]
   fn generates_bytecode_for_testfile_1() {
+    // Pinned to Petersburg, the default of solc 0.5.6, which has no PUSH0
     let contract = fs::read_to_string("testfile1.sol").expect("failed to read testfile1.sol");
     let expected_bytecode =
       fs::read_to_string("testfile1.bin").expect("failed to read testfile1.sol");
-    let byte_code = compile(&contract);
+    let byte_code = compile_for_evm_version(&contract, "petersburg");
     assert_eq!(byte_code, expected_bytecode)
   }
+
+  #[test]
+  #[should_panic(expected = "unexpected input at offset 61")]
+  fn rejects_input_that_does_not_parse() {
+    compile(
+      "pragma solidity ^0.8.0;
+contract A {
+  uint256 a = 1 gwei;
+}
+contract B {
+  uint256 b = 1 szabo;
+}
+",
+    );
+  }
+
+  #[test]
+  #[should_panic(expected = "unexpected input at offset 24")]
+  fn rejects_years_like_solc() {
+    compile(
+      "pragma solidity ^0.8.0;
+contract A {
+  uint256 a = 1 years;
+}
+",
+    );
+  }
+
+  /// The instructions of hex encoded bytecode, without push data.
+  fn instructions(bytecode: &str) -> Vec<u8> {
+    let bytes = from_hex(bytecode).unwrap();
+    let end = bytes.len();
+    let mut instructions = vec![];
+    let mut i = 0;
+    while i < end {
+      instructions.push(bytes[i]);
+      if (0x60..=0x7f).contains(&bytes[i]) {
+        i += (bytes[i] - 0x5f) as usize;
+      }
+      i += 1;
+    }
+    instructions
+  }
+
+  #[test]
+  fn generates_bytecode_for_testfile_2() {
+    // The code is laid out differently from solc, so the deployed proxy is compared by what it
+    // does: the calls it makes and the results and storage they leave behind.
+    let contract = fs::read_to_string("testfile2.sol").expect("failed to read testfile2.sol");
+    let expected_bytecode =
+      fs::read_to_string("testfile2.bin").expect("failed to read testfile2.bin");
+    let hex = |code: &str| from_hex(code).unwrap();
+    let address = |code: &str| Word::from_bytes_be(&hex(code));
+    let token = address("f25186B5081Ff5cE73482AD761DB0eB0d25abfBF");
+    let implementation = address("FB88dE099e13c3ED21F80a7a1E49f8CAEcF10df6");
+    let run = |creation_code: Vec<u8>| {
+      let mut world = World::new();
+      // Stores the arguments of `transfer(address,uint256)` in slots 0 and 1
+      world.account_mut(token).code = hex("6004355f55602435600155");
+      // Stores the first word of the calldata in slot 5 and returns the calldata followed by
+      // the address, or reverts if the word is zero
+      world.account_mut(implementation).code =
+        hex("365f5f373036525f356005555f3515601857602036015ff35b60205ffd");
+      let deployment = world.deploy(Word::ONE, creation_code);
+      let proxy = deployment.created.unwrap();
+      let mut results = vec![format!("{:?}", world.account(token).unwrap().storage)];
+      for data in &[
+        "",
+        "0000000000000000000000000000000000000000000000000000000000000007ab",
+        "00",
+      ] {
+        let outcome = world.call(Word::ONE, proxy, hex(data));
+        results.push(format!("{} {:?}", outcome.success, outcome.output));
+        results.push(format!("{:?}", world.account(proxy).unwrap().storage));
+      }
+      results
+    };
+    assert_eq!(run(hex(&compile(&contract))), run(hex(&expected_bytecode)))
+  }
+
+  #[test]
+  fn deploys_and_calls_contracts_on_the_chain() {
+    let source = "pragma solidity ^0.8.0;
+contract Store {
+  uint256 value;
+  event Stored(uint256 value);
+  constructor(uint256 initial) { value = initial; }
+  function store(uint256 v) public { value = v; emit Stored(v); }
+  function get() public view returns (uint256, uint256) { return (value, 1); }
+  function describe() public view returns (string memory, bool, address) {
+    return (\"stored\", true, msg.sender);
+  }
+  function fail() public { revert(\"no \\\"way\\\"\"); }
+}";
+    let bytecode = compile(source);
+    let (_, root) = parse(source.as_bytes()).ok().unwrap();
+    let contract_abi = abi::to_json(&root.contracts[0]);
+    let word = |value: u8| format!("{:064x}", value);
+    let deployment = deploy(&bytecode, &word(7), Some(contract_abi));
+    let address = &deployment[deployment.find(r#""address":""#).unwrap() + 11..][..42];
+    assert!(deployment.starts_with(r#"{"success":true,"address":"0x"#));
+
+    let stored = call(
+      address,
+      &format!("{}{}", hex(&abi::selector("store(uint256)")), word(9)),
+    );
+    assert!(stored.contains(&format!(
+      r#""logs":[{{"address":"{}","topics":["0x{}"],"data":"0x{}"}}]"#,
+      address,
+      hex(&abi::keccak256(b"Stored(uint256)")),
+      word(9)
+    )));
+    let get = call(address, &hex(&abi::selector("get()")));
+    assert!(get.contains(r#""returnValue":["9","1"]"#));
+    let describe = call(address, &hex(&abi::selector("describe()")));
+    assert!(describe.contains(&format!(r#""returnValue":["stored",true,"0x{:040x}"]"#, 1)));
+    // Without the ABI the return data is only split into words
+    let bare = deploy(&bytecode, &word(7), None);
+    let bare = &bare[bare.find(r#""address":""#).unwrap() + 11..][..42];
+    let get = call(bare, &hex(&abi::selector("get()")));
+    assert!(get.contains(&format!(
+      r#""returnValue":["0x{}","0x{}"]"#,
+      word(7),
+      word(1)
+    )));
+    let fail = call(address, &hex(&abi::selector("fail()")));
+    assert!(fail.starts_with(r#"{"success":false,"#));
+    assert!(fail.ends_with(r#""revertReason":"no \"way\""}"#));
+  }
+
+  #[test]
+  fn maps_runtime_code_to_source() {
+    let source = "pragma solidity ^0.8.0;
+contract Counter {
+  uint256 count;
+  function increment() public { count = add(count, 1); }
+  function add(uint256 a, uint256 b) internal pure returns (uint256) { return a + b; }
+}";
+    let (_, root) = parse(source.as_bytes()).ok().unwrap();
+    let runtime = root.contracts[0]
+      .runtime_assembly(&root.contracts, &[])
+      .unwrap()
+      .assemble(EvmVersion::default());
+    let maps = source_maps(source);
+    let map = &maps[maps.find(r#""deployedSourceMap":""#).unwrap() + 21..];
+    let map = &map[..map.find('"').unwrap()];
+
+    // Expands the compressed entries into the source text and jump type of each instruction
+    let mut fields = vec![String::new(); 5];
+    let entries: Vec<(&str, String)> = map
+      .split(';')
+      .map(|entry| {
+        for (i, field) in entry.split(':').enumerate() {
+          if !field.is_empty() {
+            fields[i] = field.to_string();
+          }
+        }
+        let start: usize = fields[0].parse().unwrap();
+        let length: usize = fields[1].parse().unwrap();
+        (&source[start..start + length], fields[3].clone())
+      })
+      .collect();
+    let ops = instructions(&to_hex(&runtime));
+    assert_eq!(entries.len(), ops.len());
+    assert!(entries[0].0.starts_with("contract Counter {"));
+
+    let sstore = ops
+      .iter()
+      .position(|op| *op == OpCode::SSTORE as u8)
+      .unwrap();
+    assert_eq!(entries[sstore].0, "count = add(count, 1);");
+    let jumps: Vec<&(&str, String)> = entries.iter().filter(|(_, jump)| jump != "-").collect();
+    assert_eq!(
+      jumps,
+      vec![
+        &(
+          "function increment() public { count = add(count, 1); }",
+          "i".to_string()
+        ),
+        &("count = add(count, 1);", "i".to_string()),
+        &(
+          "function increment() public { count = add(count, 1); }",
+          "o".to_string()
+        ),
+        &(
+          &source[source.find("function add").unwrap()..source.len() - 2],
+          "o".to_string()
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn lists_selectors_topics_and_error_selectors() {
+    let source = r#"pragma solidity ^0.8.0;
+contract Token {
+    uint256 public totalSupply;
+    event Transfer(address indexed from, address indexed to, uint256 value);
+    event Anonymous(uint256 value) anonymous;
+    error InsufficientBalance(uint256 available, uint256 required);
+    function transfer(address to, uint256 value) public returns (bool) {
+        emit Transfer(msg.sender, to, value);
+        return true;
+    }
+    function approve(address spender, uint256 value) public returns (bool) {
+        return transfer(spender, 0);
+    }
+}
+"#;
+    assert_eq!(
+      method_identifiers(source),
+      concat!(
+        r#"{"Token":{"methods":{"approve(address,uint256)":"095ea7b3","#,
+        r#""totalSupply()":"18160ddd","transfer(address,uint256)":"a9059cbb"},"#,
+        r#""events":{"Transfer(address,address,uint256)":"#,
+        r#""ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"},"#,
+        r#""errors":{"InsufficientBalance(uint256,uint256)":"cf479181"}}}"#
+      )
+    );
+  }
 }
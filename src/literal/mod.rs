@@ -1,22 +1,25 @@
 use crate::literal::boolean::parse as parse_bool;
 use crate::literal::number_unit::{parse as parse_number_unit, NumberUnit};
 use nom::{
-    alt as altm,
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{digit1, hex_digit1, multispace1},
-    combinator::{map, opt},
-    delimited as delimitedm, escaped_transform as escaped_transformm, is_not as is_notm,
-    map as mapm, named,
-    sequence::{preceded, tuple},
-    tag as tagm, IResult,
+    bytes::complete::{is_not, tag, take_while_m_n},
+    character::{
+        complete::{char, digit1, hex_digit1, line_ending, multispace1, one_of},
+        is_hex_digit,
+    },
+    combinator::{map, map_opt, map_res, opt, recognize, value},
+    multi::{fold_many0, many1, separated_list, separated_nonempty_list},
+    sequence::{delimited, preceded, tuple},
+    IResult,
 };
 use std::str::from_utf8;
 
 mod boolean;
 mod number_unit;
+mod rational;
 
 pub use boolean::Boolean;
+pub use rational::{BigUint, Rational};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Number {
@@ -24,60 +27,185 @@ pub enum Number {
     Decimal(String),
 }
 
+impl Number {
+    /// The exact value of the number, e.g. `1/2` for `0.5` and `1000` for `1_000` or `1e3`.
+    pub fn value(&self) -> Result<Rational, String> {
+        match self {
+            Number::Hex(hex) => Ok(Rational::integer(BigUint::from_digits(
+                &hex.trim_start_matches("0x").replace('_', ""),
+                16,
+            ))),
+            Number::Decimal(decimal) => {
+                let decimal = decimal.replace('_', "").to_lowercase();
+                let (mantissa, exponent) = match decimal.find('e') {
+                    Some(index) => (&decimal[..index], &decimal[index + 1..]),
+                    None => (&decimal[..], "0"),
+                };
+                let (integer, fraction) = match mantissa.find('.') {
+                    Some(index) => (&mantissa[..index], &mantissa[index + 1..]),
+                    None => (mantissa, ""),
+                };
+                let digits = format!("{}{}", integer, fraction);
+                let value = Rational::new(
+                    false,
+                    BigUint::from_digits(&digits, 10),
+                    BigUint::from_digits(&format!("1{}", "0".repeat(fraction.len())), 10),
+                )?;
+                let exponent = exponent
+                    .parse::<i64>()
+                    .map_err(|_| format!("Invalid exponent in {}", decimal))?;
+                let scale = Rational::integer(BigUint::from_u64(10)).pow(&Rational {
+                    negative: exponent < 0,
+                    ..Rational::integer(BigUint::from_u64(exponent.unsigned_abs()))
+                })?;
+                value.mul(&scale)
+            }
+        }
+    }
+}
+
 pub type NumberLiteral = (Number, Option<NumberUnit>);
 
+/// The value of a number literal with its unit multiplied in.
+pub fn number_value(literal: &NumberLiteral) -> Result<Rational, String> {
+    let (number, unit) = literal;
+    let value = number.value()?;
+    match unit {
+        Some(unit) => value.mul(&Rational::integer(BigUint::from_u64(unit.multiplier()))),
+        None => Ok(value),
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal {
     Number(NumberLiteral),
-    String(String),
+    String(Vec<u8>),
+    Hex(Vec<u8>),
     Boolean(Boolean),
 }
 
-// TODO: support more than integer
+/// Digits that may be separated by single underscores, like `1_000`.
+fn parse_digits(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    recognize(separated_nonempty_list(char('_'), digit1))(i)
+}
+
+/// Parses integers, fractions like `2.5` or `.5` and scientific notation like `1e18`.
 fn parse_decimal_number(i: &[u8]) -> IResult<&[u8], Number> {
-    map(digit1, |d| {
-        Number::Decimal(from_utf8(d).ok().unwrap().to_string())
-    })(i)
+    map(
+        recognize(tuple((
+            alt((
+                recognize(tuple((parse_digits, opt(tuple((char('.'), parse_digits)))))),
+                recognize(tuple((char('.'), parse_digits))),
+            )),
+            opt(tuple((one_of("eE"), opt(char('-')), parse_digits))),
+        ))),
+        |d| Number::Decimal(from_utf8(d).ok().unwrap().to_string()),
+    )(i)
 }
 
 fn parse_hex_number(i: &[u8]) -> IResult<&[u8], Number> {
-    map(preceded(tag("0x"), hex_digit1), |s| {
-        let mut num = from_utf8(s).ok().unwrap().to_string();
-        num.insert_str(0, "0x");
-        Number::Hex(num)
-    })(i)
+    map(
+        preceded(
+            tag("0x"),
+            recognize(separated_nonempty_list(char('_'), hex_digit1)),
+        ),
+        |s| {
+            let mut num = from_utf8(s).ok().unwrap().to_string();
+            num.insert_str(0, "0x");
+            Number::Hex(num)
+        },
+    )(i)
 }
 
-// TODO: Parse Hex Literal
-fn parse_hex_literal() {}
+/// Parses the escape sequences of string literals. A backslash before a line break continues
+/// the literal on the next line.
+fn parse_escape_sequence(i: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    preceded(
+        char('\\'),
+        alt((
+            map(one_of("\\'\"nrt"), |c| {
+                vec![match c {
+                    'n' => b'\n',
+                    'r' => b'\r',
+                    't' => b'\t',
+                    c => c as u8,
+                }]
+            }),
+            map_res(
+                preceded(char('x'), take_while_m_n(2, 2, is_hex_digit)),
+                |hex: &[u8]| u8::from_str_radix(from_utf8(hex).unwrap(), 16).map(|b| vec![b]),
+            ),
+            map_opt(
+                preceded(char('u'), take_while_m_n(4, 4, is_hex_digit)),
+                |hex: &[u8]| {
+                    u32::from_str_radix(from_utf8(hex).unwrap(), 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                        .map(|c| c.to_string().into_bytes())
+                },
+            ),
+            value(vec![], line_ending),
+        )),
+    )(i)
+}
 
+/// Parses the contents of a string literal delimited by `quote`, which is either `"` or `'`.
+fn parse_quoted(quote: char, i: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    let special = if quote == '"' { "\"\\\r\n" } else { "'\\\r\n" };
+    delimited(
+        char(quote),
+        fold_many0(
+            alt((
+                map(is_not(special), |s: &[u8]| s.to_vec()),
+                parse_escape_sequence,
+            )),
+            vec![],
+            |mut bytes, part| {
+                bytes.extend(part);
+                bytes
+            },
+        ),
+        char(quote),
+    )(i)
+}
+
+/// Parses `"..."`, `'...'` and `unicode"..."`, whose value is UTF-8 encoded.
 fn parse_string_literal(i: &[u8]) -> IResult<&[u8], Literal> {
-    fn to_s(i: Vec<u8>) -> String {
-        String::from_utf8_lossy(&i).into_owned()
-    }
+    map(
+        preceded(
+            opt(tag("unicode")),
+            alt((|i| parse_quoted('"', i), |i| parse_quoted('\'', i))),
+        ),
+        Literal::String,
+    )(i)
+}
 
-    named!(
-        string_content<String>,
-        mapm!(
-            escaped_transformm!(
-                is_notm!("\"\\"),
-                '\\',
-                altm!(
-                    tagm!("\\") => { |_| &b"\\"[..] } |
-                    tagm!("\"") => { |_| &b"\""[..] } |
-                    tagm!("n") => { |_| &b"\n"[..] } |
-                    tagm!("r") => { |_| &b"\r"[..] } |
-                    tagm!("t") => { |_| &b"\t"[..] }
-                )
-            ),
-            to_s
-        )
-    );
-    named!(
-        string<String>,
-        delimitedm!(tagm!("\""), string_content, tagm!("\""))
-    );
-    map(string, |s| Literal::String(s))(i)
+/// Hex digits in pairs, each pair being one byte. Bytes may be separated by single underscores.
+fn parse_hex_bytes(i: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    map(
+        separated_list(
+            char('_'),
+            many1(map_res(
+                take_while_m_n(2, 2, is_hex_digit),
+                |hex: &[u8]| u8::from_str_radix(from_utf8(hex).unwrap(), 16),
+            )),
+        ),
+        |groups| groups.concat(),
+    )(i)
+}
+
+/// Parses `hex"00ff"` or `hex'00ff'`.
+fn parse_hex_literal(i: &[u8]) -> IResult<&[u8], Literal> {
+    map(
+        preceded(
+            tag("hex"),
+            alt((
+                delimited(char('"'), parse_hex_bytes, char('"')),
+                delimited(char('\''), parse_hex_bytes, char('\'')),
+            )),
+        ),
+        Literal::Hex,
+    )(i)
 }
 
 fn parse_number(i: &[u8]) -> IResult<&[u8], Number> {
@@ -91,6 +219,7 @@ fn parse_number_literal(i: &[u8]) -> IResult<&[u8], NumberLiteral> {
 pub fn parse(i: &[u8]) -> IResult<&[u8], Literal> {
     alt((
         parse_string_literal,
+        parse_hex_literal,
         map(parse_bool, |b| Literal::Boolean(b)),
         map(parse_number_literal, |n| Literal::Number(n)),
     ))(i)
@@ -144,11 +273,76 @@ mod tests {
             let (remaining, b) = result.ok().unwrap();
             assert_eq!(
                 (from_utf8(remaining).unwrap(), b),
-                ("  \n", Literal::String("hello\" world".to_string()))
+                ("  \n", Literal::String(b"hello\" world".to_vec()))
+            )
+        }
+    }
+
+    #[test]
+    fn parses_empty_string_literal() {
+        let input = b"\"\");";
+        let result = parse_string_literal(input);
+        if result.is_err() {
+            result.expect("should parse empty string literal");
+        } else {
+            let (remaining, b) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), b),
+                (");", Literal::String(vec![]))
+            )
+        }
+    }
+
+    #[test]
+    fn parses_escape_sequences() {
+        let input = b"'it\\'s \\x41\\u00e9\\\n\\t\"';";
+        let result = parse_string_literal(input);
+        if result.is_err() {
+            result.expect("should parse escape sequences");
+        } else {
+            let (remaining, b) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), b),
+                (";", Literal::String(b"it's A\xc3\xa9\t\"".to_vec()))
+            )
+        }
+    }
+
+    #[test]
+    fn parses_unicode_string_literal() {
+        let input = "unicode\"Hello 😃\";".as_bytes();
+        let result = parse(input);
+        if result.is_err() {
+            result.expect("should parse unicode string literal");
+        } else {
+            let (remaining, b) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), b),
+                (";", Literal::String("Hello 😃".as_bytes().to_vec()))
+            )
+        }
+    }
+
+    #[test]
+    fn parses_hex_literal() {
+        let input = b"hex'00ff_1A';";
+        let result = parse(input);
+        if result.is_err() {
+            result.expect("should parse hex literal");
+        } else {
+            let (remaining, b) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), b),
+                (";", Literal::Hex(vec![0x00, 0xff, 0x1a]))
             )
         }
     }
 
+    #[test]
+    fn rejects_odd_number_of_hex_digits() {
+        assert!(parse_hex_literal(b"hex\"abc\";").is_err());
+    }
+
     #[test]
     fn hex_number_takes_precedence_over_decimal() {
         let input = b"0xFB88dE099e13c3ED21F80a7a1E49f8CAEcF10df6\n";
@@ -167,4 +361,87 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_decimal_number_with_underscores_and_exponent() {
+        let input = b"1_000.5e-3;";
+        let result = parse_decimal_number(input);
+        if result.is_err() {
+            result.expect("should parse decimal number");
+        } else {
+            let (remaining, b) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), b),
+                (";", Number::Decimal("1_000.5e-3".to_string()))
+            )
+        }
+    }
+
+    #[test]
+    fn evaluates_number_literals() {
+        let value = |input: &str, unit| {
+            number_value(&(Number::Decimal(input.to_string()), unit))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(value("1_000", None), "1000");
+        assert_eq!(value(".5", None), "1/2");
+        assert_eq!(value("2.5e3", None), "2500");
+        assert_eq!(value("1e-3", None), "1/1000");
+        assert_eq!(value("0.5", Some(NumberUnit::Ether)), "500000000000000000");
+        assert_eq!(value("2", Some(NumberUnit::Days)), "172800");
+        assert_eq!(
+            number_value(&(Number::Hex("0xff_ff".to_string()), None)),
+            Ok(Rational::integer(BigUint::from_u64(65535)))
+        );
+        // Huge exponents are too large instead of overflowing
+        for exponent in &["-2147483648", "9223372036854775807", "-9223372036854775808"] {
+            assert_eq!(
+                number_value(&(Number::Decimal(format!("1e{}", exponent)), None)),
+                Err("Constant is too large.".to_string())
+            );
+        }
+        assert_eq!(
+            number_value(&(Number::Decimal("1e9223372036854775808".to_string()), None)),
+            Err("Invalid exponent in 1e9223372036854775808".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_gwei_number_literal() {
+        let input = b"1 gwei;";
+        let result = parse(input);
+        if result.is_err() {
+            result.expect("should parse gwei literal");
+        } else {
+            let (remaining, literal) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), literal),
+                (
+                    ";",
+                    Literal::Number((Number::Decimal("1".to_string()), Some(NumberUnit::Gwei)))
+                )
+            )
+        }
+        assert_eq!(
+            number_value(&(Number::Decimal("2".to_string()), Some(NumberUnit::Gwei)))
+                .unwrap()
+                .to_string(),
+            "2000000000"
+        );
+    }
+
+    #[test]
+    fn leaves_units_removed_from_solidity_unparsed() {
+        for unit in &["szabo", "finney", "years"] {
+            let input = format!("1 {};", unit);
+            let (remaining, literal) = parse(input.as_bytes()).ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), literal),
+                (
+                    &format!(" {};", unit)[..],
+                    Literal::Number((Number::Decimal("1".to_string()), None))
+                )
+            )
+        }
+    }
 }
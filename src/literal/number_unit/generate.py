@@ -13,7 +13,8 @@ print("""use nom::{
 };
 """)
 with open('units.txt') as f:
-    lines = [line.rstrip() for line in f]
+    rows = [line.rstrip().split(" ") for line in f]
+    lines = [name for name, _ in rows]
     for line in lines:
         print(f"const {line.upper()}: &str = r#\"{line}\"#;")
     print("")
@@ -23,6 +24,16 @@ pub enum NumberUnit {""")
         print(f"\t{snake_to_upper_camel(line)},")
     print("}")
     print("")
+    print("impl NumberUnit {")
+    print("\t/// The factor a number literal with the unit is multiplied by.")
+    print("\tpub fn multiplier(&self) -> u64 {")
+    print("\t\tmatch self {")
+    for name, multiplier in rows:
+        print(f"\t\t\tNumberUnit::{snake_to_upper_camel(name)} => {multiplier},")
+    print("\t\t}")
+    print("\t}")
+    print("}")
+    print("")
     for line in lines:
         print(f"""fn parse_{line.lower()}(i: &[u8]) -> IResult<&[u8], NumberUnit> {{
     named!(semi, tag!(r#"{line}"#));
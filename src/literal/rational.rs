@@ -0,0 +1,452 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Constants with more bits than this are rejected, like solc does.
+const MAX_BITS: usize = 4096;
+
+/// An arbitrary precision unsigned integer, stored as little endian 32 bit limbs without
+/// trailing zero limbs.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BigUint(Vec<u32>);
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint(vec![])
+    }
+
+    pub fn from_u64(value: u64) -> BigUint {
+        BigUint(vec![value as u32, (value >> 32) as u32]).normalized()
+    }
+
+    /// Parses digits of the given radix, which have to be valid.
+    pub fn from_digits(digits: &str, radix: u32) -> BigUint {
+        digits.chars().fold(BigUint::zero(), |value, digit| {
+            value
+                .mul(&BigUint::from_u64(radix as u64))
+                .add(&BigUint::from_u64(digit.to_digit(radix).unwrap() as u64))
+        })
+    }
+
+    pub fn from_bytes_be(bytes: &[u8]) -> BigUint {
+        let limbs = bytes
+            .rchunks(4)
+            .map(|chunk| chunk.iter().fold(0, |limb, byte| limb << 8 | *byte as u32))
+            .collect();
+        BigUint(limbs).normalized()
+    }
+
+    fn normalized(mut self) -> BigUint {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+        self
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The number of significant bits.
+    pub fn bits(&self) -> usize {
+        match self.0.last() {
+            Some(last) => self.0.len() * 32 - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.0
+            .get(index / 32)
+            .map_or(false, |limb| limb >> (index % 32) & 1 == 1)
+    }
+
+    fn is_even(&self) -> bool {
+        !self.bit(0)
+    }
+
+    pub fn add(&self, other: &BigUint) -> BigUint {
+        let mut result = vec![];
+        let mut carry = 0u64;
+        for i in 0..self.0.len().max(other.0.len()) {
+            let sum =
+                *self.0.get(i).unwrap_or(&0) as u64 + *other.0.get(i).unwrap_or(&0) as u64 + carry;
+            result.push(sum as u32);
+            carry = sum >> 32;
+        }
+        result.push(carry as u32);
+        BigUint(result).normalized()
+    }
+
+    /// Subtracts a value that is not larger than `self`.
+    pub fn sub(&self, other: &BigUint) -> BigUint {
+        let mut result = vec![];
+        let mut borrow = 0i64;
+        for i in 0..self.0.len() {
+            let mut difference = self.0[i] as i64 - *other.0.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = 0;
+            if difference < 0 {
+                difference += 1 << 32;
+                borrow = 1;
+            }
+            result.push(difference as u32);
+        }
+        BigUint(result).normalized()
+    }
+
+    pub fn mul(&self, other: &BigUint) -> BigUint {
+        let mut result = vec![0u32; self.0.len() + other.0.len()];
+        for (i, a) in self.0.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.0.iter().enumerate() {
+                let product = *a as u64 * *b as u64 + result[i + j] as u64 + carry;
+                result[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            result[i + other.0.len()] = carry as u32;
+        }
+        BigUint(result).normalized()
+    }
+
+    fn shl1(&self) -> BigUint {
+        self.add(self)
+    }
+
+    fn shr1(&self) -> BigUint {
+        let mut result = self.0.clone();
+        for i in 0..result.len() {
+            let high = self.0.get(i + 1).map_or(0, |limb| limb & 1);
+            result[i] = (result[i] >> 1) | (high << 31);
+        }
+        BigUint(result).normalized()
+    }
+
+    /// Returns the quotient and the remainder, using binary long division.
+    pub fn div_rem(&self, divisor: &BigUint) -> (BigUint, BigUint) {
+        let mut quotient = vec![0u32; self.0.len()];
+        let mut remainder = BigUint::zero();
+        for index in (0..self.bits()).rev() {
+            remainder = remainder.shl1();
+            if self.bit(index) {
+                remainder = remainder.add(&BigUint::from_u64(1));
+            }
+            if remainder >= *divisor {
+                remainder = remainder.sub(divisor);
+                quotient[index / 32] |= 1 << (index % 32);
+            }
+        }
+        (BigUint(quotient).normalized(), remainder)
+    }
+
+    /// The greatest common divisor, using the binary algorithm.
+    fn gcd(&self, other: &BigUint) -> BigUint {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        if a.is_zero() {
+            return b;
+        }
+        let mut shift = 0;
+        while a.is_even() && b.is_even() && !b.is_zero() {
+            a = a.shr1();
+            b = b.shr1();
+            shift += 1;
+        }
+        while !b.is_zero() {
+            while a.is_even() {
+                a = a.shr1();
+            }
+            while b.is_even() && !b.is_zero() {
+                b = b.shr1();
+            }
+            if a > b {
+                std::mem::swap(&mut a, &mut b);
+            }
+            b = b.sub(&a);
+        }
+        for _ in 0..shift {
+            a = a.shl1();
+        }
+        a
+    }
+
+    /// The value as big endian bytes without leading zeros.
+    pub fn to_bytes_be(&self) -> Vec<u8> {
+        let bytes: Vec<u8> = self
+            .0
+            .iter()
+            .rev()
+            .flat_map(|limb| limb.to_be_bytes().to_vec())
+            .collect();
+        bytes.into_iter().skip_while(|b| *b == 0).collect()
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ten = BigUint::from_u64(10);
+        let mut digits = vec![];
+        let mut value = self.clone();
+        while !value.is_zero() {
+            let (quotient, remainder) = value.div_rem(&ten);
+            digits.push(std::char::from_digit(*remainder.0.first().unwrap_or(&0), 10).unwrap());
+            value = quotient;
+        }
+        if digits.is_empty() {
+            digits.push('0');
+        }
+        write!(f, "{}", digits.iter().rev().collect::<String>())
+    }
+}
+
+/// The exact value of a constant expression, as solc evaluates literals.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rational {
+    pub negative: bool,
+    pub numerator: BigUint,
+    pub denominator: BigUint,
+}
+
+impl Rational {
+    pub fn new(
+        negative: bool,
+        numerator: BigUint,
+        denominator: BigUint,
+    ) -> Result<Rational, String> {
+        if denominator.is_zero() {
+            return Err("Division by zero.".to_string());
+        }
+        let gcd = numerator.gcd(&denominator);
+        let rational = Rational {
+            negative: negative && !numerator.is_zero(),
+            numerator: numerator.div_rem(&gcd).0,
+            denominator: denominator.div_rem(&gcd).0,
+        };
+        if rational.numerator.bits() > MAX_BITS || rational.denominator.bits() > MAX_BITS {
+            return Err("Constant is too large.".to_string());
+        }
+        Ok(rational)
+    }
+
+    pub fn integer(value: BigUint) -> Rational {
+        Rational {
+            negative: false,
+            numerator: value,
+            denominator: BigUint::from_u64(1),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == BigUint::from_u64(1)
+    }
+
+    pub fn add(&self, other: &Rational) -> Result<Rational, String> {
+        let a = self.numerator.mul(&other.denominator);
+        let b = other.numerator.mul(&self.denominator);
+        let denominator = self.denominator.mul(&other.denominator);
+        if self.negative == other.negative {
+            Rational::new(self.negative, a.add(&b), denominator)
+        } else if a >= b {
+            Rational::new(self.negative, a.sub(&b), denominator)
+        } else {
+            Rational::new(other.negative, b.sub(&a), denominator)
+        }
+    }
+
+    pub fn sub(&self, other: &Rational) -> Result<Rational, String> {
+        self.add(&Rational {
+            negative: !other.negative,
+            ..other.clone()
+        })
+    }
+
+    pub fn mul(&self, other: &Rational) -> Result<Rational, String> {
+        Rational::new(
+            self.negative != other.negative,
+            self.numerator.mul(&other.numerator),
+            self.denominator.mul(&other.denominator),
+        )
+    }
+
+    pub fn div(&self, other: &Rational) -> Result<Rational, String> {
+        Rational::new(
+            self.negative != other.negative,
+            self.numerator.mul(&other.denominator),
+            self.denominator.mul(&other.numerator),
+        )
+    }
+
+    /// The remainder of integers, which has the sign of the dividend.
+    pub fn rem(&self, other: &Rational) -> Result<Rational, String> {
+        if !self.is_integer() || !other.is_integer() {
+            return Err("Operator % is only supported for integer constants.".to_string());
+        }
+        if other.numerator.is_zero() {
+            return Err("Modulo zero.".to_string());
+        }
+        let remainder = self.numerator.div_rem(&other.numerator).1;
+        Rational::new(self.negative, remainder, BigUint::from_u64(1))
+    }
+
+    /// Raises to an integer power, using exponentiation by squaring.
+    pub fn pow(&self, exponent: &Rational) -> Result<Rational, String> {
+        if !exponent.is_integer() {
+            return Err("Exponent has to be an integer constant.".to_string());
+        }
+        let one = Rational::integer(BigUint::from_u64(1));
+        let mut count = match exponent.numerator.bits() {
+            0 => 0,
+            // Only zero and one can be raised to huge powers without being too large.
+            bits if bits > 32 => {
+                return match self.numerator.bits().max(self.denominator.bits()) {
+                    0 | 1 if !self.negative => Ok(self.clone()),
+                    _ => Err("Constant is too large.".to_string()),
+                }
+            }
+            _ => exponent.numerator.0[0],
+        };
+        let mut result = one.clone();
+        let mut base = self.clone();
+        while count > 0 {
+            if count & 1 == 1 {
+                result = result.mul(&base)?;
+            }
+            count >>= 1;
+            if count > 0 {
+                base = base.mul(&base)?;
+            }
+        }
+        if exponent.negative {
+            result = one.div(&result)?;
+        }
+        Ok(result)
+    }
+
+    /// Whether the value is an integer in the range of an integer type.
+    pub fn fits(&self, bits: usize, signed: bool) -> bool {
+        if !self.is_integer() {
+            return false;
+        }
+        match (signed, self.negative) {
+            (false, negative) => !negative && self.numerator.bits() <= bits,
+            (true, false) => self.numerator.bits() < bits,
+            (true, true) => self.numerator.sub(&BigUint::from_u64(1)).bits() < bits,
+        }
+    }
+
+    /// The value as an index or length, if it is a small enough integer.
+    pub fn to_usize(&self) -> Option<usize> {
+        if !self.fits(64, false) {
+            return None;
+        }
+        let value = self
+            .numerator
+            .to_bytes_be()
+            .iter()
+            .fold(0u64, |value, byte| value << 8 | *byte as u64);
+        usize::try_from(value).ok()
+    }
+
+    /// The value as a 256 bit two's complement word, if it is an integer that fits.
+    pub fn to_word(&self) -> Option<[u8; 32]> {
+        if !self.fits(256, self.negative) {
+            return None;
+        }
+        let bytes = self.numerator.to_bytes_be();
+        let mut word = [0u8; 32];
+        word[32 - bytes.len()..].copy_from_slice(&bytes);
+        if self.negative {
+            let mut carry = true;
+            for byte in word.iter_mut().rev() {
+                let (value, overflow) = (!*byte).overflowing_add(carry as u8);
+                *byte = value;
+                carry = overflow;
+            }
+        }
+        Some(word)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(value: u64) -> Rational {
+        Rational::integer(BigUint::from_u64(value))
+    }
+
+    #[test]
+    fn reduces_fractions() {
+        let value = integer(10).div(&integer(4)).unwrap();
+        assert_eq!(value.to_string(), "5/2");
+        assert_eq!(value.mul(&integer(2)).unwrap(), integer(5));
+        assert_eq!(
+            integer(1).div(&integer(0)),
+            Err("Division by zero.".to_string())
+        );
+    }
+
+    #[test]
+    fn checks_integer_ranges() {
+        let max = integer(2)
+            .pow(&integer(256))
+            .unwrap()
+            .sub(&integer(1))
+            .unwrap();
+        assert!(max.fits(256, false));
+        assert!(!max.add(&integer(1)).unwrap().fits(256, false));
+        let min = integer(0).sub(&integer(128)).unwrap();
+        assert!(min.fits(8, true));
+        assert!(!min.sub(&integer(1)).unwrap().fits(8, true));
+        assert_eq!(min.to_word().map(|word| word[31]), Some(0x80));
+        assert_eq!(min.to_word().map(|word| word[0]), Some(0xff));
+    }
+
+    #[test]
+    fn rejects_too_large_constants() {
+        assert_eq!(
+            integer(2).pow(&integer(5000)),
+            Err("Constant is too large.".to_string())
+        );
+        assert_eq!(integer(1).pow(&integer(1 << 40)), Ok(integer(1)));
+    }
+
+    #[test]
+    fn converts_only_representable_values_to_usize() {
+        assert_eq!(integer(42).to_usize(), Some(42));
+        assert_eq!(integer(10).div(&integer(4)).unwrap().to_usize(), None);
+        assert_eq!(integer(0).sub(&integer(1)).unwrap().to_usize(), None);
+        let too_large = integer(2).pow(&integer(64)).unwrap();
+        assert_eq!(too_large.to_usize(), None);
+        let max_u32 = integer(u32::MAX as u64);
+        assert_eq!(max_u32.to_usize(), Some(u32::MAX as usize));
+        let above_u32 = max_u32.add(&integer(1)).unwrap();
+        assert_eq!(above_u32.to_usize(), usize::try_from(1u64 << 32).ok());
+    }
+}
@@ -1,10 +1,44 @@
 print("// GENERATED: DO NOT EDIT")
 
 with open('codes.txt') as f:
-    print("#[derive(Copy, Clone)]")
+    lines = [line.rstrip().split(" ") for line in f]
+    print("#[derive(Copy, Clone, Debug, PartialEq)]")
     print("pub enum OpCode {")
-    lines = [line for line in f]
-    for line in lines:
-        name, token = line.rstrip().split(" ")
+    for name, token, _, _ in lines:
         print(f"\t{name.upper()} = {token},")
     print("}")
+    print("")
+    print("impl OpCode {")
+    print("\t/// Number of items this instruction pops off the stack.")
+    print("\tpub fn stack_args(self) -> usize {")
+    print("\t\tmatch self {")
+    for name, _, args, _ in lines:
+        print(f"\t\t\tOpCode::{name.upper()} => {args},")
+    print("\t\t}")
+    print("\t}")
+    print("")
+    print("\t/// Number of items this instruction pushes onto the stack.")
+    print("\tpub fn stack_returns(self) -> usize {")
+    print("\t\tmatch self {")
+    for name, _, _, returns in lines:
+        print(f"\t\t\tOpCode::{name.upper()} => {returns},")
+    print("\t\t}")
+    print("\t}")
+    print("")
+    print("\tpub fn from_byte(byte: u8) -> Option<OpCode> {")
+    print("\t\tmatch byte {")
+    for name, token, _, _ in lines:
+        print(f"\t\t\t{token} => Some(OpCode::{name.upper()}),")
+    print("\t\t\t_ => None,")
+    print("\t\t}")
+    print("\t}")
+    print("")
+    print("\t/// The instruction with the given lower case name, like `mstore`.")
+    print("\tpub fn from_name(name: &str) -> Option<OpCode> {")
+    print("\t\tmatch name {")
+    for name, _, _, _ in lines:
+        print(f"\t\t\t\"{name.lower()}\" => Some(OpCode::{name.upper()}),")
+    print("\t\t\t_ => None,")
+    print("\t\t}")
+    print("\t}")
+    print("}")
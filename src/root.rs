@@ -1,7 +1,7 @@
 use crate::{
+  codegen::EvmVersion,
   definition::{parse_contract, Contract},
-  directive::{parse_pragma_directive, PragmaDirective},
-  op_codes::OpCode,
+  directive::{parse_import_directive, parse_pragma_directive, ImportDirective, PragmaDirective},
 };
 use nom::{
   character::complete::multispace0,
@@ -13,29 +13,18 @@ use nom::{
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Root {
-  pragma_directive: PragmaDirective,
-  contracts: Vec<Contract>,
+  pub pragma_directive: PragmaDirective,
+  pub imports: Vec<ImportDirective>,
+  pub contracts: Vec<Contract>,
 }
 
 impl Root {
-  pub fn op_codes(self) -> Vec<u32> {
+  pub fn op_codes(&self, evm_version: EvmVersion) -> Result<Vec<u32>, String> {
     let mut codes = Vec::<u32>::new();
-    for contract in self.contracts {
-      for code in contract.op_codes() {
-        codes.push(code);
-      }
-    }
-    codes
-  }
-
-  pub fn runtime_op_codes(self) -> Vec<u32> {
-    let mut codes = Vec::<u32>::new();
-    for contract in self.contracts {
-      for code in contract.runtime_op_codes() {
-        codes.push(code);
-      }
+    for contract in &self.contracts {
+      codes.extend(contract.op_codes(&self.contracts, evm_version)?);
     }
-    codes
+    Ok(codes)
   }
 }
 
@@ -43,12 +32,14 @@ pub fn parse(i: &[u8]) -> IResult<&[u8], Root> {
   map(
     tuple((
       parse_pragma_directive,
+      many0(parse_import_directive),
       many0(preceded(multispace0, parse_contract)),
     )),
     |x| {
-      let (pragma_directive, contracts) = x;
+      let (pragma_directive, imports, contracts) = x;
       Root {
         pragma_directive,
+        imports,
         contracts,
       }
     },
@@ -0,0 +1,49 @@
+use nom::{character::complete::multispace0, error::ErrorKind, IResult};
+
+/// A range of the source code. Parsers only see the rest of the input, so a span records how
+/// much input was left where it starts. The offset follows once the length of the whole source
+/// is known.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+  pub rest: usize,
+  pub length: usize,
+}
+
+impl Span {
+  /// The byte offset of the span in a source of `source_length` bytes.
+  pub fn start(&self, source_length: usize) -> usize {
+    source_length - self.rest
+  }
+}
+
+/// Runs `parser` and also returns the span of what it parsed, without leading whitespace.
+pub fn spanned<'a, O, F>(parser: F) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], (O, Span)>
+where
+  F: Fn(&'a [u8]) -> IResult<&'a [u8], O>,
+{
+  move |i: &'a [u8]| {
+    let (start, _) = multispace0::<_, (&[u8], ErrorKind)>(i)?;
+    let (remaining, output) = parser(i)?;
+    let span = Span {
+      rest: start.len(),
+      length: start.len().saturating_sub(remaining.len()),
+    };
+    Ok((remaining, (output, span)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nom::bytes::complete::tag;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn spans_parsed_input_without_leading_whitespace() {
+    let source = b"  \n foo bar";
+    let (remaining, (_, span)) = spanned(tag(" \n foo"))(&source[1..]).ok().unwrap();
+    assert_eq!(remaining, b" bar");
+    assert_eq!((span.start(source.len()), span.length), (4, 3));
+  }
+}
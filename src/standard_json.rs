@@ -0,0 +1,730 @@
+use crate::{
+  abi,
+  codegen::{disassembly, source_map, Assembly, EvmVersion},
+  definition::{Contract, ContractType},
+  elementary_type_name::ElementaryTypeName,
+  expression::{array_length, TypeName},
+  hex::to_hex,
+  json::{self, Json},
+  root::{parse, Root},
+};
+use std::collections::BTreeMap;
+
+/// An entry of the `errors` of the output.
+struct Error {
+  kind: &'static str,
+  message: String,
+  /// The file and the byte range the error refers to.
+  location: Option<(String, usize, usize)>,
+}
+
+impl Error {
+  fn new(kind: &'static str, message: String) -> Error {
+    Error {
+      kind,
+      message,
+      location: None,
+    }
+  }
+
+  fn to_json(&self, sources: &BTreeMap<String, String>) -> String {
+    let mut formatted = format!("{}: {}\n", self.kind, self.message);
+    let mut location = String::new();
+    if let Some((file, start, end)) = &self.location {
+      let content = &sources[file][..*start];
+      let line = content.matches('\n').count() + 1;
+      let column = content.len() - content.rfind('\n').map_or(0, |i| i + 1) + 1;
+      formatted.push_str(&format!(" --> {}:{}:{}:\n", file, line, column));
+      location = format!(
+        r#","sourceLocation":{{"end":{},"file":{},"start":{}}}"#,
+        end,
+        json::string(file),
+        start
+      );
+    }
+    format!(
+      r#"{{"component":"general","formattedMessage":{},"message":{},"severity":"error"{},"type":"{}"}}"#,
+      json::string(&formatted),
+      json::string(&self.message),
+      location,
+      self.kind
+    )
+  }
+}
+
+/// `context:prefix=target`, which replaces `prefix` by `target` in the imports of files whose
+/// name starts with `context`.
+struct Remapping {
+  context: String,
+  prefix: String,
+  target: String,
+}
+
+fn parse_remapping(remapping: &str) -> Option<Remapping> {
+  let (lhs, target) = remapping.split_at(remapping.find('=')?);
+  let (context, prefix) = match lhs.find(':') {
+    Some(index) => (&lhs[..index], &lhs[index + 1..]),
+    None => ("", lhs),
+  };
+  if prefix.is_empty() {
+    return None;
+  }
+  Some(Remapping {
+    context: context.to_string(),
+    prefix: prefix.to_string(),
+    target: target[1..].to_string(),
+  })
+}
+
+struct Settings<'a> {
+  evm_version: EvmVersion,
+  remappings: Vec<Remapping>,
+  output_selection: Option<&'a Json>,
+}
+
+fn parse_settings(input: &Json) -> Result<Settings<'_>, String> {
+  let settings = match input.get("settings") {
+    None => {
+      return Ok(Settings {
+        evm_version: EvmVersion::default(),
+        remappings: vec![],
+        output_selection: None,
+      })
+    }
+    Some(settings @ Json::Object(_)) => settings,
+    Some(_) => return Err("\"settings\" must be an object.".to_string()),
+  };
+  let evm_version = match settings.get("evmVersion") {
+    None => EvmVersion::default(),
+    Some(Json::String(name)) => {
+      EvmVersion::from_name(name).map_err(|_| "Invalid EVM version requested.".to_string())?
+    }
+    Some(_) => return Err("\"evmVersion\" must be a string.".to_string()),
+  };
+  let mut remappings = vec![];
+  match settings.get("remappings") {
+    None => {}
+    Some(Json::Array(entries)) => {
+      for entry in entries {
+        let remapping = entry
+          .as_str()
+          .ok_or("\"remappings\" must be an array of strings.")?;
+        remappings.push(
+          parse_remapping(remapping)
+            .ok_or_else(|| format!("Invalid remapping: \"{}\"", remapping))?,
+        );
+      }
+    }
+    Some(_) => return Err("\"settings.remappings\" must be an array of strings.".to_string()),
+  }
+  // The code isn't optimized yet, but the settings are checked like solc does
+  if let Some(optimizer) = settings.get("optimizer") {
+    match optimizer.get("enabled") {
+      None | Some(Json::Bool(_)) => {}
+      Some(_) => return Err("The \"enabled\" setting must be a Boolean.".to_string()),
+    }
+    match optimizer.get("runs") {
+      None => {}
+      Some(Json::Number(runs)) if runs.parse::<u64>().is_ok() => {}
+      Some(_) => return Err("The \"runs\" setting must be an unsigned number.".to_string()),
+    }
+  }
+  Ok(Settings {
+    evm_version,
+    remappings,
+    output_selection: settings.get("outputSelection"),
+  })
+}
+
+/// Removes `.` and `..` segments from a path.
+fn normalize(path: &str) -> String {
+  let mut segments: Vec<&str> = vec![];
+  for segment in path.split('/') {
+    match segment {
+      "." => {}
+      ".." if !segments.is_empty() && segments.last() != Some(&"..") => {
+        segments.pop();
+      }
+      segment => segments.push(segment),
+    }
+  }
+  segments.join("/")
+}
+
+/// The name of the source an import of `importer` refers to. Paths starting with `./` or `../`
+/// are relative to the importing file, then the remapping with the longest context and prefix
+/// applies.
+fn resolve_import(importer: &str, path: &str, remappings: &[Remapping]) -> String {
+  let path = if path.starts_with("./") || path.starts_with("../") {
+    let directory = importer.rfind('/').map_or("", |index| &importer[..=index]);
+    normalize(&format!("{}{}", directory, path))
+  } else {
+    path.to_string()
+  };
+  let remapping = remappings
+    .iter()
+    .filter(|r| importer.starts_with(&r.context) && path.starts_with(&r.prefix))
+    .max_by_key(|r| (r.context.len(), r.prefix.len()));
+  match remapping {
+    Some(r) => normalize(&format!("{}{}", r.target, &path[r.prefix.len()..])),
+    None => path,
+  }
+}
+
+/// The contracts defined in `file`, followed by those it imports directly or indirectly.
+fn visible_contracts<'a>(
+  file: &str,
+  roots: &'a BTreeMap<String, Root>,
+  remappings: &[Remapping],
+  importers: &mut Vec<String>,
+) -> Result<Vec<&'a Contract>, Error> {
+  let root = &roots[file];
+  let mut contracts: Vec<&Contract> = root.contracts.iter().collect();
+  if importers.iter().any(|importer| importer == file) {
+    return Ok(contracts);
+  }
+  importers.push(file.to_string());
+  for import in &root.imports {
+    let path = resolve_import(file, &import.path, remappings);
+    if !roots.contains_key(&path) {
+      return Err(Error::new(
+        "ParserError",
+        format!("Source \"{}\" not found: File not found.", path),
+      ));
+    }
+    let imported = visible_contracts(&path, roots, remappings, importers)?;
+    for symbol in &import.symbols {
+      if !imported.iter().any(|c| c.identifier == *symbol) {
+        return Err(Error::new(
+          "DeclarationError",
+          format!(
+            "Declaration \"{}\" not found in \"{}\" (referenced as \"{}\").",
+            symbol, path, import.path
+          ),
+        ));
+      }
+    }
+    for contract in imported {
+      let wanted = import.symbols.is_empty() || import.symbols.contains(&contract.identifier);
+      if wanted
+        && !contracts
+          .iter()
+          .any(|c| c.identifier == contract.identifier)
+      {
+        contracts.push(contract);
+      }
+    }
+  }
+  importers.pop();
+  Ok(contracts)
+}
+
+/// Whether `output`, like `evm.bytecode.object`, is selected for a contract. Selecting a prefix
+/// like `evm.bytecode` selects everything below it, and `*` selects everything.
+fn is_selected(selection: Option<&Json>, file: &str, contract: &str, output: &str) -> bool {
+  let selection = match selection {
+    Some(selection) => selection,
+    None => return false,
+  };
+  let selects = |outputs: &Json| match outputs {
+    Json::Array(outputs) => outputs.iter().filter_map(Json::as_str).any(|selected| {
+      selected == "*" || output == selected || output.starts_with(&format!("{}.", selected))
+    }),
+    _ => false,
+  };
+  [file, "*"]
+    .iter()
+    .filter_map(|file| selection.get(file))
+    .any(|contracts| {
+      [contract, "*"]
+        .iter()
+        .filter_map(|contract| contracts.get(contract))
+        .any(selects)
+    })
+}
+
+/// The identifier of a type in the storage layout, as solc names it. Adds the description of
+/// the type and the types it consists of to `types`.
+fn storage_type(type_name: &TypeName, types: &mut BTreeMap<String, String>) -> String {
+  let label = match type_name {
+    TypeName::ElementaryTypeName(ElementaryTypeName::AddressPayable) => {
+      "address payable".to_string()
+    }
+    TypeName::UserDefinedTypeName(path) => format!("contract {}", path.join(".")),
+    type_name => type_name.abi_name(),
+  };
+  let (id, description) = match type_name {
+    TypeName::ElementaryTypeName(name) if name.is_dynamic() => (
+      format!("t_{}_storage", label),
+      format!(
+        r#""encoding":"bytes","label":"{}","numberOfBytes":"32""#,
+        label
+      ),
+    ),
+    TypeName::ElementaryTypeName(_) => (
+      format!("t_{}", label.replace(' ', "_")),
+      format!(
+        r#""encoding":"inplace","label":"{}","numberOfBytes":"{}""#,
+        label,
+        type_name.storage_bytes().unwrap_or(32)
+      ),
+    ),
+    TypeName::UserDefinedTypeName(path) => (
+      format!("t_contract({})", path.join(".")),
+      format!(
+        r#""encoding":"inplace","label":"{}","numberOfBytes":"{}""#,
+        label,
+        type_name.storage_bytes().unwrap_or(32)
+      ),
+    ),
+    TypeName::Mapping(key, value) => {
+      let key = storage_type(key, types);
+      let value = storage_type(value, types);
+      (
+        format!("t_mapping({},{})", key, value),
+        format!(
+          r#""encoding":"mapping","key":"{}","label":"{}","numberOfBytes":"32","value":"{}""#,
+          key, label, value
+        ),
+      )
+    }
+    TypeName::Array(element, length) => {
+      let base = storage_type(element, types);
+      match length.as_ref().map(|length| array_length(length)) {
+        Some(Ok(length)) => (
+          format!("t_array({}){}_storage", base, length),
+          format!(
+            r#""base":"{}","encoding":"inplace","label":"{}","numberOfBytes":"{}""#,
+            base,
+            label,
+            type_name.storage_bytes().unwrap_or(32)
+          ),
+        ),
+        _ => (
+          format!("t_array({})dyn_storage", base),
+          format!(
+            r#""base":"{}","encoding":"dynamic_array","label":"{}","numberOfBytes":"32""#,
+            base, label
+          ),
+        ),
+      }
+    }
+  };
+  types.insert(id.clone(), format!("{{{}}}", description));
+  id
+}
+
+fn storage_layout_json(file: &str, contract: &Contract) -> String {
+  let mut types = BTreeMap::new();
+  let storage: Vec<String> = contract
+    .storage_layout()
+    .into_iter()
+    .map(|(position, decl)| {
+      format!(
+        r#"{{"contract":{},"label":"{}","offset":{},"slot":"{}","type":"{}"}}"#,
+        json::string(&format!("{}:{}", file, contract.identifier)),
+        decl.identifier,
+        position.offset,
+        position.slot,
+        storage_type(&decl.type_name, &mut types)
+      )
+    })
+    .collect();
+  let types: Vec<String> = types
+    .iter()
+    .map(|(id, description)| format!(r#""{}":{}"#, id, description))
+    .collect();
+  format!(
+    r#"{{"storage":[{}],"types":{{{}}}}}"#,
+    storage.join(","),
+    types.join(",")
+  )
+}
+
+/// The selected outputs of `evm.bytecode` or `evm.deployedBytecode`, which are empty for
+/// contracts without code.
+fn bytecode_json(
+  asm: Option<&Assembly>,
+  source_index: usize,
+  source_length: usize,
+  evm_version: EvmVersion,
+  selected: &dyn Fn(&str) -> bool,
+) -> String {
+  let code = asm.map_or(vec![], |asm| asm.assemble(evm_version));
+  let mut fields = vec![];
+  if selected("linkReferences") {
+    fields.push(r#""linkReferences":{}"#.to_string());
+  }
+  if selected("object") {
+    fields.push(format!(r#""object":"{}""#, to_hex(&code)));
+  }
+  if selected("opcodes") {
+    let bytes: Vec<u8> = code.iter().map(|c| *c as u8).collect();
+    fields.push(format!(
+      r#""opcodes":"{}""#,
+      disassembly::disassemble(&bytes)
+    ));
+  }
+  if selected("sourceMap") {
+    let map = asm.map_or(String::new(), |asm| {
+      source_map::source_map(asm, source_index, source_length)
+    });
+    fields.push(format!(r#""sourceMap":"{}""#, map));
+  }
+  format!("{{{}}}", fields.join(","))
+}
+
+/// The selected outputs of a contract of the source `file`, whose index is `source_index`.
+/// `contracts` are the contracts visible in the file.
+fn contract_json(
+  file: &str,
+  source_index: usize,
+  source_length: usize,
+  contract: &Contract,
+  contracts: &[Contract],
+  settings: &Settings,
+) -> Result<String, Error> {
+  let selected = |output: &str| {
+    is_selected(
+      settings.output_selection,
+      file,
+      &contract.identifier,
+      output,
+    )
+  };
+  let mut fields = vec![];
+  if selected("abi") {
+    fields.push(format!(r#""abi":{}"#, abi::to_json(contract)));
+  }
+  let mut evm = vec![];
+  for (output, runtime) in &[("bytecode", false), ("deployedBytecode", true)] {
+    let output = format!("evm.{}", output);
+    let selected_field = |field: &str| selected(&format!("{}.{}", output, field));
+    if !["linkReferences", "object", "opcodes", "sourceMap"]
+      .iter()
+      .any(|field| selected_field(field))
+    {
+      continue;
+    }
+    let asm = if contract.contract_type != ContractType::Contract {
+      None
+    } else if *runtime {
+      Some(contract.runtime_assembly(contracts, &[]))
+    } else {
+      Some(contract.creation_assembly(contracts, &[]))
+    };
+    let asm = asm
+      .transpose()
+      .map_err(|message| Error::new("TypeError", message))?;
+    evm.push(format!(
+      r#""{}":{}"#,
+      &output[4..],
+      bytecode_json(
+        asm.as_ref(),
+        source_index,
+        source_length,
+        settings.evm_version,
+        &selected_field
+      )
+    ));
+  }
+  if selected("evm.methodIdentifiers") {
+    evm.push(format!(
+      r#""methodIdentifiers":{}"#,
+      json::hashes(contract.method_identifiers())
+    ));
+  }
+  if !evm.is_empty() {
+    fields.push(format!(r#""evm":{{{}}}"#, evm.join(",")));
+  }
+  if selected("storageLayout") {
+    fields.push(format!(
+      r#""storageLayout":{}"#,
+      storage_layout_json(file, contract)
+    ));
+  }
+  Ok(format!("{{{}}}", fields.join(",")))
+}
+
+fn errors_json(errors: &[Error], sources: &BTreeMap<String, String>) -> String {
+  let errors: Vec<String> = errors.iter().map(|e| e.to_json(sources)).collect();
+  format!("[{}]", errors.join(","))
+}
+
+/// Compiles the sources of a Standard JSON input, like `solc --standard-json`, and returns the
+/// Standard JSON output with the `contracts`, `errors` and `sources`.
+pub fn compile(input: &str) -> String {
+  let input = match json::parse(input.as_bytes()) {
+    Ok((remaining, input)) if remaining.is_empty() => input,
+    _ => {
+      let error = Error::new(
+        "JSONError",
+        "Input is not a valid JSON document.".to_string(),
+      );
+      return format!(
+        r#"{{"errors":{}}}"#,
+        errors_json(&[error], &BTreeMap::new())
+      );
+    }
+  };
+  let mut sources = BTreeMap::new();
+  let result = parse_settings(&input).and_then(|settings| {
+    match input.get("language").and_then(Json::as_str) {
+      Some("Solidity") => {}
+      _ => return Err("Only \"Solidity\" is supported as a language.".to_string()),
+    }
+    match input.get("sources") {
+      Some(Json::Object(entries)) if !entries.is_empty() => {
+        for (name, source) in entries {
+          let content = source
+            .get("content")
+            .and_then(Json::as_str)
+            .ok_or_else(|| format!("Source \"{}\" has no content.", name))?;
+          sources.insert(name.clone(), content.to_string());
+        }
+      }
+      _ => return Err("No input sources specified.".to_string()),
+    }
+    Ok(settings)
+  });
+  let settings = match result {
+    Ok(settings) => settings,
+    Err(message) => {
+      let error = Error::new("JSONError", message);
+      return format!(r#"{{"errors":{}}}"#, errors_json(&[error], &sources));
+    }
+  };
+
+  let mut errors = vec![];
+  let mut roots = BTreeMap::new();
+  for (name, content) in &sources {
+    let offset = match parse(content.as_bytes()) {
+      Ok((remaining, root)) => {
+        roots.insert(name.clone(), root);
+        match remaining.iter().position(|c| !c.is_ascii_whitespace()) {
+          None => continue,
+          Some(position) => content.len() - remaining.len() + position,
+        }
+      }
+      Err(nom::Err::Error((remaining, _))) | Err(nom::Err::Failure((remaining, _))) => {
+        content.len() - remaining.len()
+      }
+      Err(nom::Err::Incomplete(_)) => content.len(),
+    };
+    errors.push(Error {
+      kind: "ParserError",
+      message: "Expected pragma, import directive or contract definition.".to_string(),
+      location: Some((name.clone(), offset, content.len())),
+    });
+  }
+
+  let mut contracts = vec![];
+  if errors.is_empty() {
+    for (index, (name, root)) in roots.iter().enumerate() {
+      let visible = match visible_contracts(name, &roots, &settings.remappings, &mut vec![]) {
+        Ok(visible) => visible,
+        Err(error) => {
+          errors.push(error);
+          continue;
+        }
+      };
+      let visible: Vec<Contract> = visible.into_iter().cloned().collect();
+      let mut entries = vec![];
+      for contract in &visible[..root.contracts.len()] {
+        match contract_json(
+          name,
+          index,
+          sources[name].len(),
+          contract,
+          &visible,
+          &settings,
+        ) {
+          Ok(json) => entries.push(format!(r#""{}":{}"#, contract.identifier, json)),
+          Err(error) => errors.push(error),
+        }
+      }
+      contracts.push(format!(
+        r#"{}:{{{}}}"#,
+        json::string(name),
+        entries.join(",")
+      ));
+    }
+  }
+
+  let ids: Vec<String> = sources
+    .keys()
+    .enumerate()
+    .map(|(id, name)| format!(r#"{}:{{"id":{}}}"#, json::string(name), id))
+    .collect();
+  format!(
+    r#"{{"contracts":{{{}}},"errors":{},"sources":{{{}}}}}"#,
+    contracts.join(","),
+    errors_json(&errors, &sources),
+    ids.join(",")
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pretty_assertions::assert_eq;
+
+  fn input(sources: &str, settings: &str) -> String {
+    format!(
+      r#"{{"language":"Solidity","sources":{},"settings":{}}}"#,
+      sources, settings
+    )
+  }
+
+  #[test]
+  fn resolves_imports_with_remappings() {
+    let remappings = vec![
+      parse_remapping("@oz/=lib/openzeppelin/").unwrap(),
+      parse_remapping("src/legacy:@oz/=lib/openzeppelin-v3/").unwrap(),
+    ];
+    assert_eq!(
+      resolve_import("src/a/Token.sol", "../b/./Math.sol", &remappings),
+      "src/b/Math.sol"
+    );
+    assert_eq!(
+      resolve_import("src/Token.sol", "@oz/token/ERC20.sol", &remappings),
+      "lib/openzeppelin/token/ERC20.sol"
+    );
+    assert_eq!(
+      resolve_import("src/legacy/Token.sol", "@oz/token/ERC20.sol", &remappings),
+      "lib/openzeppelin-v3/token/ERC20.sol"
+    );
+  }
+
+  #[test]
+  fn compiles_selected_outputs() {
+    let sources = r#"{
+  "src/Counter.sol": {"content": "pragma solidity ^0.8.0;\nimport {IERC20} from \"@lib/IERC20.sol\";\ncontract Counter {\n  uint256 public count;\n  mapping(address => uint8) flags;\n  function increment() public { count = count + 1; }\n}"},
+  "lib/IERC20.sol": {"content": "pragma solidity ^0.8.0;\ninterface IERC20 { function totalSupply() external view returns (uint256); }"}
+}"#;
+    let settings = r#"{
+  "evmVersion": "paris",
+  "optimizer": {"enabled": true, "runs": 200},
+  "remappings": ["@lib/=lib/"],
+  "outputSelection": {
+    "src/Counter.sol": {"Counter": ["evm.methodIdentifiers", "storageLayout"]},
+    "*": {"*": ["evm.deployedBytecode.object"]}
+  }
+}"#;
+    let output = compile(&input(sources, settings));
+    let (_, root) = parse(
+      b"pragma solidity ^0.8.0;
+contract Counter {
+  uint256 public count;
+  mapping(address => uint8) flags;
+  function increment() public { count = count + 1; }
+}",
+    )
+    .ok()
+    .unwrap();
+    let runtime = root.contracts[0]
+      .runtime_assembly(&root.contracts, &[])
+      .unwrap()
+      .assemble(EvmVersion::Paris);
+    assert_eq!(
+      output,
+      format!(
+        concat!(
+          r#"{{"contracts":{{"lib/IERC20.sol":{{"IERC20":{{"evm":{{"deployedBytecode":{{"object":""}}}}}}}},"#,
+          r#""src/Counter.sol":{{"Counter":{{"evm":{{"deployedBytecode":{{"object":"{}"}},"#,
+          r#""methodIdentifiers":{{"count()":"06661abd","increment()":"d09de08a"}}}},"#,
+          r#""storageLayout":{{"storage":["#,
+          r#"{{"contract":"src/Counter.sol:Counter","label":"count","offset":0,"slot":"0","type":"t_uint256"}},"#,
+          r#"{{"contract":"src/Counter.sol:Counter","label":"flags","offset":0,"slot":"1","type":"t_mapping(t_address,t_uint8)"}}],"#,
+          r#""types":{{"t_address":{{"encoding":"inplace","label":"address","numberOfBytes":"20"}},"#,
+          r#""t_mapping(t_address,t_uint8)":{{"encoding":"mapping","key":"t_address","label":"mapping(address => uint8)","numberOfBytes":"32","value":"t_uint8"}},"#,
+          r#""t_uint256":{{"encoding":"inplace","label":"uint256","numberOfBytes":"32"}},"#,
+          r#""t_uint8":{{"encoding":"inplace","label":"uint8","numberOfBytes":"1"}}}}}}}}}}}},"#,
+          r#""errors":[],"sources":{{"lib/IERC20.sol":{{"id":0}},"src/Counter.sol":{{"id":1}}}}}}"#
+        ),
+        to_hex(&runtime)
+      )
+    )
+  }
+
+  #[test]
+  fn lays_out_packed_storage() {
+    let (_, root) = parse(
+      b"pragma solidity ^0.8.0;
+contract Packed {
+  uint128 a;
+  uint8[4] small;
+  bool flag;
+  address owner;
+  uint256 big;
+}",
+    )
+    .ok()
+    .unwrap();
+    let entry = |label: &str, offset: usize, slot: usize, id: &str| {
+      format!(
+        r#"{{"contract":"src/Packed.sol:Packed","label":"{}","offset":{},"slot":"{}","type":"{}"}}"#,
+        label, offset, slot, id
+      )
+    };
+    let inplace = |id: &str, label: &str, bytes: usize| {
+      format!(
+        r#""{}":{{"encoding":"inplace","label":"{}","numberOfBytes":"{}"}}"#,
+        id, label, bytes
+      )
+    };
+    assert_eq!(
+      storage_layout_json("src/Packed.sol", &root.contracts[0]),
+      format!(
+        r#"{{"storage":[{}],"types":{{{}}}}}"#,
+        [
+          entry("a", 0, 0, "t_uint128"),
+          entry("small", 0, 1, "t_array(t_uint8)4_storage"),
+          entry("flag", 0, 2, "t_bool"),
+          entry("owner", 1, 2, "t_address"),
+          entry("big", 0, 3, "t_uint256"),
+        ]
+        .join(","),
+        [
+          inplace("t_address", "address", 20),
+          r#""t_array(t_uint8)4_storage":{"base":"t_uint8","encoding":"inplace","label":"uint8[4]","numberOfBytes":"32"}"#.to_string(),
+          inplace("t_bool", "bool", 1),
+          inplace("t_uint128", "uint128", 16),
+          inplace("t_uint256", "uint256", 32),
+          inplace("t_uint8", "uint8", 1),
+        ]
+        .join(",")
+      )
+    );
+  }
+
+  #[test]
+  fn reports_errors() {
+    assert_eq!(
+      compile("{"),
+      concat!(
+        r#"{"errors":[{"component":"general","formattedMessage":"JSONError: Input is not a valid JSON document.\n","#,
+        r#""message":"Input is not a valid JSON document.","severity":"error","type":"JSONError"}]}"#
+      )
+    );
+    assert!(compile(&input(
+      r#"{"a.sol":{"content":""}}"#,
+      r#"{"evmVersion":"frontier"}"#
+    ))
+    .contains(r#""message":"Invalid EVM version requested.""#));
+    let output = compile(&input(
+      r#"{"a.sol":{"content":"pragma solidity ^0.8.0;\ncontract A {\n  function f() public { g(); }\n}\n}"}}"#,
+      r#"{"outputSelection":{"*":{"*":["evm.bytecode.object"]}}}"#,
+    ));
+    assert!(output.contains(concat!(
+      r#""formattedMessage":"ParserError: Expected pragma, import directive or contract definition.\n --> a.sol:5:1:\n","#,
+    )));
+    assert!(output.contains(r#""sourceLocation":{"end":71,"file":"a.sol","start":70}"#));
+    let output = compile(&input(
+      r#"{"a.sol":{"content":"pragma solidity ^0.8.0;\nimport \"./b.sol\";\ncontract A { function f() public { g(); } }"}}"#,
+      r#"{}"#,
+    ));
+    assert!(output.contains(r#""message":"Source \"b.sol\" not found: File not found.""#));
+  }
+}
@@ -1,26 +1,101 @@
-use crate::atom::parse_identifier;
+use crate::{
+    atom::{
+        keyword::{parse_assembly, parse_function},
+        parse_identifier, word_end,
+    },
+    codegen::{Assembly, Tag},
+    definition::StoragePosition,
+    literal::{number_value, parse as parse_literal, BigUint, Boolean, Literal, Rational},
+    op_codes::OpCode,
+};
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{char, multispace0},
-    combinator::map,
-    multi::separated_nonempty_list,
-    sequence::preceded,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{complete, map, opt, recognize, verify},
+    multi::{many0, separated_list, separated_nonempty_list},
+    sequence::{delimited, preceded, terminated, tuple},
     IResult,
 };
+use std::str::from_utf8;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AssemblyExpression {
+    Literal(Literal),
+    Identifier(String),
+    FunctionCall(String, Vec<AssemblyExpression>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssemblyFunctionDefinition {
+    pub identifier: String,
+    pub parameters: Vec<String>,
+    pub returns: Vec<String>,
+    pub block: Vec<AssemblyStatement>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssemblySwitch {
+    pub expression: AssemblyExpression,
+    pub cases: Vec<(Literal, Vec<AssemblyStatement>)>,
+    pub default: Option<Vec<AssemblyStatement>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssemblyFor {
+    pub init: Vec<AssemblyStatement>,
+    pub condition: AssemblyExpression,
+    pub post: Vec<AssemblyStatement>,
+    pub body: Vec<AssemblyStatement>,
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AssemblyStatement {
+    Block(Vec<AssemblyStatement>),
+    FunctionDefinition(AssemblyFunctionDefinition),
+    /// `let a, b := f()`, where the variables are zero without a value.
+    VariableDeclaration(Vec<String>, Option<AssemblyExpression>),
+    Assignment(Vec<String>, AssemblyExpression),
+    If(AssemblyExpression, Vec<AssemblyStatement>),
+    Switch(AssemblySwitch),
+    For(AssemblyFor),
+    BreakContinue(AssemblyBreakContinue),
+    /// Exits the current function.
+    Leave,
+    Expression(AssemblyExpression),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct InlineAssemblyStatement {
+    /// The dialect, like `"evmasm"`.
     pub identifier: Option<String>,
-    pub assembly_statements: Vec<AssemblyStatement>
+    pub assembly_statements: Vec<AssemblyStatement>,
+}
+
+const KEYWORDS: [&str; 12] = [
+    "break", "case", "continue", "default", "false", "for", "function", "if", "leave", "let",
+    "switch", "true",
+];
+
+/// Identifiers may contain dots, like `x.slot` for the storage slot of a state variable.
+fn parse_assembly_identifier(i: &[u8]) -> IResult<&[u8], String> {
+    verify(
+        map(
+            recognize(tuple((
+                parse_identifier,
+                many0(complete(preceded(char('.'), parse_identifier))),
+            ))),
+            |b| from_utf8(b).unwrap().to_string(),
+        ),
+        |identifier: &String| !KEYWORDS.contains(&identifier.as_str()),
+    )(i)
 }
 
 fn parse_assembly_identifier_list(i: &[u8]) -> IResult<&[u8], Vec<String>> {
-    separated_nonempty_list(char(','), preceded(multispace0, parse_identifier))(i)
+    separated_nonempty_list(
+        preceded(multispace0, char(',')),
+        preceded(multispace0, parse_assembly_identifier),
+    )(i)
 }
 
 const BREAK: &str = "break";
@@ -44,9 +119,785 @@ fn parse_break_continue(i: &[u8]) -> IResult<&[u8], AssemblyBreakContinue> {
     alt((parse_break, parse_continue))(i)
 }
 
+/// A keyword of Yul that is not followed by more identifier characters.
+pub fn keyword<'a>(word: &'static str) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+    move |i| preceded(multispace0, terminated(tag(word), word_end))(i)
+}
+
+pub fn parse_assembly_expression(i: &[u8]) -> IResult<&[u8], AssemblyExpression> {
+    preceded(
+        multispace0,
+        alt((
+            map(
+                terminated(parse_literal, word_end),
+                AssemblyExpression::Literal,
+            ),
+            map(
+                tuple((
+                    parse_assembly_identifier,
+                    opt(complete(preceded(
+                        preceded(multispace0, char('(')),
+                        terminated(
+                            separated_list(
+                                preceded(multispace0, char(',')),
+                                parse_assembly_expression,
+                            ),
+                            preceded(multispace0, char(')')),
+                        ),
+                    ))),
+                )),
+                |(identifier, args)| match args {
+                    Some(args) => AssemblyExpression::FunctionCall(identifier, args),
+                    None => AssemblyExpression::Identifier(identifier),
+                },
+            ),
+        )),
+    )(i)
+}
+
+pub fn parse_assembly_block(i: &[u8]) -> IResult<&[u8], Vec<AssemblyStatement>> {
+    delimited(
+        preceded(multispace0, char('{')),
+        many0(parse_assembly_statement),
+        preceded(multispace0, char('}')),
+    )(i)
+}
+
+fn parse_assembly_function_definition(i: &[u8]) -> IResult<&[u8], AssemblyFunctionDefinition> {
+    map(
+        tuple((
+            preceded(multispace0, terminated(parse_function, word_end)),
+            preceded(multispace1, parse_assembly_identifier),
+            delimited(
+                preceded(multispace0, char('(')),
+                opt(parse_assembly_identifier_list),
+                preceded(multispace0, char(')')),
+            ),
+            opt(preceded(
+                preceded(multispace0, tag("->")),
+                parse_assembly_identifier_list,
+            )),
+            parse_assembly_block,
+        )),
+        |(_, identifier, parameters, returns, block)| AssemblyFunctionDefinition {
+            identifier,
+            parameters: parameters.unwrap_or_default(),
+            returns: returns.unwrap_or_default(),
+            block,
+        },
+    )(i)
+}
+
+fn parse_assembly_variable_declaration(
+    i: &[u8],
+) -> IResult<&[u8], (Vec<String>, Option<AssemblyExpression>)> {
+    preceded(
+        keyword("let"),
+        tuple((
+            parse_assembly_identifier_list,
+            opt(preceded(
+                preceded(multispace0, tag(":=")),
+                parse_assembly_expression,
+            )),
+        )),
+    )(i)
+}
+
+fn parse_assembly_assignment(i: &[u8]) -> IResult<&[u8], (Vec<String>, AssemblyExpression)> {
+    tuple((
+        parse_assembly_identifier_list,
+        preceded(preceded(multispace0, tag(":=")), parse_assembly_expression),
+    ))(i)
+}
+
+fn parse_assembly_switch(i: &[u8]) -> IResult<&[u8], AssemblySwitch> {
+    map(
+        verify(
+            tuple((
+                preceded(keyword("switch"), parse_assembly_expression),
+                many0(tuple((
+                    preceded(
+                        keyword("case"),
+                        preceded(multispace0, terminated(parse_literal, word_end)),
+                    ),
+                    parse_assembly_block,
+                ))),
+                opt(preceded(keyword("default"), parse_assembly_block)),
+            )),
+            |(_, cases, default)| !cases.is_empty() || default.is_some(),
+        ),
+        |(expression, cases, default)| AssemblySwitch {
+            expression,
+            cases,
+            default,
+        },
+    )(i)
+}
+
+fn parse_assembly_for(i: &[u8]) -> IResult<&[u8], AssemblyFor> {
+    map(
+        tuple((
+            preceded(keyword("for"), parse_assembly_block),
+            parse_assembly_expression,
+            parse_assembly_block,
+            parse_assembly_block,
+        )),
+        |(init, condition, post, body)| AssemblyFor {
+            init,
+            condition,
+            post,
+            body,
+        },
+    )(i)
+}
+
+pub fn parse_assembly_statement(i: &[u8]) -> IResult<&[u8], AssemblyStatement> {
+    alt((
+        map(parse_assembly_block, AssemblyStatement::Block),
+        map(
+            parse_assembly_function_definition,
+            AssemblyStatement::FunctionDefinition,
+        ),
+        map(parse_assembly_variable_declaration, |(variables, value)| {
+            AssemblyStatement::VariableDeclaration(variables, value)
+        }),
+        map(
+            tuple((
+                preceded(keyword("if"), parse_assembly_expression),
+                parse_assembly_block,
+            )),
+            |(condition, block)| AssemblyStatement::If(condition, block),
+        ),
+        map(parse_assembly_switch, AssemblyStatement::Switch),
+        map(parse_assembly_for, AssemblyStatement::For),
+        map(keyword("leave"), |_| AssemblyStatement::Leave),
+        map(
+            preceded(multispace0, terminated(parse_break_continue, word_end)),
+            AssemblyStatement::BreakContinue,
+        ),
+        map(parse_assembly_assignment, |(variables, value)| {
+            AssemblyStatement::Assignment(variables, value)
+        }),
+        map(parse_assembly_expression, AssemblyStatement::Expression),
+    ))(i)
+}
+
+/// `assembly "evmasm" { ... }`, where the dialect is optional.
+pub fn parse_inline_assembly_statement(i: &[u8]) -> IResult<&[u8], InlineAssemblyStatement> {
+    map(
+        tuple((
+            preceded(multispace0, terminated(parse_assembly, word_end)),
+            opt(preceded(
+                multispace0,
+                delimited(char('"'), parse_identifier, char('"')),
+            )),
+            parse_assembly_block,
+        )),
+        |(_, identifier, assembly_statements)| InlineAssemblyStatement {
+            identifier,
+            assembly_statements,
+        },
+    )(i)
+}
+
+/// A Solidity variable that inline assembly can refer to by name.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExternalVariable {
+    /// A local variable in the stack slot at the position.
+    Stack(usize),
+    /// A state variable at the storage position, which is only accessible through `x.slot`
+    /// and `x.offset`.
+    Storage(StoragePosition),
+}
+
+/// The builtin function of Yul with the given name, which is an instruction that doesn't
+/// manipulate the stack or control flow directly.
+fn builtin(name: &str) -> Option<OpCode> {
+    let op = match name {
+        "keccak256" => OpCode::SHA3,
+        "prevrandao" => OpCode::DIFFICULTY,
+        "sha3" => return None,
+        _ => OpCode::from_name(name)?,
+    };
+    match op as u8 {
+        // JUMP, JUMPI, PC, JUMPDEST, PUSH, DUP and SWAP
+        0x56..=0x58 | 0x5b | 0x5f..=0x9f => None,
+        // Instructions that were never activated
+        0xb0..=0xef | 0xfc => None,
+        _ => Some(op),
+    }
+}
+
+/// The objects that `datasize` and `dataoffset` can refer to when compiling a Yul object: the
+/// object itself and its children, which are sub assemblies.
+pub struct YulObjects {
+    pub name: String,
+    pub children: Vec<(String, usize)>,
+}
+
+struct Loop {
+    continue_tag: Tag,
+    break_tag: Tag,
+    stack_height: usize,
+}
+
+/// Describes the Yul function being generated, so that `leave` knows where to go.
+struct YulFrame {
+    exit: Tag,
+    stack_height: usize,
+}
+
+/// Generates the code of Yul statements into an assembly, keeping track of where the Yul
+/// variables live on the stack.
+pub struct YulContext<'a, 'b> {
+    pub asm: &'b mut Assembly,
+    externals: Vec<(String, ExternalVariable)>,
+    variables: Vec<(&'a str, usize)>,
+    functions: Vec<(&'a AssemblyFunctionDefinition, Tag)>,
+    loops: Vec<Loop>,
+    frame: Option<YulFrame>,
+    objects: Option<YulObjects>,
+}
+
+impl<'a, 'b> YulContext<'a, 'b> {
+    /// Later externals shadow earlier ones of the same name.
+    pub fn new(asm: &'b mut Assembly, externals: Vec<(String, ExternalVariable)>) -> Self {
+        YulContext {
+            asm,
+            externals,
+            variables: vec![],
+            functions: vec![],
+            loops: vec![],
+            frame: None,
+            objects: None,
+        }
+    }
+
+    /// Makes the data builtins available, which only exist outside of inline assembly.
+    pub fn with_objects(mut self, objects: YulObjects) -> Self {
+        self.objects = Some(objects);
+        self
+    }
+
+    fn builtin(&self, name: &str) -> Option<OpCode> {
+        match name {
+            "datacopy" if self.objects.is_some() => Some(OpCode::CODECOPY),
+            _ => builtin(name),
+        }
+    }
+
+    /// `datasize("X")` and `dataoffset("X")`, where `X` is the current object or one of its
+    /// children.
+    fn data_op_codes(&mut self, name: &str, args: &[AssemblyExpression]) -> Result<(), String> {
+        let object = match args {
+            [AssemblyExpression::Literal(Literal::String(object))] => {
+                from_utf8(object).map_err(|_| "Object name has to be valid UTF-8.".to_string())?
+            }
+            _ => {
+                return Err(format!(
+                    "Function \"{}\" expects a single string literal argument.",
+                    name
+                ))
+            }
+        };
+        let objects = self.objects.as_ref().unwrap();
+        if object == objects.name {
+            match name {
+                "datasize" => self.asm.push_program_size(),
+                _ => self.asm.push(&[0]),
+            }
+            return Ok(());
+        }
+        let sub = objects
+            .children
+            .iter()
+            .find(|(child, _)| child == object)
+            .map(|(_, sub)| *sub)
+            .ok_or_else(|| format!("Unknown data object \"{}\".", object))?;
+        match name {
+            "datasize" => self.asm.push_sub_size(sub),
+            _ => self.asm.push_sub_offset(sub),
+        }
+        Ok(())
+    }
+
+    fn variable(&self, name: &str) -> Option<usize> {
+        self.variables
+            .iter()
+            .rev()
+            .find(|(variable, _)| *variable == name)
+            .map(|(_, position)| *position)
+    }
+
+    fn external(&self, name: &str) -> Option<ExternalVariable> {
+        self.externals
+            .iter()
+            .rev()
+            .find(|(external, _)| external == name)
+            .map(|(_, variable)| variable.clone())
+    }
+
+    fn function(&self, name: &str) -> Option<(&'a AssemblyFunctionDefinition, Tag)> {
+        self.functions
+            .iter()
+            .rev()
+            .find(|(function, _)| function.identifier == name)
+            .cloned()
+    }
+
+    fn load(&mut self, position: usize) -> Result<(), String> {
+        let depth = self.asm.stack_height() - position;
+        if depth > 16 {
+            return Err("Stack too deep, try removing local variables.".to_string());
+        }
+        self.asm.dup(depth);
+        Ok(())
+    }
+
+    fn store(&mut self, position: usize) -> Result<(), String> {
+        let depth = self.asm.stack_height() - 1 - position;
+        if depth > 16 {
+            return Err("Stack too deep, try removing local variables.".to_string());
+        }
+        if depth > 0 {
+            self.asm.swap(depth);
+        }
+        self.asm.op(OpCode::POP);
+        Ok(())
+    }
+
+    /// The position of a variable that can be assigned to.
+    fn assignable(&self, name: &str) -> Result<usize, String> {
+        if let Some(position) = self.variable(name) {
+            return Ok(position);
+        }
+        match self.external(name) {
+            Some(ExternalVariable::Stack(position)) => Ok(position),
+            Some(ExternalVariable::Storage(_)) => Err(format!(
+                "Only local variables are supported. To access storage variables, use the \".slot\" and \".offset\" suffixes, like {}.slot",
+                name
+            )),
+            None => Err(format!("Variable not found or variable not lvalue: {}", name)),
+        }
+    }
+
+    fn literal_op_codes(&mut self, literal: &Literal) -> Result<(), String> {
+        match literal {
+            Literal::Number(number) => match number_value(number)?.to_word() {
+                Some(word) if number.1.is_none() => self.asm.push(&word),
+                _ => return Err(format!("Invalid number literal in assembly: {:?}", number)),
+            },
+            Literal::String(bytes) | Literal::Hex(bytes) => {
+                if bytes.len() > 32 {
+                    return Err(format!("String literal too long ({} > 32)", bytes.len()));
+                }
+                let mut word = bytes.clone();
+                word.resize(32, 0);
+                self.asm.push(&word);
+            }
+            Literal::Boolean(Boolean::True) => self.asm.push(&[1]),
+            Literal::Boolean(Boolean::False) => self.asm.push(&[0]),
+        }
+        Ok(())
+    }
+
+    fn identifier_op_codes(&mut self, name: &str) -> Result<(), String> {
+        if let Some(position) = self.variable(name) {
+            return self.load(position);
+        }
+        match self.external(name) {
+            Some(ExternalVariable::Stack(position)) => return self.load(position),
+            Some(ExternalVariable::Storage(_)) => {
+                self.assignable(name)?;
+            }
+            None => {}
+        }
+        if let Some(index) = name.rfind('.') {
+            let (base, suffix) = (&name[..index], &name[index + 1..]);
+            match (self.external(base), suffix) {
+                (Some(ExternalVariable::Storage(position)), "slot") => {
+                    self.asm.push_usize(position.slot);
+                    return Ok(());
+                }
+                (Some(ExternalVariable::Storage(position)), "offset") => {
+                    self.asm.push_usize(position.offset);
+                    return Ok(());
+                }
+                (Some(ExternalVariable::Stack(position)), "slot") => return self.load(position),
+                (Some(ExternalVariable::Stack(_)), "offset") => {
+                    self.asm.push(&[0]);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        // Instructions without arguments may be used without parentheses, like `gas`
+        match self.builtin(name) {
+            Some(op) if op.stack_args() == 0 && op.stack_returns() == 1 => {
+                self.asm.op(op);
+                Ok(())
+            }
+            _ => Err(format!("Identifier not found: {}", name)),
+        }
+    }
+
+    /// Pushes the values of the expression, returning how many there are.
+    pub fn expression_op_codes(&mut self, expr: &'a AssemblyExpression) -> Result<usize, String> {
+        match expr {
+            AssemblyExpression::Literal(literal) => self.literal_op_codes(literal)?,
+            AssemblyExpression::Identifier(name) => self.identifier_op_codes(name)?,
+            AssemblyExpression::FunctionCall(name, args)
+                if self.objects.is_some() && (name == "datasize" || name == "dataoffset") =>
+            {
+                self.data_op_codes(name, args)?
+            }
+            AssemblyExpression::FunctionCall(name, args) => {
+                let (parameters, returns) = match (self.function(name), self.builtin(name)) {
+                    (Some((function, _)), _) => (function.parameters.len(), function.returns.len()),
+                    (None, Some(op)) => (op.stack_args(), op.stack_returns()),
+                    (None, None) => return Err(format!("Function not found: {}", name)),
+                };
+                if args.len() != parameters {
+                    return Err(format!(
+                        "Function \"{}\" expects {} arguments but got {}.",
+                        name,
+                        parameters,
+                        args.len()
+                    ));
+                }
+                let height = self.asm.stack_height();
+                let return_tag = self.asm.new_tag();
+                let function = self.function(name);
+                if function.is_some() {
+                    self.asm.push_tag(return_tag);
+                }
+                // Arguments are evaluated from right to left, leaving the first one on top
+                for arg in args.iter().rev() {
+                    self.single_value(arg)?;
+                }
+                match function {
+                    Some((_, entry)) => {
+                        self.asm.jump(entry);
+                        self.asm.tag(return_tag);
+                        self.asm.set_stack_height(height + returns);
+                    }
+                    None => self.asm.op(self.builtin(name).unwrap()),
+                }
+                return Ok(returns);
+            }
+        }
+        Ok(1)
+    }
+
+    fn single_value(&mut self, expr: &'a AssemblyExpression) -> Result<(), String> {
+        match self.expression_op_codes(expr)? {
+            1 => Ok(()),
+            count => Err(format!(
+                "Expected expression to evaluate to one value, but got {} values instead.",
+                count
+            )),
+        }
+    }
+
+    /// Generates a block, hoisting the functions defined in it and popping its variables at
+    /// the end.
+    pub fn block_op_codes(&mut self, statements: &'a [AssemblyStatement]) -> Result<(), String> {
+        let variables = self.variables.len();
+        let functions = self.functions.len();
+        let height = self.asm.stack_height();
+        for statement in statements {
+            if let AssemblyStatement::FunctionDefinition(function) = statement {
+                if self.functions[functions..]
+                    .iter()
+                    .any(|(f, _)| f.identifier == function.identifier)
+                {
+                    return Err(format!(
+                        "Function name {} already taken in this scope.",
+                        function.identifier
+                    ));
+                }
+                let tag = self.asm.new_tag();
+                self.functions.push((function, tag));
+            }
+        }
+        for statement in statements {
+            self.statement_op_codes(statement)?;
+        }
+        while self.asm.stack_height() > height {
+            self.asm.op(OpCode::POP);
+        }
+        self.variables.truncate(variables);
+        self.functions.truncate(functions);
+        Ok(())
+    }
+
+    /// Generates the body of a function out of line, jumping over it. The caller pushes the
+    /// return address and the arguments, the first one on top. The function replaces them
+    /// with its return values, the first one deepest.
+    fn function_op_codes(
+        &mut self,
+        function: &'a AssemblyFunctionDefinition,
+    ) -> Result<(), String> {
+        let (_, entry) = self
+            .functions
+            .iter()
+            .rev()
+            .find(|(f, _)| std::ptr::eq(*f, function))
+            .cloned()
+            .unwrap();
+        let height = self.asm.stack_height();
+        let skip = self.asm.new_tag();
+        self.asm.jump(skip);
+
+        self.asm.tag(entry);
+        let base = height + 1;
+        self.asm.set_stack_height(base + function.parameters.len());
+        let outer_variables = std::mem::take(&mut self.variables);
+        let outer_externals = std::mem::take(&mut self.externals);
+        let outer_loops = std::mem::take(&mut self.loops);
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            self.variables
+                .push((parameter, base + function.parameters.len() - 1 - index));
+        }
+        for variable in &function.returns {
+            self.asm.push(&[0]);
+            self.variables.push((variable, self.asm.stack_height() - 1));
+        }
+        let exit = self.asm.new_tag();
+        let outer_frame = self.frame.replace(YulFrame {
+            exit,
+            stack_height: self.asm.stack_height(),
+        });
+        let result = self.block_op_codes(&function.block);
+        self.frame = outer_frame;
+        self.variables = outer_variables;
+        self.externals = outer_externals;
+        self.loops = outer_loops;
+        result?;
+        self.asm.tag(exit);
+
+        let count = 1 + function.parameters.len() + function.returns.len();
+        let layout: Vec<usize> = (0..count).collect();
+        let mut target: Vec<usize> = (count - function.returns.len()..count).collect();
+        target.push(0);
+        self.asm.rearrange(&layout, &target)?;
+        self.asm.op(OpCode::JUMP);
+
+        self.asm.set_stack_height(height);
+        self.asm.tag(skip);
+        Ok(())
+    }
+
+    fn statement_op_codes(&mut self, statement: &'a AssemblyStatement) -> Result<(), String> {
+        match statement {
+            AssemblyStatement::Block(statements) => self.block_op_codes(statements)?,
+            AssemblyStatement::FunctionDefinition(function) => self.function_op_codes(function)?,
+            AssemblyStatement::VariableDeclaration(variables, value) => {
+                match value {
+                    Some(value) => {
+                        let count = self.expression_op_codes(value)?;
+                        if count != variables.len() {
+                            return Err(format!(
+                                "Variable count mismatch for declaration of \"{}\": {} variables and {} values.",
+                                variables.join(", "),
+                                variables.len(),
+                                count
+                            ));
+                        }
+                    }
+                    None => {
+                        for _ in variables {
+                            self.asm.push(&[0]);
+                        }
+                    }
+                }
+                let base = self.asm.stack_height() - variables.len();
+                for (index, variable) in variables.iter().enumerate() {
+                    self.variables.push((variable, base + index));
+                }
+            }
+            AssemblyStatement::Assignment(variables, value) => {
+                let count = self.expression_op_codes(value)?;
+                if count != variables.len() {
+                    return Err(format!(
+                        "Variable count mismatch for assignment to \"{}\": {} variables and {} values.",
+                        variables.join(", "),
+                        variables.len(),
+                        count
+                    ));
+                }
+                for variable in variables.iter().rev() {
+                    let position = self.assignable(variable)?;
+                    self.store(position)?;
+                }
+            }
+            AssemblyStatement::If(condition, block) => {
+                let end = self.asm.new_tag();
+                self.single_value(condition)?;
+                self.asm.op(OpCode::ISZERO);
+                self.asm.jumpi(end);
+                self.block_op_codes(block)?;
+                self.asm.tag(end);
+            }
+            AssemblyStatement::Switch(switch) => {
+                let mut values: Vec<[u8; 32]> = vec![];
+                for (literal, _) in &switch.cases {
+                    let value = match literal {
+                        Literal::Number(number) => number_value(number)?.to_word(),
+                        Literal::Boolean(Boolean::True) => {
+                            Rational::integer(BigUint::from_u64(1)).to_word()
+                        }
+                        Literal::Boolean(Boolean::False) => {
+                            Rational::integer(BigUint::zero()).to_word()
+                        }
+                        Literal::String(bytes) | Literal::Hex(bytes) if bytes.len() <= 32 => {
+                            let mut word = [0; 32];
+                            word[..bytes.len()].copy_from_slice(bytes);
+                            Some(word)
+                        }
+                        _ => None,
+                    }
+                    .ok_or_else(|| format!("Invalid case literal: {:?}", literal))?;
+                    if values.contains(&value) {
+                        return Err("Duplicate case defined.".to_string());
+                    }
+                    values.push(value);
+                }
+                let height = self.asm.stack_height();
+                self.single_value(&switch.expression)?;
+                let end = self.asm.new_tag();
+                let tags: Vec<Tag> = switch.cases.iter().map(|_| self.asm.new_tag()).collect();
+                for (value, tag) in values.iter().zip(tags.iter()) {
+                    self.asm.op(OpCode::DUP1);
+                    self.asm.push(value);
+                    self.asm.op(OpCode::EQ);
+                    self.asm.jumpi(*tag);
+                }
+                if let Some(default) = &switch.default {
+                    self.block_op_codes(default)?;
+                }
+                self.asm.jump(end);
+                for ((_, block), tag) in switch.cases.iter().zip(tags) {
+                    self.asm.tag(tag);
+                    self.block_op_codes(block)?;
+                    self.asm.jump(end);
+                }
+                self.asm.tag(end);
+                self.asm.op(OpCode::POP);
+                debug_assert_eq!(self.asm.stack_height(), height);
+            }
+            AssemblyStatement::For(for_loop) => {
+                let variables = self.variables.len();
+                let functions = self.functions.len();
+                let height = self.asm.stack_height();
+                for statement in &for_loop.init {
+                    if let AssemblyStatement::FunctionDefinition(function) = statement {
+                        let tag = self.asm.new_tag();
+                        self.functions.push((function, tag));
+                    }
+                }
+                for statement in &for_loop.init {
+                    self.statement_op_codes(statement)?;
+                }
+                let start = self.asm.new_tag();
+                let post = self.asm.new_tag();
+                let end = self.asm.new_tag();
+                self.asm.tag(start);
+                self.single_value(&for_loop.condition)?;
+                self.asm.op(OpCode::ISZERO);
+                self.asm.jumpi(end);
+                self.loops.push(Loop {
+                    continue_tag: post,
+                    break_tag: end,
+                    stack_height: self.asm.stack_height(),
+                });
+                self.block_op_codes(&for_loop.body)?;
+                self.loops.pop();
+                self.asm.tag(post);
+                self.block_op_codes(&for_loop.post)?;
+                self.asm.jump(start);
+                self.asm.tag(end);
+                while self.asm.stack_height() > height {
+                    self.asm.op(OpCode::POP);
+                }
+                self.variables.truncate(variables);
+                self.functions.truncate(functions);
+            }
+            AssemblyStatement::BreakContinue(keyword) => {
+                let (tag, stack_height, name) = match (self.loops.last(), keyword) {
+                    (Some(l), AssemblyBreakContinue::Break) => (l.break_tag, l.stack_height, BREAK),
+                    (Some(l), AssemblyBreakContinue::Continue) => {
+                        (l.continue_tag, l.stack_height, CONTINUE)
+                    }
+                    (None, AssemblyBreakContinue::Break) => (0, 0, BREAK),
+                    (None, AssemblyBreakContinue::Continue) => (0, 0, CONTINUE),
+                };
+                if self.loops.is_empty() {
+                    return Err(format!(
+                        "Keyword \"{}\" needs to be inside a for-loop body.",
+                        name
+                    ));
+                }
+                self.jump_out(tag, stack_height);
+            }
+            AssemblyStatement::Leave => match &self.frame {
+                Some(frame) => {
+                    let (exit, stack_height) = (frame.exit, frame.stack_height);
+                    self.jump_out(exit, stack_height);
+                }
+                None => {
+                    return Err("Keyword \"leave\" can only be used inside a function.".to_string())
+                }
+            },
+            AssemblyStatement::Expression(expr) => {
+                let count = self.expression_op_codes(expr)?;
+                if count != 0 {
+                    return Err(format!(
+                        "Top-level expressions are not supposed to return values (this expression returns {} value{}). Use ``pop()`` or assign them.",
+                        count,
+                        if count == 1 { "" } else { "s" }
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pops everything above `stack_height` and jumps to `tag`, leaving the bookkeeping of the
+    /// stack height as it was.
+    fn jump_out(&mut self, tag: Tag, stack_height: usize) {
+        let height = self.asm.stack_height();
+        while self.asm.stack_height() > stack_height {
+            self.asm.op(OpCode::POP);
+        }
+        self.asm.jump(tag);
+        self.asm.set_stack_height(height);
+    }
+}
+
+impl InlineAssemblyStatement {
+    /// Generates the code of the assembly block. Solidity variables are visible by name.
+    pub fn op_codes(
+        &self,
+        asm: &mut Assembly,
+        externals: Vec<(String, ExternalVariable)>,
+    ) -> Result<(), String> {
+        if let Some(dialect) = &self.identifier {
+            if dialect != "evmasm" {
+                return Err(format!(
+                    "Only \"evmasm\" supported as inline assembly dialect, not \"{}\".",
+                    dialect
+                ));
+            }
+        }
+        YulContext::new(asm, externals).block_op_codes(&self.assembly_statements)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{codegen::EvmVersion, literal::Number};
 
     use std::str::from_utf8;
 
@@ -97,4 +948,204 @@ mod tests {
             )
         }
     }
+
+    fn identifier(name: &str) -> AssemblyExpression {
+        AssemblyExpression::Identifier(name.to_string())
+    }
+
+    fn number(value: &str) -> AssemblyExpression {
+        AssemblyExpression::Literal(Literal::Number((Number::Decimal(value.to_string()), None)))
+    }
+
+    fn call(name: &str, args: Vec<AssemblyExpression>) -> AssemblyExpression {
+        AssemblyExpression::FunctionCall(name.to_string(), args)
+    }
+
+    #[test]
+    fn parses_inline_assembly_with_switch() {
+        let input = b"assembly \"evmasm\" {
+            let result := delegatecall(sub(gas, 10000), to, 0, calldatasize(), 0, 0)
+            switch result case 0 {revert(0, returndatasize)} default {return (0, returndatasize)}
+        }";
+        let result = parse_inline_assembly_statement(input);
+        if result.is_err() {
+            result.expect("should parse inline assembly");
+        } else {
+            let (remaining, assembly) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), assembly),
+                (
+                    "",
+                    InlineAssemblyStatement {
+                        identifier: Some("evmasm".to_string()),
+                        assembly_statements: vec![
+                            AssemblyStatement::VariableDeclaration(
+                                vec!["result".to_string()],
+                                Some(call(
+                                    "delegatecall",
+                                    vec![
+                                        call("sub", vec![identifier("gas"), number("10000")]),
+                                        identifier("to"),
+                                        number("0"),
+                                        call("calldatasize", vec![]),
+                                        number("0"),
+                                        number("0"),
+                                    ]
+                                ))
+                            ),
+                            AssemblyStatement::Switch(AssemblySwitch {
+                                expression: identifier("result"),
+                                cases: vec![(
+                                    Literal::Number((Number::Decimal("0".to_string()), None)),
+                                    vec![AssemblyStatement::Expression(call(
+                                        "revert",
+                                        vec![number("0"), identifier("returndatasize")]
+                                    ))]
+                                )],
+                                default: Some(vec![AssemblyStatement::Expression(call(
+                                    "return",
+                                    vec![number("0"), identifier("returndatasize")]
+                                ))]),
+                            }),
+                        ],
+                    }
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn parses_assembly_function_definition() {
+        let input = b"function f(a, b) -> x, y { x, y := g(a.slot) if lt(a, b) { leave } }";
+        let result = parse_assembly_statement(input);
+        if result.is_err() {
+            result.expect("should parse function definition");
+        } else {
+            let (remaining, function) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), function),
+                (
+                    "",
+                    AssemblyStatement::FunctionDefinition(AssemblyFunctionDefinition {
+                        identifier: "f".to_string(),
+                        parameters: vec!["a".to_string(), "b".to_string()],
+                        returns: vec!["x".to_string(), "y".to_string()],
+                        block: vec![
+                            AssemblyStatement::Assignment(
+                                vec!["x".to_string(), "y".to_string()],
+                                call("g", vec![identifier("a.slot")])
+                            ),
+                            AssemblyStatement::If(
+                                call("lt", vec![identifier("a"), identifier("b")]),
+                                vec![AssemblyStatement::Leave]
+                            ),
+                        ],
+                    })
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn parses_assembly_for_loop() {
+        let input =
+            b"for { let i := 0 } lt(i, 0x10) { i := add(i, 1) } { if i { continue } break }";
+        let result = parse_assembly_statement(input);
+        if result.is_err() {
+            result.expect("should parse for loop");
+        } else {
+            let (remaining, for_loop) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), for_loop),
+                (
+                    "",
+                    AssemblyStatement::For(AssemblyFor {
+                        init: vec![AssemblyStatement::VariableDeclaration(
+                            vec!["i".to_string()],
+                            Some(number("0"))
+                        )],
+                        condition: call(
+                            "lt",
+                            vec![
+                                identifier("i"),
+                                AssemblyExpression::Literal(Literal::Number((
+                                    Number::Hex("0x10".to_string()),
+                                    None
+                                )))
+                            ]
+                        ),
+                        post: vec![AssemblyStatement::Assignment(
+                            vec!["i".to_string()],
+                            call("add", vec![identifier("i"), number("1")])
+                        )],
+                        body: vec![
+                            AssemblyStatement::If(
+                                identifier("i"),
+                                vec![AssemblyStatement::BreakContinue(
+                                    AssemblyBreakContinue::Continue
+                                )]
+                            ),
+                            AssemblyStatement::BreakContinue(AssemblyBreakContinue::Break),
+                        ],
+                    })
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn parses_assembly_literals() {
+        let input = b"let a, b := f(\"abc\", hex\"01ff\", true)";
+        let result = parse_assembly_statement(input);
+        if result.is_err() {
+            result.expect("should parse literals");
+        } else {
+            let (remaining, declaration) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), declaration),
+                (
+                    "",
+                    AssemblyStatement::VariableDeclaration(
+                        vec!["a".to_string(), "b".to_string()],
+                        Some(call(
+                            "f",
+                            vec![
+                                AssemblyExpression::Literal(Literal::String(b"abc".to_vec())),
+                                AssemblyExpression::Literal(Literal::Hex(vec![0x01, 0xff])),
+                                AssemblyExpression::Literal(Literal::Boolean(Boolean::True)),
+                            ]
+                        ))
+                    )
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn generates_code_for_variables_and_builtins() {
+        let (_, block) = parse_assembly_block(b"{ let x := 1 sstore(x, y) }")
+            .ok()
+            .unwrap();
+        let mut asm = Assembly::new();
+        asm.push(&[7]);
+        let externals = vec![("y".to_string(), ExternalVariable::Stack(0))];
+        YulContext::new(&mut asm, externals)
+            .block_op_codes(&block)
+            .unwrap();
+        assert_eq!(asm.stack_height(), 1);
+        assert_eq!(
+            asm.assemble(EvmVersion::default()),
+            vec![0x60, 0x07, 0x60, 0x01, 0x81, 0x81, 0x55, 0x50]
+        )
+    }
+
+    #[test]
+    fn rejects_wrong_number_of_arguments() {
+        let (_, block) = parse_assembly_block(b"{ pop(add(1)) }").ok().unwrap();
+        let mut asm = Assembly::new();
+        assert_eq!(
+            YulContext::new(&mut asm, vec![]).block_op_codes(&block),
+            Err("Function \"add\" expects 2 arguments but got 1.".to_string())
+        )
+    }
 }
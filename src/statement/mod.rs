@@ -1,20 +1,32 @@
 use crate::{
-    atom::parse_identifier,
-    expression::{parse_expression, parse_type_name, Expression, TypeName},
+    atom::{
+        keyword::{parse_emit, parse_return, parse_revert, parse_unchecked},
+        parse_identifier, word_end,
+    },
+    codegen::Context,
+    expression::{
+        parse_expression, parse_type_name, push_default_value, Expression, FunctionCall,
+        PrimaryExpression, TypeName,
+    },
+    op_codes::OpCode,
+    span::{spanned, Span},
+    statement::assembly::{
+        parse_inline_assembly_statement, ExternalVariable, InlineAssemblyStatement,
+    },
     storage_location::{parse as parse_storage_location, StorageLocation},
 };
 use nom::{
     branch::alt,
     bytes::streaming::tag,
     character::complete::{char, multispace0, multispace1},
-    combinator::{complete, map, opt},
-    multi::{many0, separated_list},
+    combinator::{map, map_res, opt},
+    multi::many0,
     sequence::{delimited, preceded, terminated, tuple},
     IResult,
 };
 use std::str::from_utf8;
 
-mod assembly;
+pub mod assembly;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct VariableDeclaration {
@@ -23,6 +35,41 @@ pub struct VariableDeclaration {
     pub identifier: String,
 }
 
+impl VariableDeclaration {
+    /// Makes sure a reference type is initialized from a value in the same data location, as
+    /// copying between locations is not supported yet.
+    fn check_location(&self, rhs: &Expression, ctx: &Context) -> Result<(), String> {
+        if self.type_name.is_value_type() {
+            return Ok(());
+        }
+        let location = match &self.storage_location {
+            Some(location) => location,
+            None => {
+                return Err(format!(
+                    "Data location must be \"storage\" or \"memory\" for variable of type {}",
+                    self.type_name.abi_name()
+                ))
+            }
+        };
+        match rhs.reference_type(ctx) {
+            Some(reference)
+                if reference.location == StorageLocation::Storage
+                    && *location == StorageLocation::Memory
+                    && self.type_name.is_byte_array() =>
+            {
+                Ok(())
+            }
+            Some(reference) if reference.location != *location => Err(format!(
+                "Copying {} from {} to {} is not supported yet",
+                self.type_name.abi_name(),
+                format!("{:?}", reference.location).to_lowercase(),
+                format!("{:?}", location).to_lowercase()
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
 pub fn parse_variable_declaration(i: &[u8]) -> IResult<&[u8], VariableDeclaration> {
     map(
         tuple((
@@ -43,7 +90,9 @@ pub fn parse_variable_declaration(i: &[u8]) -> IResult<&[u8], VariableDeclaratio
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct VariableDefinition {
-    pub declarations: Vec<VariableDeclaration>,
+    /// The variables the values are assigned to. Values can be ignored by leaving the
+    /// declaration out, like in `(uint a, , bool c) = f();`.
+    pub declarations: Vec<Option<VariableDeclaration>>,
     pub rhs: Expression,
 }
 
@@ -51,12 +100,18 @@ pub fn parse_variable_definition(i: &[u8]) -> IResult<&[u8], VariableDefinition>
     map(
         tuple((
             alt((
-                map(parse_variable_declaration, |x| vec![x]),
+                map(parse_variable_declaration, |x| vec![Some(x)]),
                 delimited(
                     preceded(multispace0, char('(')),
-                    preceded(
-                        multispace0,
-                        separated_list(char(','), parse_variable_declaration),
+                    map(
+                        tuple((
+                            opt(parse_variable_declaration),
+                            many0(preceded(
+                                preceded(multispace0, char(',')),
+                                opt(parse_variable_declaration),
+                            )),
+                        )),
+                        |(first, rest)| std::iter::once(first).chain(rest).collect(),
                     ),
                     preceded(multispace0, char(')')),
                 ),
@@ -75,42 +130,230 @@ pub fn parse_variable_definition(i: &[u8]) -> IResult<&[u8], VariableDefinition>
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
-    Block(Vec<Statement>),
+    Block(Vec<(Statement, Span)>),
+    /// A block whose arithmetic wraps around instead of reverting on overflow.
+    UncheckedBlock(Vec<(Statement, Span)>),
     // TODO: IfStatement
     // TODO: WhileStatement
     // TODO: ForStatement
     // TODO: DoWhileStatement
     // TODO: Continue
     // TODO: Break
-    // TODO: Return
+    Return(Option<Expression>),
+    InlineAssembly(InlineAssemblyStatement),
     // TODO: Throw
-    // TODO: Emit
+    Emit(FunctionCall),
+    /// `revert E(args)`, which reverts with a custom error.
+    Revert(FunctionCall),
+    /// The `_` in a modifier, where the body of the modified function goes.
+    Placeholder,
     Expression(Expression),
     VariableDeclaration(VariableDeclaration),
     VariableDefinition(VariableDefinition),
 }
 
 impl Statement {
-    pub fn op_codes(self) -> Vec<u32> {
-        let mut codes = vec![];
+    pub fn op_codes<'a>(&'a self, ctx: &mut Context<'a>) -> Result<(), String> {
         match self {
             Statement::Block(statements) => {
-                for statement in statements {
-                    codes.extend(statement.op_codes())
+                let scope = ctx.open_scope();
+                block_op_codes(statements, ctx)?;
+                ctx.close_scope(scope);
+            }
+            Statement::UncheckedBlock(statements) => {
+                if ctx.set_unchecked(true) {
+                    return Err("\"unchecked\" blocks cannot be nested.".to_string());
+                }
+                let scope = ctx.open_scope();
+                block_op_codes(statements, ctx)?;
+                ctx.close_scope(scope);
+                ctx.set_unchecked(false);
+            }
+            Statement::Return(expr) => {
+                let returns = match ctx.frame() {
+                    Some(frame) => frame.returns.clone(),
+                    None => {
+                        return Err(
+                            "\"return\" is only allowed inside of functions and modifiers"
+                                .to_string(),
+                        )
+                    }
+                };
+                if let Some(expr) = expr {
+                    let targets: Vec<_> = returns
+                        .iter()
+                        .map(|(_, type_name)| Some((type_name.clone(), None)))
+                        .collect();
+                    let count = expr.converted_values(&targets, ctx)?;
+                    if count != returns.len() {
+                        return Err(format!(
+                            "Different number of arguments in return statement than in returns declaration ({} instead of {}).",
+                            count,
+                            returns.len()
+                        ));
+                    }
+                    for (position, _) in returns.iter().rev() {
+                        ctx.store_local(*position)?;
+                    }
+                }
+                ctx.jump_to_exit()?;
+            }
+            Statement::Placeholder => match ctx.placeholder() {
+                Some(placeholder) => placeholder.op_codes(ctx)?,
+                None => return Err("\"_\" is only allowed inside of modifiers.".to_string()),
+            },
+            Statement::Emit(call) => {
+                let event = match call.expr.as_ref() {
+                    Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) => ctx
+                        .event(identifier, call.argument_count())
+                        .ok_or_else(|| format!("Undeclared event: {}", identifier))?,
+                    _ => return Err("Expression has to be an event invocation.".to_string()),
+                };
+                let args = call.ordered_arguments(&event.parameter_names())?;
+                event.emit_op_codes(&args, ctx)?;
+            }
+            Statement::Revert(call) => {
+                let error = match call.expr.as_ref() {
+                    Expression::PrimaryExpression(PrimaryExpression::Identifier(identifier)) => ctx
+                        .error(identifier, call.argument_count())
+                        .ok_or_else(|| format!("Undeclared error: {}", identifier))?,
+                    _ => return Err("Expression has to be an error.".to_string()),
+                };
+                let args = call.ordered_arguments(&error.parameter_names())?;
+                error.revert_op_codes(&args, ctx)?;
+            }
+            Statement::InlineAssembly(assembly) => {
+                let mut externals: Vec<(String, ExternalVariable)> = ctx
+                    .contract
+                    .storage_layout()
+                    .into_iter()
+                    .map(|(position, decl)| {
+                        (decl.identifier.clone(), ExternalVariable::Storage(position))
+                    })
+                    .collect();
+                externals.extend(
+                    ctx.locals()
+                        .iter()
+                        .map(|local| (local.name.clone(), ExternalVariable::Stack(local.position))),
+                );
+                assembly.op_codes(&mut ctx.asm, externals)?;
+            }
+            Statement::Expression(expr) => {
+                for _ in 0..expr.op_codes(ctx)? {
+                    ctx.asm.op(OpCode::POP);
+                }
+            }
+            Statement::VariableDeclaration(decl) => {
+                push_default_value(&decl.type_name, &decl.storage_location, ctx)?;
+                ctx.push_local(&decl.identifier, &decl.type_name, &decl.storage_location);
+            }
+            Statement::VariableDefinition(def) => {
+                if let [Some(decl)] = &def.declarations[..] {
+                    decl.check_location(&def.rhs, ctx)?;
+                }
+                let targets: Vec<_> = def
+                    .declarations
+                    .iter()
+                    .map(|decl| {
+                        decl.as_ref()
+                            .map(|decl| (decl.type_name.clone(), decl.storage_location.clone()))
+                    })
+                    .collect();
+                let count = def.rhs.converted_values(&targets, ctx)?;
+                if count != def.declarations.len() {
+                    return Err(format!(
+                        "Different number of components on the left hand side ({}) than on the right hand side ({}).",
+                        def.declarations.len(),
+                        count
+                    ));
+                }
+                let base = ctx.asm.stack_height() - count;
+                let layout: Vec<usize> = (0..count).collect();
+                let kept: Vec<usize> = layout
+                    .iter()
+                    .cloned()
+                    .filter(|index| def.declarations[*index].is_some())
+                    .collect();
+                ctx.asm.rearrange(&layout, &kept)?;
+                for (index, decl) in def.declarations.iter().flatten().enumerate() {
+                    ctx.bind_local(
+                        &decl.identifier,
+                        &decl.type_name,
+                        &decl.storage_location,
+                        base + index,
+                    );
                 }
             }
-            Statement::Expression(expr) => codes.extend(expr.op_codes()),
-            Statement::VariableDeclaration(decl) => {}
-            Statement::VariableDefinition(def) => {}
-            _ => {}
         }
+        Ok(())
+    }
+}
 
-        codes
+/// Generates the statements of a block, attributing the code of each one to its span.
+pub fn block_op_codes<'a>(
+    statements: &'a [(Statement, Span)],
+    ctx: &mut Context<'a>,
+) -> Result<(), String> {
+    for (statement, span) in statements {
+        let outer = ctx.asm.set_span(Some(*span));
+        statement.op_codes(ctx)?;
+        ctx.asm.set_span(outer);
     }
+    Ok(())
+}
+
+fn parse_emit_statement(i: &[u8]) -> IResult<&[u8], FunctionCall> {
+    map_res(
+        preceded(
+            preceded(multispace0, parse_emit),
+            preceded(multispace1, parse_expression),
+        ),
+        |expr| match expr {
+            Expression::FunctionCall(call) => Ok(call),
+            _ => Err("expression has to be an event invocation"),
+        },
+    )(i)
+}
+
+fn parse_revert_statement(i: &[u8]) -> IResult<&[u8], FunctionCall> {
+    map_res(
+        preceded(
+            preceded(multispace0, terminated(parse_revert, word_end)),
+            preceded(multispace1, parse_expression),
+        ),
+        |expr| match expr {
+            Expression::FunctionCall(call) => Ok(call),
+            _ => Err("expression has to be an error"),
+        },
+    )(i)
+}
+
+fn parse_return_statement(i: &[u8]) -> IResult<&[u8], Option<Expression>> {
+    preceded(
+        preceded(multispace0, terminated(parse_return, word_end)),
+        opt(preceded(multispace0, parse_expression)),
+    )(i)
 }
 
 pub fn parse_statement(i: &[u8]) -> IResult<&[u8], Statement> {
     alt((
+        terminated(
+            alt((
+                map(parse_emit_statement, Statement::Emit),
+                map(parse_revert_statement, Statement::Revert),
+                map(parse_return_statement, Statement::Return),
+                map(terminated(char('_'), word_end), |_| Statement::Placeholder),
+            )),
+            preceded(multispace0, tag(";")),
+        ),
+        map(
+            preceded(
+                preceded(multispace0, terminated(parse_unchecked, word_end)),
+                parse_block,
+            ),
+            Statement::UncheckedBlock,
+        ),
+        map(parse_inline_assembly_statement, Statement::InlineAssembly),
         alt((
             map(parse_block, |x| Statement::Block(x)),
             terminated(
@@ -143,10 +386,10 @@ pub fn parse_statement(i: &[u8]) -> IResult<&[u8], Statement> {
     ))(i)
 }
 
-pub fn parse_block(i: &[u8]) -> IResult<&[u8], Vec<Statement>> {
+pub fn parse_block(i: &[u8]) -> IResult<&[u8], Vec<(Statement, Span)>> {
     delimited(
         preceded(multispace0, tag("{")),
-        many0(preceded(multispace0, parse_statement)),
+        many0(spanned(preceded(multispace0, parse_statement))),
         preceded(multispace0, tag("}")),
     )(i)
 }
@@ -155,9 +398,9 @@ pub fn parse_block(i: &[u8]) -> IResult<&[u8], Vec<Statement>> {
 mod tests {
     use super::*;
 
-    use crate::elementary_type_name::ElementaryTypeName;
-    use crate::expression::{Expression, PrimaryExpression};
-    use crate::literal::Boolean;
+    use crate::elementary_type_name::{ElementaryTypeName, UInt};
+    use crate::expression::FunctionCallArguments;
+    use crate::literal::{Boolean, Number};
     use pretty_assertions::assert_eq;
     use std::str::from_utf8;
 
@@ -219,16 +462,16 @@ mod tests {
                     "",
                     VariableDefinition {
                         declarations: vec![
-                            VariableDeclaration {
+                            Some(VariableDeclaration {
                                 type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
                                 storage_location: Some(StorageLocation::Memory),
                                 identifier: "a".to_string(),
-                            },
-                            VariableDeclaration {
+                            }),
+                            Some(VariableDeclaration {
                                 type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
                                 storage_location: Some(StorageLocation::Memory),
                                 identifier: "b".to_string(),
-                            }
+                            })
                         ],
                         rhs: Expression::PrimaryExpression(PrimaryExpression::BooleanLiteral(
                             Boolean::True
@@ -252,11 +495,11 @@ mod tests {
                 (
                     "",
                     VariableDefinition {
-                        declarations: vec![VariableDeclaration {
+                        declarations: vec![Some(VariableDeclaration {
                             type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
                             storage_location: Some(StorageLocation::Memory),
                             identifier: "a".to_string(),
-                        },],
+                        })],
                         rhs: Expression::PrimaryExpression(PrimaryExpression::BooleanLiteral(
                             Boolean::True
                         ))
@@ -266,6 +509,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_variable_definition_with_empty_components() {
+        let input = b"(uint a, , bool c) = g();";
+        let result = parse_statement(input);
+        if result.is_err() {
+            result.expect("should parse variable definition");
+        } else {
+            let (remaining, statement) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), statement),
+                (
+                    "",
+                    Statement::VariableDefinition(VariableDefinition {
+                        declarations: vec![
+                            Some(VariableDeclaration {
+                                type_name: TypeName::ElementaryTypeName(ElementaryTypeName::UInt(
+                                    UInt::Uint
+                                )),
+                                storage_location: None,
+                                identifier: "a".to_string(),
+                            }),
+                            None,
+                            Some(VariableDeclaration {
+                                type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
+                                storage_location: None,
+                                identifier: "c".to_string(),
+                            })
+                        ],
+                        rhs: Expression::FunctionCall(FunctionCall {
+                            expr: Box::new(Expression::PrimaryExpression(
+                                PrimaryExpression::Identifier("g".to_string())
+                            )),
+                            args: FunctionCallArguments::ExpressionList(vec![])
+                        })
+                    })
+                )
+            )
+        }
+    }
+
     #[test]
     fn parses_block() {
         let input = b"{(bool     memory     a,     bool     memory    b   ) =    true;}";
@@ -278,23 +561,126 @@ mod tests {
                 (from_utf8(remaining).unwrap(), decl),
                 (
                     "",
-                    vec![Statement::VariableDefinition(VariableDefinition {
-                        declarations: vec![
-                            VariableDeclaration {
-                                type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
-                                storage_location: Some(StorageLocation::Memory),
-                                identifier: "a".to_string(),
-                            },
-                            VariableDeclaration {
-                                type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
-                                storage_location: Some(StorageLocation::Memory),
-                                identifier: "b".to_string(),
-                            }
-                        ],
-                        rhs: Expression::PrimaryExpression(PrimaryExpression::BooleanLiteral(
-                            Boolean::True
-                        ))
-                    })]
+                    vec![(
+                        Statement::VariableDefinition(VariableDefinition {
+                            declarations: vec![
+                                Some(VariableDeclaration {
+                                    type_name: TypeName::ElementaryTypeName(
+                                        ElementaryTypeName::Bool
+                                    ),
+                                    storage_location: Some(StorageLocation::Memory),
+                                    identifier: "a".to_string(),
+                                }),
+                                Some(VariableDeclaration {
+                                    type_name: TypeName::ElementaryTypeName(
+                                        ElementaryTypeName::Bool
+                                    ),
+                                    storage_location: Some(StorageLocation::Memory),
+                                    identifier: "b".to_string(),
+                                })
+                            ],
+                            rhs: Expression::PrimaryExpression(PrimaryExpression::BooleanLiteral(
+                                Boolean::True
+                            ))
+                        }),
+                        Span {
+                            rest: 64,
+                            length: 63
+                        }
+                    )]
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn parses_emit_statement() {
+        let input = b"emit Transfer(from, to, 1);";
+        let result = parse_statement(input);
+        if result.is_err() {
+            result.expect("should parse emit statement");
+        } else {
+            let (remaining, stmt) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), stmt),
+                (
+                    "",
+                    Statement::Emit(FunctionCall {
+                        expr: Box::new(Expression::PrimaryExpression(
+                            PrimaryExpression::Identifier("Transfer".to_string())
+                        )),
+                        args: FunctionCallArguments::ExpressionList(vec![
+                            Expression::PrimaryExpression(PrimaryExpression::Identifier(
+                                "from".to_string()
+                            )),
+                            Expression::PrimaryExpression(PrimaryExpression::Identifier(
+                                "to".to_string()
+                            )),
+                            Expression::PrimaryExpression(PrimaryExpression::NumberLiteral((
+                                Number::Decimal("1".to_string()),
+                                None
+                            ))),
+                        ])
+                    })
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn parses_revert_statement() {
+        let input = b"revert Unauthorized(msg.sender);";
+        let result = parse_statement(input);
+        if result.is_err() {
+            result.expect("should parse revert statement");
+        } else {
+            let (remaining, stmt) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), stmt),
+                (
+                    "",
+                    Statement::Revert(FunctionCall {
+                        expr: Box::new(Expression::PrimaryExpression(
+                            PrimaryExpression::Identifier("Unauthorized".to_string())
+                        )),
+                        args: FunctionCallArguments::ExpressionList(vec![
+                            Expression::MemberAccess(
+                                Box::new(Expression::PrimaryExpression(
+                                    PrimaryExpression::Identifier("msg".to_string())
+                                )),
+                                Box::new(Expression::PrimaryExpression(
+                                    PrimaryExpression::Identifier("sender".to_string())
+                                ))
+                            ),
+                        ])
+                    })
+                )
+            )
+        }
+    }
+
+    #[test]
+    fn parses_revert_call_as_expression() {
+        let input = b"revert(\"reason\");";
+        let result = parse_statement(input);
+        if result.is_err() {
+            result.expect("should parse revert call");
+        } else {
+            let (remaining, stmt) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), stmt),
+                (
+                    "",
+                    Statement::Expression(Expression::FunctionCall(FunctionCall {
+                        expr: Box::new(Expression::PrimaryExpression(
+                            PrimaryExpression::Identifier("revert".to_string())
+                        )),
+                        args: FunctionCallArguments::ExpressionList(vec![
+                            Expression::PrimaryExpression(PrimaryExpression::StringLiteral(
+                                b"reason".to_vec()
+                            )),
+                        ])
+                    }))
                 )
             )
         }
@@ -312,14 +698,42 @@ mod tests {
                 (from_utf8(remaining).unwrap(), decl),
                 (
                     "",
-                    vec![Statement::VariableDeclaration(VariableDeclaration {
-                        type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
-                        storage_location: Some(StorageLocation::Memory),
-                        identifier: "a".to_string(),
-                    })]
+                    vec![(
+                        Statement::VariableDeclaration(VariableDeclaration {
+                            type_name: TypeName::ElementaryTypeName(ElementaryTypeName::Bool),
+                            storage_location: Some(StorageLocation::Memory),
+                            identifier: "a".to_string(),
+                        }),
+                        Span {
+                            rest: 15,
+                            length: 14
+                        }
+                    )]
                 )
             )
         }
     }
 
+    #[test]
+    fn parses_unchecked_block() {
+        let input = b"unchecked { a; }";
+        let result = parse_statement(input);
+        if result.is_err() {
+            result.expect("should parse unchecked block");
+        } else {
+            let (remaining, statement) = result.ok().unwrap();
+            assert_eq!(
+                (from_utf8(remaining).unwrap(), statement),
+                (
+                    "",
+                    Statement::UncheckedBlock(vec![(
+                        Statement::Expression(Expression::PrimaryExpression(
+                            PrimaryExpression::Identifier("a".to_string())
+                        )),
+                        Span { rest: 4, length: 2 }
+                    )])
+                )
+            )
+        }
+    }
 }
@@ -0,0 +1,134 @@
+use crate::{
+  codegen::Assembly,
+  op_codes::OpCode,
+  statement::assembly::{keyword, parse_assembly_block, AssemblyStatement, YulContext, YulObjects},
+};
+use nom::{
+  bytes::complete::take_while1,
+  character::complete::{char, multispace0},
+  combinator::map,
+  multi::many0,
+  sequence::{delimited, preceded, tuple},
+  IResult,
+};
+use std::str::from_utf8;
+
+/// A standalone Yul object. By convention, the code of the outer object deploys its first child
+/// object, which holds the runtime code.
+#[derive(Debug, PartialEq, Clone)]
+pub struct YulObject {
+  pub name: String,
+  pub code: Vec<AssemblyStatement>,
+  pub objects: Vec<YulObject>,
+}
+
+fn parse_object_name(i: &[u8]) -> IResult<&[u8], String> {
+  map(
+    preceded(
+      multispace0,
+      delimited(char('"'), take_while1(|c| c != b'"'), char('"')),
+    ),
+    |name| from_utf8(name).unwrap().to_string(),
+  )(i)
+}
+
+/// `object "X" { code { ... } object "X_deployed" { ... } }`
+pub fn parse_object(i: &[u8]) -> IResult<&[u8], YulObject> {
+  map(
+    tuple((
+      keyword("object"),
+      parse_object_name,
+      preceded(multispace0, char('{')),
+      preceded(keyword("code"), parse_assembly_block),
+      many0(parse_object),
+      preceded(multispace0, char('}')),
+    )),
+    |(_, name, _, code, objects, _)| YulObject {
+      name,
+      code,
+      objects,
+    },
+  )(i)
+}
+
+impl YulObject {
+  /// The code of the object, with the children as sub assemblies.
+  pub fn assembly(&self) -> Result<Assembly, String> {
+    let mut asm = Assembly::new();
+    let mut children = vec![];
+    for object in &self.objects {
+      if object.name == self.name || children.iter().any(|(name, _)| *name == object.name) {
+        return Err(format!("Object name \"{}\" already exists.", object.name));
+      }
+      children.push((object.name.clone(), asm.append_sub(object.assembly()?)));
+    }
+    let objects = YulObjects {
+      name: self.name.clone(),
+      children,
+    };
+    YulContext::new(&mut asm, vec![])
+      .with_objects(objects)
+      .block_op_codes(&self.code)?;
+    // Don't run into the code of the children
+    asm.op(OpCode::STOP);
+    Ok(asm)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::codegen::EvmVersion;
+
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn parses_nested_objects() {
+    let input = b"object \"A\" {
+  code { }
+  object \"A_deployed\" { code { stop() } }
+}";
+    let result = parse_object(input);
+    if result.is_err() {
+      result.expect("should parse nested objects");
+    } else {
+      let (remaining, object) = result.ok().unwrap();
+      assert_eq!(
+        (from_utf8(remaining).unwrap(), object),
+        (
+          "",
+          YulObject {
+            name: "A".to_string(),
+            code: vec![],
+            objects: vec![YulObject {
+              name: "A_deployed".to_string(),
+              code: vec![AssemblyStatement::Expression(
+                crate::statement::assembly::AssemblyExpression::FunctionCall(
+                  "stop".to_string(),
+                  vec![]
+                )
+              )],
+              objects: vec![],
+            }],
+          }
+        )
+      )
+    }
+  }
+
+  #[test]
+  fn copies_child_object_with_data_builtins() {
+    let input = b"object \"A\" {
+  code {
+    datacopy(0, dataoffset(\"B\"), datasize(\"B\"))
+    return(0, datasize(\"B\"))
+  }
+  object \"B\" { code { invalid() } }
+}";
+    let (_, object) = parse_object(input).ok().unwrap();
+    assert_eq!(
+      object.assembly().unwrap().assemble(EvmVersion::default()),
+      vec![0x60, 0x02, 0x60, 0x0b, 0x5f, 0x39, 0x60, 0x02, 0x5f, 0xf3, 0x00, 0xfe, 0x00]
+    )
+  }
+}